@@ -0,0 +1,135 @@
+//! Segments of slow modules, such as the pending flatpak updates, kept in
+//! `$XDG_CACHE_HOME/statusbar/` so that a restart shows them at once instead of nothing until
+//! their first fetch completes
+//!
+//! A restored segment keeps the time it was fetched, so it's shown with its age once past the
+//! time to live of its module, as is a segment whose refresh keeps failing.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::output::Segment;
+use crate::style::Level;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    text: String,
+    level: Level,
+    #[serde(default)]
+    values: Vec<(String, String)>,
+    /// seconds since the epoch
+    fetched: u64,
+    expires: u64,
+}
+
+/// `$XDG_CACHE_HOME/statusbar`, or `~/.cache/statusbar`
+pub fn default_dir() -> Option<PathBuf> {
+    let cache_home = match std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache_home.join("statusbar"))
+}
+
+/// The segment of `module` cached in `dir`, if any, to publish before its first fetch
+pub fn load(dir: &Path, module: &'static str) -> Option<Segment> {
+    let json = std::fs::read_to_string(path(dir, module)).ok()?;
+    let entry = serde_json::from_str(&json).ok()?;
+    Some(segment(module, entry, SystemTime::now(), Instant::now()))
+}
+
+/// Keep the freshly fetched `segment`, of [`Segment::cached`], in `dir`
+pub fn keep(dir: &Path, segment: &Segment) {
+    if let Err(e) = save(dir, segment) {
+        debug!(target: segment.module, "unable to cache in {}: {e}", dir.display());
+    }
+}
+
+fn path(dir: &Path, module: &str) -> PathBuf {
+    dir.join(format!("{module}.json"))
+}
+
+/// Write to a temporary file renamed over the entry, never leaving a partial file
+fn save(dir: &Path, segment: &Segment) -> std::io::Result<()> {
+    let Some(entry) = entry(segment, SystemTime::now(), Instant::now()) else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(dir)?;
+    let path = path(dir, segment.module);
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string(&entry)?)?;
+    std::fs::rename(tmp, path)
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The entry of a segment of [`Segment::cached`], its expiry on the wall clock
+fn entry(segment: &Segment, now: SystemTime, instant: Instant) -> Option<Entry> {
+    let ttl = segment.expires?.saturating_duration_since(instant);
+    Some(Entry {
+        text: segment.text.clone(),
+        level: segment.level,
+        values: segment.values.clone(),
+        fetched: unix_secs(segment.fetched?),
+        expires: unix_secs(now + ttl),
+    })
+}
+
+/// The segment of an entry, expired if its expiry on the wall clock has passed
+fn segment(module: &'static str, entry: Entry, now: SystemTime, instant: Instant) -> Segment {
+    let ttl = entry.expires.saturating_sub(unix_secs(now));
+    let mut segment = Segment::new(module, entry.text)
+        .level(entry.level)
+        .expires(instant + Duration::from_secs(ttl));
+    segment.values = entry.values;
+    segment.fetched = Some(UNIX_EPOCH + Duration::from_secs(entry.fetched));
+    segment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_round_trip() {
+        let now = SystemTime::now();
+        let instant = Instant::now();
+        let fetched = Segment::new("flatpak", "fp 3")
+            .value("updates", 3)
+            .cached(Duration::from_secs(3600));
+        let entry = entry(&fetched, now, instant).unwrap();
+        assert!(entry.expires - unix_secs(now) >= 3599);
+        assert_eq!(
+            entry,
+            serde_json::from_str(&serde_json::to_string(&entry).unwrap()).unwrap()
+        );
+
+        // restored two hours later, past its time to live
+        let later = now + Duration::from_secs(7200);
+        let restored = segment("flatpak", entry, later, instant);
+        assert_eq!(restored.text, "fp 3");
+        assert_eq!(restored.get::<u32>("updates"), Some(3));
+        assert_eq!(restored.expires, Some(instant));
+        assert!(restored.fetched.is_some());
+
+        assert!(super::entry(&Segment::new("flatpak", "fp 3"), now, instant).is_none());
+    }
+
+    #[test]
+    fn load_kept() {
+        let dir = std::env::temp_dir().join(format!("statusbar-cache-{}", std::process::id()));
+        let fetched = Segment::new("cert", "cert example.com 12d").cached(Duration::from_secs(60));
+        keep(&dir, &fetched);
+
+        assert_eq!(load(&dir, "cert").unwrap().text, "cert example.com 12d");
+        assert!(load(&dir, "flatpak").is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+//! Click events of i3bar and swaybar, read from stdin
+
+use std::io::BufRead;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::Sender;
+
+use log::warn;
+use serde::Deserialize;
+
+use crate::signal;
+
+/// clap value parser for `--on-click module=command`
+pub fn parse_on_click(s: &str) -> Result<(String, String), String> {
+    let (module, command) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid click action `{s}`, expected module=command"))?;
+    if !crate::MODULES.contains(&module) {
+        return Err(format!(
+            "unknown module `{module}`, expected one of: {}",
+            crate::MODULES.join(", ")
+        ));
+    }
+    Ok((module.to_string(), command.to_string()))
+}
+
+#[derive(Deserialize)]
+struct Event {
+    name: Option<String>,
+}
+
+/// Name of the clicked block from a line of the infinite array of events, `None` for the
+/// opening bracket and malformed events
+fn parse(line: &str) -> Option<String> {
+    let line = line.trim().trim_start_matches(['[', ',']).trim_start();
+    serde_json::from_str::<Event>(line).ok()?.name
+}
+
+/// Read click events until stdin closes, running the `--on-click` command of the clicked module
+/// or else sending the module to the render thread for its built-in action
+pub fn read(commands: Vec<(String, String)>, tx: Sender<String>) {
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        let Some(module) = parse(&line) else {
+            continue;
+        };
+
+        if let Some((_, command)) = commands.iter().find(|(m, _)| *m == module) {
+            spawn(command);
+        } else if crate::MODULES.contains(&module.as_str()) {
+            if tx.send(module).is_err() {
+                return;
+            }
+            signal::request_refresh();
+        }
+    }
+}
+
+/// Run `command` detached from the protocol streams, reaping it in the background
+fn spawn(command: &str) {
+    let child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => warn!("unable to run `{command}`: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn click_events() {
+        // as sent by i3bar, after the opening bracket
+        assert_eq!(parse("["), None);
+        let event = r#"{"name":"cpu","instance":null,"button":1,"modifiers":[],"x":1520,"y":12}"#;
+        assert_eq!(parse(event).as_deref(), Some("cpu"));
+        assert_eq!(parse(&format!(",{event}")).as_deref(), Some("cpu"));
+        assert_eq!(parse(&format!("[{event}")).as_deref(), Some("cpu"));
+        assert_eq!(parse(r#",{"button":1}"#), None);
+        assert_eq!(parse(",{\"name\":"), None);
+    }
+
+    #[test]
+    fn click_actions() {
+        assert_eq!(
+            parse_on_click("bat=notify-send \"$(acpi)\""),
+            Ok(("bat".to_string(), "notify-send \"$(acpi)\"".to_string()))
+        );
+        assert_eq!(
+            parse_on_click("cpu"),
+            Err("invalid click action `cpu`, expected module=command".to_string())
+        );
+        assert!(parse_on_click("gpu=nvtop")
+            .unwrap_err()
+            .starts_with("unknown module `gpu`"));
+    }
+}
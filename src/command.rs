@@ -0,0 +1,137 @@
+//! Running external commands with a deadline
+//!
+//! A child outliving its deadline gets SIGTERM, then SIGKILL after a grace period, and is always
+//! waited for so none is left a zombie. Its output is captured up to [`MAX_OUTPUT`] per stream.
+
+use std::io::{self, Read};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// Bytes of stdout and of stderr kept, the rest read and discarded
+pub const MAX_OUTPUT: usize = 1024 * 1024;
+
+/// Time between SIGTERM and SIGKILL of a child past its deadline
+const KILL_GRACE: Duration = Duration::from_millis(500);
+
+/// Time to finish reading the pipes once the child exited, which a grandchild may hold open
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Interval of checking whether the child exited
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Run `command` to completion, capturing its output. The child is stopped and `Ok(None)`
+/// returned if it is still running after `timeout`.
+pub fn output_timeout(command: &mut Command, timeout: Duration) -> io::Result<Option<Output>> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    wait_timeout(child, timeout)
+}
+
+/// Wait for `child` up to `timeout`, then stop it, reaping it either way
+fn wait_timeout(mut child: Child, timeout: Duration) -> io::Result<Option<Output>> {
+    // drain the pipes while waiting, so a chatty child can't block on a full pipe
+    let stdout = child.stdout.take().map(drain);
+    let stderr = child.stderr.take().map(drain);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            stop(&mut child)?;
+            return Ok(None);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    let collect = |rx: Option<Receiver<Vec<u8>>>| {
+        rx.and_then(|rx| rx.recv_timeout(DRAIN_TIMEOUT).ok())
+            .unwrap_or_default()
+    };
+    Ok(Some(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    }))
+}
+
+/// Read `pipe` to its end on a thread of its own, sending the first [`MAX_OUTPUT`] bytes
+fn drain(mut pipe: impl Read + Send + 'static) -> Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::sync_channel(1);
+    std::thread::spawn(move || {
+        let mut buf = vec![];
+        let mut chunk = [0; 8192];
+        loop {
+            match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let kept = n.min(MAX_OUTPUT - buf.len());
+                    buf.extend_from_slice(&chunk[..kept]);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+        let _ = tx.send(buf);
+    });
+    rx
+}
+
+/// SIGTERM, then SIGKILL if still running after [`KILL_GRACE`], and reap
+fn stop(child: &mut Child) -> io::Result<()> {
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: plain syscall, the child isn't reaped yet so the pid is still its own
+        unsafe { libc::kill(pid, libc::SIGTERM) };
+    }
+    let grace = Instant::now() + KILL_GRACE;
+    while Instant::now() < grace {
+        if child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    // already exited is fine, it's reaped by the wait
+    let _ = child.kill();
+    child.wait().map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hung_child_is_stopped_and_reaped() {
+        let child = Command::new("sleep")
+            .arg("60")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        let started = Instant::now();
+        assert!(wait_timeout(child, Duration::from_millis(200))
+            .unwrap()
+            .is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        // no zombie: reaped, its pid has no process
+        assert!(!std::path::Path::new(&format!("/proc/{pid}")).exists());
+    }
+
+    #[test]
+    fn bounded_output() {
+        let output = output_timeout(
+            Command::new("sh").args(["-c", "head -c 2000000 /dev/zero; echo done >&2"]),
+            Duration::from_secs(10),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), MAX_OUTPUT);
+        assert_eq!(output.stderr, b"done\n");
+    }
+}
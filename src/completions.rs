@@ -0,0 +1,95 @@
+//! Shell completions, for `statusbar completions <shell>`
+//!
+//! Unless static, the written script calls back into statusbar with `COMPLETE=<shell>` on each
+//! completion, handled by [`handle`] before parsing the arguments. This completes the values
+//! known at runtime, such as the interfaces of `--interface` and the mount points of
+//! `--smart-mount`.
+
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::CommandFactory;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::Shells;
+use clap_complete::{CompleteEnv, Shell};
+use statusbar::modules::{self, MOUNTS_PATH};
+use statusbar::MODULES;
+
+use crate::Cli;
+
+const BIN: &str = "statusbar";
+const SYSFS_NET: &str = "/sys/class/net";
+
+/// Answer a completion request of the registered script and exit, if this is one
+pub fn handle() {
+    CompleteEnv::with_factory(Cli::command).complete();
+}
+
+/// Write the completion script for `shell` to stdout. A static script only completes the
+/// options and their fixed values, but doesn't need statusbar at completion time.
+pub fn write(shell: Shell, static_script: bool) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    if static_script {
+        // generate() panics on write errors, such as a closed pipe
+        let mut buf = vec![];
+        clap_complete::generate(shell, &mut Cli::command(), BIN, &mut buf);
+        stdout.write_all(&buf)?;
+        return stdout.flush();
+    }
+
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let Some(completer) = shells.completer(&name) else {
+        return Err(io::Error::other(format!(
+            "no runtime completion for {name}, use --static"
+        )));
+    };
+    completer.write_registration("COMPLETE", BIN, BIN, &completer_path(), &mut stdout)?;
+    stdout.flush()
+}
+
+/// Path the script calls, as run now, so that a statusbar outside of PATH completes itself
+fn completer_path() -> String {
+    let arg0 = std::env::args_os()
+        .next()
+        .unwrap_or_else(|| OsString::from(BIN));
+    let mut path = PathBuf::from(arg0);
+    if path.components().count() > 1 {
+        if let Ok(current_dir) = std::env::current_dir() {
+            path = current_dir.join(path);
+        }
+    }
+    path.to_string_lossy().into_owned()
+}
+
+/// Interfaces in sysfs, for `--interface`
+pub fn interfaces() -> Vec<CompletionCandidate> {
+    let Ok(read_dir) = std::fs::read_dir(SYSFS_NET) else {
+        return vec![];
+    };
+    let mut names: Vec<OsString> = read_dir
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name())
+        .collect();
+    names.sort_unstable();
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Mount points, for `--readonly-mount` and `--smart-mount`
+pub fn mount_points() -> Vec<CompletionCandidate> {
+    let mounts = std::fs::read_to_string(MOUNTS_PATH).unwrap_or_default();
+    modules::mount_points(&mounts)
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Names of the modules, for the options configuring them
+pub fn modules() -> Vec<CompletionCandidate> {
+    MODULES
+        .iter()
+        .copied()
+        .map(CompletionCandidate::new)
+        .collect()
+}
@@ -0,0 +1,296 @@
+//! Config file, its options read before those of the command line, and again on SIGHUP
+//!
+//! Each line sets an option as `key = value`, such as `time_format = "%H:%M"`, the key being the
+//! long option without its dashes, `-` and `_` alike. A flag is set by its bare key, or by `true`
+//! and `false`. Options given per module, such as `--show-above cpu=20` or `--glyph degree=C`, are
+//! also set as `cpu.show_above = 20` and `glyph.degree = C`, and those taking several values,
+//! such as `interface`, by repeating the line. Lines starting with `#` are comments.
+//!
+//! An option given on the command line replaces that of the file, every value of it. Keys of no
+//! option are left out with a warning, and reported by `doctor`.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, Command};
+
+use crate::MODULES;
+
+/// `$XDG_CONFIG_HOME/statusbar/config`, or `~/.config/statusbar/config`
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("statusbar").join("config"))
+}
+
+/// Path of `--config` in `command_line`, the arguments without the binary
+pub fn given(command_line: &[OsString]) -> Option<PathBuf> {
+    let mut args = command_line.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Options set by a config file
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    /// long name of each option set, along with its argument such as `--time-format=%H:%M`
+    options: Vec<(String, OsString)>,
+    /// keys of no option, along with their line
+    pub unknown: Vec<(usize, String)>,
+}
+
+impl Config {
+    /// Read the file at `path`, of the options of `command`
+    pub fn read(path: &Path, command: &Command) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+        Self::parse(&text, command).map_err(|e| format!("{}:{e}", path.display()))
+    }
+
+    /// Options of `command` set by the lines of `text`, an error such as "3: `order` needs a
+    /// value" starting with its line
+    pub fn parse(text: &str, command: &Command) -> Result<Self, String> {
+        let mut config = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(unquote(value.trim()))),
+                None => (line, None),
+            };
+            let key = key.replace('_', "-");
+            let Some((arg, prefix)) = option(command, &key) else {
+                config.unknown.push((i + 1, key));
+                continue;
+            };
+            let name = arg.get_long().unwrap();
+            let argument = match (arg.get_action().takes_values(), value, prefix) {
+                (false, None | Some("true"), None) => format!("--{name}"),
+                (false, Some("false"), None) => continue,
+                (false, Some(value), _) => {
+                    return Err(format!(
+                        "{}: `{key}` is a flag, expected true or false, not `{value}`",
+                        i + 1
+                    ))
+                }
+                (_, None, _) => return Err(format!("{}: `{key}` needs a value", i + 1)),
+                (true, Some(value), None) => format!("--{name}={value}"),
+                (true, Some(value), Some(prefix)) => format!("--{name}={prefix}={value}"),
+            };
+            config.options.push((name.to_string(), argument.into()));
+        }
+        Ok(config)
+    }
+
+    /// Arguments of the options, followed by those of `command_line`, its first being the
+    /// binary. Options also given on the command line are left out.
+    pub fn args(&self, command_line: &[OsString]) -> Vec<OsString> {
+        let given: Vec<&str> = command_line
+            .iter()
+            .skip(1)
+            .filter_map(|arg| arg.to_str()?.strip_prefix("--"))
+            .map(|arg| arg.split_once('=').map_or(arg, |(name, _)| name))
+            .collect();
+        let options = self
+            .options
+            .iter()
+            .filter(|(name, _)| !given.contains(&name.as_str()))
+            .map(|(_, argument)| argument.clone());
+        command_line
+            .iter()
+            .take(1)
+            .cloned()
+            .chain(options)
+            .chain(command_line.iter().skip(1).cloned())
+            .collect()
+    }
+}
+
+/// Option of `command` set by `key`, along with the module or name its value is given for, such
+/// as `--show-above` and cpu of `cpu.show-above`, or `--glyph` and degree of `glyph.degree`
+fn option<'a>(command: &'a Command, key: &'a str) -> Option<(&'a Arg, Option<&'a str>)> {
+    let Some((first, second)) = key.split_once('.') else {
+        return Some((long(command, key)?, None));
+    };
+    if let Some(arg) = long(command, &format!("{first}-{second}")) {
+        return Some((arg, None));
+    }
+    if MODULES.contains(&first) {
+        if let Some(arg) = long(command, second).filter(|arg| arg.get_action().takes_values()) {
+            return Some((arg, Some(first)));
+        }
+    }
+    let arg = long(command, first).filter(|arg| arg.get_action().takes_values())?;
+    Some((arg, Some(second)))
+}
+
+/// Option `--name` of `command`, other than those only of the command line
+fn long<'a>(command: &'a Command, name: &str) -> Option<&'a Arg> {
+    if name == "config" {
+        return None;
+    }
+    command.get_arguments().find(|arg| {
+        arg.get_long() == Some(name)
+            && !matches!(
+                arg.get_action(),
+                ArgAction::Help | ArgAction::HelpShort | ArgAction::HelpLong | ArgAction::Version
+            )
+    })
+}
+
+/// `value` without the double or single quotes around it, such as of an empty string or one
+/// ending with a space
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("statusbar")
+            .version("1.0")
+            .arg(Arg::new("config").long("config"))
+            .arg(Arg::new("time_format").long("time-format"))
+            .arg(Arg::new("prefix").long("prefix"))
+            .arg(
+                Arg::new("interface")
+                    .long("interface")
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                Arg::new("cpu_smoothing")
+                    .long("cpu-smoothing")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("show_above")
+                    .long("show-above")
+                    .action(ArgAction::Append),
+            )
+            .arg(Arg::new("glyph").long("glyph").action(ArgAction::Append))
+            .arg(Arg::new("lid").long("lid").action(ArgAction::SetTrue))
+            .arg(Arg::new("once").long("once").action(ArgAction::SetTrue))
+    }
+
+    fn args(config: &Config) -> Vec<OsString> {
+        config.args(&["statusbar".into()])
+    }
+
+    #[test]
+    fn options_and_flags() {
+        let text = "\
+# the bar of the laptop
+time_format = \"%H:%M\"
+prefix = ''
+interface = wlan0
+interface = eth0
+
+lid
+once = false
+";
+        let config = Config::parse(text, &command()).unwrap();
+        assert_eq!(
+            args(&config),
+            [
+                "statusbar",
+                "--time-format=%H:%M",
+                "--prefix=",
+                "--interface=wlan0",
+                "--interface=eth0",
+                "--lid"
+            ]
+        );
+        assert!(config.unknown.is_empty());
+    }
+
+    #[test]
+    fn options_per_module() {
+        let text = "\
+cpu.smoothing = 0.3
+cpu.show_above = 20
+glyph.degree = C
+";
+        let config = Config::parse(text, &command()).unwrap();
+        assert_eq!(
+            args(&config),
+            [
+                "statusbar",
+                "--cpu-smoothing=0.3",
+                "--show-above=cpu=20",
+                "--glyph=degree=C"
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_keys_left_out() {
+        let text = "colour = red\nlid\nconfig = other\nversion\ncpu.colour = red\n";
+        let config = Config::parse(text, &command()).unwrap();
+        assert_eq!(args(&config), ["statusbar", "--lid"]);
+        assert_eq!(
+            config.unknown,
+            [
+                (1, "colour".to_string()),
+                (3, "config".to_string()),
+                (4, "version".to_string()),
+                (5, "cpu.colour".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let e = Config::parse("lid = yes", &command()).unwrap_err();
+        assert_eq!(e, "1: `lid` is a flag, expected true or false, not `yes`");
+        let e = Config::parse("\nprefix", &command()).unwrap_err();
+        assert_eq!(e, "2: `prefix` needs a value");
+    }
+
+    #[test]
+    fn command_line_replaces_the_file() {
+        let text = "interface = wlan0\ninterface = eth0\nprefix = '> '\nlid";
+        let config = Config::parse(text, &command()).unwrap();
+        let command_line: Vec<OsString> = ["statusbar", "--interface", "usb0", "--prefix=$ "]
+            .map(OsString::from)
+            .into();
+        assert_eq!(
+            config.args(&command_line),
+            ["statusbar", "--lid", "--interface", "usb0", "--prefix=$ "]
+        );
+    }
+
+    #[test]
+    fn config_given() {
+        let given = |args: &[&str]| given(&args.iter().map(OsString::from).collect::<Vec<_>>());
+        assert_eq!(given(&["--lid"]), None);
+        assert_eq!(
+            given(&["--config", "bar.conf", "--lid"]),
+            Some(PathBuf::from("bar.conf"))
+        );
+        assert_eq!(
+            given(&["--config=bar.conf"]),
+            Some(PathBuf::from("bar.conf"))
+        );
+    }
+}
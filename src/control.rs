@@ -0,0 +1,198 @@
+//! Unix socket accepting line commands to query and control the bar
+//!
+//! Each command is replied to with a line starting with `OK` or `ERR`, but for `stats`, whose
+//! table follows `OK` on lines of its own, the last being `END`.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{profile, signal};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Values shared with the render thread
+#[derive(Debug, Default)]
+pub struct State {
+    /// the composed status, as last written
+    pub status: String,
+    /// (module, text) of the displayed segments
+    pub values: Vec<(&'static str, String)>,
+    /// modules hidden with `toggle`
+    pub hidden: HashSet<String>,
+    /// `page` requested the next page of `--page`
+    pub next_page: bool,
+}
+
+/// `$XDG_RUNTIME_DIR/statusbar.sock`
+pub fn default_path() -> Option<PathBuf> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")?;
+    Some(Path::new(&runtime_dir).join("statusbar.sock"))
+}
+
+/// Bind the socket, replacing a stale one left behind by a crash
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another instance is listening",
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    signal::remove_on_exit(path);
+    Ok(listener)
+}
+
+/// Handle each client on a thread of its own, so one holding its connection open doesn't keep
+/// the others waiting
+pub fn serve(listener: UnixListener, state: Arc<Mutex<State>>) {
+    for stream in listener.incoming().filter_map(Result::ok) {
+        let state = Arc::clone(&state);
+        std::thread::spawn(move || handle(stream, &state));
+    }
+}
+
+fn handle(stream: UnixStream, state: &Mutex<State>) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut writer = &stream;
+    for line in BufReader::new(&stream).lines() {
+        let line = line?;
+        let (reply, quit) = command(line.trim(), state);
+        writeln!(writer, "{reply}")?;
+        if quit {
+            signal::request_stop();
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reply to `line`, and whether to quit
+fn command(line: &str, state: &Mutex<State>) -> (String, bool) {
+    let mut words = line.split_ascii_whitespace();
+    let reply = match (words.next(), words.next(), words.next()) {
+        (Some("status"), None, _) => format!("OK {}", state.lock().unwrap().status),
+        (Some("json"), None, _) => {
+            let values: serde_json::Map<_, _> = state
+                .lock()
+                .unwrap()
+                .values
+                .iter()
+                .map(|(module, text)| (module.to_string(), text.clone().into()))
+                .collect();
+            format!("OK {}", serde_json::Value::Object(values))
+        }
+        (Some("refresh"), None, _) => {
+            signal::request_refresh();
+            "OK".to_string()
+        }
+        (Some("toggle"), Some(module), None) => {
+            if crate::MODULES.contains(&module) {
+                let mut state = state.lock().unwrap();
+                let shown = !state.hidden.remove(module);
+                if shown {
+                    state.hidden.insert(module.to_string());
+                }
+                signal::request_refresh();
+                format!("OK {module} {}", if shown { "hidden" } else { "shown" })
+            } else {
+                format!("ERR unknown module {module}")
+            }
+        }
+        (Some("page"), None, _) => {
+            state.lock().unwrap().next_page = true;
+            signal::request_refresh();
+            "OK".to_string()
+        }
+        (Some("pause"), None, _) => {
+            let paused = signal::toggle_pause();
+            format!("OK {}", if paused { "paused" } else { "resumed" })
+        }
+        (Some("stats"), None, _) => stats(profile::table()),
+        (Some("quit"), None, _) => return ("OK".to_string(), true),
+        _ => format!("ERR unknown command {line:?}, expected status, json, refresh, toggle <module>, page, pause, stats, or quit"),
+    };
+    (reply, false)
+}
+
+/// Reply to `stats`, the lines of `table` between `OK` and `END`
+fn stats(table: Option<String>) -> String {
+    match table {
+        Some(table) => format!("OK\n{}\nEND", table.trim_end()),
+        None => "ERR profiling is off, start with --profile".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> Mutex<State> {
+        Mutex::new(State {
+            status: "cpu 02%, 2024-01-01 00:00:00".to_string(),
+            values: vec![
+                ("cpu", "cpu 02%".to_string()),
+                ("time", "00:00".to_string()),
+            ],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn queries() {
+        let state = state();
+        assert_eq!(
+            command("status", &state),
+            ("OK cpu 02%, 2024-01-01 00:00:00".to_string(), false)
+        );
+        assert_eq!(
+            command("json", &state).0,
+            r#"OK {"cpu":"cpu 02%","time":"00:00"}"#
+        );
+        assert_eq!(command("quit", &state), ("OK".to_string(), true));
+    }
+
+    #[test]
+    fn toggles() {
+        let state = state();
+        assert_eq!(command("toggle net", &state).0, "OK net hidden");
+        assert!(state.lock().unwrap().hidden.contains("net"));
+        assert_eq!(command("toggle net", &state).0, "OK net shown");
+        assert!(state.lock().unwrap().hidden.is_empty());
+        assert_eq!(command("toggle gpu", &state).0, "ERR unknown module gpu");
+
+        assert_eq!(command("page", &state).0, "OK");
+        assert!(state.lock().unwrap().next_page);
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let state = state();
+        for line in ["", "stat", "toggle", "toggle net cpu", "status now"] {
+            let (reply, quit) = command(line, &state);
+            assert!(
+                reply.starts_with("ERR unknown command"),
+                "{line:?}: {reply}"
+            );
+            assert!(!quit);
+        }
+    }
+
+    #[test]
+    fn stats_framed() {
+        let table = "module  passes\ncpu         60\n".to_string();
+        assert_eq!(
+            stats(Some(table)),
+            "OK\nmodule  passes\ncpu         60\nEND"
+        );
+        assert!(stats(None).starts_with("ERR"));
+    }
+}
@@ -0,0 +1,274 @@
+//! Report of the `doctor` subcommand: the detection steps of the daemon, run once for the given
+//! options, so that a setup problem shows up before the bar starts with a module missing
+//!
+//! Every setting is an option checked by clap, those of the config file included, leaving its
+//! keys of no option to report.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::modules::{
+    self, lid_detected, Memory, Module, Oom, Selection, MEMINFO_PATH, POWER_SUPPLY_PATH,
+    SYS_NET_PATH, VMSTAT_PATH,
+};
+use crate::output::Xsetroot;
+use crate::paths::Paths;
+use crate::sensors;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Ok,
+    /// missing, leaving out an optional part
+    Warning,
+    /// missing, needed by the given options
+    Error,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    checks: Vec<(Level, String)>,
+}
+
+impl Report {
+    pub fn ok(&mut self, message: impl Into<String>) {
+        self.checks.push((Level::Ok, message.into()));
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.checks.push((Level::Warning, message.into()));
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.checks.push((Level::Error, message.into()));
+    }
+
+    /// Whether something needed is missing, to exit nonzero
+    pub fn failed(&self) -> bool {
+        self.checks.iter().any(|(level, _)| *level == Level::Error)
+    }
+
+    /// A line per check, such as "ok    mem: /proc/meminfo readable"
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (level, message) in &self.checks {
+            let level = match level {
+                Level::Ok => "ok",
+                Level::Warning => "warn",
+                Level::Error => "error",
+            };
+            let _ = writeln!(out, "{level:<5} {message}");
+        }
+        out
+    }
+
+    /// The config file read, a warning for each key of no option, left out
+    pub fn config(&mut self, path: &Path, unknown: &[(usize, String)]) {
+        self.ok(format!("config: {} read", path.display()));
+        for (line, key) in unknown {
+            self.warning(format!(
+                "config: {}:{line}: unknown key `{key}`, left out",
+                path.display()
+            ));
+        }
+    }
+
+    /// mem, shown by every bar
+    pub fn memory(&mut self, paths: &Paths) {
+        let path = paths.resolve(MEMINFO_PATH);
+        if Memory::new(&path).collect().is_some() {
+            self.ok(format!("mem: {} readable", path.display()));
+        } else {
+            self.error(format!("mem: unable to read {}", path.display()));
+        }
+    }
+
+    /// bat, left out without batteries as on a desktop
+    pub fn batteries(&mut self, paths: &Paths) {
+        let power_supply = paths.resolve(POWER_SUPPLY_PATH);
+        let found = modules::batteries(&power_supply);
+        if found.is_empty() {
+            self.warning(format!(
+                "bat: no battery in {}, bat hidden",
+                power_supply.display()
+            ));
+        } else {
+            self.ok(format!("bat: found {}", found.join(", ")));
+        }
+    }
+
+    /// The interfaces of each pattern of `--interface`, an error for those matching none, such
+    /// as a typo
+    pub fn interfaces(&mut self, selection: &Selection, paths: &Paths) {
+        let sys_net = paths.resolve(SYS_NET_PATH);
+        let names = match names(&sys_net) {
+            Ok(names) => names,
+            Err(e) => {
+                self.error(format!("net: unable to list {}: {e}", sys_net.display()));
+                return;
+            }
+        };
+        if selection.is_empty() {
+            self.ok("net: no --interface, net hidden");
+        }
+        for pattern in selection.patterns() {
+            let matched: Vec<&str> = names
+                .iter()
+                .filter(|name| pattern.matches(name))
+                .map(String::as_str)
+                .collect();
+            if matched.is_empty() {
+                self.error(format!(
+                    "net: --interface {pattern} matches none of {}",
+                    names.join(", ")
+                ));
+            } else {
+                self.ok(format!(
+                    "net: --interface {pattern} matches {}",
+                    matched.join(", ")
+                ));
+            }
+        }
+    }
+
+    /// DISPLAY and xsetroot, needed by `--output xsetroot`
+    pub fn display(&mut self, required: bool) {
+        match Xsetroot::check() {
+            Ok(()) => self.ok("X: DISPLAY set and xsetroot installed"),
+            Err(e) if required => self.error(format!("X: {e}")),
+            Err(e) => self.warning(format!("X: {e}")),
+        }
+    }
+
+    /// The power supplies, hwmon chips, thermal zones, and backlights, a warning for those
+    /// unreadable
+    pub fn sensors(&mut self, paths: &Paths, excluded: &[String]) {
+        let listing = sensors::listing(paths, excluded);
+        if listing.is_empty() {
+            self.warning("sensors: none found");
+        }
+        for line in listing.lines() {
+            if line.contains("error(") {
+                self.warning(format!("sensors: {line}"));
+            } else {
+                self.ok(format!("sensors: {line}"));
+            }
+        }
+    }
+
+    /// lid, from `--lid`
+    pub fn lid(&mut self, paths: &Paths) {
+        if lid_detected(paths) {
+            self.ok("lid: found");
+        } else {
+            self.error("lid: no lid device found");
+        }
+    }
+
+    /// oom, from `--oom`, left out without the counter
+    pub fn oom(&mut self, paths: &Paths) {
+        let vmstat = paths.resolve(VMSTAT_PATH);
+        match Oom::new(&vmstat) {
+            Ok(Some(_)) => self.ok(format!("oom: oom_kill in {}", vmstat.display())),
+            Ok(None) => self.warning(format!(
+                "oom: no oom_kill in {}, before Linux 4.13, oom hidden",
+                vmstat.display()
+            )),
+            Err(e) => self.warning(format!("oom: {}: {e}, oom hidden", vmstat.display())),
+        }
+    }
+
+    /// A file of an option, such as `--todo`
+    pub fn file(&mut self, option: &str, path: &Path) {
+        match std::fs::metadata(path) {
+            Ok(_) => self.ok(format!("{option}: {} found", path.display())),
+            Err(e) => self.error(format!("{option}: {}: {e}", path.display())),
+        }
+    }
+}
+
+/// Sorted names of the interfaces, the directories of `sys_net`
+fn names(sys_net: &Path) -> std::io::Result<Vec<String>> {
+    let mut names = std::fs::read_dir(sys_net)?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    names.sort_unstable();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> Paths {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        Paths {
+            sysfs: fixtures.join("sys"),
+            procfs: fixtures.join("proc"),
+        }
+    }
+
+    #[test]
+    fn interface_typo() {
+        let selection = Selection::new(&["lo".into(), "wlna0".into()], &[]).unwrap();
+        let mut report = Report::default();
+        report.interfaces(&selection, &fixtures());
+        assert!(report.failed());
+        assert_eq!(
+            report.render(),
+            "ok    net: --interface lo matches lo\n\
+             error net: --interface wlna0 matches none of lo\n"
+        );
+    }
+
+    #[test]
+    fn fixture_tree() {
+        let mut report = Report::default();
+        report.memory(&fixtures());
+        report.batteries(&fixtures());
+        assert!(!report.failed());
+        assert_eq!(
+            report.render(),
+            format!(
+                "ok    mem: {} readable\n\
+                 ok    bat: found BAT0, BAT1\n",
+                fixtures().procfs.join("meminfo").display()
+            )
+        );
+
+        let missing = Paths {
+            sysfs: "/nonexistent".into(),
+            procfs: "/nonexistent".into(),
+        };
+        let mut report = Report::default();
+        report.memory(&missing);
+        report.batteries(&missing);
+        assert!(report.failed());
+    }
+
+    #[test]
+    fn unknown_config_keys() {
+        let mut report = Report::default();
+        report.config(Path::new("bar.conf"), &[(3, "colour".to_string())]);
+        assert!(!report.failed());
+        assert_eq!(
+            report.render(),
+            "ok    config: bar.conf read\n\
+             warn  config: bar.conf:3: unknown key `colour`, left out\n"
+        );
+    }
+
+    #[test]
+    fn oom_counter() {
+        let mut report = Report::default();
+        report.oom(&fixtures());
+        // before Linux 4.13
+        report.oom(&Paths {
+            procfs: fixtures().procfs.with_file_name("proc-2.6"),
+            ..fixtures()
+        });
+        assert!(!report.failed());
+        let render = report.render();
+        assert!(render.starts_with("ok    oom: oom_kill in "), "{render}");
+        assert!(render.contains("\nwarn  oom: "), "{render}");
+    }
+}
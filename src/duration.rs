@@ -0,0 +1,124 @@
+//! Parsing and display of human durations such as `90s`, `15m`, `6h`, `3d`
+
+use std::time::Duration;
+
+/// clap value parser for durations: a number followed by one of `s`, `m`, `h`, `d`. Zero is
+/// rejected, the intervals and timeouts it configures spinning without a wait.
+pub fn parse(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`, expected e.g. 30s, 15m, 6h, 2d"))?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => {
+            return Err(format!(
+                "invalid duration unit `{unit}`, expected one of s, m, h, d"
+            ))
+        }
+    };
+    if value == 0 {
+        return Err(format!("invalid duration `{s}`, expected more than 0"));
+    }
+    value
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid duration `{s}`, too long"))
+}
+
+/// Time until the display of `age` by [`format_age`] changes
+pub fn until_next_change(age: Duration) -> Duration {
+    let unit = match age.as_secs() {
+        0..=59 => 1,
+        60..=3599 => 60,
+        3600..=86399 => 3600,
+        _ => 86400,
+    };
+    Duration::from_secs((age.as_secs() / unit + 1) * unit) - age
+}
+
+/// Time until the display of a `remaining` time by [`format_age`] changes, counting down
+pub fn until_countdown_change(remaining: Duration) -> Duration {
+    let unit = match remaining.as_secs() {
+        0..=59 => 1,
+        60..=3599 => 60,
+        3600..=86399 => 3600,
+        _ => 86400,
+    };
+    Duration::from_secs(remaining.as_secs() % unit + 1)
+}
+
+/// Compact display of an age, using the largest whole unit: `45s`, `25m`, `6h`, `3d`
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(parse("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse(" 90s "), Ok(Duration::from_secs(90)));
+        assert_eq!(parse("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse("6h"), Ok(Duration::from_secs(21_600)));
+        assert_eq!(parse("3d"), Ok(Duration::from_secs(259_200)));
+        assert!(parse("3w").unwrap_err().contains("unit `w`"));
+        assert!(parse("m").is_err());
+        assert!(parse("-5s").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_zero_and_overflow() {
+        assert!(parse("0s").unwrap_err().contains("more than 0"));
+        assert!(parse("0d").is_err());
+        assert!(parse("18446744073709551615d")
+            .unwrap_err()
+            .contains("too long"));
+        assert!(parse("18446744073709551616s").is_err());
+        assert_eq!(
+            parse("18446744073709551615s"),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn ages() {
+        let secs = Duration::from_secs;
+        assert_eq!(format_age(secs(0)), "0s");
+        assert_eq!(format_age(secs(59)), "59s");
+        assert_eq!(format_age(secs(60)), "1m");
+        assert_eq!(format_age(secs(3599)), "59m");
+        assert_eq!(format_age(secs(3600)), "1h");
+        assert_eq!(format_age(secs(86_399)), "23h");
+        assert_eq!(format_age(secs(3 * 86_400 + 5)), "3d");
+    }
+
+    #[test]
+    fn next_change_of_the_age() {
+        let secs = Duration::from_secs;
+        assert_eq!(
+            until_next_change(Duration::from_millis(400)),
+            Duration::from_millis(600)
+        );
+        assert_eq!(until_next_change(secs(59)), secs(1));
+        // 1m until 2m
+        assert_eq!(until_next_change(secs(90)), secs(30));
+        assert_eq!(until_next_change(secs(3599)), secs(1));
+        assert_eq!(until_next_change(secs(3600)), secs(3600));
+        assert_eq!(until_next_change(secs(86_400 + 60)), secs(86_400 - 60));
+        assert_eq!(format_age(secs(90) + until_next_change(secs(90))), "2m");
+    }
+}
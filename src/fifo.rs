@@ -0,0 +1,109 @@
+//! Writing the status to a named pipe, for `tail -f` style readers such as polybar
+
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::path::PathBuf;
+
+use crate::output::Output;
+
+pub struct Fifo {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl Fifo {
+    /// Create the FIFO at `path` if missing, returning whether it was created
+    pub fn create(path: PathBuf) -> io::Result<(Self, bool)> {
+        let created = match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_fifo() => false,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and isn't a FIFO", path.display()),
+                ))
+            }
+            Err(_) => {
+                let c_path = CString::new(path.as_os_str().as_bytes())?;
+                // SAFETY: c_path is a valid nul terminated string
+                if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                true
+            }
+        };
+
+        Ok((Self { path, file: None }, created))
+    }
+}
+
+impl Output for Fifo {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    /// Write `status` as a line. Without a reader attached the update is dropped, so this
+    /// never blocks.
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        if self.file.is_none() {
+            // opening for writing fails with ENXIO instead of blocking while there's no reader
+            match OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&self.path)
+            {
+                Ok(file) => self.file = Some(file),
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Some(file) = &mut self.file {
+            // EPIPE (the reader went away) closes the pipe until a reader returns. EAGAIN
+            // (a reader not keeping up) only drops this update.
+            match writeln!(file, "{status}") {
+                Err(e) if e.kind() != io::ErrorKind::WouldBlock => self.file = None,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn written_to_a_reader() {
+        let dir = std::env::temp_dir().join(format!("statusbar-fifo-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("status");
+
+        let (mut fifo, created) = Fifo::create(path.clone()).unwrap();
+        assert!(created);
+        assert!(!Fifo::create(path.clone()).unwrap().1);
+        // dropped without a reader
+        fifo.write("cpu 01%").unwrap();
+
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .unwrap();
+        fifo.write("cpu 02%").unwrap();
+        let mut line = [0; 64];
+        let len = reader.read(&mut line).unwrap();
+        assert_eq!(&line[..len], b"cpu 02%\n");
+
+        let file = dir.join("file");
+        std::fs::write(&file, "").unwrap();
+        let e = Fifo::create(file).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+//! The non-ASCII characters of the segments, such as the arrows of traffic or the degree sign of
+//! temp, from the set of `--glyphs` with the overrides of `--glyph`
+//!
+//! Bitmap fonts render most of them as tofu, so every module goes through [`Glyphs`] rather than
+//! writing one of its own.
+
+use clap::ValueEnum;
+
+/// A set of glyphs of `--glyphs`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Set {
+    /// plain ASCII, such as `v` and `^` for the traffic
+    Ascii,
+    /// symbols of common fonts, such as `↓` and `↑`
+    #[default]
+    Unicode,
+    /// icons of Nerd Fonts
+    Nerd,
+}
+
+/// A glyph of a segment, overridden with `--glyph name=glyph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Glyph {
+    /// before the bytes received of traffic
+    Down,
+    /// before the bytes sent of traffic
+    Up,
+    /// after a temperature
+    Degree,
+    /// between the stored and compressed sizes of zram
+    Arrow,
+    /// after the charge of a battery held at its charge limit
+    Capped,
+    /// before an unsynced clock
+    Warning,
+    /// after the charge of an ups on mains power
+    Ac,
+    /// before a camera or microphone in use
+    InUse,
+    /// before the charge of a mouse
+    Mouse,
+    /// before the charge of a keyboard
+    Keyboard,
+    /// at the end of a truncated text
+    Ellipsis,
+}
+
+impl Glyph {
+    fn get(self, set: Set) -> &'static str {
+        match (self, set) {
+            (Self::Down, Set::Ascii) => "v",
+            (Self::Down, Set::Unicode) => "↓",
+            (Self::Down, Set::Nerd) => "\u{f063}",
+            (Self::Up, Set::Ascii) => "^",
+            (Self::Up, Set::Unicode) => "↑",
+            (Self::Up, Set::Nerd) => "\u{f062}",
+            (Self::Degree, Set::Ascii) => "deg",
+            (Self::Degree, _) => "°",
+            (Self::Arrow, Set::Ascii) => "->",
+            (Self::Arrow, Set::Unicode) => "→",
+            (Self::Arrow, Set::Nerd) => "\u{f061}",
+            (Self::Capped, Set::Ascii) => " capped",
+            (Self::Capped, Set::Unicode) => "⏸",
+            (Self::Capped, Set::Nerd) => "\u{f04c}",
+            (Self::Warning, Set::Ascii) => "!",
+            (Self::Warning, Set::Unicode) => "⚠",
+            (Self::Warning, Set::Nerd) => "\u{f071}",
+            (Self::Ac, Set::Ascii) => "ac",
+            (Self::Ac, Set::Unicode) => "⚡",
+            (Self::Ac, Set::Nerd) => "\u{f0e7}",
+            (Self::InUse, Set::Ascii) => "*",
+            (Self::InUse, Set::Unicode) => "●",
+            (Self::InUse, Set::Nerd) => "\u{f111}",
+            (Self::Mouse, Set::Ascii) => "mouse",
+            (Self::Mouse, Set::Unicode) => "🖱",
+            (Self::Mouse, Set::Nerd) => "\u{f245}",
+            (Self::Keyboard, Set::Ascii) => "kbd",
+            (Self::Keyboard, Set::Unicode) => "⌨",
+            (Self::Keyboard, Set::Nerd) => "\u{f11c}",
+            (Self::Ellipsis, Set::Ascii) => "...",
+            (Self::Ellipsis, _) => "…",
+        }
+    }
+}
+
+/// clap value parser for `--glyph name=glyph`
+pub fn parse_glyph(s: &str) -> Result<(Glyph, String), String> {
+    let (name, glyph) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid glyph `{s}`, expected name=glyph"))?;
+    let name = Glyph::from_str(name, false).map_err(|_| {
+        let names: Vec<String> = Glyph::value_variants()
+            .iter()
+            .filter_map(|glyph| Some(glyph.to_possible_value()?.get_name().to_string()))
+            .collect();
+        format!(
+            "unknown glyph `{name}`, expected one of: {}",
+            names.join(", ")
+        )
+    })?;
+    Ok((name, glyph.to_string()))
+}
+
+/// The glyphs of a set, some of them overridden
+#[derive(Debug, Clone, Default)]
+pub struct Glyphs {
+    set: Set,
+    /// the last for a glyph overridden twice
+    overrides: Vec<(Glyph, String)>,
+}
+
+impl Glyphs {
+    pub fn new(set: Set, overrides: Vec<(Glyph, String)>) -> Self {
+        Self { set, overrides }
+    }
+
+    pub fn get(&self, glyph: Glyph) -> &str {
+        self.overrides
+            .iter()
+            .rev()
+            .find(|(overridden, _)| *overridden == glyph)
+            .map_or(glyph.get(self.set), |(_, text)| text)
+    }
+
+    /// Whether only ASCII is output, for the labels that are words rather than glyphs such as
+    /// the script of an input method
+    pub fn ascii(&self) -> bool {
+        self.set == Set::Ascii
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_set_is_ascii() {
+        let ascii = Glyphs::new(Set::Ascii, vec![]);
+        for glyph in Glyph::value_variants() {
+            assert!(ascii.get(*glyph).is_ascii(), "{glyph:?}");
+            assert!(!Glyphs::default().get(*glyph).is_ascii(), "{glyph:?}");
+        }
+    }
+
+    #[test]
+    fn overrides() {
+        let glyphs = Glyphs::new(
+            Set::Nerd,
+            vec![
+                parse_glyph("degree=C").unwrap(),
+                parse_glyph("in-use=REC ").unwrap(),
+                parse_glyph("degree=°C").unwrap(),
+            ],
+        );
+        assert_eq!(glyphs.get(Glyph::Degree), "°C");
+        assert_eq!(glyphs.get(Glyph::InUse), "REC ");
+        assert_eq!(glyphs.get(Glyph::Up), "\u{f062}");
+        assert!(parse_glyph("degrees=C")
+            .unwrap_err()
+            .contains("expected one of: down, up, degree"));
+        assert!(parse_glyph("degree").is_err());
+    }
+}
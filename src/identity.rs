@@ -0,0 +1,90 @@
+//! Host and user names of the `host` and `user` segments, falling back on other sources where
+//! sysinfo has none, such as in minimal containers and sandboxes
+
+use std::path::Path;
+
+/// Host name as set for the running kernel
+const KERNEL_HOSTNAME: &str = "/proc/sys/kernel/hostname";
+
+/// Accounts, for the name of the effective user
+const PASSWD: &str = "/etc/passwd";
+
+/// Shown when no source has a host name
+const UNKNOWN: &str = "unknown";
+
+/// Host name from sysinfo, else the kernel, else `HOSTNAME`, else "unknown"
+pub fn host_name(sysinfo: Option<String>) -> String {
+    host_name_from(
+        sysinfo,
+        Path::new(KERNEL_HOSTNAME),
+        std::env::var("HOSTNAME").ok(),
+    )
+}
+
+fn host_name_from(sysinfo: Option<String>, kernel: &Path, env: Option<String>) -> String {
+    let kernel = std::fs::read_to_string(kernel).ok();
+    first([sysinfo, kernel, env]).unwrap_or_else(|| UNKNOWN.to_string())
+}
+
+/// User name from sysinfo, else the account of the effective user, else `USER` or `LOGNAME`
+pub fn user_name(sysinfo: Option<String>) -> Option<String> {
+    // SAFETY: plain syscall, which can't fail
+    let uid = unsafe { libc::geteuid() };
+    let passwd = std::fs::read_to_string(PASSWD).ok();
+    let account = passwd.and_then(|passwd| account_name(&passwd, uid).map(str::to_string));
+    let env = |name| std::env::var(name).ok();
+    first([sysinfo, account, env("USER"), env("LOGNAME")])
+}
+
+/// Name of the account of `uid` in `passwd`, of `name:password:uid:gid:...` lines
+fn account_name(passwd: &str, uid: u32) -> Option<&str> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let id = fields.nth(1)?;
+        (id.parse() == Ok(uid)).then_some(name)
+    })
+}
+
+/// First non-empty name of `names`, trimmed of whitespace such as a trailing newline
+fn first(names: impl IntoIterator<Item = Option<String>>) -> Option<String> {
+    names
+        .into_iter()
+        .flatten()
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MISSING: &str = "/nonexistent/hostname";
+
+    #[test]
+    fn host_name_fallbacks() {
+        let dir = std::env::temp_dir().join(format!("statusbar-identity-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let kernel = dir.join("hostname");
+        std::fs::write(&kernel, "box\n").unwrap();
+
+        let sysinfo = Some("vm".to_string());
+        let env = Some("container".to_string());
+        assert_eq!(host_name_from(sysinfo, &kernel, env.clone()), "vm");
+        assert_eq!(host_name_from(None, &kernel, env.clone()), "box");
+        assert_eq!(host_name_from(Some(String::new()), &kernel, None), "box");
+        assert_eq!(host_name_from(None, Path::new(MISSING), env), "container");
+        assert_eq!(host_name_from(None, Path::new(MISSING), None), "unknown");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn account_of_uid() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+            # comment\n\
+            wcampbell:x:1000:1000::/home/wcampbell:/bin/fish\n";
+        assert_eq!(account_name(passwd, 0), Some("root"));
+        assert_eq!(account_name(passwd, 1000), Some("wcampbell"));
+        assert_eq!(account_name(passwd, 1001), None);
+    }
+}
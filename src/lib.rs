@@ -0,0 +1,90 @@
+//! Status bar for dwm and other bars, the modules and outputs behind the `statusbar` binary
+
+// stable only, rust-version in Cargo.toml is the MSRV
+#![forbid(unstable_features)]
+
+pub mod cache;
+pub mod clicks;
+pub mod command;
+pub mod config;
+pub mod control;
+pub mod doctor;
+pub mod duration;
+pub mod fifo;
+pub mod glyphs;
+pub mod identity;
+pub mod locale;
+pub mod lock;
+pub mod logging;
+pub mod marquee;
+pub mod metrics;
+pub mod modules;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod netlink;
+pub mod notify;
+pub mod output;
+pub mod pages;
+pub mod paths;
+pub mod pattern;
+pub mod percent;
+pub mod profile;
+pub mod record;
+pub mod replay;
+pub mod sanitize;
+pub mod sensors;
+#[cfg(feature = "dbus")]
+pub mod service;
+pub mod signal;
+pub mod smoothing;
+pub mod status_file;
+pub mod store;
+pub mod style;
+pub mod systemd;
+pub mod throughput;
+pub mod tmux;
+pub mod truncate;
+pub mod uevent;
+
+/// Names of the segments, as used by options configuring them per module
+pub const MODULES: &[&str] = &[
+    "prefix",
+    "host",
+    "user",
+    "cpu",
+    "temp",
+    "throttle",
+    "mem",
+    "swap",
+    "zram",
+    "cgroup",
+    "net",
+    "nm",
+    "traffic",
+    "tcp",
+    "fd",
+    "bat",
+    "oom",
+    "readonly",
+    "smart",
+    "todo",
+    "backup",
+    "timer",
+    "calendar",
+    "snapshot",
+    "flatpak",
+    "cert",
+    "http",
+    "offline",
+    "ntp",
+    "ups",
+    "lid",
+    "idle",
+    "displays",
+    "peripherals",
+    "im",
+    "privacy",
+    "sink",
+    "time",
+    "page",
+];
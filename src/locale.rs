@@ -0,0 +1,55 @@
+//! Locale of `--time-locale` for the names and formats of the time, such as `Dienstag, 20. Feb`
+//! of `%A, %e. %b` in de_DE, or `20.02.2024` of `%x`
+//!
+//! The data of the locales comes with chrono, so a locale works whether installed or not. It
+//! applies to the names of weekdays and months, to `%p`, and to the formats of the locale, `%c`,
+//! `%x`, `%X`, and `%r`, chrono's English without a locale.
+
+pub use chrono::Locale;
+
+/// Locales of the C library, whose names are those of chrono
+const UNLOCALIZED: &[&str] = &["C", "POSIX", "C.utf8", "C.UTF-8"];
+
+/// The locale of the environment for times, of LC_ALL or else LC_TIME, `None` for the C
+/// locale. LANG alone is left out, for bars to stay the same unless asked.
+pub fn from_env() -> Option<String> {
+    ["LC_ALL", "LC_TIME"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|locale| !locale.is_empty())
+        .filter(|locale| !UNLOCALIZED.contains(&locale.as_str()))
+}
+
+/// The locale of chrono of `locale`, such as `de_DE.UTF-8` or one of `locale -a`
+pub fn load(locale: &str) -> Result<Locale, String> {
+    Locale::try_from(identifier(locale).as_str())
+        .map_err(|_| format!("unknown locale `{locale}`, expected such as de_DE.UTF-8"))
+}
+
+/// `de_DE.UTF-8` as `de_DE` of chrono, the codeset left out and the modifier kept
+fn identifier(locale: &str) -> String {
+    let (name, modifier) = locale
+        .split_once('@')
+        .map_or((locale, None), |(name, modifier)| (name, Some(modifier)));
+    let language = name.split_once('.').map_or(name, |(language, _)| language);
+    match modifier {
+        Some(modifier) => format!("{language}@{modifier}"),
+        None => language.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_identifiers() {
+        assert_eq!(identifier("de_DE.UTF-8"), "de_DE");
+        assert_eq!(identifier("de_DE.utf8"), "de_DE");
+        assert_eq!(identifier("sr_RS.UTF-8@latin"), "sr_RS@latin");
+        assert_eq!(identifier("POSIX"), "POSIX");
+        assert_eq!(load("de_DE.UTF-8"), Ok(Locale::de_DE));
+        assert_eq!(load("sr_RS.UTF-8@latin"), Ok(Locale::sr_RS_latin));
+        assert!(load("xx_XX.UTF-8").is_err());
+    }
+}
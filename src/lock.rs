@@ -0,0 +1,172 @@
+//! Single instance lock, an flock on a file holding the pid of its owner
+//!
+//! The lock is released by the kernel when the owner exits, however it exits, so a file left
+//! behind by a crash is simply locked again. The owner removes the file on a clean exit, which
+//! an instance waiting on the lock checks for once it has it.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Time for the replaced instance to exit
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+const REPLACE_POLL: Duration = Duration::from_millis(50);
+
+/// `$XDG_RUNTIME_DIR/statusbar.lock`, or `statusbar-<name>.lock` for instances that can run
+/// alongside others, such as one per waybar module
+pub fn default_path(name: Option<&str>) -> Option<PathBuf> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")?;
+    let file_name = match name {
+        Some(name) => format!("statusbar-{name}.lock"),
+        None => "statusbar.lock".to_string(),
+    };
+    Some(Path::new(&runtime_dir).join(file_name))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// locked by another instance, its pid if written
+    Held(Option<i32>),
+    /// the other instance didn't exit in time after SIGTERM
+    NotReplaced(i32),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Held(Some(pid)) => write!(f, "another instance is running (pid {pid})"),
+            Self::Held(None) => write!(f, "another instance is running"),
+            Self::NotReplaced(pid) => write!(
+                f,
+                "the running instance (pid {pid}) didn't exit within {}s",
+                REPLACE_TIMEOUT.as_secs()
+            ),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Lock `path` for as long as the returned file is open, writing our pid into it. With
+/// `replace`, the instance holding it is sent SIGTERM and waited for.
+pub fn acquire(path: &Path, replace: bool) -> Result<File, Error> {
+    loop {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        if !try_lock(&file)? {
+            let pid = read_pid(&mut file);
+            let Some(pid) = pid.filter(|_| replace) else {
+                return Err(Error::Held(pid));
+            };
+            // SAFETY: plain syscall
+            if unsafe { libc::kill(pid, libc::SIGTERM) } < 0 {
+                return Err(Error::Io(io::Error::last_os_error()));
+            }
+            let start = Instant::now();
+            while !try_lock(&file)? {
+                if start.elapsed() >= REPLACE_TIMEOUT {
+                    return Err(Error::NotReplaced(pid));
+                }
+                std::thread::sleep(REPLACE_POLL);
+            }
+        }
+
+        // removed by the previous owner while waiting, another instance could lock a new file
+        // at the same path
+        if !is_at(&file, path)? {
+            continue;
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())?;
+        return Ok(file);
+    }
+}
+
+/// Whether `file` is still the file at `path`
+fn is_at(file: &File, path: &Path) -> io::Result<bool> {
+    let opened = file.metadata()?;
+    match std::fs::metadata(path) {
+        Ok(current) => Ok(current.dev() == opened.dev() && current.ino() == opened.ino()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Take the exclusive lock without blocking, returning whether taken
+fn try_lock(file: &File) -> io::Result<bool> {
+    // SAFETY: plain syscall on an open fd
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => Ok(false),
+        _ => Err(e),
+    }
+}
+
+/// Pid written by the owner, `None` while it hasn't written it yet
+fn read_pid(file: &mut File) -> Option<i32> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok().filter(|pid| *pid > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_by_its_owner() {
+        let dir = std::env::temp_dir().join(format!("statusbar-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("statusbar.lock");
+
+        let lock = acquire(&path, false).unwrap();
+        let pid = std::process::id();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("{pid}\n"));
+        match acquire(&path, false) {
+            Err(Error::Held(Some(held))) => assert_eq!(held as u32, pid),
+            other => panic!("{other:?}"),
+        }
+
+        // a crash leaving the file behind
+        drop(lock);
+        let mut lock = acquire(&path, false).unwrap();
+        assert_eq!(read_pid(&mut lock), Some(pid as i32));
+        lock.set_len(0).unwrap();
+        assert_eq!(read_pid(&mut lock), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            Error::Held(Some(42)).to_string(),
+            "another instance is running (pid 42)"
+        );
+        assert_eq!(Error::Held(None).to_string(), "another instance is running");
+        assert_eq!(
+            Error::NotReplaced(42).to_string(),
+            "the running instance (pid 42) didn't exit within 5s"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Logging to stderr or `--log-file`, and throttling of failures repeated on every tick
+//!
+//! Lines are `LEVEL module: message`, the module being the target without the crate prefix, or
+//! the name of the module given as target by the collectors of main. Lines written to a file are
+//! prefixed with the local time, stderr usually being timestamped by the service manager.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Interval of the summaries of a [`Throttle`]
+const THROTTLE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            Self::Error => LevelFilter::Error,
+            Self::Warn => LevelFilter::Warn,
+            Self::Info => LevelFilter::Info,
+            Self::Debug => LevelFilter::Debug,
+            Self::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+struct Logger {
+    level: LevelFilter,
+    /// `None` for stderr
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // the debug output of dependencies, such as every netlink message, would drown ours
+        let ours = record
+            .module_path()
+            .is_some_and(|path| path.split("::").next() == Some("statusbar"));
+        if !ours && record.level() > Level::Warn {
+            return;
+        }
+        let target = record.target();
+        let target = target.strip_prefix("statusbar::").unwrap_or(target);
+        let line = format!("{} {target}: {}\n", record.level(), record.args());
+
+        // nowhere left to report failing to log
+        match &self.file {
+            Some(file) => {
+                let now = chrono::Local::now().format("%FT%T%.3f%:z");
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = write!(file, "{now} {line}");
+            }
+            None => {
+                let _ = io::stderr().write_all(line.as_bytes());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        }
+    }
+}
+
+/// Log from `level`, appending to `file` if given or else to stderr
+pub fn init(level: LogLevel, file: Option<&Path>) -> io::Result<()> {
+    let file = match file {
+        Some(path) => Some(Mutex::new(
+            OpenOptions::new().create(true).append(true).open(path)?,
+        )),
+        None => None,
+    };
+    let logger = Logger {
+        level: level.filter(),
+        file,
+    };
+    log::set_logger(Box::leak(Box::new(logger))).map_err(|e| io::Error::other(e.to_string()))?;
+    log::set_max_level(level.filter());
+    Ok(())
+}
+
+/// Rate limit of a failure repeated on every tick, such as an unreadable sensor: the first is
+/// logged, then at most one every [`THROTTLE_INTERVAL`] summarizing those in between.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    /// when the last failure was logged, `None` while succeeding
+    logged: Option<Instant>,
+    suppressed: u64,
+}
+
+impl Throttle {
+    /// Count a failure, returning whether to log it now along with the suffix summarizing the
+    /// failures not logged since the last
+    pub fn failed(&mut self) -> Option<Suppressed> {
+        match self.logged {
+            Some(logged) if logged.elapsed() < THROTTLE_INTERVAL => {
+                self.suppressed += 1;
+                None
+            }
+            _ => {
+                self.logged = Some(Instant::now());
+                Some(Suppressed(std::mem::take(&mut self.suppressed)))
+            }
+        }
+    }
+
+    /// Mark a success, returning whether it was failing. The next failure is logged at once.
+    pub fn succeeded(&mut self) -> bool {
+        self.suppressed = 0;
+        self.logged.take().is_some()
+    }
+}
+
+/// Displayed after a message as " (N more in the last 60s)", or as nothing
+pub struct Suppressed(u64);
+
+impl fmt::Display for Suppressed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => Ok(()),
+            n => write!(
+                f,
+                " ({n} more in the last {}s)",
+                THROTTLE_INTERVAL.as_secs()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttle_summarizes() {
+        let mut throttle = Throttle::default();
+        assert_eq!(throttle.failed().unwrap().to_string(), "");
+        assert!(throttle.failed().is_none());
+        assert!(throttle.failed().is_none());
+
+        // as after the interval
+        throttle.logged = Some(Instant::now() - THROTTLE_INTERVAL);
+        assert_eq!(
+            throttle.failed().unwrap().to_string(),
+            " (2 more in the last 60s)"
+        );
+
+        assert!(throttle.succeeded());
+        assert!(!throttle.succeeded());
+        assert_eq!(throttle.failed().unwrap().to_string(), "");
+    }
+}
@@ -1,210 +1,1439 @@
-#![feature(string_remove_matches)]
-#![feature(let_chains)]
+// stable only, rust-version in Cargo.toml is the MSRV
+#![forbid(unstable_features)]
 
-use std::fmt::Write;
-use std::net::IpAddr;
-use std::path::Path;
-use std::process::Command;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex};
+use std::thread::{Scope, ScopedJoinHandle};
 use std::time::Duration;
 
-use chrono::{DateTime, Local};
-use clap::Parser;
+use chrono::format::StrftimeItems;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCandidates;
 use local_ip_address::list_afinet_netifas;
-use sysinfo::{get_current_pid, ProcessExt, System, SystemExt, UserExt};
+use log::{error, info, warn};
+use statusbar::config::Config;
+use statusbar::fifo::Fifo;
+use statusbar::glyphs::{self, Glyph, Glyphs};
+use statusbar::locale::Locale;
+use statusbar::logging::{self, LogLevel};
+use statusbar::marquee::{self};
+use statusbar::metrics::{self, Metrics};
+#[cfg(feature = "dbus")]
+use statusbar::modules::ImBackend;
+use statusbar::modules::{
+    self, parse_sink_alias, CpuField, DataCapPeriod, MemMode, Module, NetMode, Selection, Sensor,
+    SnapshotBackend, UpsBackend, CPU_FIELDS,
+};
+#[cfg(feature = "network")]
+use statusbar::modules::{CertTarget, HttpCheck, HttpMethod};
+#[cfg(feature = "mqtt")]
+use statusbar::mqtt;
+use statusbar::output::{self, Lemonbar, Output, Outputs, Segment};
+use statusbar::pages::Page;
+use statusbar::paths::Paths;
+use statusbar::pattern::Pattern;
+#[cfg(feature = "dbus")]
+use statusbar::service;
+use statusbar::status_file::StatusFile;
+use statusbar::store::Store;
+use statusbar::style::{self, Colors};
+use statusbar::tmux::Tmux;
+use statusbar::{
+    cache, clicks, config, control, doctor, duration, identity, locale, lock, profile, record,
+    replay, sensors, signal, smoothing, systemd, MODULES,
+};
+use sysinfo::{
+    get_current_pid, ProcessExt, ProcessRefreshKind, RefreshKind, System, SystemExt, UserExt,
+};
 
-const BAT0_PATH: &str = "/sys/class/power_supply/BAT0/capacity";
-const BAT1_PATH: &str = "/sys/class/power_supply/BAT1/capacity";
+mod completions;
+mod registry;
+mod render;
 
 #[derive(Debug, Parser)]
 #[command(version)]
 struct Cli {
-    /// network interface for display of ip addresses
-    #[arg(long)]
+    /// network interface for display of ip addresses, or a glob pattern of them such as "wl*",
+    /// or "all" for every interface with an address other than loopback. Followed by an alias
+    /// such as "wlan0=wifi" to label its addresses with `--net-labels`.
+    #[arg(long, add = ArgValueCandidates::new(completions::interfaces))]
     interface: Vec<String>,
 
+    /// label the addresses with their interface, when of more than one
+    #[arg(long)]
+    net_labels: bool,
+
+    /// which interfaces of `--interface` to show the addresses of
+    #[arg(long, value_enum, default_value_t = NetMode::All)]
+    net_mode: NetMode,
+
+    /// glob pattern of the interfaces left out of `--interface`, such as "veth*"
+    #[arg(long, add = ArgValueCandidates::new(completions::interfaces))]
+    exclude_interface: Vec<String>,
+
+    /// hide net when the interfaces have no address, instead of showing "net down"
+    #[arg(long)]
+    hide_net_down: bool,
+
+    /// show the bytes received and sent today by the interfaces of `--interface`, or all but
+    /// loopback without, such as "today ↓4.2G ↑310.0M". Saved every minute to
+    /// $XDG_STATE_HOME/statusbar/traffic.json, and reset at midnight.
+    #[arg(long)]
+    traffic: bool,
+
+    /// bytes `--traffic` may receive and send in `--data-cap-period`, such as "50G", adding the
+    /// percentage used. Styled from 75% as a warning and 90% as critical, unless set by
+    /// `--warning` and `--critical`.
+    #[arg(long, value_parser = record::parse_size, requires = "traffic")]
+    data_cap: Option<u64>,
+
+    /// period of `--data-cap`
+    #[arg(long, value_enum, default_value_t = DataCapPeriod::Month)]
+    data_cap_period: DataCapPeriod,
+
+    /// send a desktop notification with notify-send from 90% of `--data-cap`, once per period
+    #[arg(long, requires = "data_cap")]
+    data_cap_notify: bool,
+
+    /// show the mount points of `--readonly-mount` remounted read-only, such as "FS RO: /home"
+    /// when a dying disk is remounted by ext4
+    #[arg(long)]
+    readonly: bool,
+
+    /// glob pattern of the mount points checked by `--readonly`, repeat for several
+    #[arg(long, value_parser = Pattern::new, default_values = ["/", "/home"], requires = "readonly", add = ArgValueCandidates::new(completions::mount_points))]
+    readonly_mount: Vec<Pattern>,
+
+    /// glob pattern of the mount points read-only on purpose, such as "/usr" or bind mounts,
+    /// left out of `--readonly`
+    #[arg(long, value_parser = Pattern::new, requires = "readonly", add = ArgValueCandidates::new(completions::mount_points))]
+    readonly_exclude: Vec<Pattern>,
+
+    /// send a desktop notification with notify-send when a mount point of `--readonly` becomes
+    /// read-only
+    #[arg(long, requires = "readonly")]
+    readonly_notify: bool,
+
+    /// disk whose SMART health is checked with `smartctl -H`, such as "/dev/nvme0n1", repeat
+    /// for several. Shows "SMART FAIL: nvme0n1" while one fails, nothing while they pass.
+    #[arg(long)]
+    smart_device: Vec<PathBuf>,
+
+    /// mount point whose disk is checked like `--smart-device`, such as "/"
+    #[arg(long, add = ArgValueCandidates::new(completions::mount_points))]
+    smart_mount: Vec<PathBuf>,
+
+    /// time between checks of `--smart-device` and `--smart-mount`
+    #[arg(long, value_parser = duration::parse, default_value = "30m")]
+    smart_interval: Duration,
+
+    /// send a desktop notification with notify-send when a disk starts failing its SMART check
+    #[arg(long)]
+    smart_notify: bool,
+
+    /// temperature of a hwmon channel as "chip/channel:label", such as "k10temp/temp1:cpu",
+    /// repeat for several, shown as "cpu 62° gpu 71°". `--warning temp=80` styles from 80° of
+    /// the hottest. The chips and channels are listed by `--list-sensors`.
+    #[arg(long, value_parser = Sensor::parse)]
+    temp: Vec<Sensor>,
+
+    /// temperature of an ACPI thermal zone as "type:label", such as "x86_pkg_temp:cpu", for
+    /// machines without a hwmon channel of the CPU, shown among those of `--temp`. Zones are
+    /// matched by type, as their numbering changes across boots; an index such as "3" is pinned
+    /// to the type of that zone at startup. The zones are listed by `--list-sensors`.
+    #[arg(long, value_parser = Sensor::parse_zone)]
+    thermal_zone: Vec<Sensor>,
+
+    /// type of thermal zones never read, such as "acpitz" of a dead sensor stuck at 26.8°,
+    /// repeat for several
+    #[arg(long)]
+    thermal_zone_exclude: Vec<String>,
+
+    /// show "THROTTLE" while the CPU is thermally throttled, from the throttling counters of
+    /// Intel CPUs, or elsewhere its frequency staying well below its maximum under load
+    #[arg(long)]
+    throttle: bool,
+
+    /// time `--throttle` stays shown after the last throttling
+    #[arg(long, value_parser = duration::parse, default_value = "30s")]
+    throttle_quiet: Duration,
+
+    /// show the rates of pages swapped in and out, such as "swp in:12.0M/s out:4.0M/s", hidden
+    /// while not swapping
+    #[arg(long)]
+    swap: bool,
+
+    /// show "swp 0" while not swapping instead of hiding `--swap`
+    #[arg(long, requires = "swap")]
+    swap_show_idle: bool,
+
+    /// show the compression of the pages swapped to zram, such as "zram 1.9G→640.0M (3.0x)", or
+    /// to zswap without a zram device. Hidden without either.
+    #[arg(long)]
+    zram: bool,
+
+    /// show the cpu and memory use of a cgroup, such as "build: cpu 340% mem 6.1G", as a
+    /// systemd unit such as build.slice, or a path below /sys/fs/cgroup. Hidden while the
+    /// cgroup doesn't exist. Needs cgroup v2.
+    #[arg(long)]
+    cgroup: Option<String>,
+
+    /// show the established TCP connections and the listening sockets, such as "tcp 42/12",
+    /// updated every 5s
+    #[arg(long)]
+    tcp: bool,
+
+    /// count only the connections to this remote port, and the sockets listening on it
+    #[arg(long, requires = "tcp")]
+    tcp_filter_port: Option<u16>,
+
+    /// show the file descriptors in use out of the system maximum, such as "fd 63%", from 50%
+    /// unless `--show-above fd=` says otherwise, as a warning from 70% and critical from 90%
+    #[arg(long)]
+    fd: bool,
+
+    /// also show the descriptors of the bar itself out of its RLIMIT_NOFILE, such as
+    /// "fd 3% self 85%", the fuller of both styling the segment
+    #[arg(long, requires = "fd")]
+    fd_self: bool,
+
+    /// show the kills of the OOM killer since the bar started, such as "oom 1", from the first
+    /// kill. Left out on kernels before Linux 4.13, without the counter.
+    #[arg(long)]
+    oom: bool,
+
+    /// hide `--oom` this long after the last kill, such as "1h", instead of until restarting
+    #[arg(long, value_parser = duration::parse, requires = "oom")]
+    oom_reset_after: Option<Duration>,
+
+    /// send a desktop notification with notify-send naming the time of each OOM kill
+    #[arg(long, requires = "oom")]
+    oom_notify: bool,
+
     /// override return from first user in sys.users()
     #[arg(long)]
     username: Option<String>,
+
+    /// override the host name, otherwise from the system and read again every minute
+    #[arg(long)]
+    hostname: Option<String>,
+
+    /// only show the host name up to its first dot, such as "ws03" of "ws03.corp.example.com"
+    #[arg(long)]
+    hostname_short: bool,
+
+    /// replace "[{host}][{user}] => " before the other segments, empty to remove it. Shown as
+    /// the prefix module, which can be ordered and hidden as the others.
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// strftime format of the time, such as "%H:%M"
+    #[arg(long, default_value = modules::TIME_FORMAT)]
+    time_format: String,
+
+    /// leave the seconds out of the time, such as "09:04" of "%H:%M:%S", updating it once a
+    /// minute. Formats showing them otherwise, such as "%s", or "%X" with `--time-locale`, are
+    /// rejected
+    #[arg(long)]
+    clock_no_seconds: bool,
+
+    /// blank the ":" between the hours, minutes, and seconds of the time every other second,
+    /// showing that the bar isn't frozen
+    #[arg(long)]
+    clock_blink: bool,
+
+    /// strftime format of the date, shown after a click on the time
+    #[arg(long, default_value = modules::DATE_FORMAT)]
+    date_format: String,
+
+    /// locale of `--time-format` and `--date-format`, such as "de_DE.UTF-8": the names of
+    /// weekdays and months, %p, and its own formats of %c, %x, %X, and %r. LC_ALL or LC_TIME by
+    /// default, English without.
+    #[arg(long)]
+    time_locale: Option<String>,
+
+    /// only output ASCII, the same as `--glyphs ascii`
+    #[arg(long, conflicts_with = "glyphs")]
+    ascii: bool,
+
+    /// set of the glyphs of the segments, such as the arrows of `--traffic`: ascii for bitmap
+    /// fonts, or nerd for the icons of Nerd Fonts
+    #[arg(long, value_enum, default_value_t)]
+    glyphs: glyphs::Set,
+
+    /// replace a glyph of `--glyphs`, such as "degree=C" or "in-use=REC ". One of down, up,
+    /// degree, arrow, capped, warning, ac, in-use, mouse, keyboard, or ellipsis.
+    #[arg(long, value_parser = glyphs::parse_glyph)]
+    glyph: Vec<(Glyph, String)>,
+
+    /// todo.txt file to display the pending task count of
+    #[arg(long)]
+    todo: Option<PathBuf>,
+
+    /// only count todo.txt tasks containing this token, such as "@work"
+    #[arg(long, requires = "todo")]
+    todo_filter: Option<String>,
+
+    /// file touched after each backup, its mtime is displayed as the backup age
+    #[arg(long, conflicts_with = "backup_restic")]
+    backup_stamp: Option<PathBuf>,
+
+    /// display the age of the newest restic snapshot, using the RESTIC_* environment
+    #[arg(long)]
+    backup_restic: bool,
+
+    /// interval between `restic snapshots` runs
+    #[arg(long, value_parser = duration::parse, default_value = "1h")]
+    backup_interval: Duration,
+
+    /// backup age beyond which it is marked with `!`
+    #[arg(long, value_parser = duration::parse, default_value = "2d")]
+    backup_warn: Duration,
+
+    /// calendar to display the next event of, such as "next: standup 10:00" then "standup in
+    /// 12m" within the hour, as an .ics file or a directory of them, repeatable. Events
+    /// recurring other than daily or weekly are left out.
+    #[arg(long, value_name = "PATH")]
+    ics: Vec<PathBuf>,
+
+    /// time ahead within which the next event of `--ics` is shown, hidden without any
+    #[arg(long, value_parser = duration::parse, default_value = "12h", requires = "ics")]
+    ics_lookahead: Duration,
+
+    /// systemd timer to display the time until it next elapses, such as "backup in 3h" of
+    /// backup.timer, from the system or the user manager, repeatable. Marked with `!` while
+    /// inactive, left out when on neither.
+    #[cfg(feature = "dbus")]
+    #[arg(long, value_name = "UNIT")]
+    timer: Vec<String>,
+
+    /// display the age of the newest filesystem snapshot, such as "snap 2h"
+    #[arg(long)]
+    snapshot_backend: Option<SnapshotBackend>,
+
+    /// snapper config of `--snapshot-backend snapper`
+    #[arg(long, default_value = "root")]
+    snapshot_config: String,
+
+    /// directory of the snapshots of `--snapshot-backend timeshift`
+    #[arg(long, default_value = "/timeshift/snapshots")]
+    snapshot_path: PathBuf,
+
+    /// interval between `snapper list` runs
+    #[arg(long, value_parser = duration::parse, default_value = "15m")]
+    snapshot_interval: Duration,
+
+    /// snapshot age beyond which it is marked with `!`
+    #[arg(long, value_parser = duration::parse, default_value = "1d")]
+    snapshot_warn: Duration,
+
+    /// display the number of flatpak apps with an update, such as "fp 3", checked every
+    /// `--updates-interval`. Hidden without flatpak installed.
+    #[arg(long)]
+    flatpak_updates: bool,
+
+    /// time between the checks for updates
+    #[arg(long, value_parser = duration::parse, default_value = "1h")]
+    updates_interval: Duration,
+
+    /// host whose TLS certificate expiry is checked, as "host[:port]" with the port defaulting
+    /// to 443, repeat for several. Shows "cert example.com 12d" within `--cert-warn-days` of
+    /// expiring, and "cert example.com ?" when the check fails.
+    #[cfg(feature = "network")]
+    #[arg(long, value_parser = CertTarget::parse)]
+    cert_check: Vec<CertTarget>,
+
+    /// days before expiring from which a certificate of `--cert-check` is displayed
+    #[cfg(feature = "network")]
+    #[arg(long, default_value_t = 21)]
+    cert_warn_days: i64,
+
+    /// time between checks of `--cert-check`
+    #[cfg(feature = "network")]
+    #[arg(long, value_parser = duration::parse, default_value = "1h")]
+    cert_interval: Duration,
+
+    /// endpoint checked every minute as "name:url", with ":status" appended to expect that
+    /// status instead of any 2xx or 3xx, such as "grafana:https://nas/api:401". Repeat for
+    /// several. Shows "down: grafana" after two failed checks in a row. Needs curl.
+    #[cfg(feature = "network")]
+    #[arg(long, value_parser = HttpCheck::parse)]
+    http_check: Vec<HttpCheck>,
+
+    /// request method of `--http-check`
+    #[cfg(feature = "network")]
+    #[arg(long, value_enum, default_value_t)]
+    http_method: HttpMethod,
+
+    /// detect being offline, as without a default route, meanwhile pausing and hiding the
+    /// modules that reach remote hosts (flatpak, cert, and http), which update at once when back
+    /// online
+    #[arg(long)]
+    offline: bool,
+
+    /// text of the segment shown while offline, empty for none
+    #[arg(long, default_value = "offline", requires = "offline")]
+    offline_marker: String,
+
+    /// display a warning when the clock isn't NTP synchronized, according to timedated
+    #[cfg(feature = "dbus")]
+    #[arg(long)]
+    ntp: bool,
+
+    /// also display the clock offset reported by `chronyc tracking`
+    #[cfg(feature = "dbus")]
+    #[arg(long, requires = "ntp")]
+    ntp_offset: bool,
+
+    /// display the charge and status of a UPS, through the given backend
+    #[arg(long)]
+    ups_backend: Option<UpsBackend>,
+
+    /// apcupsd host:port, or NUT ups name [default: localhost:3551 or ups@localhost]
+    #[arg(long, requires = "ups_backend")]
+    ups_name: Option<String>,
+
+    /// display `lid closed` while the laptop lid is closed
+    #[arg(long)]
+    lid: bool,
+
+    /// display the number of connected displays, from RandR
+    #[cfg(feature = "x11")]
+    #[arg(long)]
+    displays: bool,
+
+    /// list the names of connected displays, instead of their number
+    #[cfg(feature = "x11")]
+    #[arg(long, requires = "displays")]
+    displays_names: bool,
+
+    /// display the name of the active NetworkManager connection, such as "HomeWifi", followed by
+    /// its VPNs, and "connecting…" while activating. Hidden while NetworkManager doesn't run.
+    #[cfg(feature = "dbus")]
+    #[arg(long)]
+    nm: bool,
+
+    /// display the idle time of the session from `--idle-floor`, such as "idle 4m", from X or
+    /// the screen saver of KDE or GNOME on Wayland. Hidden without either.
+    #[arg(long)]
+    idle: bool,
+
+    /// idle time below which `--idle` is hidden, so it only shows while away
+    #[arg(long, value_parser = duration::parse, default_value = "1m", requires = "idle")]
+    idle_floor: Duration,
+
+    /// display the battery of wireless mice and keyboards, from UPower
+    #[cfg(feature = "dbus")]
+    #[arg(long)]
+    peripherals: bool,
+
+    /// display the active input method of fcitx5 or IBus, such as "拼" for pinyin or "us" for
+    /// a keyboard layout, hidden while neither runs
+    #[cfg(feature = "dbus")]
+    #[arg(long)]
+    im_backend: Option<ImBackend>,
+
+    /// display `●cam` and `●mic` while the camera or microphone is in use
+    #[arg(long)]
+    privacy_indicators: bool,
+
+    /// display the default audio output device
+    #[arg(long)]
+    sink: bool,
+
+    /// name to display for an audio output, such as "alsa_output.pci-0000:00:1f.3.hdmi-stereo=tv"
+    #[arg(long, value_parser = parse_sink_alias, requires = "sink")]
+    sink_alias: Vec<(String, String)>,
+
+    /// maximum width of a segment, such as "sink=20", longer text scrolls
+    #[arg(long, value_parser = marquee::parse_max_width)]
+    max_width: Vec<(String, usize)>,
+
+    /// separator between repetitions of scrolling text
+    #[arg(long, default_value = "   ")]
+    scroll_gap: String,
+
+    /// truncate segments wider than their maximum width, instead of scrolling them
+    #[arg(long)]
+    no_scroll: bool,
+
+    /// maximum width of a whole segment, such as "cpu=10,net=30", longer segments are truncated
+    #[arg(long, value_parser = marquee::parse_max_width, value_delimiter = ',')]
+    max: Vec<(String, usize)>,
+
+    /// maximum width of the status, segments are dropped by `--priority` to fit
+    #[arg(long)]
+    max_length: Option<usize>,
+
+    /// modules kept longest to fit `--max-length`, highest first; unlisted modules are dropped
+    /// first, from the right
+    #[arg(long, value_parser = parse_module, add = ArgValueCandidates::new(completions::modules), value_delimiter = ',')]
+    priority: Vec<String>,
+
+    /// modules of a page, such as "cpu,mem,bat", repeat for each page. The bar rotates between
+    /// the pages with a "1/2" indicator, showing modules in no page, such as time, on every page.
+    #[arg(long, value_parser = Page::parse)]
+    page: Vec<Page>,
+
+    /// time each `--page` is shown before the next, also shown by clicking the indicator or the
+    /// page command of `--control`
+    #[arg(long, value_parser = duration::parse, default_value = "5s")]
+    page_interval: Duration,
+
+    /// format and destination of the status, repeat to write to several outputs. Without it,
+    /// i3bar under sway, xsetroot under X, and stdout otherwise.
+    #[arg(long, value_enum)]
+    output: Vec<output::Format>,
+
+    /// command run when the module is clicked in i3bar, such as "sink=pavucontrol". Without
+    /// one, clicking time switches to the date, net to the throughput, and page to the next page.
+    #[arg(long, value_parser = clicks::parse_on_click)]
+    on_click: Vec<(String, String)>,
+
+    /// list the interfaces with their addresses, one per line such as
+    /// "eth0 ipv4 inet:192.0.2.2 inet6:fe80::1", then exit. The second field is "ipv4" for those
+    /// displayed by `--interface`, else "-".
+    #[arg(long)]
+    list_interfaces: bool,
+
+    /// list the power supplies, hwmon chips with their temp and fan channels, thermal zones with
+    /// those of `--thermal-zone-exclude` marked, and backlights in sysfs, then exit
+    #[arg(long)]
+    list_sensors: bool,
+
+    /// print the status in the format of the first `--output` once and exit, without writing
+    /// to the output
+    #[arg(long)]
+    once: bool,
+
+    /// mark the segments of `--output xsetroot` for clicks with the dwm statuscmd patch, using
+    /// the ids: cpu=1, mem=2, net=3, bat=4, todo=5, backup=6, ntp=7, ups=8, lid=9, displays=10,
+    /// peripherals=11, privacy=12, sink=13, time=14, host, user, and prefix=15, page=16, traffic=17,
+    /// oom=18, swap=19, readonly=20, smart=21,
+    /// throttle=22, temp=23, im=24, flatpak=25,
+    /// snapshot=26, cert=27, http=28, offline=29, zram=30, cgroup=31, tcp=32, fd=33, nm=34, timer=35, idle=36, calendar=37
+    #[arg(long)]
+    statuscmd: bool,
+
+    /// value of a module from which it is displayed as a warning, such as "cpu=70"
+    #[arg(long, value_parser = style::parse_threshold)]
+    warning: Vec<(String, f64)>,
+
+    /// value of a module from which it is displayed as critical, such as "bat=10"
+    #[arg(long, value_parser = style::parse_threshold)]
+    critical: Vec<(String, f64)>,
+
+    /// modules flashing while critical, alternating between "!! bat [04%] !!" and the text
+    /// without the marks every second, such as "bat,cpu"
+    #[arg(long, value_parser = parse_module, add = ArgValueCandidates::new(completions::modules), value_delimiter = ',')]
+    critical_flash: Vec<String>,
+
+    /// value of a module from which it is shown, hidden below, such as "cpu=20"
+    #[arg(long, value_parser = style::parse_threshold)]
+    show_above: Vec<(String, f64)>,
+
+    /// value of a module up to which it is shown, hidden above, such as "bat=30"
+    #[arg(long, value_parser = style::parse_threshold)]
+    show_below: Vec<(String, f64)>,
+
+    /// write "[paused]" once when paused by SIGUSR2, instead of leaving the last status
+    #[arg(long)]
+    pause_marker: bool,
+
+    /// written when stopped by SIGTERM, SIGINT, or the quit command, instead of clearing the bar
+    #[arg(long, default_value = "")]
+    exit_text: String,
+
+    /// foreground color of warnings
+    #[arg(long, value_parser = style::parse_color, default_value = "#ffb52a")]
+    warning_color: String,
+
+    /// background color of warnings
+    #[arg(long, value_parser = style::parse_color)]
+    warning_background: Option<String>,
+
+    /// foreground color of critical values
+    #[arg(long, value_parser = style::parse_color, default_value = "#fb4934")]
+    critical_color: String,
+
+    /// background color of critical values
+    #[arg(long, value_parser = style::parse_color)]
+    critical_background: Option<String>,
+
+    /// display "n/a" for modules without data, such as net without an address, instead of
+    /// hiding them
+    #[arg(long)]
+    keep_empty: bool,
+
+    /// width of the percentages of cpu, mem, and bat, zero padded so the bar doesn't shift as
+    /// they change magnitude
+    #[arg(long, default_value_t = 2)]
+    percent_width: usize,
+
+    /// decimals of the percentages of cpu, mem, and bat
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=1))]
+    percent_decimals: u8,
+
+    /// what counts as used memory
+    #[arg(long, value_enum, default_value_t)]
+    mem_mode: MemMode,
+
+    /// display the cpu usage as a moving average, the weight of the latest sample such as 0.3,
+    /// so it doesn't strobe across the thresholds. Only the display is smoothed, not the metrics.
+    #[arg(long, value_parser = smoothing::parse_alpha)]
+    cpu_smoothing: Option<f64>,
+
+    /// display the cpu time by kind from /proc/stat, such as "cpu u37 s8 io12 st0" for user,
+    /// system, iowait, and steal, to tell a busy machine from one waiting on IO or stolen by its
+    /// hypervisor. The busy time, without idle and iowait, styles the segment.
+    #[arg(long, conflicts_with = "cpu_smoothing")]
+    cpu_detail: bool,
+
+    /// kinds of cpu time of `--cpu-detail`, in order
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = CPU_FIELDS.to_vec(), requires = "cpu_detail")]
+    cpu_fields: Vec<CpuField>,
+
+    /// keep displaying a battery capacity until it moves by more than this many points, such as
+    /// 1 for one flapping between 79 and 80
+    #[arg(long)]
+    bat_hysteresis: Option<f64>,
+
+    /// display the charge limit of batteries with one, such as "bat [62% cap 80%]". A battery held
+    /// at its limit on AC shows as "80%⏸" either way.
+    #[arg(long)]
+    battery_show_limit: bool,
+
+    /// order of the segments, such as "cpu,mem,bat,net,time", unlisted modules follow in their
+    /// default order
+    #[arg(long, value_parser = parse_module, add = ArgValueCandidates::new(completions::modules), value_delimiter = ',')]
+    order: Vec<String>,
+
+    /// modules in the left section of lemonbar
+    #[arg(long, value_parser = parse_module, add = ArgValueCandidates::new(completions::modules), value_delimiter = ',')]
+    lemonbar_left: Vec<String>,
+
+    /// modules in the center section of lemonbar
+    #[arg(long, value_parser = parse_module, add = ArgValueCandidates::new(completions::modules), value_delimiter = ',')]
+    lemonbar_center: Vec<String>,
+
+    /// modules in the right section of lemonbar, along with modules not in any section
+    #[arg(long, value_parser = parse_module, add = ArgValueCandidates::new(completions::modules), value_delimiter = ',')]
+    lemonbar_right: Vec<String>,
+
+    /// modules on the bottom bar of the dwm extrabar patch, in this order, such as "net,bat".
+    /// `--output xsetroot` writes the others, `--extrabar-separator`, then these.
+    #[arg(long, value_parser = parse_module, add = ArgValueCandidates::new(completions::modules), value_delimiter = ',')]
+    bottom: Vec<String>,
+
+    /// character on which the extrabar patch splits the status into the top and bottom bars,
+    /// left out of the segments
+    #[arg(long, default_value_t = ';', requires = "bottom")]
+    extrabar_separator: char,
+
+    /// pixels of padding between segments in dzen2
+    #[arg(long, default_value_t = 10)]
+    dzen2_padding: u32,
+
+    /// only output this module in waybar, for running an instance per module
+    #[arg(long, value_parser = parse_module, add = ArgValueCandidates::new(completions::modules))]
+    waybar_module: Option<String>,
+
+    /// named pipe for `--output fifo`, created if missing
+    #[arg(long, required_if_eq("output", "fifo"))]
+    fifo_path: Option<PathBuf>,
+
+    /// file for `--output file`, its directory is created if missing
+    #[arg(long, required_if_eq("output", "file"))]
+    file_path: Option<PathBuf>,
+
+    /// end the status written by `--output file` with a newline
+    #[arg(long)]
+    file_append_newline: bool,
+
+    /// socket of the tmux server for `--output tmux`, instead of the default server
+    #[arg(long)]
+    tmux_socket: Option<PathBuf>,
+
+    /// address to serve prometheus metrics of the displayed values on, such as 127.0.0.1:9920
+    #[arg(long)]
+    metrics_listen: Option<SocketAddr>,
+
+    /// MQTT broker to publish the displayed values to, such as "mqtt://nas:1883", each module
+    /// on a retained topic of its own such as "<prefix>/cpu" and "<prefix>/bat/BAT0"
+    #[cfg(feature = "mqtt")]
+    #[arg(long, value_parser = mqtt::Broker::parse)]
+    mqtt_broker: Option<mqtt::Broker>,
+
+    /// topic prefix of `--mqtt-broker`, "statusbar/<hostname>" by default. "<prefix>/status" is
+    /// "online", or "offline" once the bar exits or loses its connection.
+    #[cfg(feature = "mqtt")]
+    #[arg(long, requires = "mqtt_broker")]
+    mqtt_prefix: Option<String>,
+
+    /// append the numeric values of the modules to this file at most once a second, such as to
+    /// graph the battery drain over a day. CSV only has the percentages of cpu, mem, and bat.
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    /// format of `--record`
+    #[arg(long, value_enum, default_value_t = record::Format::Csv)]
+    record_format: record::Format,
+
+    /// size from which `--record` is moved to "<path>.1", replacing the previous one, such as
+    /// "512K" or "10M"
+    #[arg(long, value_parser = record::parse_size, default_value = "10M")]
+    record_max_size: u64,
+
+    /// append every segment as published by its module, and the files the modules read to
+    /// collect them, to this JSON Lines file, to replay with `--replay`
+    #[arg(long)]
+    record_raw: Option<PathBuf>,
+
+    /// render the segments of a `--record-raw` file instead of collecting any, with the flags
+    /// shaping them such as the thresholds and order, then exit. The statuses go to stdout, as
+    /// the outputs writing there or plain lines.
+    #[arg(long, conflicts_with_all = ["once", "record_raw"])]
+    replay: Option<PathBuf>,
+
+    /// pace of `--replay`, such as 1 for the recorded pace or 10 for ten times as fast, each
+    /// segment as soon as the previous is rendered with 0
+    #[arg(long, value_parser = replay::parse_speed, default_value_t = 0.0, requires = "replay")]
+    replay_speed: f64,
+
+    /// accept commands on a unix socket: status, json, refresh, toggle <module>, page, pause,
+    /// stats, quit
+    #[arg(long)]
+    control: bool,
+
+    /// path of the control socket [default: $XDG_RUNTIME_DIR/statusbar.sock]
+    #[arg(long, requires = "control")]
+    control_socket: Option<PathBuf>,
+
+    /// time the collection of each module and the render of each status, logged every minute
+    /// with `--log-level info` and replied to the `stats` command of `--control`
+    #[arg(long)]
+    profile: bool,
+
+    /// fraction of its interval from which a module collecting slower on average is warned about
+    #[arg(long, value_parser = profile::parse_fraction, default_value_t = 0.5, requires = "profile")]
+    profile_warn: f64,
+
+    /// own `dev.wcampbell.statusbar` on the session bus, exposing the status and values
+    #[cfg(feature = "dbus")]
+    #[arg(long)]
+    dbus_service: bool,
+
+    /// terminate the running instance and take over, instead of exiting
+    #[arg(long)]
+    replace: bool,
+
+    /// level from which messages are logged
+    #[arg(long, value_enum, default_value_t = LogLevel::Warn)]
+    log_level: LogLevel,
+
+    /// directory read instead of /sys, such as a fixture tree in tests
+    #[arg(long, hide = true, default_value = "/sys")]
+    sysfs_root: PathBuf,
+
+    /// directory read instead of /proc, such as a fixture tree in tests
+    #[arg(long, hide = true, default_value = "/proc")]
+    procfs_root: PathBuf,
+
+    /// append the log to this file, instead of writing it to stderr
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// file of options read before those of the command line, which replace them, such as
+    /// `time_format = "%H:%M"` or `cpu.show_above = 20` for `--show-above cpu=20`, a line each.
+    /// Read again on SIGHUP, restarting the modules, while the outputs and the options of the
+    /// sockets, services, and recordings need a restart. $XDG_CONFIG_HOME/statusbar/config by
+    /// default, if it exists.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn main() {
-    let args = Cli::parse();
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// write the completion script of a shell to stdout, such as for
+    /// `statusbar completions fish | source`
+    Completions {
+        shell: clap_complete::Shell,
 
-    // test optional features
-    let battery_00_enable = Path::new(BAT0_PATH).exists();
-    let battery_01_enable = Path::new(BAT1_PATH).exists();
+        /// only complete the options, without calling statusbar for values such as the
+        /// interfaces
+        #[arg(long = "static")]
+        static_script: bool,
+    },
 
-    // start
-    let (ip_addresses_tx, ip_addresses_rx) = channel();
-    let (bat0_tx, bat0_rx) = channel();
-    let (bat1_tx, bat1_rx) = channel();
-    let (mem_tx, mem_rx) = channel();
-    let (cpu_tx, cpu_rx) = channel();
-    let m_sys = Arc::new(Mutex::new(System::new_all()));
+    /// run the detection steps for the given options, such as
+    /// `statusbar --interface wlan0 --lid doctor`, print a report, and exit nonzero if something
+    /// they need is missing
+    Doctor,
+}
 
-    // First call to sys functions, grabbing host_name and user name, and also ip addresses
-    let (sys_host_name, sys_user_name) = {
-        let mut sys = m_sys.lock().unwrap();
+/// clap value parser for the name of a module
+fn parse_module(s: &str) -> Result<String, String> {
+    if MODULES.contains(&s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "unknown module `{s}`, expected one of: {}",
+            MODULES.join(", ")
+        ))
+    }
+}
 
-        sys.refresh_all();
+/// Options of `command_line` after those of the config file, of `--config` or the default one if
+/// it exists, along with the file
+fn parse(command_line: &[OsString]) -> Result<(Cli, Option<(PathBuf, Config)>), clap::Error> {
+    let path = config::given(command_line.get(1..).unwrap_or_default())
+        .or_else(|| config::default_path().filter(|path| path.exists()));
+    let Some(path) = path else {
+        return Ok((Cli::try_parse_from(command_line)?, None));
+    };
+    let config = Config::read(&path, &Cli::command()).map_err(|e| {
+        Cli::command().error(
+            clap::error::ErrorKind::InvalidValue,
+            format!("invalid config {e}"),
+        )
+    })?;
+    let args = Cli::try_parse_from(config.args(command_line))?;
+    Ok((args, Some((path, config))))
+}
 
-        let pid = get_current_pid().unwrap();
+/// Warn about the keys of the config file matching no option, left out
+fn warn_unknown((path, config): &(PathBuf, Config)) {
+    for (line, key) in &config.unknown {
+        warn!("{}:{line}: unknown key `{key}`, left out", path.display());
+    }
+}
 
-        // overide sys.users()
-        let mut name = "";
-        if let Some(username) = &args.username {
-            name = username;
-        } else {
-            if let Some(process) = sys.process(pid) {
-                if let Some(user_id) = process.user_id() {
-                    name = sys.get_user_by_id(user_id).unwrap().name();
-                }
+fn paths(args: &Cli) -> Paths {
+    Paths {
+        sysfs: args.sysfs_root.clone(),
+        procfs: args.procfs_root.clone(),
+    }
+}
+
+fn glyphs(args: &Cli) -> Glyphs {
+    let set = if args.ascii {
+        glyphs::Set::Ascii
+    } else {
+        args.glyphs
+    };
+    Glyphs::new(set, args.glyph.clone())
+}
+
+/// The interfaces and the locale of the time of `args`, an error being of an invalid argument.
+/// A locale of the environment unknown to chrono only warns, bars staying the same.
+fn prepare(args: &Cli) -> Result<(Selection, Option<Locale>), String> {
+    let selection = Selection::new(&args.interface, &args.exclude_interface)
+        .map_err(|e| format!("invalid interface: {e}"))?;
+    for (flag, format) in [
+        ("--time-format", &args.time_format),
+        ("--date-format", &args.date_format),
+    ] {
+        if StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error) {
+            return Err(format!("invalid {flag} `{format}`"));
+        }
+    }
+    let time_locale = match (&args.time_locale, locale::from_env()) {
+        (Some(time_locale), _) => {
+            Some(locale::load(time_locale).map_err(|e| format!("invalid --time-locale: {e}"))?)
+        }
+        (None, Some(time_locale)) => locale::load(&time_locale)
+            .inspect_err(|e| warn!("{e}, the time in English"))
+            .ok(),
+        (None, None) => None,
+    };
+    Ok((selection, time_locale))
+}
+
+/// The modules of `args` along with their glyphs, `clicks` as for [`registry::modules`]
+fn build(args: &Cli, clicks: bool) -> Result<(Glyphs, Vec<Box<dyn Module>>), String> {
+    let (selection, time_locale) = prepare(args)?;
+    let glyphs = glyphs(args);
+    let modules = registry::modules(args, &paths(args), &glyphs, selection, time_locale, clicks)?;
+    Ok((glyphs, modules))
+}
+
+/// Modules scheduled on threads of a scope, until replaced on a reload
+struct Running<'scope> {
+    names: Vec<&'static str>,
+    stop: Arc<AtomicBool>,
+    lanes: Vec<ScopedJoinHandle<'scope, ()>>,
+}
+
+impl<'scope> Running<'scope> {
+    fn start<'env>(
+        scope: &'scope Scope<'scope, 'env>,
+        modules: Vec<Box<dyn Module>>,
+        once: bool,
+        cache: Option<&'env Path>,
+        store: &'env Store,
+    ) -> Self {
+        let names = modules.iter().map(|module| module.name()).collect();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let stopped = move || stopped.load(Ordering::Acquire);
+        let lanes = modules::run(scope, modules, once, cache, store, stopped);
+        Self { names, stop, lanes }
+    }
+
+    /// Stop the modules, returning their names once dropped
+    fn stop(self) -> Vec<&'static str> {
+        self.stop.store(true, Ordering::Release);
+        signal::wake();
+        for lane in self.lanes {
+            let _ = lane.join();
+        }
+        self.names
+    }
+}
+
+/// Report of the `doctor` subcommand, exiting 1 if something needed is missing
+fn doctor(args: &Cli, paths: &Paths, config: Option<&(PathBuf, Config)>) -> ! {
+    let mut report = doctor::Report::default();
+    if let Some((path, config)) = config {
+        report.config(path, &config.unknown);
+    }
+    report.memory(paths);
+    report.batteries(paths);
+    match Selection::new(&args.interface, &args.exclude_interface) {
+        Ok(selection) => report.interfaces(&selection, paths),
+        Err(e) => report.error(format!("net: invalid interface: {e}")),
+    }
+    report.display(args.output.contains(&output::Format::Xsetroot));
+    report.sensors(paths, &args.thermal_zone_exclude);
+    if args.lid {
+        report.lid(paths);
+    }
+    if args.oom {
+        report.oom(paths);
+    }
+    if let Some(todo) = &args.todo {
+        report.file("todo", todo);
+    }
+    if let Some(stamp) = &args.backup_stamp {
+        report.file("backup", stamp);
+    }
+    print!("{}", report.render());
+    std::process::exit(i32::from(report.failed()))
+}
+
+/// Exit with a usage error, as for an invalid argument
+fn exit_usage(message: String) -> ! {
+    Cli::command()
+        .error(clap::error::ErrorKind::ArgumentConflict, message)
+        .exit()
+}
+
+/// Print the interfaces of `--list-interfaces`, sorted by name
+fn list_interfaces() {
+    let network_interfaces = match list_afinet_netifas() {
+        Ok(network_interfaces) => network_interfaces,
+        Err(e) => {
+            error!("unable to list interfaces: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut names: Vec<&str> = network_interfaces.iter().map(|(name, _)| &**name).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    for name in names {
+        let mut addresses: Vec<&IpAddr> = vec![];
+        for (_, ip) in network_interfaces.iter().filter(|(n, _)| n == name) {
+            if !addresses.contains(&ip) {
+                addresses.push(ip);
             }
         }
+        let ipv4 = if addresses.iter().any(|ip| ip.is_ipv4()) {
+            "ipv4"
+        } else {
+            "-"
+        };
+
+        let mut line = format!("{name} {ipv4}");
+        for ip in addresses {
+            let family = if ip.is_ipv4() { "inet" } else { "inet6" };
+            line.push_str(&format!(" {family}:{ip}"));
+        }
+        println!("{line}");
+    }
+}
+
+fn main() {
+    completions::handle();
+    let command_line: Vec<OsString> = std::env::args_os().collect();
+    let (mut args, config) = parse(&command_line).unwrap_or_else(|e| e.exit());
+
+    if let Err(e) = logging::init(args.log_level, args.log_file.as_deref()) {
+        eprintln!("statusbar: unable to open --log-file: {e}");
+        std::process::exit(1);
+    }
+    if let Some(config) = &config {
+        warn_unknown(config);
+    }
+
+    // the statuses of a replay only go to stdout
+    if args.replay.is_some() {
+        args.output.retain(|format| format.is_stdout());
+        if args.output.is_empty() {
+            args.output.push(output::Format::Stdout);
+        }
+    }
+    if args.output.is_empty() {
+        let (format, reason) = output::Format::detect();
+        let name = format.to_possible_value().unwrap();
+        info!("--output {}, as {reason}", name.get_name());
+        args.output.push(format);
+    }
+
+    if let Some(Command::Completions {
+        shell,
+        static_script,
+    }) = &args.command
+    {
+        if let Err(e) = completions::write(*shell, *static_script) {
+            error!("unable to write completions: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.list_interfaces {
+        list_interfaces();
+        return;
+    }
+    let paths = paths(&args);
+    if args.list_sensors {
+        sensors::list(&paths, &args.thermal_zone_exclude);
+        return;
+    }
+    if let Some(Command::Doctor) = args.command {
+        doctor(&args, &paths, config.as_ref());
+    }
+
+    let mut formats = HashSet::new();
+    if let Some(format) = args.output.iter().find(|format| !formats.insert(**format)) {
+        let name = format.to_possible_value().unwrap();
+        exit_usage(format!("--output {} given more than once", name.get_name()));
+    }
+    if args
+        .output
+        .iter()
+        .filter(|format| format.is_stdout())
+        .count()
+        > 1
+    {
+        exit_usage(
+            "only one of --output lemonbar, dzen2, waybar, i3bar, or stdout can write to stdout"
+                .into(),
+        );
+    }
+
+    let (selection, time_locale) = prepare(&args).unwrap_or_else(|e| exit_usage(e));
+    let replay = args.replay.as_deref().map(|path| match replay::load(path) {
+        Ok(steps) => steps,
+        Err(e) => exit_usage(format!("invalid --replay: {e}")),
+    });
 
-        (sys.host_name().unwrap(), name.to_string())
+    if !args.once && args.output.contains(&output::Format::Xsetroot) {
+        if let Err(e) = output::Xsetroot::check() {
+            error!("{e}");
+            std::process::exit(1);
+        }
+    }
+
+    // a second instance would make the bar flicker between both statuses, a waybar module
+    // only excludes the instances of the same module
+    let _lock = match lock::default_path(args.waybar_module.as_deref()) {
+        _ if args.once || replay.is_some() => None,
+        Some(path) => match lock::acquire(&path, args.replace) {
+            Ok(lock) => {
+                signal::remove_on_exit(&path);
+                Some(lock)
+            }
+            Err(e @ lock::Error::Held(_)) => {
+                error!("{e}, pass --replace to take over");
+                std::process::exit(1);
+            }
+            Err(e) => {
+                error!("unable to lock {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        },
+        None => {
+            warn!("XDG_RUNTIME_DIR isn't set, not checking for another instance");
+            None
+        }
     };
 
-    // Thread updating every n seconds
-    std::thread::scope(|x| {
-        x.spawn(move || {
-            loop {
-                // Battery 0
-                if battery_00_enable {
-                    let mut bat0 = std::fs::read_to_string(BAT0_PATH).unwrap();
-                    bat0.remove_matches('\n');
-                    bat0_tx.send(bat0).unwrap();
-                }
+    if !args.once && replay.is_none() {
+        systemd::connect();
+    }
+
+    // `pkill -USR1 statusbar`, waybar signaling custom modules to refresh them, SIGHUP reloading
+    // the config file, and SIGTERM and SIGINT clearing it before exiting
+    signal::install();
 
-                // Battery 1
-                if battery_01_enable {
-                    let mut bat1 = std::fs::read_to_string(BAT1_PATH).unwrap();
-                    bat1.remove_matches('\n');
-                    bat1_tx.send(bat1).unwrap();
+    let colors = Colors {
+        warning: (args.warning_color.clone(), args.warning_background.clone()),
+        critical: (
+            args.critical_color.clone(),
+            args.critical_background.clone(),
+        ),
+    };
+    let outputs = args
+        .output
+        .iter()
+        // only composed as the first output, then printed
+        .take(if args.once { 1 } else { usize::MAX })
+        .map(|format| -> Box<dyn Output> {
+            match format {
+                _ if args.once && !format.is_stdout() => {
+                    let xsetroot = *format == output::Format::Xsetroot;
+                    Box::new(output::Stdout(Box::new(output::Xsetroot {
+                        statuscmd: args.statuscmd && xsetroot,
+                        bottom: if xsetroot {
+                            args.bottom.clone()
+                        } else {
+                            vec![]
+                        },
+                        separator: args.extrabar_separator,
+                    })))
+                }
+                output::Format::Xsetroot => Box::new(output::Xsetroot {
+                    statuscmd: args.statuscmd,
+                    bottom: args.bottom.clone(),
+                    separator: args.extrabar_separator,
+                }),
+                output::Format::Lemonbar => Box::new(Lemonbar {
+                    left: args.lemonbar_left.clone(),
+                    center: args.lemonbar_center.clone(),
+                    right: args.lemonbar_right.clone(),
+                    colors: colors.clone(),
+                }),
+                output::Format::Dzen2 => Box::new(output::Dzen2 {
+                    colors: colors.clone(),
+                    padding: args.dzen2_padding,
+                }),
+                output::Format::Waybar => Box::new(output::Waybar {
+                    module: args.waybar_module.clone(),
+                }),
+                // without the header of the infinite array
+                output::Format::I3bar if args.once => {
+                    Box::new(output::Stdout(Box::new(output::I3bar::new(colors.clone()))))
                 }
+                output::Format::I3bar => Box::new(output::I3bar::new(colors.clone())),
+                output::Format::Fifo => {
+                    let path = args.fifo_path.clone().unwrap();
+                    match Fifo::create(path.clone()) {
+                        Ok((fifo, created)) => {
+                            if created {
+                                signal::remove_on_exit(&path);
+                            }
+                            Box::new(fifo)
+                        }
+                        Err(e) => {
+                            error!("unable to create fifo {}: {e}", path.display());
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                output::Format::File => Box::new(StatusFile::new(
+                    args.file_path.clone().unwrap(),
+                    args.file_append_newline,
+                )),
+                output::Format::Tmux => Box::new(Tmux::new(args.tmux_socket.clone())),
+                output::Format::Stdout => Box::new(output::Plain),
+            }
+        })
+        .collect();
+    let outputs = Outputs::new(outputs);
+
+    let control = Arc::new(Mutex::new(control::State::default()));
+    let control_listener = if args.control {
+        let Some(path) = args.control_socket.clone().or_else(control::default_path) else {
+            error!("XDG_RUNTIME_DIR isn't set, pass --control-socket");
+            std::process::exit(1);
+        };
+        match control::bind(&path) {
+            Ok(listener) => Some(listener),
+            Err(e) => {
+                warn!(
+                    "unable to listen on {}, disabling control: {e}",
+                    path.display()
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let metrics = Arc::new(Mutex::new(Metrics::default()));
+    let metrics_listener = args.metrics_listen.map(|addr| match metrics::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("unable to serve metrics on {addr}: {e}");
+            std::process::exit(1);
+        }
+    });
+
+    #[cfg(feature = "dbus")]
+    let service_conn = if args.dbus_service {
+        match service::connect() {
+            Ok(conn) => Some(conn),
+            Err(zbus::Error::NameTaken) => {
+                error!(
+                    "{} is owned by another instance on the session bus",
+                    service::NAME
+                );
+                std::process::exit(1);
+            }
+            Err(e) => {
+                warn!("unable to connect to the session bus, disabling service: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
 
-                // Ram usage
-                let ram = std::fs::read_to_string("/proc/meminfo").unwrap();
-                let lines = &ram.split('\n').collect::<Vec<&str>>();
-                // Memory Total
-                let mem_total = lines[0].split_ascii_whitespace().collect::<Vec<&str>>();
-                let mem_total = mem_total[1].parse::<u64>().unwrap();
-                // Memory Free
-                let mem_free = lines[1].split_ascii_whitespace().collect::<Vec<&str>>();
-                let mem_free = mem_free[1].parse::<u64>().unwrap();
-
-                let memory_usage = mem_total / mem_free;
-                mem_tx.send(memory_usage).unwrap();
-
-                // Cpu Usage
-                let mut sys = m_sys.lock().unwrap();
-                sys.refresh_cpu();
-                let new_avg_cpu_usage: f32 = ((sys
-                    .cpus()
+    info!(
+        "features: dbus {}, x11 {}",
+        if cfg!(feature = "dbus") { "on" } else { "off" },
+        if cfg!(feature = "x11") { "on" } else { "off" }
+    );
+    match list_afinet_netifas() {
+        Ok(network_interfaces) => {
+            for pattern in selection.patterns() {
+                if network_interfaces
                     .iter()
-                    .map(sysinfo::CpuExt::cpu_usage)
-                    .sum::<f32>())
-                    / sys.cpus().len() as f32)
-                    .ceil();
-                cpu_tx.send(new_avg_cpu_usage).unwrap();
-                drop(sys);
-
-                std::thread::sleep(Duration::from_secs(1));
-
-                // Ip Address
-                let mut ip_addresses = vec![];
-                let network_interfaces = list_afinet_netifas().unwrap();
-                for (_, ip) in network_interfaces.iter().filter(|(name, ip)| {
-                    args.interface.iter().any(|a| *a == *name) && matches!(ip, IpAddr::V4(_))
-                }) {
-                    if !ip_addresses.iter().any(|x| x == &ip.to_string()) {
-                        ip_addresses.push(ip.to_string());
-                    }
+                    .any(|(name, _)| pattern.matches(name))
+                {
+                    info!(target: "net", "interface {pattern} found");
+                } else {
+                    warn!(target: "net", "interface {pattern} not found, yet");
                 }
+            }
+        }
+        Err(e) => warn!(target: "net", "unable to list interfaces: {e}"),
+    }
 
-                // create ip addresses string
-                let mut ip_addresses_string = "[".to_string();
-                for (i, address) in ip_addresses.iter().enumerate() {
-                    ip_addresses_string += &address.to_string();
+    let glyphs = glyphs(&args);
 
-                    if i != ip_addresses.len() - 1 {
-                        ip_addresses_string += ", ";
-                    }
+    // net only sampling the throughput for its view after a click
+    let clicks = formats.contains(&output::Format::I3bar);
+    let scheduled = registry::modules(&args, &paths, &glyphs, selection, time_locale, clicks)
+        .unwrap_or_else(|e| exit_usage(e));
+
+    // start
+    let store = Store::default();
+    let (clicks_tx, clicks_rx) = channel();
+    let (reloads_tx, reloads_rx) = channel();
+
+    // user name, only refreshing this process and the users, dropped afterwards
+    let sys_user_name = {
+        let mut sys = System::new_with_specifics(RefreshKind::new().with_users_list());
+        let pid = get_current_pid().ok();
+        if let Some(pid) = pid {
+            sys.refresh_process_specifics(pid, ProcessRefreshKind::new().with_user());
+        }
+
+        let user_name = match &args.username {
+            Some(username) => Some(username.clone()),
+            None => {
+                let user = pid
+                    .and_then(|pid| sys.process(pid))
+                    .and_then(|process| process.user_id())
+                    .and_then(|user_id| sys.get_user_by_id(user_id));
+                let user_name = identity::user_name(user.map(|user| user.name().to_string()));
+                if user_name.is_none() {
+                    warn!(target: "user", "unable to find the user of this process");
                 }
-                ip_addresses_string += "]";
-                ip_addresses_tx.send(ip_addresses_string).unwrap();
+                user_name
             }
+        };
+        user_name
+    };
+    // hidden rather than shown empty, even with --keep-empty, and left to the recording of a
+    // replay
+    if replay.is_none() {
+        store.set("user", sys_user_name.filter(|name| !name.is_empty()));
+    }
+    let started = store.generation();
+
+    // Thread updating every n seconds
+    #[cfg(feature = "mqtt")]
+    let mqtt_publisher = args.mqtt_broker.as_ref().map(|_| {
+        let prefix = args.mqtt_prefix.clone().unwrap_or_else(|| {
+            let host = identity::host_name(System::new().host_name());
+            format!("statusbar/{host}")
         });
+        mqtt::Publisher::new(prefix)
+    });
 
-        // X updater thread
-        x.spawn(move || {
+    // segments of slow modules, shown at once on a restart
+    let cache_dir = cache::default_dir();
 
-            // Status string
-            let mut last_bat0 = String::new();
-            let mut last_bat1 = String::new();
-            let mut last_mem_usage = 0;
-            let mut last_cpu_usage = 0.0;
-            let mut last_addrs = String::new();
+    let args = Arc::new(args);
+    let store = &store;
+    let glyphs = &glyphs;
+    let cache_dir = &cache_dir;
+    let command_line = &command_line;
+    std::thread::scope(|x| {
+        // Metrics, served from the values of the render thread
+        if let Some(listener) = metrics_listener {
+            let metrics = metrics.clone();
+            x.spawn(move || metrics::serve(listener, metrics));
+        }
 
-            let mut status = String::new();
+        // Control socket, shared with the render thread
+        if let Some(listener) = control_listener {
+            let control = control.clone();
+            x.spawn(move || control::serve(listener, control));
+        }
 
-            loop {
-                status.clear();
-                // Get the time and make the status message
-                let local: DateTime<Local> = Local::now();
+        // Recording, fed by the render thread
+        let recording = args.record.clone().map(|path| {
+            let (tx, rx) = channel();
+            let (format, max_size) = (args.record_format, args.record_max_size);
+            let recorder = x.spawn(move || record::serve(path, format, max_size, rx));
+            (tx, recorder)
+        });
 
-                // Battery
-                let mut battery_s = String::new();
-                if let Ok(bat0) = bat0_rx.try_recv() && battery_00_enable {
-                    last_bat0 = bat0.clone();
-                }
-                if !last_bat0.is_empty() {
-                    battery_s.push_str(&format!("{last_bat0}%"));
-                }
-                if let Ok(bat1) = bat1_rx.try_recv() && battery_01_enable {
-                    last_bat1 = bat1.clone();
-                }
-                if !last_bat1.is_empty() {
-                    battery_s.push_str(&format!(", {last_bat1}%"));
-                }
-                let battery_s = if battery_s.is_empty() {
-                    String::new()
-                } else {
-                    format!(" bat [{battery_s}],")
-                };
+        // Profile report, of the timings of the scheduler and the render thread
+        if args.profile {
+            profile::enable(args.profile_warn);
+            x.spawn(profile::report);
+        }
 
-                // Mem
-                if let Ok(mem_usage) = mem_rx.try_recv() {
-                    last_mem_usage = mem_usage;
-                }
+        // Raw recording, fed by the store and the scheduler
+        if let Some(path) = args.record_raw.clone() {
+            let (tx, rx) = channel();
+            replay::record_to(tx);
+            x.spawn(move || replay::serve(path, rx));
+        }
 
-                // Cpu
-                if let Ok(cpu_usage) = cpu_rx.try_recv() {
-                    last_cpu_usage = cpu_usage;
-                }
+        // D-Bus service, fed by the render thread
+        #[cfg(feature = "dbus")]
+        let service_tx = service_conn.map(|conn| {
+            let (tx, rx) = channel();
+            x.spawn(move || service::serve(conn, rx));
+            tx
+        });
 
-                // Ip
-                if let Ok(ip_addrs) = ip_addresses_rx.try_recv() {
-                    last_addrs = ip_addrs;
-                }
+        // MQTT, fed by the render thread
+        #[cfg(feature = "mqtt")]
+        if let (Some(broker), Some(publisher)) = (args.mqtt_broker.clone(), &mqtt_publisher) {
+            let prefix = publisher.prefix().to_string();
+            let queue = publisher.queue();
+            x.spawn(move || mqtt::run(broker, prefix, queue));
+        }
+
+        // Replay, instead of any module
+        let mut running = None;
+        if let Some(steps) = replay {
+            let speed = args.replay_speed;
+            x.spawn(move || replay::replay(steps, speed, store));
+        } else {
+            // Clicks, read from i3bar
+            if clicks {
+                let commands = args.on_click.clone();
+                x.spawn(move || clicks::read(commands, clicks_tx));
+            }
 
-                write!(
-                    status,
-                    "[{sys_host_name}][{sys_user_name}] => cpu {last_cpu_usage:02}%, mem {last_mem_usage:02}%, net {last_addrs},{battery_s} {}",
-                    local.format("%F %T")
-                )
-                .unwrap();
+            // Every module, each on its own interval, a single pass with --once before rendering
+            let cache = cache_dir.as_deref();
+            running = Some(Running::start(x, scheduled, args.once, cache, store));
+        }
 
-                // Write and flush the status
-                let _ = Command::new("xsetroot")
-                    .args(["-name", &status])
-                    .status()
-                    .unwrap();
+        // Rendering, until exiting
+        let render = render::Render {
+            outputs,
+            clicks: clicks_rx,
+            control,
+            metrics,
+            recording,
+            #[cfg(feature = "dbus")]
+            service: service_tx,
+            #[cfg(feature = "mqtt")]
+            mqtt: mqtt_publisher,
+            started,
+            reloads: reloads_rx,
+        };
+        let rendered = Arc::clone(&args);
+        x.spawn(move || render.run(rendered, store, glyphs));
 
-                std::thread::sleep(Duration::from_secs(1));
+        // Reloads of the config file on SIGHUP, replacing the modules, until stopping
+        let Some(mut running) = running.filter(|_| !args.once) else {
+            return;
+        };
+        let mut current = args;
+        let mut seen = 0;
+        while signal::wait_for_reload(&mut seen) {
+            let (reloaded, config) = match parse(command_line) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    let e = e.to_string();
+                    let e = e.lines().next().unwrap_or_default();
+                    error!(
+                        "unable to reload, keeping the options: {}",
+                        e.trim_start_matches("error: ")
+                    );
+                    continue;
+                }
+            };
+            // checked before stopping the modules, unlike the sensors of --temp
+            if let Err(e) = prepare(&reloaded) {
+                error!("unable to reload, keeping the options: {e}");
+                continue;
             }
-        });
+            if let Some(config) = &config {
+                warn_unknown(config);
+            }
+            info!("reloading, restarting the modules");
+
+            // stopped before the new ones start, such as traffic saving its totals first
+            let names = running.stop();
+            let (args, (glyphs, scheduled)) = match build(&reloaded, clicks) {
+                Ok(built) => (Arc::new(reloaded), built),
+                Err(e) => {
+                    error!("unable to reload, restarting the modules with the options: {e}");
+                    let built = build(&current, clicks).unwrap_or_else(|e| {
+                        error!("unable to restart the modules: {e}");
+                        (self::glyphs(&current), vec![])
+                    });
+                    (current, built)
+                }
+            };
+
+            // left out of the new modules
+            let hidden: Vec<Segment> = names
+                .iter()
+                .filter(|name| !scheduled.iter().any(|module| module.name() == **name))
+                .copied()
+                .map(Segment::hidden)
+                .collect();
+            if !hidden.is_empty() {
+                store.publish(hidden);
+            }
+            let _ = reloads_tx.send((Arc::clone(&args), glyphs));
+
+            running = Running::start(x, scheduled, false, cache_dir.as_deref(), store);
+            current = args;
+        }
     });
 }
@@ -0,0 +1,213 @@
+//! Horizontal scrolling of segments wider than their configured maximum width
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use unicode_width::UnicodeWidthChar;
+
+/// clap value parser for `--max-width module=width`
+pub fn parse_max_width(s: &str) -> Result<(String, usize), String> {
+    let (module, width) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid max width `{s}`, expected module=width"))?;
+    if !crate::MODULES.contains(&module) {
+        return Err(format!(
+            "unknown module `{module}`, expected one of: {}",
+            crate::MODULES.join(", ")
+        ));
+    }
+    let width = width
+        .parse()
+        .ok()
+        .filter(|width| *width > 0)
+        .ok_or_else(|| format!("invalid width `{width}`"))?;
+    Ok((module.to_string(), width))
+}
+
+pub struct Marquee {
+    max_widths: HashMap<String, usize>,
+    gap: String,
+    scroll: bool,
+    /// end of the truncated text without `scroll`
+    ellipsis: String,
+    /// per module: the text being scrolled, and the char offset into it
+    offsets: HashMap<String, (String, usize)>,
+}
+
+impl Marquee {
+    pub fn new(max_widths: Vec<(String, usize)>, gap: String, scroll: bool) -> Self {
+        Self {
+            max_widths: max_widths.into_iter().collect(),
+            gap,
+            scroll,
+            ellipsis: "…".to_string(),
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// End of the truncated text without scrolling, `…` by default
+    pub fn ellipsis(mut self, ellipsis: impl Into<String>) -> Self {
+        self.ellipsis = ellipsis.into();
+        self
+    }
+
+    /// Fit `text` of `module` in its max width, advancing the scroll by one char per call
+    pub fn apply<'a>(&mut self, module: &str, text: &'a str) -> Cow<'a, str> {
+        let Some(&max_width) = self.max_widths.get(module) else {
+            return Cow::Borrowed(text);
+        };
+        if width(text) <= max_width {
+            self.offsets.remove(module);
+            return Cow::Borrowed(text);
+        }
+
+        if !self.scroll {
+            return Cow::Owned(truncate(text, max_width, &self.ellipsis));
+        }
+
+        let (scrolled, offset) = self
+            .offsets
+            .entry(module.to_string())
+            .or_insert_with(|| (text.to_string(), 0));
+        // restart from the beginning when the underlying text changes
+        if scrolled != text {
+            text.clone_into(scrolled);
+            *offset = 0;
+        }
+
+        let cycle: Vec<char> = text.chars().chain(self.gap.chars()).collect();
+        let window = window(&cycle, *offset, max_width);
+        *offset = (*offset + 1) % cycle.len();
+        Cow::Owned(window)
+    }
+}
+
+/// Display width of `text`, in terminal columns
+pub fn width(text: &str) -> usize {
+    text.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+/// `max_width` columns of `cycle` repeated, starting at char `offset`
+fn window(cycle: &[char], offset: usize, max_width: usize) -> String {
+    let mut window = String::new();
+    let mut used = 0;
+    for c in cycle.iter().cycle().skip(offset) {
+        let w = c.width().unwrap_or(0);
+        if used + w > max_width {
+            break;
+        }
+        window.push(*c);
+        used += w;
+    }
+    // a wide char didn't fit in the last column
+    window.extend(std::iter::repeat_n(' ', max_width - used));
+    window
+}
+
+/// Cut `text` to `max_width` columns, ending in `ellipsis` unless even it doesn't fit
+pub fn truncate(text: &str, max_width: usize, ellipsis: &str) -> String {
+    let ellipsis = if width(ellipsis) <= max_width {
+        ellipsis
+    } else {
+        ""
+    };
+    let room = max_width - width(ellipsis);
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > room {
+            break;
+        }
+        truncated.push(c);
+        used += w;
+    }
+    truncated.push_str(ellipsis);
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marquee(max_width: usize, gap: &str) -> Marquee {
+        Marquee::new(
+            vec![("media".to_string(), max_width)],
+            gap.to_string(),
+            true,
+        )
+    }
+
+    fn scroll(marquee: &mut Marquee, text: &str, passes: usize) -> Vec<String> {
+        (0..passes)
+            .map(|_| marquee.apply("media", text).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn wraps_around_through_the_gap() {
+        let mut marquee = marquee(5, " | ");
+        assert_eq!(
+            scroll(&mut marquee, "abcdefg", 11),
+            [
+                "abcde", "bcdef", "cdefg", "defg ", "efg |", "fg | ", "g | a", " | ab", "| abc",
+                " abcd", "abcde",
+            ]
+        );
+    }
+
+    #[test]
+    fn without_gap() {
+        let mut marquee = marquee(3, "");
+        assert_eq!(
+            scroll(&mut marquee, "abcd", 5),
+            ["abc", "bcd", "cda", "dab", "abc"]
+        );
+    }
+
+    #[test]
+    fn restarts_on_changes() {
+        let mut marquee = marquee(3, " ");
+        scroll(&mut marquee, "abcdef", 2);
+        assert_eq!(marquee.apply("media", "uvwxyz"), "uvw");
+        assert_eq!(marquee.apply("media", "ab"), "ab");
+        assert_eq!(marquee.apply("media", "abcdef"), "abc");
+        assert_eq!(marquee.apply("cpu", "abcdef"), "abcdef");
+    }
+
+    #[test]
+    fn wide_chars() {
+        // a wide char not fitting in the last column leaves it blank
+        let mut cjk = marquee(5, " ");
+        let windows = scroll(&mut cjk, "日本語のテキスト", 3);
+        assert_eq!(windows, ["日本 ", "本語 ", "語の "]);
+        assert!(windows.iter().all(|window| width(window) == 5));
+
+        let mut emoji = marquee(4, " ");
+        let windows = scroll(&mut emoji, "🎵 song", 7);
+        assert_eq!(
+            windows,
+            ["🎵 s", " son", "song", "ong ", "ng  ", "g 🎵", " 🎵 "]
+        );
+        assert!(windows.iter().all(|window| width(window) == 4));
+    }
+
+    #[test]
+    fn truncates() {
+        let mut marquee =
+            Marquee::new(vec![("media".to_string(), 4)], " ".to_string(), false).ellipsis("...");
+        assert_eq!(marquee.apply("media", "abcdef"), "a...");
+        assert_eq!(truncate("abcdef", 4, "…"), "abc…");
+        assert_eq!(truncate("abcdef", 2, "..."), "ab");
+        assert_eq!(truncate("日本語", 4, "…"), "日…");
+        assert_eq!(truncate("🎵🎵🎵", 5, "…"), "🎵🎵…");
+    }
+
+    #[test]
+    fn widths() {
+        assert_eq!(width("abc"), 3);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("🎵"), 2);
+        assert_eq!(width("e\u{301}"), 1);
+    }
+}
@@ -0,0 +1,189 @@
+//! Prometheus exporter of the displayed values
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write as _};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Latest values, as displayed by the render thread
+#[derive(Debug, Default, Clone)]
+pub struct Metrics {
+    pub cpu_usage_percent: f64,
+    pub memory_used_bytes: u64,
+    /// (battery, capacity percent)
+    pub batteries: Vec<(String, f64)>,
+    /// (interface, number of ip addresses)
+    pub ip_counts: Vec<(String, usize)>,
+}
+
+impl Metrics {
+    /// Prometheus text exposition format
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP statusbar_cpu_usage_percent Average cpu usage.");
+        let _ = writeln!(out, "# TYPE statusbar_cpu_usage_percent gauge");
+        let _ = writeln!(
+            out,
+            "statusbar_cpu_usage_percent {}",
+            self.cpu_usage_percent
+        );
+
+        let _ = writeln!(out, "# HELP statusbar_memory_used_bytes Used memory.");
+        let _ = writeln!(out, "# TYPE statusbar_memory_used_bytes gauge");
+        let _ = writeln!(
+            out,
+            "statusbar_memory_used_bytes {}",
+            self.memory_used_bytes
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP statusbar_battery_capacity_percent Battery capacity."
+        );
+        let _ = writeln!(out, "# TYPE statusbar_battery_capacity_percent gauge");
+        for (battery, capacity) in &self.batteries {
+            let _ = writeln!(
+                out,
+                "statusbar_battery_capacity_percent{{battery=\"{battery}\"}} {capacity}"
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP statusbar_network_ip_count IPv4 addresses of the interface."
+        );
+        let _ = writeln!(out, "# TYPE statusbar_network_ip_count gauge");
+        for (interface, count) in &self.ip_counts {
+            let interface = interface.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(
+                out,
+                "statusbar_network_ip_count{{interface=\"{interface}\"}} {count}"
+            );
+        }
+
+        out
+    }
+}
+
+/// Bind the exporter, failing at startup instead of in the background
+pub fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    TcpListener::bind(addr)
+}
+
+/// Serve `/metrics` to one client at a time
+pub fn serve(listener: TcpListener, metrics: Arc<Mutex<Metrics>>) {
+    for stream in listener.incoming().filter_map(Result::ok) {
+        let _ = respond(stream, &metrics);
+    }
+}
+
+fn respond(mut stream: TcpStream, metrics: &Mutex<Metrics>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // drain the headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_ascii_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let (status, body) = match (method, path.split('?').next().unwrap_or_default()) {
+        ("GET", "/metrics") => {
+            // clone, so the render thread is never held up by a slow client
+            let metrics = metrics.lock().unwrap().clone();
+            ("200 OK", metrics.render())
+        }
+        ("GET", _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+
+    let allow = if status.starts_with("405") {
+        "Allow: GET\r\n"
+    } else {
+        ""
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n{allow}Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn metrics() -> Metrics {
+        Metrics {
+            cpu_usage_percent: 12.5,
+            memory_used_bytes: 4_294_967_296,
+            batteries: vec![("BAT0".to_string(), 80.0)],
+            ip_counts: vec![("wl\"an0".to_string(), 2)],
+        }
+    }
+
+    #[test]
+    fn exposition_format() {
+        assert_eq!(
+            metrics().render(),
+            "\
+# HELP statusbar_cpu_usage_percent Average cpu usage.
+# TYPE statusbar_cpu_usage_percent gauge
+statusbar_cpu_usage_percent 12.5
+# HELP statusbar_memory_used_bytes Used memory.
+# TYPE statusbar_memory_used_bytes gauge
+statusbar_memory_used_bytes 4294967296
+# HELP statusbar_battery_capacity_percent Battery capacity.
+# TYPE statusbar_battery_capacity_percent gauge
+statusbar_battery_capacity_percent{battery=\"BAT0\"} 80
+# HELP statusbar_network_ip_count IPv4 addresses of the interface.
+# TYPE statusbar_network_ip_count gauge
+statusbar_network_ip_count{interface=\"wl\\\"an0\"} 2
+"
+        );
+    }
+
+    #[test]
+    fn requests() {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let metrics = Mutex::new(metrics());
+        let request = |request: &str| {
+            let mut client = TcpStream::connect(addr).unwrap();
+            client.write_all(request.as_bytes()).unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            respond(stream, &metrics).unwrap();
+            let mut response = String::new();
+            client.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = request("GET /metrics?x=1 HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        let body = metrics.lock().unwrap().render();
+        assert!(response.contains(&format!("Content-Length: {}\r\n", body.len())));
+        assert!(response.ends_with(&format!("\r\n\r\n{body}")));
+
+        let response = request("GET / HTTP/1.1\r\n\r\n");
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{response}"
+        );
+        let response = request("POST /metrics HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(response.contains("Allow: GET\r\n"));
+    }
+}
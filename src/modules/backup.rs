@@ -0,0 +1,222 @@
+//! Age of the newest backup, from a stamp file's mtime or restic's snapshot list
+
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Local};
+use log::{debug, warn};
+use serde::Deserialize;
+
+use super::Module;
+use crate::command::output_timeout;
+use crate::duration;
+use crate::output::Segment;
+use crate::style::Level;
+
+/// How often the stamp file is re-checked, cheap enough to not need configuring
+const STAMP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bound on a `restic snapshots` run, it may need to reach a remote repository
+const RESTIC_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub enum Source {
+    /// mtime of a file touched after each successful backup
+    Stamp(PathBuf),
+    /// newest snapshot of the restic repository configured through the environment
+    Restic,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    time: String,
+}
+
+/// Age of the newest backup, marked with `!` beyond `warn_age`, collected again as its display
+/// changes. Without a backup the segment has no data. A failed restic run keeps the previous
+/// time, marked stale at once, and without restic installed the module is disabled.
+pub struct Backup {
+    source: Source,
+    /// interval of checking the source
+    interval: Duration,
+    warn_age: Duration,
+    newest: Option<DateTime<Local>>,
+    checked: Option<Instant>,
+    /// last successful check, the age of the segment
+    fetched: Option<SystemTime>,
+    failing: bool,
+    missing: bool,
+}
+
+impl Backup {
+    /// Checking the stamp file every minute, or restic every `restic_interval`
+    pub fn new(source: Source, restic_interval: Duration, warn_age: Duration) -> Self {
+        let interval = match source {
+            Source::Stamp(_) => STAMP_INTERVAL,
+            Source::Restic => restic_interval,
+        };
+        Self {
+            source,
+            interval,
+            warn_age,
+            newest: None,
+            checked: None,
+            fetched: None,
+            failing: false,
+            missing: false,
+        }
+    }
+
+    fn age(&self) -> Option<Duration> {
+        self.newest
+            .map(|time| (Local::now() - time).to_std().unwrap_or_default())
+    }
+}
+
+impl Module for Backup {
+    fn name(&self) -> &'static str {
+        "backup"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        if self
+            .checked
+            .is_none_or(|checked| checked.elapsed() >= self.interval)
+        {
+            self.checked = Some(Instant::now());
+            match &self.source {
+                Source::Stamp(path) => {
+                    self.fetched = Some(SystemTime::now());
+                    self.newest = std::fs::metadata(path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .map(DateTime::<Local>::from);
+                }
+                // a failed run (such as the repository being locked) keeps the previous time,
+                // showing stale data instead of dropping the segment
+                Source::Restic => match restic_newest() {
+                    Ok(restic) => {
+                        self.fetched = Some(SystemTime::now());
+                        self.failing = false;
+                        self.newest = restic;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        debug!(target: "backup", "restic isn't installed, disabling");
+                        self.missing = true;
+                        return None;
+                    }
+                    Err(e) => {
+                        if !self.failing {
+                            warn!("restic snapshots failed: {e}");
+                            self.failing = true;
+                        }
+                    }
+                },
+            }
+        }
+        let mut segment = segment(self.age(), self.warn_age);
+        segment.fetched = self.fetched;
+        if self.failing {
+            segment = segment.expires(Instant::now());
+        }
+        Some(segment)
+    }
+
+    /// Until the next check, or the displayed age changing
+    fn interval(&self) -> Duration {
+        let checked = self.checked.map_or(self.interval, |c| c.elapsed());
+        let mut interval = self.interval.saturating_sub(checked);
+        if let Some(age) = self.age() {
+            interval = interval.min(duration::until_next_change(age));
+        }
+        interval
+    }
+
+    fn reset(&mut self) {
+        self.checked = None;
+    }
+
+    /// Of restic, the stamp file being cheap to check. Fetched on its last successful run,
+    /// rather than as the age it shows changes.
+    fn cache_ttl(&self) -> Option<Duration> {
+        matches!(self.source, Source::Restic).then_some(self.interval + RESTIC_TIMEOUT)
+    }
+
+    fn blocking(&self) -> bool {
+        matches!(self.source, Source::Restic)
+    }
+
+    fn disabled(&self) -> bool {
+        self.missing
+    }
+}
+
+/// `bak 6h`, marked as a warning beyond `warn_age`
+fn segment(age: Option<Duration>, warn_age: Duration) -> Segment {
+    let Some(age) = age else {
+        return Segment::new("backup", "");
+    };
+    let (mark, level) = if age > warn_age {
+        ("!", Level::Warning)
+    } else {
+        ("", Level::Normal)
+    };
+    Segment::new("backup", format!("bak {}{mark}", duration::format_age(age))).level(level)
+}
+
+/// Time of the newest restic snapshot, `Ok(None)` if the repository has none
+fn restic_newest() -> io::Result<Option<DateTime<Local>>> {
+    let output = output_timeout(
+        Command::new("restic").args(["snapshots", "--json", "--no-lock"]),
+        RESTIC_TIMEOUT,
+    )?
+    .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "timed out"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().next().unwrap_or_default().to_string();
+        return Err(io::Error::other(message));
+    }
+    newest(&output.stdout).map_err(io::Error::other)
+}
+
+/// Time of the newest snapshot of `restic snapshots --json`
+fn newest(json: &[u8]) -> serde_json::Result<Option<DateTime<Local>>> {
+    let snapshots: Vec<Snapshot> = serde_json::from_slice(json)?;
+    Ok(snapshots
+        .iter()
+        .filter_map(|s| DateTime::parse_from_rfc3339(&s.time).ok())
+        .max()
+        .map(|time| time.with_timezone(&Local)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn ages() {
+        let warn_age = 24 * HOUR;
+        assert_eq!(segment(None, warn_age).text, "");
+        let recent = segment(Some(6 * HOUR), warn_age);
+        assert_eq!(recent.text, "bak 6h");
+        assert_eq!(recent.level, Level::Normal);
+        let old = segment(Some(3 * 24 * HOUR), warn_age);
+        assert_eq!(old.text, "bak 3d!");
+        assert_eq!(old.level, Level::Warning);
+    }
+
+    #[test]
+    fn restic_snapshots() {
+        let newest = newest(include_bytes!("fixtures/restic-snapshots")).unwrap();
+        assert_eq!(
+            newest.unwrap(),
+            DateTime::parse_from_rfc3339("2024-02-20T02:00:07.120934775Z").unwrap()
+        );
+        assert_eq!(super::newest(b"[]\n").unwrap(), None);
+        assert!(super::newest(b"Fatal: unable to open config file").is_err());
+    }
+}
@@ -0,0 +1,435 @@
+//! Capacity of the batteries, from sysfs, refreshed on the power_supply uevents of the kernel
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use super::{read_into, Module, Trigger};
+use crate::glyphs::{Glyph, Glyphs};
+use crate::logging::Throttle;
+use crate::output::Segment;
+use crate::percent::Percent;
+use crate::smoothing::Hysteresis;
+use crate::uevent;
+
+pub const POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval while watching uevents, for drivers not sending one on each capacity change
+const FALLBACK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long an unreadable battery keeps its last capacity, such as around a resume, before
+/// being dropped as removed
+const GRACE: Duration = Duration::from_secs(10);
+
+/// Interval of looking for the batteries not found, such as one inserted in a hot-swap bay
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Batteries displayed, when found
+const NAMES: &[&str] = &["BAT0", "BAT1"];
+
+pub struct Battery {
+    power_supply: PathBuf,
+    /// in the order of [`NAMES`]
+    batteries: Vec<Found>,
+    /// when the batteries were last looked for
+    probed: Instant,
+    /// whether the uevents are watched, cleared if the watcher fails
+    events: Arc<AtomicBool>,
+    percent: Percent,
+    /// points the capacity moves by before the one shown follows, 0 for none
+    hysteresis: f64,
+    /// display the charge limit of the batteries with one
+    show_limit: bool,
+    glyphs: Glyphs,
+}
+
+/// Battery found, until unreadable for longer than [`GRACE`]
+struct Found {
+    name: &'static str,
+    /// its capacity file
+    path: PathBuf,
+    throttle: Throttle,
+    /// contents of the capacity file, the last read while unreadable
+    capacity: String,
+    /// buffer of the reads, swapped with `capacity` when successful
+    read: String,
+    /// since when it's unreadable
+    failing: Option<Instant>,
+    shown: Hysteresis,
+    /// its charge_control_end_threshold, for a battery with a charge limit
+    limit_path: Option<PathBuf>,
+}
+
+/// Capacity of a battery, and its charge limit
+struct Reading {
+    name: &'static str,
+    capacity: f64,
+    /// the capacity displayed
+    shown: f64,
+    limit: Option<f64>,
+    /// held at its limit on AC, or kept from charging
+    capped: bool,
+}
+
+impl Battery {
+    /// With the batteries found in `power_supply`, such as /sys/class/power_supply
+    pub fn new(power_supply: &Path) -> Self {
+        let mut battery = Self {
+            power_supply: power_supply.to_path_buf(),
+            batteries: vec![],
+            probed: Instant::now(),
+            events: Arc::default(),
+            percent: Percent::default(),
+            hysteresis: 0.0,
+            show_limit: false,
+            glyphs: Glyphs::default(),
+        };
+        battery.probe();
+        if battery.batteries.is_empty() {
+            info!(target: "bat", "no battery found");
+        }
+        battery
+    }
+
+    pub fn percent(mut self, percent: Percent) -> Self {
+        self.percent = percent;
+        self
+    }
+
+    /// Keep displaying a capacity until it moves by more than `points`, such as 1 for one
+    /// flapping between 79 and 80
+    pub fn hysteresis(mut self, points: f64) -> Self {
+        self.hysteresis = points;
+        for found in &mut self.batteries {
+            found.shown = Hysteresis::new(points);
+        }
+        self
+    }
+
+    /// Display the charge limit, such as `62% cap 80%`
+    pub fn show_limit(mut self, show_limit: bool) -> Self {
+        self.show_limit = show_limit;
+        self
+    }
+
+    /// Of the mark of a battery held at its limit, such as `80%⏸`
+    pub fn glyphs(mut self, glyphs: Glyphs) -> Self {
+        self.glyphs = glyphs;
+        self
+    }
+
+    /// Look for the batteries not found yet
+    fn probe(&mut self) {
+        self.probed = Instant::now();
+        for name in NAMES {
+            if self.batteries.iter().any(|found| found.name == *name) {
+                continue;
+            }
+            let path = self.power_supply.join(name).join("capacity");
+            if !path.exists() {
+                continue;
+            }
+            let limit_path = self
+                .power_supply
+                .join(name)
+                .join("charge_control_end_threshold");
+            let limit_path = limit_path.exists().then_some(limit_path);
+            info!(target: "bat", "found {name}");
+            self.batteries.push(Found {
+                name,
+                path,
+                throttle: Throttle::default(),
+                capacity: String::new(),
+                read: String::new(),
+                failing: None,
+                shown: Hysteresis::new(self.hysteresis),
+                limit_path,
+            });
+        }
+        self.batteries
+            .sort_by_key(|found| NAMES.iter().position(|name| *name == found.name));
+    }
+}
+
+/// Names of the batteries in `power_supply`, such as /sys/class/power_supply
+pub fn batteries(power_supply: &Path) -> Vec<&'static str> {
+    NAMES
+        .iter()
+        .copied()
+        .filter(|name| power_supply.join(name).join("capacity").exists())
+        .collect()
+}
+
+impl Default for Battery {
+    fn default() -> Self {
+        Self::new(Path::new(POWER_SUPPLY_PATH))
+    }
+}
+
+impl Module for Battery {
+    fn name(&self) -> &'static str {
+        "bat"
+    }
+
+    /// An unreadable battery, such as one removed, is left out after [`GRACE`]
+    fn collect(&mut self) -> Option<Segment> {
+        if self.probed.elapsed() >= PROBE_INTERVAL {
+            self.probe();
+        }
+        let now = Instant::now();
+        self.batteries.retain_mut(|found| {
+            read(found, now);
+            let gone = found
+                .failing
+                .is_some_and(|failing| now.duration_since(failing) >= GRACE);
+            if gone {
+                info!(target: "bat", "{} removed", found.name);
+            }
+            !gone
+        });
+        let readings: Vec<_> = self
+            .batteries
+            .iter_mut()
+            .filter_map(|found| {
+                let capacity = found.capacity.parse().ok()?;
+                let (limit, capped) = limit(found, capacity);
+                Some(Reading {
+                    name: found.name,
+                    capacity,
+                    shown: found.shown.update(capacity),
+                    limit,
+                    capped,
+                })
+            })
+            .collect();
+        Some(segment(&readings, self))
+    }
+
+    /// Polled while a battery is unreadable, to drop it once removed
+    fn interval(&self) -> Duration {
+        let failing = self.batteries.iter().any(|found| found.failing.is_some());
+        if self.events.load(Ordering::Relaxed) && !failing {
+            FALLBACK_INTERVAL
+        } else {
+            INTERVAL
+        }
+    }
+
+    /// Any power_supply uevent, such as plugging AC in, triggers a sample of the batteries
+    fn watch(&mut self, trigger: Trigger) {
+        if self.batteries.is_empty() {
+            return;
+        }
+        let monitor = match uevent::Monitor::open() {
+            Ok(monitor) => monitor,
+            Err(e) => {
+                warn!(target: "bat", "unable to watch uevents, polling: {e}");
+                return;
+            }
+        };
+        self.events.store(true, Ordering::Relaxed);
+        let events = self.events.clone();
+        std::thread::spawn(move || loop {
+            if let Err(e) = monitor.wait("power_supply") {
+                warn!(target: "bat", "unable to receive uevents, polling: {e}");
+                events.store(false, Ordering::Relaxed);
+                return;
+            }
+            if trigger.stopped() {
+                return;
+            }
+            trigger.fire();
+        });
+    }
+
+    fn reset(&mut self) {
+        for found in &mut self.batteries {
+            found.shown.reset();
+        }
+    }
+}
+
+/// Capacity of a battery into its buffer, left at the last read if unreadable
+fn read(found: &mut Found, now: Instant) {
+    match read_into(&found.path, &mut found.read) {
+        Ok(()) => {
+            found.throttle.succeeded();
+            found.failing = None;
+            found.read.truncate(found.read.trim_end().len());
+            std::mem::swap(&mut found.capacity, &mut found.read);
+        }
+        Err(e) => {
+            found.failing.get_or_insert(now);
+            if let Some(suppressed) = found.throttle.failed() {
+                let path = found.path.display();
+                warn!(target: "bat", "unable to read {path}: {e}{suppressed}");
+            }
+        }
+    }
+}
+
+/// Charge limit of a battery with one, and whether it's held at it: at the limit or not
+/// charging while on AC, or with charging inhibited by its charge_behaviour
+fn limit(found: &Found, capacity: f64) -> (Option<f64>, bool) {
+    let Some(limit_path) = &found.limit_path else {
+        return (None, false);
+    };
+    let dir = limit_path.parent().unwrap_or(limit_path);
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap_or_default();
+    let limit = read("charge_control_end_threshold")
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|limit| *limit < 100.0);
+    let status = read("status");
+    let on_ac = !status.is_empty() && status.trim() != "Discharging";
+    // the selected one in brackets, such as "auto [inhibit-charge] force-discharge"
+    let inhibited = read("charge_behaviour").contains("[inhibit-charge]");
+    let held = limit.is_some_and(|limit| capacity >= limit || status.trim() == "Not charging");
+    (limit, on_ac && (held || inhibited))
+}
+
+/// `bat [87%, 42%]`, the emptiest battery being the percentage, and each capacity a value named
+/// by its battery. Empty without a readable battery.
+fn segment(readings: &[Reading], battery: &Battery) -> Segment {
+    if readings.is_empty() {
+        return Segment::new("bat", "");
+    }
+    let mut text = String::from("bat [");
+    for (i, reading) in readings.iter().enumerate() {
+        if i > 0 {
+            text.push_str(", ");
+        }
+        battery.percent.write(reading.shown, &mut text);
+        text.push('%');
+        if reading.capped {
+            text.push_str(battery.glyphs.get(Glyph::Capped));
+        }
+        if let Some(limit) = reading.limit.filter(|_| battery.show_limit) {
+            text.push_str(&format!(" cap {limit:.0}%"));
+        }
+    }
+    text.push(']');
+
+    let emptiest = readings
+        .iter()
+        .map(|reading| reading.capacity)
+        .fold(f64::MAX, f64::min);
+    let shown = readings
+        .iter()
+        .map(|reading| reading.shown)
+        .fold(f64::MAX, f64::min);
+    let mut segment = Segment::new("bat", text).percentage(emptiest);
+    if shown != emptiest {
+        segment = segment.shown(shown);
+    }
+    for reading in readings {
+        segment = segment.value(reading.name, reading.capacity);
+        if let Some(limit) = reading.limit {
+            segment = segment.value(format!("{}_limit", reading.name), limit);
+        }
+    }
+    segment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyphs::Set;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src/modules/fixtures/power_supply")
+    }
+
+    #[test]
+    fn captured_batteries() {
+        let segment = Battery::new(&fixtures()).collect().unwrap();
+        assert_eq!(segment.text, "bat [87%, 42%]");
+        assert_eq!(segment.percentage, Some(42.0));
+        assert_eq!(segment.get::<f64>("BAT0"), Some(87.0));
+        assert_eq!(segment.get::<f64>("BAT1"), Some(42.0));
+    }
+
+    #[test]
+    fn without_battery() {
+        let mut battery = Battery::new(&fixtures().join("AC"));
+        assert!(battery.batteries.is_empty());
+        assert_eq!(battery.collect().unwrap().text, "");
+    }
+
+    #[test]
+    fn removed_and_reinserted_battery() {
+        let dir = std::env::temp_dir().join(format!("statusbar-bat-{}", std::process::id()));
+        let capacity = dir.join("BAT0/capacity");
+        std::fs::create_dir_all(capacity.parent().unwrap()).unwrap();
+        std::fs::write(&capacity, "87\n").unwrap();
+        let mut battery = Battery::new(&dir);
+        assert_eq!(battery.collect().unwrap().text, "bat [87%]");
+
+        // the last capacity during the grace period, then left out
+        std::fs::remove_file(&capacity).unwrap();
+        assert_eq!(battery.collect().unwrap().text, "bat [87%]");
+        battery.batteries[0].failing = Some(Instant::now() - GRACE);
+        assert_eq!(battery.collect().unwrap().text, "");
+        assert!(battery.batteries.is_empty());
+
+        // back once probed again
+        std::fs::write(&capacity, "86\n").unwrap();
+        assert_eq!(battery.collect().unwrap().text, "");
+        battery.probed = Instant::now() - PROBE_INTERVAL;
+        assert_eq!(battery.collect().unwrap().text, "bat [86%]");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removed_battery_left_out() {
+        let dir = std::env::temp_dir().join(format!("statusbar-bat-empty-{}", std::process::id()));
+        for (name, capacity) in [("BAT0", ""), ("BAT1", "42\n")] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            std::fs::write(dir.join(name).join("capacity"), capacity).unwrap();
+        }
+        let segment = Battery::new(&dir).collect().unwrap();
+        assert_eq!(segment.text, "bat [42%]");
+        assert_eq!(segment.get::<f64>("BAT0"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn held_at_the_charge_limit() {
+        let dir = fixtures().with_file_name("power_supply-limit");
+        let segment = Battery::new(&dir).collect().unwrap();
+        assert_eq!(segment.text, "bat [80%⏸]");
+        assert_eq!(segment.get::<f64>("BAT0_limit"), Some(80.0));
+        let mut battery = Battery::new(&dir)
+            .glyphs(Glyphs::new(Set::Ascii, vec![]))
+            .show_limit(true);
+        assert_eq!(battery.collect().unwrap().text, "bat [80% capped cap 80%]");
+        // without the attribute, as before
+        let segment = Battery::new(&fixtures())
+            .show_limit(true)
+            .collect()
+            .unwrap();
+        assert_eq!(segment.text, "bat [87%, 42%]");
+    }
+
+    #[test]
+    fn flapping_capacity_held() {
+        let reading = Reading {
+            name: "BAT0",
+            capacity: 79.0,
+            shown: 80.0,
+            limit: None,
+            capped: false,
+        };
+        let segment = segment(&[reading], &Battery::new(&fixtures().join("AC")));
+        assert_eq!(segment.text, "bat [80%]");
+        assert_eq!(segment.percentage, Some(79.0));
+        assert_eq!(segment.shown, Some(80.0));
+        assert_eq!(segment.get::<f64>("BAT0"), Some(79.0));
+    }
+}
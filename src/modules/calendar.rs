@@ -0,0 +1,741 @@
+//! Next event of local calendars, such as `next: standup 10:00`, then `standup in 12m` within
+//! the hour, from .ics files or directories of them as synced by vdirsyncer
+//!
+//! Files are parsed again only once their mtime or size changed. Events recurring daily or
+//! weekly are expanded, other rules are skipped and logged. All-day events are left out, and
+//! those of a TZID missing from the time zone database, such as a Windows one, skipped and
+//! logged.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use log::{info, warn};
+
+use super::Module;
+use crate::duration;
+use crate::output::Segment;
+
+/// Interval of checking the files for changes, and of updating the segment at the latest
+const INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time before an event from which it counts down
+const SOON: Duration = Duration::from_secs(60 * 60);
+
+/// Upper bound of the days walked for the next occurrence of a rule, for one with a COUNT from
+/// decades ago
+const MAX_DAYS: i64 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+}
+
+/// RRULE of the supported subset
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<Start>,
+    /// BYDAY, every day of the frequency when empty
+    days: Vec<Weekday>,
+}
+
+/// Time zone of a DATE-TIME
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    /// floating, in the local time zone
+    Local,
+    Utc,
+    /// of its TZID
+    Tz(Tz),
+}
+
+impl Zone {
+    /// Date of `time` in the zone
+    fn date(&self, time: DateTime<Local>) -> NaiveDate {
+        match self {
+            Self::Local => time.date_naive(),
+            Self::Utc => time.naive_utc().date(),
+            Self::Tz(tz) => time.with_timezone(tz).date_naive(),
+        }
+    }
+}
+
+/// DTSTART, and the other DATE-TIME properties
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Start {
+    time: NaiveDateTime,
+    zone: Zone,
+}
+
+impl Start {
+    fn local(&self) -> Option<DateTime<Local>> {
+        match self.zone {
+            Zone::Local => Local.from_local_datetime(&self.time).earliest(),
+            Zone::Utc => Some(Utc.from_utc_datetime(&self.time).with_timezone(&Local)),
+            Zone::Tz(tz) => Some(
+                tz.from_local_datetime(&self.time)
+                    .earliest()?
+                    .with_timezone(&Local),
+            ),
+        }
+    }
+
+    /// The time in `zone`, such as of an EXDATE in UTC in that of its DTSTART, a floating one
+    /// being the same in any zone
+    fn within(&self, zone: Zone) -> Option<NaiveDateTime> {
+        if self.zone == zone || self.zone == Zone::Local {
+            return Some(self.time);
+        }
+        let local = self.local()?;
+        Some(match zone {
+            Zone::Local => local.naive_local(),
+            Zone::Utc => local.naive_utc(),
+            Zone::Tz(tz) => local.with_timezone(&tz).naive_local(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Event {
+    summary: String,
+    start: Start,
+    rule: Option<Rule>,
+    excluded: Vec<NaiveDateTime>,
+}
+
+impl Event {
+    /// First occurrence starting at or after `after`, until `before`
+    fn next(&self, after: DateTime<Local>, before: DateTime<Local>) -> Option<DateTime<Local>> {
+        let Some(rule) = &self.rule else {
+            let start = self.start.local()?;
+            return (start >= after && start <= before).then_some(start);
+        };
+        let mut occurrences = 0;
+        let first = self.start.time.date();
+        // monday of the week of the start, weeks being counted from it
+        let week = first - chrono::Duration::days(first.weekday().num_days_from_monday().into());
+        // the periods ending before `after` skipped, from a day early for the shifts of time
+        // zones, unless their occurrences are counted
+        let skipped = match rule.count {
+            Some(_) => 0,
+            None => {
+                let (origin, days) = match rule.frequency {
+                    Frequency::Daily => (first, i64::from(rule.interval)),
+                    Frequency::Weekly => (week, 7 * i64::from(rule.interval)),
+                };
+                let since = (self.start.zone.date(after) - origin).num_days() - 1;
+                let period = origin + chrono::Duration::days(since.div_euclid(days) * days);
+                (period - first).num_days().max(0)
+            }
+        };
+        for day in skipped..skipped + MAX_DAYS {
+            let date = first + chrono::Duration::days(day);
+            let period = match rule.frequency {
+                Frequency::Daily => day,
+                Frequency::Weekly => (date - week).num_days() / 7,
+            };
+            if period % i64::from(rule.interval) != 0 {
+                continue;
+            }
+            let on_day = match (rule.frequency, rule.days.is_empty()) {
+                (_, false) => rule.days.contains(&date.weekday()),
+                (Frequency::Daily, true) => true,
+                (Frequency::Weekly, true) => date.weekday() == first.weekday(),
+            };
+            if !on_day {
+                continue;
+            }
+            let time = date.and_time(self.start.time.time());
+            if rule.until.is_some_and(|until| time > until.time) {
+                return None;
+            }
+            occurrences += 1;
+            if rule.count.is_some_and(|count| occurrences > count) {
+                return None;
+            }
+            if self.excluded.contains(&time) {
+                continue;
+            }
+            let Some(start) = (Start { time, ..self.start }).local() else {
+                continue;
+            };
+            if start > before {
+                return None;
+            }
+            if start >= after {
+                return Some(start);
+            }
+        }
+        None
+    }
+}
+
+/// The next event of the calendars of `paths` within `lookahead`, hidden without any
+pub struct Calendar {
+    paths: Vec<PathBuf>,
+    lookahead: chrono::Duration,
+    /// events of each file, and the (mtime, len) they were parsed at
+    parsed: HashMap<PathBuf, ((SystemTime, u64), Vec<Event>)>,
+    /// until the next check, or the shown time changing
+    next: Duration,
+}
+
+impl Calendar {
+    pub fn new(paths: Vec<PathBuf>, lookahead: Duration) -> Self {
+        // a century standing in for longer
+        let lookahead = chrono::Duration::from_std(lookahead)
+            .unwrap_or_else(|_| chrono::Duration::days(36525))
+            .min(chrono::Duration::days(36525));
+        Self {
+            paths,
+            lookahead,
+            parsed: HashMap::new(),
+            next: INTERVAL,
+        }
+    }
+}
+
+impl Module for Calendar {
+    fn name(&self) -> &'static str {
+        "calendar"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let files = files(&self.paths);
+        self.parsed.retain(|path, _| files.contains(path));
+        for path in files {
+            let Some(stamp) = stamp(&path) else {
+                continue;
+            };
+            if self
+                .parsed
+                .get(&path)
+                .is_some_and(|(parsed, _)| *parsed == stamp)
+            {
+                continue;
+            }
+            match std::fs::read_to_string(&path) {
+                Ok(ics) => {
+                    let events = parse(&ics, &path);
+                    self.parsed.insert(path, (stamp, events));
+                }
+                Err(e) => warn!(target: "calendar", "unable to read {}: {e}", path.display()),
+            }
+        }
+
+        let now = Local::now();
+        let next = self
+            .parsed
+            .values()
+            .flat_map(|(_, events)| events)
+            .filter_map(|event| Some((event.next(now, now + self.lookahead)?, event)))
+            .min_by_key(|(start, _)| *start);
+
+        self.next = INTERVAL;
+        let Some((start, event)) = next else {
+            return Some(Segment::hidden("calendar"));
+        };
+        let remaining = (start - now).to_std().unwrap_or_default();
+        self.next = self.next.min(match remaining.checked_sub(SOON) {
+            Some(until_soon) => until_soon + Duration::from_secs(1),
+            None => duration::until_countdown_change(remaining),
+        });
+        Some(Segment::new("calendar", text(&event.summary, start, now)))
+    }
+
+    fn interval(&self) -> Duration {
+        self.next
+    }
+}
+
+/// The .ics files of `paths`, in the directories given and below them
+fn files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.is_dir() {
+                walk(&path, files);
+            } else if path.extension().is_some_and(|extension| extension == "ics") {
+                files.push(path);
+            }
+        }
+    }
+
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            walk(path, &mut files);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files
+}
+
+/// (mtime, len) of the file, to parse it again only once changed
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// `next: standup 10:00`, with the weekday when not today, or `standup in 12m` within [`SOON`]
+fn text(summary: &str, start: DateTime<Local>, now: DateTime<Local>) -> String {
+    let remaining = (start - now).to_std().unwrap_or_default();
+    if remaining < SOON {
+        return format!("{summary} in {}", duration::format_age(remaining));
+    }
+    if start.date_naive() == now.date_naive() {
+        format!("next: {summary} {}", start.format("%H:%M"))
+    } else {
+        format!("next: {summary} {}", start.format("%a %H:%M"))
+    }
+}
+
+/// Lines of `ics`, with the folded ones joined back
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in ics.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(folded) if !lines.is_empty() => lines.last_mut().unwrap().push_str(folded),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// The timed events of `ics`, those with a recurrence out of the supported subset logged and
+/// left out
+fn parse(ics: &str, path: &Path) -> Vec<Event> {
+    let mut events = vec![];
+    // components nested in the event, such as its alarms
+    let mut depth = 0;
+    let mut event: Option<Properties> = None;
+    for line in unfold(ics) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, params) = name.split_once(';').unwrap_or((name, ""));
+        match (name, value) {
+            ("BEGIN", "VEVENT") => {
+                event = Some(Properties::default());
+                depth = 0;
+                continue;
+            }
+            ("END", "VEVENT") => {
+                let Some(properties) = event.take() else {
+                    continue;
+                };
+                match properties.event() {
+                    Ok(Some(event)) => events.push(event),
+                    Ok(None) => (),
+                    Err(e) => info!(target: "calendar", "skipping {e} of {}", path.display()),
+                }
+                continue;
+            }
+            ("BEGIN", _) => depth += 1,
+            ("END", _) => depth -= 1,
+            _ => (),
+        }
+        let Some(event) = event.as_mut().filter(|_| depth == 0) else {
+            continue;
+        };
+        match name {
+            "SUMMARY" => event.summary = unescape(value),
+            // all-day events have a date only, left out
+            "DTSTART" if !params.contains("VALUE=DATE") || params.contains("VALUE=DATE-TIME") => {
+                match parse_zoned(value, params) {
+                    Ok(start) => event.start = start,
+                    Err(e) => event.invalid = Some(e),
+                }
+            }
+            "RRULE" => event.rule = Some(value.to_string()),
+            "EXDATE" => event.excluded.extend(
+                value
+                    .split(',')
+                    .filter_map(|value| parse_zoned(value, params).ok().flatten()),
+            ),
+            "STATUS" => event.cancelled = value == "CANCELLED",
+            _ => (),
+        }
+    }
+    events
+}
+
+/// Properties of a VEVENT as parsed
+#[derive(Default)]
+struct Properties {
+    summary: String,
+    start: Option<Start>,
+    rule: Option<String>,
+    excluded: Vec<Start>,
+    cancelled: bool,
+    /// why DTSTART couldn't be parsed, such as its TZID being unknown
+    invalid: Option<String>,
+}
+
+impl Properties {
+    /// The event, `None` when cancelled or all-day, failing on a rule out of the subset or an
+    /// unknown time zone
+    fn event(self) -> Result<Option<Event>, String> {
+        if self.cancelled {
+            return Ok(None);
+        }
+        if let Some(e) = self.invalid {
+            return Err(format!("{:?}: {e}", self.summary));
+        }
+        let Some(start) = self.start else {
+            return Ok(None);
+        };
+        let rule = match self.rule.as_deref().map(parse_rule) {
+            None => None,
+            Some(Ok(mut rule)) => {
+                // compared to the occurrences in the zone of the start
+                rule.until = rule.until.map(|until| Start {
+                    time: until.within(start.zone).unwrap_or(until.time),
+                    zone: start.zone,
+                });
+                Some(rule)
+            }
+            Some(Err(e)) => return Err(format!("{:?}: {e}", self.summary)),
+        };
+        Ok(Some(Event {
+            summary: self.summary,
+            start,
+            rule,
+            excluded: self
+                .excluded
+                .iter()
+                .filter_map(|excluded| excluded.within(start.zone))
+                .collect(),
+        }))
+    }
+}
+
+/// TEXT of RFC 5545, its `\,` `\;` `\n` and `\\` escapes
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push(' '),
+            Some(escaped) => unescaped.push(escaped),
+            None => (),
+        }
+    }
+    unescaped
+}
+
+/// `20240220T100000Z` in UTC, or `20240220T100000` floating
+fn parse_time(value: &str) -> Option<Start> {
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(value) => (value, Zone::Utc),
+        None => (value, Zone::Local),
+    };
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some(Start { time, zone })
+}
+
+/// DATE-TIME `value` in the zone of the TZID of `params`, such as `TZID=Europe/Berlin`, failing
+/// on one missing from the time zone database
+fn parse_zoned(value: &str, params: &str) -> Result<Option<Start>, String> {
+    let Some(start) = parse_time(value) else {
+        return Ok(None);
+    };
+    let Some(tzid) = params.split(';').find_map(|p| p.strip_prefix("TZID=")) else {
+        return Ok(Some(start));
+    };
+    if start.zone == Zone::Utc {
+        return Ok(Some(start));
+    }
+    let tzid = tzid.trim_matches('"');
+    let tz = tzid
+        .parse()
+        .map_err(|_| format!("unknown time zone {tzid:?}"))?;
+    Ok(Some(Start {
+        zone: Zone::Tz(tz),
+        ..start
+    }))
+}
+
+/// RRULE of daily or weekly events, failing with the parts out of the supported subset
+fn parse_rule(rule: &str) -> Result<Rule, String> {
+    let mut frequency = None;
+    let mut parsed = Rule {
+        frequency: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        days: vec![],
+    };
+    for part in rule.split(';') {
+        let (name, value) = part.split_once('=').unwrap_or((part, ""));
+        let invalid = || format!("invalid {part} of RRULE:{rule}");
+        match name {
+            "FREQ" => {
+                frequency = Some(match value {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    _ => return Err(format!("unsupported recurrence {rule}")),
+                })
+            }
+            "INTERVAL" => {
+                parsed.interval = value.parse().ok().filter(|i| *i > 0).ok_or_else(invalid)?
+            }
+            "COUNT" => parsed.count = Some(value.parse().map_err(|_| invalid())?),
+            "UNTIL" => {
+                let until = parse_time(value).or_else(|| {
+                    // a date only, until the end of the day
+                    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+                    Some(Start {
+                        time: date.and_hms_opt(23, 59, 59)?,
+                        zone: Zone::Local,
+                    })
+                });
+                parsed.until = Some(until.ok_or_else(invalid)?);
+            }
+            "BYDAY" => {
+                for day in value.split(',') {
+                    let day = match day {
+                        "MO" => Weekday::Mon,
+                        "TU" => Weekday::Tue,
+                        "WE" => Weekday::Wed,
+                        "TH" => Weekday::Thu,
+                        "FR" => Weekday::Fri,
+                        "SA" => Weekday::Sat,
+                        "SU" => Weekday::Sun,
+                        _ => return Err(format!("unsupported recurrence {rule}")),
+                    };
+                    parsed.days.push(day);
+                }
+            }
+            // weeks start on monday, as by default
+            "WKST" if value == "MO" => (),
+            _ => return Err(format!("unsupported recurrence {rule}")),
+        }
+    }
+    parsed.frequency = frequency.ok_or_else(|| format!("no FREQ in RRULE:{rule}"))?;
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:1\r
+SUMMARY:standup\r
+DTSTART;TZID=Europe/Berlin:20240219T100000\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR\r
+EXDATE;TZID=Europe/Berlin:20240221T100000\r
+BEGIN:VALARM\r
+TRIGGER:-PT5M\r
+SUMMARY:alarm\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:2\r
+SUMMARY:team offsite\\, day \r
+ one\r
+DTSTART;VALUE=DATE:20240221\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:3\r
+SUMMARY:board meeting\r
+DTSTART:20240222T140000\r
+RRULE:FREQ=MONTHLY;BYDAY=1TH\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:4\r
+SUMMARY:dentist\r
+DTSTART:20240221T083000\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn local(time: &str) -> DateTime<Local> {
+        parse_time(time).unwrap().local().unwrap()
+    }
+
+    fn berlin(time: &str) -> DateTime<Local> {
+        parse_zoned(time, "TZID=Europe/Berlin")
+            .unwrap()
+            .unwrap()
+            .local()
+            .unwrap()
+    }
+
+    #[test]
+    fn timed_events() {
+        let events = parse(ICS, Path::new("calendar.ics"));
+        // the all-day event and the monthly rule left out
+        let summaries: Vec<&str> = events.iter().map(|e| e.summary.as_str()).collect();
+        assert_eq!(summaries, ["standup", "dentist"]);
+        assert_eq!(
+            unfold("SUMMARY:a\r\n  b\r\nUID:1"),
+            ["SUMMARY:a b", "UID:1"]
+        );
+        assert_eq!(unescape("team offsite\\, day one"), "team offsite, day one");
+    }
+
+    #[test]
+    fn weekly_occurrences() {
+        let events = parse(ICS, Path::new("calendar.ics"));
+        let standup = &events[0];
+        let week = chrono::Duration::days(7);
+        let after = berlin("20240220T100001");
+        // the wednesday excluded
+        assert_eq!(
+            standup.next(after, after + week),
+            Some(berlin("20240222T100000"))
+        );
+        // friday, then monday after the weekend
+        let after = berlin("20240223T120000");
+        assert_eq!(
+            standup.next(after, after + week),
+            Some(berlin("20240226T100000"))
+        );
+        assert_eq!(
+            standup.next(after, after + chrono::Duration::hours(12)),
+            None
+        );
+    }
+
+    #[test]
+    fn daily_rules() {
+        let rule = parse_rule("FREQ=DAILY;INTERVAL=2;COUNT=3").unwrap();
+        let event = Event {
+            summary: "review".into(),
+            start: parse_time("20240220T090000").unwrap(),
+            rule: Some(rule),
+            excluded: vec![],
+        };
+        let after = local("20240220T090001");
+        let week = chrono::Duration::days(7);
+        assert_eq!(
+            event.next(after, after + week),
+            Some(local("20240222T090000"))
+        );
+        // past the third
+        let after = local("20240224T090001");
+        assert_eq!(event.next(after, after + week), None);
+        assert!(parse_rule("FREQ=DAILY;BYHOUR=9").is_err());
+    }
+
+    #[test]
+    fn time_zones() {
+        let ics = "BEGIN:VEVENT\r
+SUMMARY:sync\r
+DTSTART;TZID=\"America/New_York\":20240220T090000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:review\r
+DTSTART;TZID=W. Europe Standard Time:20240220T090000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:call\r
+DTSTART;TZID=Europe/Berlin:20240220T090000Z\r
+END:VEVENT\r
+";
+        // the windows time zone left out
+        let events = parse(ics, Path::new("calendar.ics"));
+        let summaries: Vec<&str> = events.iter().map(|e| e.summary.as_str()).collect();
+        assert_eq!(summaries, ["sync", "call"]);
+        assert_eq!(events[0].start.local(), Some(local("20240220T140000Z")));
+        assert_eq!(events[1].start.local(), Some(local("20240220T090000Z")));
+    }
+
+    #[test]
+    fn excluded_and_until_in_utc() {
+        let ics = "BEGIN:VEVENT\r
+SUMMARY:standup\r
+DTSTART;TZID=Europe/Berlin:20240219T100000\r
+RRULE:FREQ=DAILY;UNTIL=20240222T090000Z\r
+EXDATE:20240220T090000Z\r
+END:VEVENT\r
+";
+        let standup = &parse(ics, Path::new("calendar.ics"))[0];
+        let week = chrono::Duration::days(7);
+        let after = berlin("20240219T100001");
+        assert_eq!(
+            standup.next(after, after + week),
+            Some(berlin("20240221T100000"))
+        );
+        // the last at the time of UNTIL
+        let after = berlin("20240221T100001");
+        assert_eq!(
+            standup.next(after, after + week),
+            Some(berlin("20240222T100000"))
+        );
+        let after = berlin("20240222T100001");
+        assert_eq!(standup.next(after, after + week), None);
+    }
+
+    #[test]
+    fn rules_from_long_ago() {
+        let day = chrono::Duration::days(1);
+        let every_third_day = Event {
+            summary: "backup".into(),
+            start: parse_time("20000101T090000").unwrap(),
+            rule: Some(parse_rule("FREQ=DAILY;INTERVAL=3").unwrap()),
+            excluded: vec![],
+        };
+        // the 8814th day since the start, then the 8817th
+        let after = local("20240218T090000");
+        assert_eq!(every_third_day.next(after, after + day), Some(after));
+        let after = local("20240218T090001");
+        assert_eq!(
+            every_third_day.next(after, after + day * 7),
+            Some(local("20240221T090000"))
+        );
+
+        // every other week, from a thursday
+        let fortnightly = Event {
+            summary: "retro".into(),
+            start: parse_time("19900104T150000").unwrap(),
+            rule: Some(parse_rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,TH").unwrap()),
+            excluded: vec![],
+        };
+        // the week of the 19th being an odd one since
+        let after = local("20240215T150001");
+        assert_eq!(
+            fortnightly.next(after, after + day * 28),
+            Some(local("20240227T150000"))
+        );
+    }
+
+    #[test]
+    fn counting_down() {
+        let now = local("20240220T094800");
+        assert_eq!(
+            text("standup", local("20240220T100000"), now),
+            "standup in 12m"
+        );
+        assert_eq!(
+            text("standup", local("20240220T140000"), now),
+            "next: standup 14:00"
+        );
+        assert_eq!(
+            text("standup", local("20240221T100000"), now),
+            "next: standup Wed 10:00"
+        );
+        assert_eq!(
+            duration::until_countdown_change(Duration::from_secs(3 * 3600 + 59)),
+            Duration::from_secs(60)
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Days until the TLS certificates of hosts expire, as `cert example.com 12d` within
+//! `--cert-warn-days`
+//!
+//! The handshake is done with rustls, accepting any certificate as whether it verifies is for
+//! other modules to tell, and the notAfter of the leaf certificate is read with x509-parser.
+//! Every host is checked on its own thread with its own timeout, so a dead host doesn't delay
+//! the others.
+
+use std::fmt;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme};
+
+use super::Module;
+use crate::output::Segment;
+use crate::style::Level;
+
+/// Upper bound on connecting to a host and completing the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A host of `--cert-check`, such as `example.com:8443`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Target {
+    /// clap value parser of `host[:port]`, the port defaulting to 443. IPv6 addresses with a
+    /// port are bracketed, such as `[::1]:8443`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid host `{s}`, expected e.g. example.com:443");
+        let (host, port) = if let Some(bracketed) = s.strip_prefix('[') {
+            let (host, port) = bracketed.split_once(']').ok_or_else(invalid)?;
+            (host, port.strip_prefix(':'))
+        } else if s.matches(':').count() == 1 {
+            let (host, port) = s.split_once(':').ok_or_else(invalid)?;
+            (host, Some(port))
+        } else {
+            (s, None)
+        };
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid())?,
+            None => 443,
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// The hosts of `targets` whose certificate expires within `warn_days`, or which couldn't be
+/// checked, every `interval`. Hidden while every certificate is valid for longer.
+pub struct Cert {
+    targets: Vec<Target>,
+    interval: Duration,
+    warn_days: i64,
+    config: Arc<ClientConfig>,
+}
+
+impl Cert {
+    pub fn new(targets: Vec<Target>, interval: Duration, warn_days: i64) -> Self {
+        Self {
+            targets,
+            interval,
+            warn_days,
+            config: config(),
+        }
+    }
+}
+
+impl Module for Cert {
+    fn name(&self) -> &'static str {
+        "cert"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let checked: Vec<io::Result<DateTime<Utc>>> = std::thread::scope(|s| {
+            let checks: Vec<_> = self
+                .targets
+                .iter()
+                .map(|target| s.spawn(|| not_after(target, &self.config)))
+                .collect();
+            checks
+                .into_iter()
+                .map(|check| {
+                    check
+                        .join()
+                        .unwrap_or_else(|_| Err(io::Error::other("check panicked")))
+                })
+                .collect()
+        });
+
+        let now = Utc::now();
+        let mut expiring = vec![];
+        for (target, checked) in self.targets.iter().zip(checked) {
+            match checked {
+                Ok(not_after) => {
+                    let days = (not_after - now).num_days();
+                    if days < self.warn_days {
+                        expiring.push((target.host.as_str(), Some(days)));
+                    }
+                }
+                Err(e) => {
+                    warn!(target: "cert", "unable to check the certificate of {target}: {e}");
+                    expiring.push((target.host.as_str(), None));
+                }
+            }
+        }
+        Some(segment(&expiring))
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        // the checks run concurrently, each taking as long as its timeout
+        Some(self.interval + HANDSHAKE_TIMEOUT)
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn requires_network(&self) -> bool {
+        true
+    }
+}
+
+/// Client configuration of the handshakes, of the ring provider
+fn config() -> Arc<ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = AnyCertificate(provider.signature_verification_algorithms);
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .expect("the ring provider supports the default versions")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Arc::new(config)
+}
+
+/// Verifier accepting the certificate of any host, still checking the signatures of the
+/// handshake
+#[derive(Debug)]
+struct AnyCertificate(WebPkiSupportedAlgorithms);
+
+impl ServerCertVerifier for AnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_schemes()
+    }
+}
+
+/// notAfter of the leaf certificate presented by `target`
+fn not_after(target: &Target, config: &Arc<ClientConfig>) -> io::Result<DateTime<Utc>> {
+    let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+    let addr = (target.host.as_str(), target.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address"))?;
+    let mut socket = TcpStream::connect_timeout(&addr, HANDSHAKE_TIMEOUT)?;
+    // SNI is only sent for names
+    let name = ServerName::try_from(target.host.clone())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut conn = ClientConnection::new(Arc::clone(config), name).map_err(io::Error::other)?;
+    while conn.is_handshaking() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+        }
+        socket.set_read_timeout(Some(left))?;
+        socket.set_write_timeout(Some(left))?;
+        conn.complete_io(&mut socket)?;
+    }
+    let leaf = conn
+        .peer_certificates()
+        .and_then(|certificates| certificates.first())
+        .ok_or_else(|| io::Error::other("no certificate"))?;
+    expiry(leaf).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid certificate"))
+}
+
+/// notAfter of the validity of the DER of an X.509 certificate
+fn expiry(der: &[u8]) -> Option<DateTime<Utc>> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+    DateTime::from_timestamp(certificate.validity().not_after.timestamp(), 0)
+}
+
+/// `cert example.com 12d, other.org ?`, critical once one expired, empty without any
+fn segment(expiring: &[(&str, Option<i64>)]) -> Segment {
+    if expiring.is_empty() {
+        return Segment::new("cert", "");
+    }
+    let hosts: Vec<String> = expiring
+        .iter()
+        .map(|(host, days)| match days {
+            Some(days) if *days < 0 => format!("{host} expired"),
+            Some(days) => format!("{host} {days}d"),
+            None => format!("{host} ?"),
+        })
+        .collect();
+    let expired = expiring.iter().any(|(_, days)| days.is_some_and(|d| d < 0));
+    let level = if expired {
+        Level::Critical
+    } else {
+        Level::Warning
+    };
+    Segment::new("cert", format!("cert {}", hosts.join(", "))).level(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed certificate for example.com, valid until 2036-10-12
+    const PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBgTCCASegAwIBAgIUTsK0qTO9/gEw30gkZ9WQ/qhA6CcwCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLZXhhbXBsZS5jb20wHhcNMjYxMDE1MDI1MjI5WhcNMzYxMDEy
+MDI1MjI5WjAWMRQwEgYDVQQDDAtleGFtcGxlLmNvbTBZMBMGByqGSM49AgEGCCqG
+SM49AwEHA0IABAKumcv31KH0Tw4D0mhDKr80Oc7NLrJLvFt0mjYzguIxCRP7MaWS
+j09sY+yizQFSUEIpnWQU6swC1xNUZ3przlijUzBRMB0GA1UdDgQWBBSp6yE25qxV
+FKETGKNh6alficAj5DAfBgNVHSMEGDAWgBSp6yE25qxVFKETGKNh6alficAj5DAP
+BgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIQDuBhMhKyeHVQO/LJY5
+HPm436QuAFHZD6qM+WtbQ9ohGwIgKZroGxCaHHE/RmLChBrKfa3K0fiXOPYilrkH
+SCPhMGE=
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn parse_target() {
+        let target = |host: &str, port| Target {
+            host: host.into(),
+            port,
+        };
+        assert_eq!(Target::parse("example.com"), Ok(target("example.com", 443)));
+        assert_eq!(
+            Target::parse("example.com:8443"),
+            Ok(target("example.com", 8443))
+        );
+        assert_eq!(Target::parse("[::1]:8443"), Ok(target("::1", 8443)));
+        assert_eq!(Target::parse("::1"), Ok(target("::1", 443)));
+        assert!(Target::parse("example.com:https").is_err());
+        assert!(Target::parse(":443").is_err());
+        assert_eq!(target("::1", 443).to_string(), "[::1]:443");
+    }
+
+    #[test]
+    fn leaf_not_after() {
+        let (_, pem) = x509_parser::pem::parse_x509_pem(PEM.as_bytes()).unwrap();
+        assert_eq!(
+            expiry(&pem.contents).unwrap().to_string(),
+            "2036-10-12 02:52:29 UTC"
+        );
+        assert_eq!(expiry(&pem.contents[..40]), None);
+    }
+
+    #[test]
+    fn self_signed_handshake() {
+        let mut params = rcgen::CertificateParams::new(vec!["localhost".into()]).unwrap();
+        params.not_after = rcgen::date_time_ymd(2036, 10, 12);
+        let key = rcgen::KeyPair::generate().unwrap();
+        let certificate = params.self_signed(&key).unwrap();
+        let server = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![certificate.der().clone()],
+            rustls::pki_types::PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+        )
+        .unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut conn = rustls::ServerConnection::new(Arc::new(server)).unwrap();
+            while conn.is_handshaking() && conn.complete_io(&mut socket).is_ok() {}
+        });
+        let target = Target::parse(&format!("127.0.0.1:{port}")).unwrap();
+        let not_after = not_after(&target, &config()).unwrap();
+        assert_eq!(not_after.to_string(), "2036-10-12 00:00:00 UTC");
+        accepted.join().unwrap();
+    }
+
+    #[test]
+    fn expiring_segment() {
+        assert_eq!(segment(&[]).text, "");
+        let segment = segment(&[("example.com", Some(12)), ("other.org", None)]);
+        assert_eq!(segment.text, "cert example.com 12d, other.org ?");
+        assert_eq!(segment.level, Level::Warning);
+    }
+}
@@ -0,0 +1,287 @@
+//! Resource usage of a chosen cgroup, such as `build: cpu 340% mem 6.1G` for the slice of a
+//! local CI run
+//!
+//! A systemd unit is resolved to its cgroup through the ControlGroup of the unit on the system
+//! bus then on the session bus, or without D-Bus from the naming of the slices. While the cgroup
+//! doesn't exist, such as with its service stopped, the segment is hidden and the unit resolved
+//! again every few seconds. Only the unified hierarchy of cgroup v2 is supported.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use super::{read_into, Module};
+use crate::logging::Throttle;
+use crate::output::Segment;
+use crate::throughput;
+
+pub const CGROUP_PATH: &str = "/sys/fs/cgroup";
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval of resolving the unit again while its cgroup is missing
+const RESOLVE_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct Cgroup {
+    /// `--cgroup`, a unit or a path below /sys/fs/cgroup
+    target: String,
+    label: String,
+    /// standing in for /sys/fs/cgroup
+    root: PathBuf,
+    /// the directory of the cgroup, and when it was resolved
+    resolved: Option<(PathBuf, Instant)>,
+    /// time and usage_usec of the previous sample
+    last: Option<(Instant, u64)>,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl Cgroup {
+    /// Watching `target` below `root`, such as /sys/fs/cgroup. Fails on the cgroup v1 hierarchy.
+    pub fn new(target: &str, root: impl Into<PathBuf>) -> Result<Self, String> {
+        let root = root.into();
+        if !root.join("cgroup.controllers").exists() {
+            return Err(format!(
+                "{} isn't the unified hierarchy of cgroup v2, cgroup v1 is unsupported",
+                root.display()
+            ));
+        }
+        let target = target.trim_end_matches('/');
+        let name = target.rsplit('/').next().unwrap_or(target);
+        let label = name.rsplit_once('.').map_or(name, |(label, _)| label);
+        Ok(Self {
+            target: target.to_string(),
+            label: if label.is_empty() { "cgroup" } else { label }.to_string(),
+            root,
+            resolved: None,
+            last: None,
+            throttle: Throttle::default(),
+            buf: String::new(),
+        })
+    }
+
+    /// The directory of the cgroup, resolved again every [`RESOLVE_INTERVAL`] while missing
+    fn dir(&mut self, now: Instant) -> Option<PathBuf> {
+        if let Some((dir, resolved)) = &self.resolved {
+            if dir.is_dir() {
+                return Some(dir.clone());
+            }
+            if now.duration_since(*resolved) < RESOLVE_INTERVAL {
+                return None;
+            }
+        }
+        let dir = self.root.join(resolve(&self.target));
+        self.resolved = Some((dir.clone(), now));
+        dir.is_dir().then_some(dir)
+    }
+
+    /// Cpu usage in percent of a cpu since the previous sample, `None` on the first
+    fn cpu(&mut self, now: Instant, usage_usec: u64) -> Option<f64> {
+        let (last_time, last_usage) = self.last.replace((now, usage_usec))?;
+        let secs = now.duration_since(last_time).as_secs_f64();
+        if secs == 0.0 {
+            return None;
+        }
+        Some(usage_usec.saturating_sub(last_usage) as f64 / 1e6 / secs * 100.0)
+    }
+
+    /// memory.current and the usage_usec of cpu.stat
+    fn read(&mut self, dir: &Path) -> io::Result<(u64, u64)> {
+        read_into(&dir.join("memory.current"), &mut self.buf)?;
+        let memory = self.buf.trim().parse().map_err(invalid)?;
+        read_into(&dir.join("cpu.stat"), &mut self.buf)?;
+        let usage = self
+            .buf
+            .lines()
+            .find_map(|line| line.strip_prefix("usage_usec "))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no usage_usec"))?;
+        let usage = usage.trim().parse().map_err(invalid)?;
+        Ok((memory, usage))
+    }
+}
+
+impl Module for Cgroup {
+    fn name(&self) -> &'static str {
+        "cgroup"
+    }
+
+    /// Hidden while the cgroup doesn't exist, keeping the last displayed on failure
+    fn collect(&mut self) -> Option<Segment> {
+        let now = Instant::now();
+        let Some(dir) = self.dir(now) else {
+            if self.last.take().is_some() {
+                info!(target: "cgroup", "{} is gone, hiding", self.target);
+            }
+            return Some(Segment::new("cgroup", ""));
+        };
+        match self.read(&dir) {
+            Ok((memory, usage)) => {
+                self.throttle.succeeded();
+                let cpu = self.cpu(now, usage)?;
+                Some(segment(&self.label, cpu, memory))
+            }
+            // removed while reading
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.last = None;
+                Some(Segment::new("cgroup", ""))
+            }
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    let dir = dir.display();
+                    warn!(target: "cgroup", "unable to read {dir}: {e}{suppressed}");
+                }
+                None
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+fn invalid(e: std::num::ParseIntError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// The path of `target` below /sys/fs/cgroup: itself when a path, or the ControlGroup of the
+/// unit, or without it, where systemd places a slice or a system service
+fn resolve(target: &str) -> PathBuf {
+    if let Some(path) = target.strip_prefix(CGROUP_PATH) {
+        return PathBuf::from(path.trim_start_matches('/'));
+    }
+    if target.contains('/') {
+        return PathBuf::from(target.trim_start_matches('/'));
+    }
+    // as systemctl, a service without a suffix
+    let unit = if target.contains('.') {
+        target.to_string()
+    } else {
+        format!("{target}.service")
+    };
+    #[cfg(feature = "dbus")]
+    match unit_cgroup(&unit) {
+        Ok(cgroup) => return PathBuf::from(cgroup.trim_start_matches('/')),
+        Err(e) => log::debug!(target: "cgroup", "unable to look up {unit}: {e}"),
+    }
+    slice_path(&unit)
+}
+
+/// `a-b.slice` as `a.slice/a-b.slice`, and other units in system.slice
+fn slice_path(unit: &str) -> PathBuf {
+    let Some(name) = unit.strip_suffix(".slice") else {
+        return Path::new("system.slice").join(unit);
+    };
+    let mut path = PathBuf::new();
+    let mut prefix = String::new();
+    for part in name.split('-').filter(|part| !part.is_empty()) {
+        if !prefix.is_empty() {
+            prefix.push('-');
+        }
+        prefix.push_str(part);
+        path.push(format!("{prefix}.slice"));
+    }
+    path
+}
+
+/// ControlGroup of `unit`, a system unit or else one of the user
+#[cfg(feature = "dbus")]
+fn unit_cgroup(unit: &str) -> zbus::Result<String> {
+    use zbus::blocking::{Connection, Proxy};
+    use zbus::zvariant::OwnedObjectPath;
+
+    let interface = match unit.rsplit_once('.').map(|(_, kind)| kind) {
+        Some("slice") => "org.freedesktop.systemd1.Slice",
+        Some("scope") => "org.freedesktop.systemd1.Scope",
+        Some("socket") => "org.freedesktop.systemd1.Socket",
+        Some("mount") => "org.freedesktop.systemd1.Mount",
+        Some("swap") => "org.freedesktop.systemd1.Swap",
+        _ => "org.freedesktop.systemd1.Service",
+    };
+    let lookup = |conn: zbus::Result<Connection>| -> zbus::Result<String> {
+        let conn = conn?;
+        let manager = Proxy::new(
+            &conn,
+            "org.freedesktop.systemd1",
+            "/org/freedesktop/systemd1",
+            "org.freedesktop.systemd1.Manager",
+        )?;
+        let path: OwnedObjectPath = manager.call("GetUnit", &(unit,))?;
+        let unit = Proxy::new(&conn, "org.freedesktop.systemd1", path, interface)?;
+        let cgroup: String = unit.get_property("ControlGroup")?;
+        if cgroup.is_empty() {
+            return Err(zbus::Error::Failure("no cgroup, inactive".to_string()));
+        }
+        Ok(cgroup)
+    };
+    lookup(Connection::system()).or_else(|_| lookup(Connection::session()))
+}
+
+/// `build: cpu 340% mem 6.1G`, the cpu in percent of a cpu
+fn segment(label: &str, cpu: f64, memory: u64) -> Segment {
+    let text = format!(
+        "{label}: cpu {cpu:.0}% mem {}",
+        throughput::format_bytes(memory as f64)
+    );
+    Segment::new("cgroup", text)
+        .value("cpu_percent", cpu)
+        .value("memory_bytes", memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src/modules/fixtures/cgroup/fs")
+    }
+
+    #[test]
+    fn slices_nested() {
+        assert_eq!(slice_path("build.slice"), Path::new("build.slice"));
+        assert_eq!(
+            slice_path("user-1000.slice"),
+            Path::new("user.slice/user-1000.slice")
+        );
+        assert_eq!(
+            slice_path("sshd.service"),
+            Path::new("system.slice/sshd.service")
+        );
+        assert_eq!(
+            resolve("/sys/fs/cgroup/build.slice/"),
+            Path::new("build.slice/")
+        );
+        assert_eq!(resolve("/build.slice"), Path::new("build.slice"));
+    }
+
+    #[test]
+    fn usage_of_the_slice() {
+        let mut cgroup = Cgroup::new("/user.slice/user-1000.slice", fixtures()).unwrap();
+        assert_eq!(cgroup.label, "user-1000");
+        // the rate starts from the second sample
+        assert!(cgroup.collect().is_none());
+        let start = Instant::now();
+        cgroup.last = Some((start, 0));
+        assert_eq!(
+            cgroup.cpu(start + Duration::from_secs(2), 6_800_000),
+            Some(340.0)
+        );
+        assert_eq!(
+            segment(&cgroup.label, 340.0, 6_549_825_126).text,
+            "user-1000: cpu 340% mem 6.1G"
+        );
+    }
+
+    #[test]
+    fn missing_hidden() {
+        let mut cgroup = Cgroup::new("/stopped.slice", fixtures()).unwrap();
+        assert_eq!(cgroup.collect().unwrap().text, "");
+        assert!(Cgroup::new("/build.slice", fixtures().join("user.slice/memory.max")).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! Local time, and the date as the view after a click
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, TimeZone, Timelike};
+
+use super::Module;
+use crate::locale::Locale;
+use crate::output::Segment;
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+pub const TIME_FORMAT: &str = "%F %T";
+pub const DATE_FORMAT: &str = "%A %-d %B %Y";
+
+/// Conversions of the time, its separators between hours, minutes, and seconds
+const EXPANDED: &[(&str, &str)] = &[("%T", "%H:%M:%S"), ("%R", "%H:%M")];
+
+/// Conversions of the time as chrono formats them without a locale
+const UNLOCALIZED: &[(&str, &str)] = &[
+    ("%r", "%I:%M:%S %p"),
+    ("%X", "%H:%M:%S"),
+    ("%c", "%a %b %e %H:%M:%S %Y"),
+];
+
+pub struct Clock {
+    time_format: String,
+    date_format: String,
+    /// the time format on odd seconds with `--clock-blink`, its separators blanked
+    blinked: Option<String>,
+    seconds: bool,
+    /// names and formats of `--time-locale`, chrono's without
+    locale: Option<Locale>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(TIME_FORMAT, DATE_FORMAT)
+    }
+}
+
+impl Clock {
+    /// Formatting the time with `time_format`, and its date view with `date_format`
+    pub fn new(time_format: impl Into<String>, date_format: impl Into<String>) -> Self {
+        Self {
+            time_format: time_format.into(),
+            date_format: date_format.into(),
+            blinked: None,
+            seconds: true,
+            locale: None,
+        }
+    }
+
+    /// Without the seconds of the time format, only updated as the minute changes. Fails on a
+    /// format showing them otherwise, such as `%s`, or `%X` of the locale.
+    pub fn seconds(mut self, seconds: bool) -> Result<Self, String> {
+        if !seconds {
+            self.time_format = without_seconds(&self.time_format, self.locale.is_some())?;
+        }
+        self.seconds = seconds;
+        Ok(self)
+    }
+
+    /// Blanking the separators of the time on every other second
+    pub fn blink(mut self, blink: bool) -> Self {
+        self.blinked = blink.then(|| blinked(&self.time_format));
+        self
+    }
+
+    pub fn locale(mut self, locale: Option<Locale>) -> Self {
+        self.locale = locale;
+        self
+    }
+}
+
+impl Module for Clock {
+    fn name(&self) -> &'static str {
+        "time"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        Some(segment(&Local::now(), self))
+    }
+
+    /// Every second, or until the next minute without seconds or blinking
+    fn interval(&self) -> Duration {
+        if self.seconds || self.blinked.is_some() {
+            return INTERVAL;
+        }
+        Duration::from_secs(60 - u64::from(Local::now().second()))
+    }
+}
+
+/// Conversion specifications such as `%-d` and `%:z`, and each character between them
+fn tokens(format: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut rest = format;
+    while let Some(c) = rest.chars().next() {
+        let mut len = c.len_utf8();
+        if c == '%' {
+            // flags and widths, then the conversion
+            let flags = rest[len..]
+                .find(|c: char| !matches!(c, '-' | '_' | '0'..='9' | '^' | '#' | ':' | '.'))
+                .map_or(rest.len() - len, |flags| flags);
+            len += flags;
+            len += rest[len..].chars().next().map_or(0, char::len_utf8);
+        }
+        tokens.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+fn expand(format: &str) -> String {
+    tokens(format)
+        .into_iter()
+        .map(|token| {
+            EXPANDED
+                .iter()
+                .find(|(conversion, _)| *conversion == token)
+                .map_or(token, |(_, expanded)| expanded)
+        })
+        .collect()
+}
+
+/// `format` without `%S` and its separator, failing on the conversions showing seconds
+/// otherwise, such as `%s` and `%.3f`, or those of a locale when `localized`
+fn without_seconds(format: &str, localized: bool) -> Result<String, String> {
+    let mut expanded = String::with_capacity(format.len());
+    for token in tokens(&expand(format)) {
+        match UNLOCALIZED
+            .iter()
+            .find(|(conversion, _)| *conversion == token)
+        {
+            Some(_) if localized => {
+                return Err(format!(
+                    "{token} of --time-locale shows the seconds, which can't be left out"
+                ))
+            }
+            Some((_, unlocalized)) => expanded.push_str(unlocalized),
+            None if token == "%s"
+                || token == "%+"
+                || (token.starts_with('%') && token.ends_with('f')) =>
+            {
+                return Err(format!(
+                    "{token} shows the seconds, which can't be left out"
+                ))
+            }
+            None => expanded.push_str(token),
+        }
+    }
+    let mut kept: Vec<&str> = vec![];
+    for token in tokens(&expanded) {
+        if token == "%S" {
+            if kept.last() == Some(&":") {
+                kept.pop();
+            }
+            continue;
+        }
+        kept.push(token);
+    }
+    Ok(kept.concat())
+}
+
+/// `format` with the `:` between hours, minutes, and seconds as spaces
+fn blinked(format: &str) -> String {
+    let expanded = expand(format);
+    let tokens = tokens(&expanded);
+    let time = |token: Option<&&str>| {
+        token.is_some_and(|token| {
+            token.starts_with('%') && token.ends_with(['H', 'I', 'k', 'l', 'M', 'S'])
+        })
+    };
+    let mut blinked = String::with_capacity(expanded.len());
+    for (i, token) in tokens.iter().enumerate() {
+        let separator =
+            *token == ":" && i > 0 && time(tokens.get(i - 1)) && time(tokens.get(i + 1));
+        blinked.push_str(if separator { " " } else { token });
+    }
+    blinked
+}
+
+fn segment<Tz: TimeZone>(time: &DateTime<Tz>, clock: &Clock) -> Segment
+where
+    Tz::Offset: Display,
+{
+    let format = |format: &str| match clock.locale {
+        Some(locale) => time.format_localized(format, locale).to_string(),
+        None => time.format(format).to_string(),
+    };
+    // from the second of the time shown, so that the blink can't drift from it
+    let time_format = match &clock.blinked {
+        Some(blinked) if time.second() % 2 == 1 => blinked,
+        _ => &clock.time_format,
+    };
+    Segment::new("time", format(time_format)).alt(format(&clock.date_format))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    #[test]
+    fn time_and_date() {
+        let time = Utc.with_ymd_and_hms(2023, 3, 5, 9, 4, 7).unwrap();
+        let segment = segment(&time, &Clock::default());
+        assert_eq!(segment.text, "2023-03-05 09:04:07");
+        assert_eq!(segment.alt.as_deref(), Some("Sunday 5 March 2023"));
+    }
+
+    #[test]
+    fn localized() {
+        let clock = Clock::new(TIME_FORMAT, "%A, %e. %b").locale(Some(Locale::de_DE));
+        let time = Utc.with_ymd_and_hms(2024, 2, 20, 15, 4, 7).unwrap();
+        let segment = segment(&time, &clock);
+        assert_eq!(segment.text, "2024-02-20 15:04:07");
+        assert_eq!(segment.alt.as_deref(), Some("Dienstag, 20. Feb"));
+
+        let clock = Clock::new("%x %X", "%c").locale(Some(Locale::de_DE));
+        let segment = super::segment(&time, &clock);
+        assert_eq!(segment.text, "20.02.2024 15:04:07");
+        assert_eq!(segment.alt.as_deref(), Some("Di 20 Feb 2024 15:04:07 UTC"));
+        let clock = Clock::new("%p %-I:%M", "%r").locale(Some(Locale::ja_JP));
+        let segment = super::segment(&time, &clock);
+        assert_eq!(segment.text, "午後 3:04");
+        assert_eq!(segment.alt.as_deref(), Some("午後03時04分07秒"));
+    }
+
+    #[test]
+    fn seconds_and_blink() {
+        let time = Utc.with_ymd_and_hms(2024, 2, 20, 9, 4, 7).unwrap();
+        let clock = Clock::default().seconds(false).unwrap();
+        assert_eq!(segment(&time, &clock).text, "2024-02-20 09:04");
+        let clock = Clock::new("%-I:%M:%S %p, %:z", DATE_FORMAT).blink(true);
+        assert_eq!(segment(&time, &clock).text, "9 04 07 AM, +00:00");
+        let even = time + chrono::Duration::seconds(1);
+        assert_eq!(segment(&even, &clock).text, "9:04:08 AM, +00:00");
+        let clock = Clock::new("%R:%S", DATE_FORMAT)
+            .seconds(false)
+            .unwrap()
+            .blink(true);
+        assert_eq!(segment(&time, &clock).text, "09 04");
+    }
+
+    #[test]
+    fn locale_formats_without_seconds() {
+        let time = Utc.with_ymd_and_hms(2023, 3, 5, 21, 4, 7).unwrap();
+        for (format, with_seconds, without) in [
+            ("%r", "09:04:07 PM", "09:04 PM"),
+            ("%X", "21:04:07", "21:04"),
+            ("%c", "Sun Mar  5 21:04:07 2023", "Sun Mar  5 21:04 2023"),
+        ] {
+            // as chrono formats them without a locale
+            let clock = Clock::new(format, DATE_FORMAT);
+            assert_eq!(segment(&time, &clock).text, with_seconds);
+            let clock = clock.seconds(false).unwrap();
+            assert_eq!(segment(&time, &clock).text, without, "{format}");
+
+            let localized = Clock::new(format, DATE_FORMAT).locale(Some(Locale::de_DE));
+            let e = localized.seconds(false).err().unwrap();
+            assert_eq!(
+                e,
+                format!("{format} of --time-locale shows the seconds, which can't be left out")
+            );
+        }
+    }
+
+    #[test]
+    fn seconds_rejected() {
+        for format in ["%s", "%H:%M %+", "%T%.3f", "%H:%M:%S.%f", "%3f"] {
+            assert!(without_seconds(format, false).is_err(), "{format}");
+        }
+        // literal
+        assert_eq!(without_seconds("%%s %R", false).unwrap(), "%%s %H:%M");
+        // the same in every locale
+        assert_eq!(without_seconds("%a %T", true).unwrap(), "%a %H:%M");
+    }
+}
@@ -0,0 +1,160 @@
+//! Cpu usage, averaged over the cpus, and displayed as an average over time with
+//! `--cpu-smoothing`
+
+use std::time::{Duration, Instant};
+
+use sysinfo::{CpuExt, CpuRefreshKind, RefreshKind, System, SystemExt};
+
+use super::Module;
+use crate::output::Segment;
+use crate::percent::Percent;
+use crate::smoothing::Ema;
+
+/// Raised to sysinfo's minimum interval between samples, if ever longer
+const INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Cpu {
+    sys: System,
+    /// time of the previous sample
+    sampled: Instant,
+    percent: Percent,
+    smoothing: Option<Ema>,
+}
+
+impl Cpu {
+    /// Taking the first sample, the usage being measured between two
+    pub fn new() -> Self {
+        let kind = CpuRefreshKind::new().with_cpu_usage();
+        let sys = System::new_with_specifics(RefreshKind::new().with_cpu(kind));
+        Self {
+            sys,
+            sampled: Instant::now(),
+            percent: Percent::default(),
+            smoothing: None,
+        }
+    }
+
+    pub fn percent(mut self, percent: Percent) -> Self {
+        self.percent = percent;
+        self
+    }
+
+    /// Display the moving average of the usage, `alpha` being the weight of the latest sample
+    pub fn smoothing(mut self, alpha: f64) -> Self {
+        self.smoothing = Some(Ema::new(alpha, self.interval()));
+        self
+    }
+
+    /// Only the usage, the frequencies being read from sysfs for each cpu otherwise
+    fn sample(&mut self) {
+        self.sys
+            .refresh_cpu_specifics(CpuRefreshKind::new().with_cpu_usage());
+        self.sampled = Instant::now();
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Cpu {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        // such as with --once, right after the first sample
+        let since = self.sampled.elapsed();
+        if since < System::MINIMUM_CPU_UPDATE_INTERVAL {
+            std::thread::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL - since);
+        }
+        self.sample();
+        let usages: Vec<f32> = self.sys.cpus().iter().map(CpuExt::cpu_usage).collect();
+        Some(segment(&usages, self.smoothing.as_mut(), self.percent))
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL.max(System::MINIMUM_CPU_UPDATE_INTERVAL)
+    }
+
+    fn reset(&mut self) {
+        self.sample();
+        if let Some(smoothing) = &mut self.smoothing {
+            smoothing.reset();
+        }
+    }
+}
+
+/// `cpu 05%`, the average of `usages`, or its moving average with `smoothing`. Empty without a
+/// cpu, such as in a container hiding /proc/stat.
+fn segment(usages: &[f32], smoothing: Option<&mut Ema>, percent: Percent) -> Segment {
+    if usages.is_empty() {
+        return Segment::new("cpu", "");
+    }
+    let usage = f64::from(usages.iter().sum::<f32>()) / usages.len() as f64;
+    let shown = smoothing.map(|smoothing| smoothing.update(usage));
+    let mut text = String::from("cpu ");
+    percent.write(shown.unwrap_or(usage), &mut text);
+    text.push('%');
+    let segment = Segment::new("cpu", text).percentage(usage);
+    match shown {
+        Some(shown) => segment.shown(shown),
+        None => segment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_rounded() {
+        let average = segment(&[2.0, 5.5, 0.0, 1.0], None, Percent::default());
+        assert_eq!(average.text, "cpu 02%");
+        assert_eq!(average.percentage, Some(2.125));
+        // idle isn't rounded up
+        assert_eq!(
+            segment(&[0.2, 0.1], None, Percent::default()).text,
+            "cpu 00%"
+        );
+    }
+
+    #[test]
+    fn smoothed_text_raw_percentage() {
+        let mut smoothing = Ema::new(0.5, INTERVAL);
+        segment(&[80.0], Some(&mut smoothing), Percent::default());
+        let segment = segment(&[20.0], Some(&mut smoothing), Percent::default());
+        assert_eq!(segment.text, "cpu 50%");
+        assert_eq!(segment.percentage, Some(20.0));
+        assert_eq!(segment.shown, Some(50.0));
+    }
+
+    #[test]
+    fn without_cpu() {
+        let segment = segment(&[], None, Percent::default());
+        assert_eq!(segment.text, "");
+        assert_eq!(segment.percentage, None);
+    }
+
+    /// The first sample is taken on creation, the first collected being the usage since
+    #[test]
+    fn first_usage_is_measured() {
+        let start = Instant::now();
+        let mut cpu = Cpu::new();
+        let segment = cpu.collect().unwrap();
+        assert!(start.elapsed() >= System::MINIMUM_CPU_UPDATE_INTERVAL);
+        assert!(segment
+            .percentage
+            .is_some_and(|usage| (0.0..=100.0).contains(&usage)));
+    }
+
+    #[test]
+    fn full_usage() {
+        assert_eq!(
+            segment(&[100.0, 100.0], None, Percent::default()).text,
+            "cpu 100%"
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Cpu time by kind, such as `cpu u37 s8 io12 st0` of `--cpu-detail`, from the jiffies of
+//! /proc/stat between two samples
+//!
+//! A busy percentage alone can't tell user time from a machine stuck in iowait or stolen by its
+//! hypervisor. The guest time of virtual machines is counted in the user and nice times by the
+//! kernel, so it's taken out of those. Without a previous sample, such as on the first tick or
+//! after a suspend, there is nothing to show yet.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use log::{info, warn};
+
+use super::{read_into, Module};
+use crate::logging::Throttle;
+use crate::output::Segment;
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Half the range of the counters of 32 bit kernels, a counter going from above to below it
+/// having wrapped rather than been reset
+const HALF_U32: u64 = 1 << 31;
+
+/// A kind of cpu time of `--cpu-fields`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Field {
+    /// user time, without nice and guest time
+    User,
+    /// user time of niced processes, without guest time
+    Nice,
+    /// system time, without interrupts
+    Sys,
+    /// hardware interrupts
+    Irq,
+    /// software interrupts
+    Softirq,
+    /// idle while waiting on IO
+    Iowait,
+    /// taken by the hypervisor for other virtual machines
+    Steal,
+    /// running virtual machines
+    Guest,
+    Idle,
+}
+
+impl Field {
+    /// Before the percentage, such as `io` of `io12`
+    fn label(self) -> &'static str {
+        match self {
+            Self::User => "u",
+            Self::Nice => "n",
+            Self::Sys => "s",
+            Self::Irq => "hi",
+            Self::Softirq => "si",
+            Self::Iowait => "io",
+            Self::Steal => "st",
+            Self::Guest => "g",
+            Self::Idle => "id",
+        }
+    }
+
+    /// Name of its value in the segment
+    fn name(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Nice => "nice",
+            Self::Sys => "sys",
+            Self::Irq => "irq",
+            Self::Softirq => "softirq",
+            Self::Iowait => "iowait",
+            Self::Steal => "steal",
+            Self::Guest => "guest",
+            Self::Idle => "idle",
+        }
+    }
+}
+
+/// The default of `--cpu-fields`
+pub const DEFAULT_FIELDS: &[Field] = &[Field::User, Field::Sys, Field::Iowait, Field::Steal];
+
+const ALL_FIELDS: &[Field] = &[
+    Field::User,
+    Field::Nice,
+    Field::Sys,
+    Field::Irq,
+    Field::Softirq,
+    Field::Iowait,
+    Field::Steal,
+    Field::Guest,
+    Field::Idle,
+];
+
+/// Jiffies of the `cpu` line of /proc/stat, in its order. Kernels before 2.6.33 have fewer
+/// fields, the missing ones being 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Times([u64; 10]);
+
+impl Times {
+    const USER: usize = 0;
+    const NICE: usize = 1;
+    const SYSTEM: usize = 2;
+    const IDLE: usize = 3;
+    const IOWAIT: usize = 4;
+    const IRQ: usize = 5;
+    const SOFTIRQ: usize = 6;
+    const STEAL: usize = 7;
+    const GUEST: usize = 8;
+    const GUEST_NICE: usize = 9;
+
+    fn parse(stat: &str) -> io::Result<Self> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let line = stat
+            .lines()
+            .find(|line| line.starts_with("cpu "))
+            .ok_or_else(|| invalid("no cpu line".into()))?;
+        let mut times = Self::default();
+        for (time, field) in times
+            .0
+            .iter_mut()
+            .zip(line.split_ascii_whitespace().skip(1))
+        {
+            *time = field.parse().map_err(|e| invalid(format!("{e}: {line}")))?;
+        }
+        Ok(times)
+    }
+
+    /// Jiffies elapsed since `previous`, `None` for counters gone backwards such as after a
+    /// cpu went offline. Wrapped counters of 32 bit kernels are counted across the wrap.
+    fn since(&self, previous: &Self) -> Option<Self> {
+        let mut delta = Self::default();
+        for ((delta, now), before) in delta.0.iter_mut().zip(self.0).zip(previous.0) {
+            *delta = match now.checked_sub(before) {
+                Some(delta) => delta,
+                None if before >= HALF_U32 && before <= u64::from(u32::MAX) && now < HALF_U32 => {
+                    now + (u64::from(u32::MAX) - before) + 1
+                }
+                None => return None,
+            };
+        }
+        Some(delta)
+    }
+
+    /// Jiffies of `field`, the guest times taken out of the user and nice times counting them
+    fn get(&self, field: Field) -> u64 {
+        let t = &self.0;
+        match field {
+            Field::User => t[Self::USER].saturating_sub(t[Self::GUEST]),
+            Field::Nice => t[Self::NICE].saturating_sub(t[Self::GUEST_NICE]),
+            Field::Sys => t[Self::SYSTEM],
+            Field::Irq => t[Self::IRQ],
+            Field::Softirq => t[Self::SOFTIRQ],
+            Field::Iowait => t[Self::IOWAIT],
+            Field::Steal => t[Self::STEAL],
+            Field::Guest => t[Self::GUEST] + t[Self::GUEST_NICE],
+            Field::Idle => t[Self::IDLE],
+        }
+    }
+
+    /// All jiffies, without counting the guest times twice
+    fn total(&self) -> u64 {
+        self.0[..=Self::STEAL].iter().sum()
+    }
+}
+
+pub struct CpuDetail {
+    stat: PathBuf,
+    fields: Vec<Field>,
+    /// times of the previous sample
+    last: Option<Times>,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl CpuDetail {
+    pub fn new(stat: impl Into<PathBuf>) -> Self {
+        Self {
+            stat: stat.into(),
+            fields: DEFAULT_FIELDS.to_vec(),
+            last: None,
+            throttle: Throttle::default(),
+            buf: String::new(),
+        }
+    }
+
+    /// The kinds of time shown, in order
+    pub fn fields(mut self, fields: Vec<Field>) -> Self {
+        self.fields = fields;
+        self
+    }
+}
+
+impl Module for CpuDetail {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    /// Empty without a previous sample, and keeping the last displayed on failure or with
+    /// counters gone backwards
+    fn collect(&mut self) -> Option<Segment> {
+        let times = read_into(&self.stat, &mut self.buf).and_then(|()| Times::parse(&self.buf));
+        let times = match times {
+            Ok(times) => times,
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    let path = self.stat.display();
+                    warn!(target: "cpu", "unable to read {path}: {e}{suppressed}");
+                }
+                return None;
+            }
+        };
+        self.throttle.succeeded();
+        let Some(last) = self.last.replace(times) else {
+            return Some(Segment::new("cpu", ""));
+        };
+        let Some(delta) = times.since(&last) else {
+            info!(target: "cpu", "cpu times went backwards, starting over");
+            return None;
+        };
+        segment(&delta, &self.fields)
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// `cpu u37 s8 io12 st0` of the jiffies elapsed, with the busy percentage, idle and iowait
+/// not being busy. `None` without any elapsed.
+fn segment(delta: &Times, fields: &[Field]) -> Option<Segment> {
+    let total = delta.total();
+    if total == 0 {
+        return None;
+    }
+    let percent = |field| delta.get(field) as f64 / total as f64 * 100.0;
+    let mut text = String::from("cpu");
+    for field in fields {
+        text.push_str(&format!(" {}{:.0}", field.label(), percent(*field)));
+    }
+    let busy = 100.0 - percent(Field::Idle) - percent(Field::Iowait);
+    let segment = Segment::new("cpu", text).percentage(busy.max(0.0));
+    Some(ALL_FIELDS.iter().fold(segment, |segment, field| {
+        segment.value(field.name(), percent(*field))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn fixture(name: &str) -> Times {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/modules/fixtures/stat");
+        Times::parse(&std::fs::read_to_string(path.join(name)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn captured_breakdown() {
+        let delta = fixture("after").since(&fixture("before")).unwrap();
+        assert_eq!(delta.total(), 1000);
+        let breakdown = segment(&delta, DEFAULT_FIELDS).unwrap();
+        assert_eq!(breakdown.text, "cpu u37 s8 io12 st0");
+        assert_eq!(breakdown.percentage, Some(53.0));
+        assert_eq!(breakdown.get::<f64>("idle"), Some(35.0));
+        let others = segment(&delta, &[Field::Nice, Field::Guest, Field::Softirq]).unwrap();
+        assert_eq!(others.text, "cpu n2 g5 si1");
+    }
+
+    /// The guest time is in the user time already
+    #[test]
+    fn guest_counted_once() {
+        let delta = Times([300, 0, 100, 600, 0, 0, 0, 0, 200, 0]);
+        let segment = segment(&delta, &[Field::User, Field::Guest, Field::Idle]).unwrap();
+        assert_eq!(segment.text, "cpu u10 g20 id60");
+    }
+
+    #[test]
+    fn wrapped_and_reset_counters() {
+        let before = Times([u64::from(u32::MAX) - 9, 0, 0, 100, 0, 0, 0, 0, 0, 0]);
+        let after = Times([10, 0, 0, 120, 0, 0, 0, 0, 0, 0]);
+        let delta = after.since(&before).unwrap();
+        assert_eq!(delta.0[Times::USER], 20);
+        assert_eq!(delta.total(), 40);
+        let offline = Times([1000, 0, 0, 500, 0, 0, 0, 0, 0, 0]);
+        let remaining = Times([990, 0, 0, 520, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(remaining.since(&offline), None);
+    }
+
+    #[test]
+    fn nothing_on_the_first_tick() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/modules/fixtures/stat");
+        let mut cpu = CpuDetail::new(dir.join("before"));
+        assert_eq!(cpu.collect().unwrap().text, "");
+        cpu.stat = dir.join("after");
+        assert_eq!(cpu.collect().unwrap().text, "cpu u37 s8 io12 st0");
+        // nothing elapsed
+        assert_eq!(cpu.collect(), None);
+        assert_eq!(Times::parse("cpu  1 2 3 4\n").unwrap().0[3], 4);
+    }
+}
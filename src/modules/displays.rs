@@ -0,0 +1,161 @@
+//! Count of connected displays, from RandR
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+use x11rb::connection::{Connection as _, RequestConnection as _};
+use x11rb::protocol::randr::{self, ConnectionExt as _, NotifyMask};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+use super::{Module, Trigger};
+use crate::output::Segment;
+
+/// Interval of querying the outputs, for when RandR events can't be selected, and of
+/// reconnecting
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// `disp 2`, or the names of the connected outputs with `names`, collected again on the
+/// changes sent by RandR. Hidden while the X server is gone, disabled if it has no RandR
+/// extension.
+pub struct Displays {
+    names: bool,
+    /// connection and root window, once connected
+    conn: Option<(Arc<RustConnection>, u32)>,
+    trigger: Option<Trigger>,
+    disabled: bool,
+}
+
+impl Displays {
+    pub fn new(names: bool) -> Self {
+        Self {
+            names,
+            conn: None,
+            trigger: None,
+            disabled: false,
+        }
+    }
+
+    fn connect(&mut self) -> Option<(Arc<RustConnection>, u32)> {
+        let (conn, screen) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots[screen].root;
+        match conn.extension_information(randr::X11_EXTENSION_NAME) {
+            Ok(Some(_)) => (),
+            _ => {
+                warn!("X server has no RandR extension, disabling");
+                self.disabled = true;
+                return None;
+            }
+        }
+
+        // refreshed on change events, falling back to polling
+        let events = conn
+            .randr_select_input(root, NotifyMask::SCREEN_CHANGE | NotifyMask::OUTPUT_CHANGE)
+            .ok()
+            .and_then(|cookie| cookie.check().ok())
+            .is_some();
+        if !events {
+            warn!("unable to select RandR events, polling");
+        }
+        let conn = Arc::new(conn);
+        if let Some(trigger) = self.trigger.clone().filter(|_| events) {
+            let conn = Arc::clone(&conn);
+            std::thread::spawn(move || loop {
+                let event = conn.wait_for_event();
+                if trigger.stopped() {
+                    return;
+                }
+                // ignoring anything but RandR events, and firing on an error for the
+                // connection to be dropped
+                match event {
+                    Ok(Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_)) => trigger.fire(),
+                    Ok(_) => (),
+                    Err(_) => {
+                        trigger.fire();
+                        return;
+                    }
+                }
+            });
+        }
+        Some((conn, root))
+    }
+}
+
+impl Module for Displays {
+    fn name(&self) -> &'static str {
+        "displays"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        if self.conn.is_none() {
+            self.conn = self.connect();
+        }
+        let Some((conn, root)) = &self.conn else {
+            return Some(Segment::hidden("displays"));
+        };
+        let Ok(outputs) = connected_outputs(conn, *root) else {
+            // X server went away, hidden until it returns
+            self.conn = None;
+            return Some(Segment::hidden("displays"));
+        };
+        Some(Segment::new("displays", text(&outputs, self.names)))
+    }
+
+    fn interval(&self) -> Duration {
+        POLL_INTERVAL
+    }
+
+    fn watch(&mut self, trigger: Trigger) {
+        self.trigger = Some(trigger);
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn disabled(&self) -> bool {
+        self.disabled
+    }
+}
+
+/// `disp 2`, or `disp eDP-1, HDMI-1` of the `outputs` with `names`
+fn text(outputs: &[String], names: bool) -> String {
+    if names {
+        format!("disp {}", outputs.join(", "))
+    } else {
+        format!("disp {}", outputs.len())
+    }
+}
+
+/// Names of the outputs with a connected display
+fn connected_outputs(
+    conn: &RustConnection,
+    root: u32,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let resources = conn.randr_get_screen_resources_current(root)?.reply()?;
+    let mut outputs = vec![];
+    for output in resources.outputs {
+        let info = conn
+            .randr_get_output_info(output, resources.config_timestamp)?
+            .reply()?;
+        if info.connection == randr::Connection::CONNECTED {
+            outputs.push(String::from_utf8_lossy(&info.name).into_owned());
+        }
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connected() {
+        let outputs = ["eDP-1".to_string(), "HDMI-1".to_string()];
+        assert_eq!(text(&outputs, false), "disp 2");
+        assert_eq!(text(&outputs, true), "disp eDP-1, HDMI-1");
+        assert_eq!(text(&outputs[..1], true), "disp eDP-1");
+        assert_eq!(text(&[], false), "disp 0");
+    }
+}
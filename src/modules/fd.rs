@@ -0,0 +1,169 @@
+//! File descriptors in use out of the system maximum, from /proc/sys/fs/file-nr, as `fd 63%`
+//!
+//! Hidden below 50% unless `--show-above fd=` says otherwise, as it only matters on the way to
+//! running out. With `--fd-self`, the descriptors of the bar itself out of its RLIMIT_NOFILE are
+//! shown too, as `fd 3% self 85%`, to tell a leak of the bar apart.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::warn;
+
+use super::{read_into, Module};
+use crate::logging::Throttle;
+use crate::output::Segment;
+
+pub const FILE_NR_PATH: &str = "/proc/sys/fs/file-nr";
+pub const SELF_FD_PATH: &str = "/proc/self/fd";
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct Fd {
+    file_nr: PathBuf,
+    /// the directory of the descriptors of the bar, with `--fd-self`
+    self_fd: Option<PathBuf>,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl Fd {
+    pub fn new(file_nr: impl Into<PathBuf>) -> Self {
+        Self {
+            file_nr: file_nr.into(),
+            self_fd: None,
+            throttle: Throttle::default(),
+            buf: String::new(),
+        }
+    }
+
+    /// Also the descriptors open in `self_fd`, such as /proc/self/fd, out of the soft limit
+    pub fn self_fd(mut self, self_fd: Option<PathBuf>) -> Self {
+        self.self_fd = self_fd;
+        self
+    }
+
+    /// Percentage of the bar's own descriptors, `None` without a limit
+    fn own(&self, self_fd: &PathBuf) -> io::Result<Option<f64>> {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: plain syscall writing into limit
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if limit.rlim_cur == libc::RLIM_INFINITY || limit.rlim_cur == 0 {
+            return Ok(None);
+        }
+        // less the descriptor of the listing itself
+        let open = std::fs::read_dir(self_fd)?.count().saturating_sub(1);
+        Ok(Some(open as f64 / limit.rlim_cur as f64 * 100.0))
+    }
+}
+
+impl Module for Fd {
+    fn name(&self) -> &'static str {
+        "fd"
+    }
+
+    /// Keeping the last displayed on failure
+    fn collect(&mut self) -> Option<Segment> {
+        let system = read_into(&self.file_nr, &mut self.buf).and_then(|()| file_nr(&self.buf));
+        let system = match system {
+            Ok(system) => system,
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    let path = self.file_nr.display();
+                    warn!(target: "fd", "unable to read {path}: {e}{suppressed}");
+                }
+                return None;
+            }
+        };
+        let own = match &self.self_fd {
+            Some(self_fd) => match self.own(self_fd) {
+                Ok(own) => own,
+                Err(e) => {
+                    if let Some(suppressed) = self.throttle.failed() {
+                        let path = self_fd.display();
+                        warn!(target: "fd", "unable to list {path}: {e}{suppressed}");
+                    }
+                    return None;
+                }
+            },
+            None => None,
+        };
+        self.throttle.succeeded();
+        Some(segment(system, own))
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+/// Percentage in use of `allocated unused max`, the unused always 0 since Linux 2.6
+fn file_nr(file_nr: &str) -> io::Result<f64> {
+    let fields: Vec<u64> = file_nr
+        .split_ascii_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let [allocated, unused, max] = fields[..] else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected 3 fields, got {:?}", file_nr.trim()),
+        ));
+    };
+    Ok(allocated.saturating_sub(unused) as f64 / max.max(1) as f64 * 100.0)
+}
+
+/// `fd 63%`, or `fd 3% self 85%` with the bar's own, the percentage being the fuller
+fn segment(system: f64, own: Option<f64>) -> Segment {
+    let mut text = format!("fd {system:.0}%");
+    let mut percentage = system;
+    if let Some(own) = own {
+        text.push_str(&format!(" self {own:.0}%"));
+        percentage = percentage.max(own);
+    }
+    let mut segment = Segment::new("fd", text)
+        .percentage(percentage)
+        .value("system_percent", system);
+    if let Some(own) = own {
+        segment = segment.value("self_percent", own);
+    }
+    segment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_use() {
+        // the unused field of Linux 2.4
+        assert_eq!(file_nr("1200\t200\t10000\n").unwrap(), 10.0);
+        assert_eq!(file_nr("63000\t0\t100000\n").unwrap(), 63.0);
+        assert!(file_nr("63000\t0\n").is_err());
+        assert!(file_nr("").is_err());
+    }
+
+    #[test]
+    fn fuller_of_both() {
+        let segment = segment(3.2, Some(85.0));
+        assert_eq!(segment.text, "fd 3% self 85%");
+        assert_eq!(segment.percentage, Some(85.0));
+        assert_eq!(segment.get::<f64>("system_percent"), Some(3.2));
+    }
+
+    #[test]
+    fn own_descriptors() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/modules/fixtures/file-nr");
+        let mut fd = Fd::new(path).self_fd(Some(SELF_FD_PATH.into()));
+        let segment = fd.collect().unwrap();
+        assert!(segment.text.starts_with("fd 63% self "), "{}", segment.text);
+        assert!(segment
+            .get::<f64>("self_percent")
+            .is_some_and(|own| own > 0.0));
+    }
+}
@@ -0,0 +1,130 @@
+//! Pending flatpak updates, from `flatpak remote-ls --updates`, as `fp 3`
+//!
+//! There's no distro package checker to merge the count into, so it's a segment of its own. The
+//! count is cached, shown with its age while checks fail.
+
+use std::io;
+use std::process::Command;
+use std::time::Duration;
+
+use log::{debug, warn};
+
+use super::Module;
+use crate::command::output_timeout;
+use crate::output::Segment;
+
+/// Upper bound on a `flatpak remote-ls` run, it fetches the summaries of the remotes
+const FLATPAK_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Number of apps with an update, checked every `interval`, hidden without any. A failed check,
+/// such as while offline, keeps the previous count, shown with its age. Without flatpak
+/// installed, disabled.
+pub struct Flatpak {
+    interval: Duration,
+    failing: bool,
+    missing: bool,
+}
+
+impl Flatpak {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            failing: false,
+            missing: false,
+        }
+    }
+}
+
+impl Module for Flatpak {
+    fn name(&self) -> &'static str {
+        "flatpak"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        match updates() {
+            Ok(count) => {
+                self.failing = false;
+                Some(segment(count))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!(target: "flatpak", "flatpak isn't installed, disabling");
+                self.missing = true;
+                None
+            }
+            Err(e) => {
+                if !self.failing {
+                    warn!(target: "flatpak", "flatpak remote-ls failed: {e}");
+                    self.failing = true;
+                }
+                None
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        // a check can take as long as its timeout
+        Some(self.interval + FLATPAK_TIMEOUT)
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn requires_network(&self) -> bool {
+        true
+    }
+
+    fn disabled(&self) -> bool {
+        self.missing
+    }
+}
+
+/// Number of apps with an update
+fn updates() -> io::Result<usize> {
+    let output = output_timeout(
+        Command::new("flatpak").args(["remote-ls", "--updates", "--app", "--columns=application"]),
+        FLATPAK_TIMEOUT,
+    )?
+    .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "timed out"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().next().unwrap_or_default().to_string();
+        return Err(io::Error::other(message));
+    }
+    Ok(count(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Application IDs listed, one per line
+fn count(applications: &str) -> usize {
+    applications
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .count()
+}
+
+/// `fp 3`, empty when up to date
+fn segment(count: usize) -> Segment {
+    if count == 0 {
+        return Segment::new("flatpak", "");
+    }
+    Segment::new("flatpak", format!("fp {count}")).value("updates", count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_applications() {
+        let listed = "org.mozilla.firefox\norg.gimp.GIMP\n\ncom.valvesoftware.Steam\n";
+        assert_eq!(count(listed), 3);
+        assert_eq!(count(""), 0);
+        assert_eq!(segment(3).text, "fp 3");
+        assert_eq!(segment(0).text, "");
+    }
+}
@@ -0,0 +1,63 @@
+//! Host name, read again every minute and on a refresh so that renames show up
+
+use std::time::Duration;
+
+use sysinfo::{System, SystemExt};
+
+use super::Module;
+use crate::identity;
+use crate::output::Segment;
+
+const INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct Host {
+    /// from `--hostname`, shown instead
+    name: Option<String>,
+    /// only up to the first dot, such as `ws03` of `ws03.corp.example.com`
+    short: bool,
+}
+
+impl Host {
+    pub fn new(name: Option<String>, short: bool) -> Self {
+        Self { name, short }
+    }
+}
+
+impl Module for Host {
+    fn name(&self) -> &'static str {
+        "host"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => identity::host_name(System::new().host_name()),
+        };
+        Some(segment(&name, self.short))
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+fn segment(name: &str, short: bool) -> Segment {
+    let name = match name.split_once('.') {
+        Some((short_name, _)) if short && !short_name.is_empty() => short_name,
+        _ => name,
+    };
+    Segment::new("host", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_name() {
+        let fqdn = "workstation-03.corp.example.com";
+        assert_eq!(segment(fqdn, true).text, "workstation-03");
+        assert_eq!(segment(fqdn, false).text, fqdn);
+        assert_eq!(segment("vm", true).text, "vm");
+    }
+}
@@ -0,0 +1,258 @@
+//! Health of HTTP endpoints, as `down: jellyfin, grafana` while some fail
+//!
+//! Requests are left to curl, every endpoint on its own thread with its own timeout. An
+//! endpoint is only reported down after failing twice in a row, so a dropped request doesn't
+//! flash the segment.
+
+use std::io;
+use std::process::Command;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use log::{info, warn};
+
+use super::Module;
+use crate::command::output_timeout;
+use crate::output::Segment;
+use crate::style::Level;
+
+/// Interval between the checks of every endpoint
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bound on a request, given to curl
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Failures in a row before an endpoint is reported down
+const CONFIRMATIONS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Method {
+    #[default]
+    Get,
+    /// without downloading the body, for endpoints that support it
+    Head,
+}
+
+/// An endpoint of `--http-check`, such as `jellyfin:http://nas:8096/health`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub url: String,
+    /// status expected instead of any 2xx or 3xx, such as 401 behind auth
+    pub status: Option<u16>,
+}
+
+impl Check {
+    /// clap value parser of `name:url[:status]`. The status is a trailing three digit number,
+    /// so a url ending in a port takes a trailing `/`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid check `{s}`, expected e.g. jellyfin:http://nas:8096/");
+        let (name, url) = s.split_once(':').ok_or_else(invalid)?;
+        let (url, status) = match url.rsplit_once(':') {
+            Some((url, status)) if status.len() == 3 && url.contains("://") => {
+                match status.parse() {
+                    Ok(status @ 100..=599) => (url, Some(status)),
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => (url, None),
+        };
+        if name.is_empty() || !url.contains("://") {
+            return Err(invalid());
+        }
+        Ok(Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            status,
+        })
+    }
+
+    fn healthy(&self, status: u16) -> bool {
+        match self.status {
+            Some(expected) => status == expected,
+            None => (200..400).contains(&status),
+        }
+    }
+}
+
+/// The names of the `checks` down, hidden while every one is healthy. Without curl installed,
+/// disabled.
+pub struct Http {
+    checks: Vec<Check>,
+    method: Method,
+    /// failures in a row of each check
+    failures: Vec<u32>,
+    missing: bool,
+}
+
+impl Http {
+    pub fn new(checks: Vec<Check>, method: Method) -> Self {
+        Self {
+            failures: vec![0; checks.len()],
+            checks,
+            method,
+            missing: false,
+        }
+    }
+}
+
+impl Module for Http {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let method = self.method;
+        let statuses: Vec<io::Result<u16>> = std::thread::scope(|s| {
+            let requests: Vec<_> = self
+                .checks
+                .iter()
+                .map(|check| s.spawn(move || status(&check.url, method)))
+                .collect();
+            requests
+                .into_iter()
+                .map(|request| {
+                    request
+                        .join()
+                        .unwrap_or_else(|_| Err(io::Error::other("request panicked")))
+                })
+                .collect()
+        });
+
+        for ((check, status), failures) in self.checks.iter().zip(statuses).zip(&mut self.failures)
+        {
+            let failure = match status {
+                Ok(status) if check.healthy(status) => None,
+                Ok(0) => Some("no response".to_string()),
+                Ok(status) => Some(format!("status {status}")),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    warn!(target: "http", "curl isn't installed, disabling");
+                    self.missing = true;
+                    return None;
+                }
+                Err(e) => Some(e.to_string()),
+            };
+            match failure {
+                Some(failure) => {
+                    *failures += 1;
+                    if *failures == CONFIRMATIONS {
+                        warn!(target: "http", "{} is down: {failure}", check.name);
+                    }
+                }
+                None => {
+                    if *failures >= CONFIRMATIONS {
+                        info!(target: "http", "{} is back up", check.name);
+                    }
+                    *failures = 0;
+                }
+            }
+        }
+
+        let down: Vec<&str> = self
+            .checks
+            .iter()
+            .zip(&self.failures)
+            .filter(|(_, failures)| **failures >= CONFIRMATIONS)
+            .map(|(check, _)| check.name.as_str())
+            .collect();
+        Some(segment(&down))
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        // the checks run concurrently, curl hanging at most twice its timeout
+        Some(INTERVAL + REQUEST_TIMEOUT * 2)
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn requires_network(&self) -> bool {
+        true
+    }
+
+    fn disabled(&self) -> bool {
+        self.missing
+    }
+}
+
+/// Status of requesting `url`, 0 without a response
+fn status(url: &str, method: Method) -> io::Result<u16> {
+    let mut command = Command::new("curl");
+    command
+        .args([
+            "--silent",
+            "--output",
+            "/dev/null",
+            "--write-out",
+            "%{http_code}",
+        ])
+        .arg("--max-time")
+        .arg(REQUEST_TIMEOUT.as_secs().to_string());
+    if method == Method::Head {
+        command.arg("--head");
+    }
+    command.arg("--").arg(url);
+    // curl gives up on its own, this is for it hanging anyway
+    let output = output_timeout(&mut command, REQUEST_TIMEOUT * 2)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "timed out"))?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// `down: jellyfin, grafana`, empty while every endpoint is healthy
+fn segment(down: &[&str]) -> Segment {
+    if down.is_empty() {
+        return Segment::new("http", "");
+    }
+    Segment::new("http", format!("down: {}", down.join(", "))).level(Level::Critical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_check() {
+        let check = |url: &str, status| Check {
+            name: "jellyfin".into(),
+            url: url.into(),
+            status,
+        };
+        assert_eq!(
+            Check::parse("jellyfin:http://nas:8096/health"),
+            Ok(check("http://nas:8096/health", None))
+        );
+        assert_eq!(
+            Check::parse("jellyfin:https://nas/api:401"),
+            Ok(check("https://nas/api", Some(401)))
+        );
+        assert_eq!(
+            Check::parse("jellyfin:http://nas:808/"),
+            Ok(check("http://nas:808/", None))
+        );
+        assert!(Check::parse("jellyfin").is_err());
+        assert!(Check::parse("jellyfin:nas:8096").is_err());
+        assert!(Check::parse("jellyfin:http://nas/:999").is_err());
+    }
+
+    #[test]
+    fn healthy_statuses() {
+        let any = Check::parse("grafana:http://nas/").unwrap();
+        assert!(any.healthy(200) && any.healthy(302));
+        assert!(!any.healthy(401) && !any.healthy(0));
+        let auth = Check::parse("grafana:http://nas/:401").unwrap();
+        assert!(auth.healthy(401) && !auth.healthy(200));
+        assert_eq!(
+            segment(&["jellyfin", "grafana"]).text,
+            "down: jellyfin, grafana"
+        );
+        assert_eq!(segment(&[]).text, "");
+    }
+}
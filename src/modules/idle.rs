@@ -0,0 +1,230 @@
+//! Idle time of the session, such as `idle 4m`, from the MIT-SCREEN-SAVER extension of X, or on
+//! Wayland from the org.freedesktop.ScreenSaver of KDE or the idle monitor of GNOME
+//!
+//! Hidden below a floor, so it only shows while away. Until then the next query is when the idle
+//! time could reach the floor at the earliest, and every second once shown. Without a source
+//! the segment is hidden.
+
+use std::time::Duration;
+
+use log::{info, warn};
+
+use super::Module;
+use crate::duration;
+use crate::output::Segment;
+
+/// Interval of updating the shown idle time, and of looking for a source again once lost
+const INTERVAL: Duration = Duration::from_secs(1);
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Where the idle time is read from
+enum Source {
+    #[cfg(feature = "x11")]
+    X11(Box<x11rb::rust_connection::RustConnection>, u32),
+    #[cfg(feature = "dbus")]
+    ScreenSaver(zbus::blocking::Connection),
+    #[cfg(feature = "dbus")]
+    Mutter(zbus::blocking::Connection),
+}
+
+impl Source {
+    /// The session's own first, as the X server of Xwayland only sees the input of X clients
+    fn connect() -> Result<Self, String> {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        let mut errors = vec![];
+        let mut sources: Vec<fn() -> Result<Self, String>> = vec![
+            #[cfg(feature = "x11")]
+            Self::x11,
+            #[cfg(feature = "dbus")]
+            Self::dbus,
+        ];
+        if wayland {
+            sources.reverse();
+        }
+        for source in sources {
+            match source() {
+                Ok(source) => return Ok(source),
+                Err(e) => errors.push(e),
+            }
+        }
+        if errors.is_empty() {
+            errors.push("built without the x11 and dbus features".to_string());
+        }
+        Err(errors.join(", "))
+    }
+
+    #[cfg(feature = "x11")]
+    fn x11() -> Result<Self, String> {
+        use x11rb::connection::{Connection as _, RequestConnection as _};
+        use x11rb::protocol::screensaver;
+
+        let (conn, screen) = x11rb::connect(None).map_err(|e| format!("X: {e}"))?;
+        match conn.extension_information(screensaver::X11_EXTENSION_NAME) {
+            Ok(Some(_)) => (),
+            _ => return Err("X server has no MIT-SCREEN-SAVER extension".into()),
+        }
+        let root = conn.setup().roots[screen].root;
+        let source = Self::X11(Box::new(conn), root);
+        source.idle()?;
+        Ok(source)
+    }
+
+    #[cfg(feature = "dbus")]
+    fn dbus() -> Result<Self, String> {
+        let conn =
+            zbus::blocking::Connection::session().map_err(|e| format!("session bus: {e}"))?;
+        let screensaver = Self::ScreenSaver(conn.clone());
+        let screensaver = screensaver.idle().map(|_| screensaver);
+        screensaver.or_else(|screensaver| {
+            let mutter = Self::Mutter(conn);
+            mutter
+                .idle()
+                .map(|_| mutter)
+                .map_err(|mutter| format!("{screensaver}, {mutter}"))
+        })
+    }
+
+    fn idle(&self) -> Result<Duration, String> {
+        match *self {
+            #[cfg(feature = "x11")]
+            Self::X11(ref conn, root) => {
+                use x11rb::protocol::screensaver::ConnectionExt as _;
+
+                let info = conn
+                    .screensaver_query_info(root)
+                    .map_err(|e| e.to_string())?
+                    .reply()
+                    .map_err(|e| e.to_string())?;
+                Ok(Duration::from_millis(info.ms_since_user_input.into()))
+            }
+            #[cfg(feature = "dbus")]
+            Self::ScreenSaver(ref conn) => {
+                let ms: u32 = conn
+                    .call_method(
+                        Some("org.freedesktop.ScreenSaver"),
+                        "/org/freedesktop/ScreenSaver",
+                        Some("org.freedesktop.ScreenSaver"),
+                        "GetSessionIdleTime",
+                        &(),
+                    )
+                    .and_then(|reply| reply.body().deserialize())
+                    .map_err(|e| format!("org.freedesktop.ScreenSaver: {e}"))?;
+                Ok(Duration::from_millis(ms.into()))
+            }
+            #[cfg(feature = "dbus")]
+            Self::Mutter(ref conn) => {
+                let ms: u64 = conn
+                    .call_method(
+                        Some("org.gnome.Mutter.IdleMonitor"),
+                        "/org/gnome/Mutter/IdleMonitor/Core",
+                        Some("org.gnome.Mutter.IdleMonitor"),
+                        "GetIdletime",
+                        &(),
+                    )
+                    .and_then(|reply| reply.body().deserialize())
+                    .map_err(|e| format!("org.gnome.Mutter.IdleMonitor: {e}"))?;
+                Ok(Duration::from_millis(ms))
+            }
+        }
+    }
+}
+
+/// Idle time from `floor`, hidden below. Without a source from the start, disabled.
+pub struct Idle {
+    floor: Duration,
+    source: Option<Source>,
+    /// whether a source was ever found, to look for one again once lost
+    found: bool,
+    missing: bool,
+    next: Duration,
+}
+
+impl Idle {
+    pub fn new(floor: Duration) -> Self {
+        Self {
+            floor,
+            source: None,
+            found: false,
+            missing: false,
+            next: INTERVAL,
+        }
+    }
+}
+
+impl Module for Idle {
+    fn name(&self) -> &'static str {
+        "idle"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let source = match self.source.take() {
+            Some(source) => source,
+            None => match Source::connect() {
+                Ok(source) => source,
+                Err(e) if !self.found => {
+                    warn!(target: "idle", "no idle time source, disabling: {e}");
+                    self.missing = true;
+                    return None;
+                }
+                Err(_) => {
+                    self.next = RETRY_INTERVAL;
+                    return Some(Segment::hidden("idle"));
+                }
+            },
+        };
+        self.found = true;
+        let idle = match source.idle() {
+            Ok(idle) => idle,
+            Err(e) => {
+                info!(target: "idle", "idle time source gone, hiding: {e}");
+                self.next = RETRY_INTERVAL;
+                return Some(Segment::hidden("idle"));
+            }
+        };
+        self.source = Some(source);
+        self.next = until_update(idle, self.floor);
+        Some(match segment(idle, self.floor) {
+            Some(text) => Segment::new("idle", text),
+            None => Segment::hidden("idle"),
+        })
+    }
+
+    fn interval(&self) -> Duration {
+        self.next
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn disabled(&self) -> bool {
+        self.missing
+    }
+}
+
+/// `idle 4m` from `floor`
+fn segment(idle: Duration, floor: Duration) -> Option<String> {
+    (idle >= floor).then(|| format!("idle {}", duration::format_age(idle)))
+}
+
+/// Every second while shown, and until the idle time could reach `floor` while hidden
+fn until_update(idle: Duration, floor: Duration) -> Duration {
+    floor.saturating_sub(idle).max(INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_the_floor() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(segment(Duration::from_secs(59), minute), None);
+        assert_eq!(segment(4 * minute, minute).unwrap(), "idle 4m");
+        assert_eq!(
+            until_update(Duration::from_secs(15), minute),
+            Duration::from_secs(45)
+        );
+        assert_eq!(until_update(4 * minute, minute), INTERVAL);
+    }
+}
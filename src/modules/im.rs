@@ -0,0 +1,294 @@
+//! Active input method of fcitx5 or IBus, such as `拼` for pinyin or `us` for a keyboard layout
+//!
+//! IBus announces its engine changes with `GlobalEngineChanged` on its own bus, whose address
+//! comes from `ibus address`. fcitx5 has no signal for it on its controller, so it's queried
+//! again on any of its signals and every second. Without a daemon the segment is hidden, and
+//! one is looked for again every minute.
+
+use std::process::Command;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use log::info;
+use zbus::blocking::{proxy::Builder, Connection, MessageIterator, Proxy};
+use zbus::message::Type;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::MatchRule;
+
+use super::{Module, Trigger};
+use crate::command::output_timeout;
+use crate::glyphs::Glyphs;
+use crate::output::Segment;
+
+const FCITX5: &str = "org.fcitx.Fcitx5";
+const IBUS: &str = "org.freedesktop.IBus";
+
+/// Interval of querying fcitx5, which has no signal for input method changes
+const FCITX5_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval of looking for a daemon while none is running
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+const IBUS_ADDRESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// fcitx5 when running, otherwise IBus
+    #[default]
+    Auto,
+    Fcitx5,
+    Ibus,
+}
+
+/// A connected daemon
+enum Daemon {
+    Fcitx5(Connection),
+    Ibus(Connection),
+}
+
+impl Daemon {
+    fn connect(backend: Backend) -> Result<Self, String> {
+        match backend {
+            Backend::Auto => Self::connect(Backend::Fcitx5).or_else(|fcitx5| {
+                Self::connect(Backend::Ibus).map_err(|ibus| format!("{fcitx5}, {ibus}"))
+            }),
+            Backend::Fcitx5 => {
+                let conn = Connection::session().map_err(|e| format!("session bus: {e}"))?;
+                fcitx5_input_method(&conn).map_err(|e| format!("fcitx5: {e}"))?;
+                Ok(Self::Fcitx5(conn))
+            }
+            Backend::Ibus => {
+                let conn = ibus_connection().map_err(|e| format!("ibus: {e}"))?;
+                ibus_engine(&conn).map_err(|e| format!("ibus: {e}"))?;
+                Ok(Self::Ibus(conn))
+            }
+        }
+    }
+
+    fn input_method(&self) -> zbus::Result<String> {
+        match self {
+            Self::Fcitx5(conn) => fcitx5_input_method(conn),
+            Self::Ibus(conn) => ibus_engine(conn),
+        }
+    }
+
+    /// Close the connection, ending the streams of its signals along with their match rules
+    fn close(self) {
+        let (Self::Fcitx5(conn) | Self::Ibus(conn)) = self;
+        let _ = conn.close();
+    }
+
+    /// Signals worth querying again for
+    fn changes(&self) -> zbus::Result<MessageIterator> {
+        let rule = MatchRule::builder().msg_type(Type::Signal);
+        match self {
+            Self::Fcitx5(conn) => {
+                let rule = rule.sender(FCITX5)?.build();
+                MessageIterator::for_match_rule(rule, conn, Some(16))
+            }
+            Self::Ibus(conn) => {
+                let rule = rule.interface(IBUS)?.member("GlobalEngineChanged")?.build();
+                MessageIterator::for_match_rule(rule, conn, Some(16))
+            }
+        }
+    }
+
+    /// Interval of querying without a signal
+    fn interval(&self) -> Duration {
+        match self {
+            Self::Fcitx5(_) => FCITX5_INTERVAL,
+            // in case the daemon went away, which sends nothing
+            Self::Ibus(_) => RETRY_INTERVAL,
+        }
+    }
+}
+
+/// Label of the input method, collected again on the signals of the daemon. Hidden without
+/// one.
+pub struct Im {
+    backend: Backend,
+    /// The scripts are letters rather than glyphs, only left out for plain ASCII
+    ascii: bool,
+    daemon: Option<Daemon>,
+    missing: bool,
+    trigger: Option<Trigger>,
+}
+
+impl Im {
+    pub fn new(backend: Backend, glyphs: &Glyphs) -> Self {
+        Self {
+            backend,
+            ascii: glyphs.ascii(),
+            daemon: None,
+            missing: false,
+            trigger: None,
+        }
+    }
+
+    /// Connect to the daemon unless connected, forwarding its signals to the trigger
+    fn connect(&mut self) -> Result<(), String> {
+        if self.daemon.is_none() {
+            let daemon = Daemon::connect(self.backend)?;
+            if let Some(trigger) = self.trigger.clone() {
+                let signals = daemon.changes().map_err(|e| e.to_string())?;
+                // ends once the connection is closed
+                std::thread::spawn(move || {
+                    for signal in signals {
+                        if signal.is_err() {
+                            return;
+                        }
+                        trigger.fire();
+                    }
+                });
+            }
+            self.daemon = Some(daemon);
+        }
+        Ok(())
+    }
+}
+
+impl Module for Im {
+    fn name(&self) -> &'static str {
+        "im"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        if let Err(e) = self.connect() {
+            if !self.missing {
+                info!(target: "im", "no input method daemon, im hidden: {e}");
+                self.missing = true;
+            }
+            return Some(Segment::hidden("im"));
+        }
+        self.missing = false;
+        match self.daemon.as_ref().unwrap().input_method() {
+            Ok(name) => Some(Segment::new("im", label(&name, self.ascii))),
+            Err(e) => {
+                info!(target: "im", "input method daemon gone: {e}");
+                // ends the thread forwarding its signals, which a daemon gone never wakes
+                self.daemon.take().unwrap().close();
+                Some(Segment::hidden("im"))
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        self.daemon
+            .as_ref()
+            .map_or(RETRY_INTERVAL, Daemon::interval)
+    }
+
+    fn watch(&mut self, trigger: Trigger) {
+        self.trigger = Some(trigger);
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
+
+impl Drop for Im {
+    fn drop(&mut self) {
+        if let Some(daemon) = self.daemon.take() {
+            daemon.close();
+        }
+    }
+}
+
+fn fcitx5_input_method(conn: &Connection) -> zbus::Result<String> {
+    let controller: Proxy = Builder::new(conn)
+        .destination(FCITX5)?
+        .path("/controller")?
+        .interface("org.fcitx.Fcitx.Controller1")?
+        .cache_properties(CacheProperties::No)
+        .build()?;
+    controller.call("CurrentInputMethod", &())
+}
+
+/// Connection to the bus of IBus, at the address printed by `ibus address`
+fn ibus_connection() -> Result<Connection, String> {
+    let output = output_timeout(Command::new("ibus").arg("address"), IBUS_ADDRESS_TIMEOUT)
+        .map_err(|e| e.to_string())?
+        .ok_or("`ibus address` timed out")?;
+    let address = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || address.is_empty() || address == "(null)" {
+        return Err("not running".into());
+    }
+    zbus::blocking::connection::Builder::address(address.as_str())
+        .and_then(|builder| builder.build())
+        .map_err(|e| e.to_string())
+}
+
+/// Name of the global engine, the third field of its IBusEngineDesc
+fn ibus_engine(conn: &Connection) -> zbus::Result<String> {
+    let ibus: Proxy = Builder::new(conn)
+        .destination(IBUS)?
+        .path("/org/freedesktop/IBus")?
+        .interface(IBUS)?
+        .cache_properties(CacheProperties::No)
+        .build()?;
+    let desc: OwnedValue = ibus.get_property("GlobalEngine")?;
+    engine_name(&desc).ok_or_else(|| zbus::Error::Failure("invalid IBusEngineDesc".into()))
+}
+
+fn engine_name(desc: &Value) -> Option<String> {
+    match desc {
+        Value::Value(inner) => engine_name(inner),
+        Value::Structure(desc) => match desc.fields().get(2)? {
+            Value::Str(name) => Some(name.to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Short label of an input method, such as `拼` of pinyin and `us` of the fcitx5
+/// `keyboard-us` or the IBus `xkb:us::eng`. With `ascii`, the language instead of the script.
+fn label(name: &str, ascii: bool) -> String {
+    if let Some(layout) = name.strip_prefix("keyboard-") {
+        return layout.to_string();
+    }
+    if let Some(xkb) = name.strip_prefix("xkb:") {
+        return xkb.split(':').next().unwrap_or(xkb).to_string();
+    }
+    let (script, language) = match name {
+        "pinyin" | "libpinyin" | "shuangpin" | "rime" | "table" => ("拼", "zh"),
+        "chewing" | "libzhuyin" => ("注", "zh"),
+        "mozc" | "mozc-jp" | "anthy" | "kkc" | "skk" => ("あ", "ja"),
+        "hangul" => ("한", "ko"),
+        "unikey" | "bamboo" => ("vi", "vi"),
+        _ => return name.to_string(),
+    };
+    if ascii { language } else { script }.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::{StructureBuilder, Value};
+
+    #[test]
+    fn labels() {
+        assert_eq!(label("keyboard-us", false), "us");
+        assert_eq!(label("xkb:de:nodeadkeys:deu", false), "de");
+        assert_eq!(label("pinyin", false), "拼");
+        assert_eq!(label("pinyin", true), "zh");
+        assert_eq!(label("mozc-jp", false), "あ");
+        assert_eq!(label("m17n:sa:itrans", false), "m17n:sa:itrans");
+    }
+
+    #[test]
+    fn ibus_engine_desc() {
+        let desc = StructureBuilder::new()
+            .add_field("IBusEngineDesc")
+            .add_field(std::collections::HashMap::<&str, Value>::new())
+            .add_field("libpinyin")
+            .add_field("Intelligent Pinyin")
+            .build()
+            .unwrap();
+        let desc = Value::Value(Box::new(Value::Structure(desc)));
+        assert_eq!(engine_name(&desc), Some("libpinyin".into()));
+        assert_eq!(engine_name(&Value::U32(1)), None);
+    }
+}
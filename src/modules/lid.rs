@@ -0,0 +1,161 @@
+//! Laptop lid state, from ACPI procfs or logind's `LidClosed`
+//!
+//! logind has `LidClosed` on desktops too, always false, so it's only used when UPower's
+//! `LidIsPresent` tells there's a lid.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::Module;
+use crate::output::Segment;
+use crate::paths::Paths;
+
+const LID_DIR: &str = "/proc/acpi/button/lid";
+const INTERVAL: Duration = Duration::from_secs(2);
+
+enum Source {
+    Procfs(PathBuf),
+    #[cfg(feature = "dbus")]
+    Logind(zbus::blocking::Connection),
+}
+
+impl Source {
+    /// procfs is preferred when present, as it needs no running service
+    fn detect(paths: &Paths) -> Option<Self> {
+        if let Some(state) = procfs_state_path(&paths.resolve(LID_DIR)) {
+            return Some(Self::Procfs(state));
+        }
+
+        #[cfg(feature = "dbus")]
+        if let Some(conn) = zbus::blocking::Connection::system().ok().filter(|conn| {
+            upower_lid_present(conn).unwrap_or(false) && logind_lid_closed(conn).is_ok()
+        }) {
+            return Some(Self::Logind(conn));
+        }
+
+        None
+    }
+
+    fn closed(&self) -> Option<bool> {
+        match self {
+            Self::Procfs(path) => closed(&std::fs::read_to_string(path).ok()?),
+            #[cfg(feature = "dbus")]
+            Self::Logind(conn) => logind_lid_closed(conn).ok(),
+        }
+    }
+}
+
+/// `lid closed` while the lid is closed, hidden while open
+pub struct Lid {
+    source: Source,
+}
+
+impl Lid {
+    /// `None` on machines without a lid
+    pub fn new(paths: &Paths) -> Option<Self> {
+        Some(Self {
+            source: Source::detect(paths)?,
+        })
+    }
+}
+
+impl Module for Lid {
+    fn name(&self) -> &'static str {
+        "lid"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        Some(match self.source.closed() {
+            Some(true) => Segment::new("lid", "lid closed"),
+            _ => Segment::hidden("lid"),
+        })
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn blocking(&self) -> bool {
+        !matches!(self.source, Source::Procfs(_))
+    }
+}
+
+/// Whether there's a lid, in procfs or through logind
+pub fn detected(paths: &Paths) -> bool {
+    Source::detect(paths).is_some()
+}
+
+/// First `LID*/state` entry of `lid_dir`, such as `/proc/acpi/button/lid/LID0/state`
+fn procfs_state_path(lid_dir: &Path) -> Option<PathBuf> {
+    let mut lids: Vec<_> = std::fs::read_dir(lid_dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("LID"))
+        .map(|entry| entry.path().join("state"))
+        .filter(|state| state.exists())
+        .collect();
+    lids.sort();
+    lids.into_iter().next()
+}
+
+/// Whether `state`, such as `state:      closed`, is of a closed lid
+fn closed(state: &str) -> Option<bool> {
+    Some(state.split_ascii_whitespace().last()? == "closed")
+}
+
+#[cfg(feature = "dbus")]
+fn upower_lid_present(conn: &zbus::blocking::Connection) -> zbus::Result<bool> {
+    let proxy = zbus::blocking::proxy::Builder::<zbus::blocking::Proxy>::new(conn)
+        .destination("org.freedesktop.UPower")?
+        .path("/org/freedesktop/UPower")?
+        .interface("org.freedesktop.UPower")?
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()?;
+    proxy.get_property("LidIsPresent")
+}
+
+#[cfg(feature = "dbus")]
+fn logind_lid_closed(conn: &zbus::blocking::Connection) -> zbus::Result<bool> {
+    let proxy = zbus::blocking::proxy::Builder::<zbus::blocking::Proxy>::new(conn)
+        .destination("org.freedesktop.login1")?
+        .path("/org/freedesktop/login1")?
+        .interface("org.freedesktop.login1.Manager")?
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()?;
+    proxy.get_property("LidClosed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/modules/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn procfs_lid() {
+        let state = procfs_state_path(&fixture("lid")).unwrap();
+        assert_eq!(state, fixture("lid/LID0/state"));
+        let lid = Lid {
+            source: Source::Procfs(state),
+        };
+        assert_eq!(lid.source.closed(), Some(false));
+        assert!(!lid.blocking());
+    }
+
+    #[test]
+    fn without_a_lid() {
+        assert_eq!(procfs_state_path(&fixture("power_supply")), None);
+        assert_eq!(procfs_state_path(&fixture("missing")), None);
+    }
+
+    #[test]
+    fn states() {
+        assert_eq!(closed("state:      closed\n"), Some(true));
+        assert_eq!(closed("state:      open\n"), Some(false));
+        assert_eq!(closed(""), None);
+    }
+}
@@ -0,0 +1,327 @@
+//! Memory usage, from /proc/meminfo or the unified cgroup of the bar with `--mem-mode cgroup`
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use log::warn;
+
+use super::{read_into, Module};
+use crate::logging::Throttle;
+use crate::output::Segment;
+use crate::percent::Percent;
+
+pub const MEMINFO_PATH: &str = "/proc/meminfo";
+pub const SELF_CGROUP_PATH: &str = "/proc/self/cgroup";
+pub const CGROUP_PATH: &str = "/sys/fs/cgroup";
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// What counts as used memory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// all but MemAvailable, the caches the kernel can reclaim being free
+    #[default]
+    Available,
+    /// all but MemFree, the caches being used
+    Free,
+    /// memory.current of the slice of the bar, out of its memory.max, such as in a container
+    /// where /proc/meminfo is that of the host
+    Cgroup,
+}
+
+pub struct Memory {
+    path: PathBuf,
+    mode: Mode,
+    /// standing in for /proc/self/cgroup and /sys/fs/cgroup
+    self_cgroup: PathBuf,
+    cgroups: PathBuf,
+    throttle: Throttle,
+    /// contents of the file, reused between samples
+    buf: String,
+    percent: Percent,
+}
+
+impl Memory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            mode: Mode::default(),
+            self_cgroup: SELF_CGROUP_PATH.into(),
+            cgroups: CGROUP_PATH.into(),
+            throttle: Throttle::default(),
+            buf: String::new(),
+            percent: Percent::default(),
+        }
+    }
+
+    pub fn percent(mut self, percent: Percent) -> Self {
+        self.percent = percent;
+        self
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Read the cgroup of the bar from `self_cgroup` and its files below `cgroups`, instead of
+    /// /proc/self/cgroup and /sys/fs/cgroup
+    pub fn cgroup(mut self, self_cgroup: impl Into<PathBuf>, cgroups: impl Into<PathBuf>) -> Self {
+        self.self_cgroup = self_cgroup.into();
+        self.cgroups = cgroups.into();
+        self
+    }
+
+    /// Used and total bytes
+    fn sample(&mut self) -> io::Result<(u64, u64)> {
+        read_into(&self.path, &mut self.buf)?;
+        let meminfo = Meminfo::parse(&self.buf)?;
+        let total = meminfo.total * 1024;
+        let free = match self.mode {
+            Mode::Available => meminfo.available.unwrap_or(meminfo.free) * 1024,
+            Mode::Free => meminfo.free * 1024,
+            Mode::Cgroup => {
+                read_into(&self.self_cgroup, &mut self.buf)?;
+                return cgroup_usage(&self.buf, &self.cgroups, total);
+            }
+        };
+        Ok((total.saturating_sub(free), total))
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new(MEMINFO_PATH)
+    }
+}
+
+impl Module for Memory {
+    fn name(&self) -> &'static str {
+        "mem"
+    }
+
+    /// Keeping the last displayed on failure
+    fn collect(&mut self) -> Option<Segment> {
+        match self.sample() {
+            Ok((used, total)) => {
+                self.throttle.succeeded();
+                Some(segment(used, total, self.percent))
+            }
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    match self.mode {
+                        Mode::Cgroup => {
+                            warn!(target: "mem", "unable to read the cgroup: {e}{suppressed}")
+                        }
+                        _ => {
+                            let path = self.path.display();
+                            warn!(target: "mem", "unable to read {path}: {e}{suppressed}");
+                        }
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+/// Fields of /proc/meminfo, in KiB
+#[derive(Debug, Default, PartialEq)]
+struct Meminfo {
+    total: u64,
+    free: u64,
+    /// since Linux 3.14
+    available: Option<u64>,
+    swap_total: Option<u64>,
+    swap_free: Option<u64>,
+}
+
+impl Meminfo {
+    /// Fields found by name in any order, the unknown ignored. Only MemTotal and MemFree are
+    /// required, and a field without a number is missing, such as one cut off mid-line.
+    fn parse(meminfo: &str) -> io::Result<Self> {
+        let (mut total, mut free) = (None, None);
+        let mut parsed = Self::default();
+        for line in meminfo.lines() {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let Some(value) = value
+                .split_ascii_whitespace()
+                .next()
+                .and_then(|value| value.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            match name {
+                "MemTotal" => total = Some(value),
+                "MemFree" => free = Some(value),
+                "MemAvailable" => parsed.available = Some(value),
+                "SwapTotal" => parsed.swap_total = Some(value),
+                "SwapFree" => parsed.swap_free = Some(value),
+                _ => {}
+            }
+        }
+        let missing = |name: &str| io::Error::new(io::ErrorKind::InvalidData, format!("no {name}"));
+        parsed.total = total.ok_or_else(|| missing("MemTotal"))?;
+        parsed.free = free.ok_or_else(|| missing("MemFree"))?;
+        Ok(parsed)
+    }
+}
+
+/// Used and total bytes of the slice of the cgroup in `self_cgroup`, as of /proc/self/cgroup,
+/// below `cgroups`. The total is the lowest memory.max of the slice and its parents, or
+/// `unlimited` without any.
+fn cgroup_usage(self_cgroup: &str, cgroups: &Path, unlimited: u64) -> io::Result<(u64, u64)> {
+    // the unified hierarchy, whose line has neither an id nor controllers
+    let cgroup = self_cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no unified cgroup"))?;
+    let cgroup = Path::new(cgroup.trim_start_matches('/'));
+    // such as the user slice of the session scope of the bar, the container without any
+    let slice = cgroup
+        .ancestors()
+        .find(|ancestor| {
+            ancestor
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().ends_with(".slice"))
+        })
+        .unwrap_or(cgroup);
+
+    let read = |path: PathBuf| std::fs::read_to_string(path).map(|read| read.trim().to_string());
+    let path = cgroups.join(slice).join("memory.current");
+    let current = read(path.clone())
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+    let current = current
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{current:?}: {e}")))?;
+    let total = slice
+        .ancestors()
+        .filter_map(|ancestor| read(cgroups.join(ancestor).join("memory.max")).ok())
+        // "max" without a limit
+        .filter_map(|max| max.parse::<u64>().ok())
+        .fold(unlimited, u64::min);
+    Ok((current, total))
+}
+
+/// `mem 04%`, with the used bytes as `used_bytes`
+fn segment(used: u64, total: u64, percent: Percent) -> Segment {
+    let usage = used as f64 / total.max(1) as f64 * 100.0;
+    let mut text = String::from("mem ");
+    percent.write(usage, &mut text);
+    text.push('%');
+    Segment::new("mem", text)
+        .percentage(usage)
+        .value("used_bytes", used)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    const MEMINFO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/modules/fixtures/meminfo");
+
+    #[test]
+    fn captured_meminfo() {
+        let segment = Memory::new(MEMINFO).collect().unwrap();
+        assert_eq!(segment.text, "mem 31%");
+        assert_eq!(
+            segment.get::<u64>("used_bytes"),
+            Some((16273576 - 11219732) * 1024)
+        );
+    }
+
+    #[test]
+    fn free_counts_the_caches() {
+        let segment = Memory::new(MEMINFO).mode(Mode::Free).collect().unwrap();
+        assert_eq!(segment.text, "mem 75%");
+        assert_eq!(
+            segment.get::<u64>("used_bytes"),
+            Some((16273576 - 4068394) * 1024)
+        );
+    }
+
+    #[test]
+    fn cgroup_of_the_slice() {
+        let cgroup = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/modules/fixtures/cgroup");
+        let mut memory = Memory::new(MEMINFO)
+            .mode(Mode::Cgroup)
+            .cgroup(cgroup.join("self-cgroup"), cgroup.join("fs"));
+        // the user slice, limited by its parent
+        let segment = memory.collect().unwrap();
+        assert_eq!(segment.text, "mem 25%");
+        assert_eq!(segment.get::<u64>("used_bytes"), Some(2147483648));
+
+        // a container, whose cgroup namespace starts at its own
+        let hybrid = "1:name=systemd:/\n0::/\n";
+        let usage = cgroup_usage(hybrid, &cgroup.join("container"), u64::MAX).unwrap();
+        assert_eq!(usage, (1073741824, 4294967296));
+        // cgroup v1 only
+        let e = cgroup_usage("4:memory:/user.slice\n", &cgroup.join("fs"), 1 << 40).unwrap_err();
+        assert_eq!(e.to_string(), "no unified cgroup");
+    }
+
+    fn fixture(name: &str) -> Meminfo {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/modules/fixtures")
+            .join(name);
+        Meminfo::parse(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn captured_kernels() {
+        assert_eq!(
+            fixture("meminfo-6.18"),
+            Meminfo {
+                total: 6158152,
+                free: 2753924,
+                available: Some(5561816),
+                swap_total: Some(0),
+                swap_free: Some(0),
+            }
+        );
+        // before MemAvailable
+        assert_eq!(
+            fixture("meminfo-2.6"),
+            Meminfo {
+                total: 1922508,
+                free: 139920,
+                available: None,
+                swap_total: Some(4128764),
+                swap_free: Some(4113220),
+            }
+        );
+    }
+
+    #[test]
+    fn reordered_lines() {
+        let meminfo = "Buffers:          512364 kB\n\
+            MemFree:         4068394 kB\n\
+            HugePages_Total:       0\n\
+            MemTotal:       16273576 kB\n";
+        let meminfo = Meminfo::parse(meminfo).unwrap();
+        assert_eq!((meminfo.total, meminfo.free), (16273576, 4068394));
+    }
+
+    #[test]
+    fn truncated_mid_line() {
+        let e = Meminfo::parse("MemTotal:       16273576 kB\nMemFr").unwrap_err();
+        assert_eq!(e.to_string(), "no MemFree");
+        let e = Meminfo::parse("MemTotal:       16273576 kB\nMemFree:   ").unwrap_err();
+        assert_eq!(e.to_string(), "no MemFree");
+    }
+
+    #[test]
+    fn unreadable_keeps_the_last() {
+        assert!(Memory::new("/nonexistent/meminfo").collect().is_none());
+    }
+}
@@ -0,0 +1,847 @@
+//! Modules of every segment, sampled on an interval by the scheduler
+//!
+//! The quick modules share one scheduler thread, while those that may block, such as on a
+//! command or a bus, each get their own. Modules with an event source, such as todo or sink,
+//! fire a [`Trigger`] from a thread of theirs to be collected at once. These are blocking threads
+//! rather than the tasks of an async runtime: the modules are short reads of /proc and /sys, the
+//! commands they run already time out through `output_timeout`, and a runtime would weigh on the
+//! size of the binary and its dependencies for little.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{Scope, ScopedJoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::output::Segment;
+use crate::store::Store;
+use crate::{cache, profile, replay, signal};
+
+mod backup;
+mod battery;
+mod calendar;
+#[cfg(feature = "network")]
+mod cert;
+mod cgroup;
+mod clock;
+mod cpu;
+mod cpu_detail;
+#[cfg(feature = "x11")]
+mod displays;
+mod fd;
+mod flatpak;
+mod host;
+#[cfg(feature = "network")]
+mod http;
+mod idle;
+#[cfg(feature = "dbus")]
+mod im;
+mod lid;
+mod memory;
+mod net;
+#[cfg(feature = "dbus")]
+mod nm;
+#[cfg(feature = "dbus")]
+mod ntp;
+mod offline;
+mod oom;
+#[cfg(feature = "dbus")]
+mod peripherals;
+mod privacy;
+mod readonly;
+mod sink;
+mod smart;
+mod snapshot;
+mod swap;
+mod tcp;
+mod temp;
+mod throttle;
+#[cfg(feature = "dbus")]
+mod timer;
+mod todo;
+mod traffic;
+mod ups;
+mod zram;
+
+pub use backup::{Backup, Source as BackupSource};
+pub use battery::{batteries, Battery, POWER_SUPPLY_PATH};
+pub use calendar::Calendar;
+#[cfg(feature = "network")]
+pub use cert::{Cert, Target as CertTarget};
+pub use cgroup::Cgroup;
+pub use clock::{Clock, DATE_FORMAT, TIME_FORMAT};
+pub use cpu::Cpu;
+pub use cpu_detail::{CpuDetail, Field as CpuField, DEFAULT_FIELDS as CPU_FIELDS};
+#[cfg(feature = "x11")]
+pub use displays::Displays;
+pub use fd::{Fd, FILE_NR_PATH, SELF_FD_PATH};
+pub use flatpak::Flatpak;
+pub use host::Host;
+#[cfg(feature = "network")]
+pub use http::{Check as HttpCheck, Http, Method as HttpMethod};
+pub use idle::Idle;
+#[cfg(feature = "dbus")]
+pub use im::{Backend as ImBackend, Im};
+pub use lid::{detected as lid_detected, Lid};
+pub use memory::{Memory, Mode as MemMode, CGROUP_PATH, MEMINFO_PATH, SELF_CGROUP_PATH};
+pub use net::{Mode as NetMode, Net, Selection, SYS_NET_PATH};
+#[cfg(feature = "dbus")]
+pub use nm::Nm;
+#[cfg(feature = "dbus")]
+pub use ntp::Ntp;
+pub use offline::Offline;
+pub use oom::{Oom, VMSTAT_PATH};
+#[cfg(feature = "dbus")]
+pub use peripherals::Peripherals;
+pub use privacy::Privacy;
+pub use readonly::{mount_points, ReadOnly, MOUNTS_PATH};
+pub use sink::{parse_alias as parse_sink_alias, Sink};
+pub use smart::{devices as smart_devices, Smart, SYS_BLOCK_PATH};
+pub use snapshot::{Backend as SnapshotBackend, Snapshots};
+pub use swap::Swap;
+pub use tcp::{Tcp, TCP6_PATH, TCP_PATH};
+pub use temp::{Sensor, Temp};
+pub use throttle::{Throttle, PROC_STAT_PATH, SYS_CPU_PATH};
+#[cfg(feature = "dbus")]
+pub use timer::Timers;
+pub use todo::Todo;
+pub use traffic::{
+    default_state_path as traffic_state_path, DataCap, Period as DataCapPeriod, Traffic,
+    NET_DEV_PATH,
+};
+pub use ups::{Backend as UpsBackend, Ups};
+pub use zram::Zram;
+
+pub trait Module: Send {
+    /// Name of the module, as in `MODULES`
+    fn name(&self) -> &'static str;
+
+    /// Sample the module, `None` keeping the previous segment such as on a transient error.
+    /// A segment with empty text has no data to display.
+    fn collect(&mut self) -> Option<Segment>;
+
+    /// Interval between samples, the fallback of modules watching their events. Intervals of
+    /// whole seconds end on a second of the wall clock.
+    fn interval(&self) -> Duration;
+
+    /// Start watching the events of the module, firing `trigger` to collect it before its
+    /// interval, until [`Trigger::stopped`]. Called once before the first sample.
+    fn watch(&mut self, _trigger: Trigger) {}
+
+    /// Restart rates from a fresh baseline, after a pause
+    fn reset(&mut self) {}
+
+    /// Time a fetched segment stays fresh, for slow modules such as flatpak. Their segments are
+    /// kept in the cache directory to be shown at once on a restart, and once past it are shown
+    /// with their age rather than marked stale.
+    fn cache_ttl(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether collecting may block for long, such as on a command, a bus, or a remote host, so
+    /// it's collected on its own thread rather than holding up the others
+    fn blocking(&self) -> bool {
+        false
+    }
+
+    /// Whether it has nothing to show for good, such as without a lid, its segment then hidden
+    /// and the module no longer collected
+    fn disabled(&self) -> bool {
+        false
+    }
+
+    /// Whether it reaches remote hosts, so it's hidden and skipped while offline, and collected
+    /// as soon as back online
+    fn requires_network(&self) -> bool {
+        false
+    }
+}
+
+/// Handle of an event-driven module, for its watcher to have it collected at once
+#[derive(Clone, Default)]
+pub struct Trigger(Arc<TriggerState>);
+
+#[derive(Default)]
+struct TriggerState {
+    fired: AtomicBool,
+    /// the module no longer scheduled, such as once disabled or replaced by a reload
+    stopped: AtomicBool,
+}
+
+impl Trigger {
+    pub fn fire(&self) {
+        self.0.fired.store(true, Ordering::Release);
+        signal::wake();
+    }
+
+    /// Whether its module is no longer scheduled, for the watcher to end rather than fire for
+    /// nothing
+    pub fn stopped(&self) -> bool {
+        self.0.stopped.load(Ordering::Acquire)
+    }
+
+    /// Whether fired since the last call
+    fn take(&self) -> bool {
+        self.0.fired.swap(false, Ordering::Acquire)
+    }
+
+    fn stop(&self) {
+        self.0.stopped.store(true, Ordering::Release);
+    }
+}
+
+/// Stops the triggers of a thread of the scheduler once it returns
+struct Stopping(Vec<Trigger>);
+
+impl Drop for Stopping {
+    fn drop(&mut self) {
+        for trigger in &self.0 {
+            trigger.stop();
+        }
+    }
+}
+
+/// Intervals of a module without a sample before its segment is marked stale
+const STALE_INTERVALS: u32 = 3;
+
+/// Collect each of `modules` on its interval, and all of them on a refresh, publishing the
+/// segments of each pass into `store`, until stopping. With `once`, a single pass.
+/// Modules are also collected as soon as their [`Trigger`] fires. Those that require the network
+/// are hidden while offline, then restored from `cache` and collected once back online. Modules
+/// with a [`Module::cache_ttl`] are only collected on their interval, their fetch not being worth
+/// a refresh.
+///
+/// The segments expire after [`STALE_INTERVALS`] intervals unless collected again, and the
+/// module being collected is recorded in `store`, so that one stuck in a read shows. Those of
+/// the modules with a [`Module::cache_ttl`] are kept in `cache`, published from it before their
+/// first sample.
+pub fn schedule(modules: Vec<Box<dyn Module>>, once: bool, cache: Option<&Path>, store: &Store) {
+    let lane = Lane {
+        store,
+        cache,
+        recorded: true,
+    };
+    schedule_until(modules, once, lane, signal::stopping);
+}
+
+/// [`schedule`] `modules` on threads of `scope`: the quick ones together, and each
+/// [`Module::blocking`] one on its own, only the quick ones recorded in `store` as being
+/// collected. With `once`, the pass of the quick ones is done before returning.
+///
+/// The threads also return once `stop` returns true, checked each time woken and before
+/// publishing, so the modules can be replaced as on a reload. Their handles are returned for
+/// the modules to be dropped by then.
+pub fn run<'scope, 'env>(
+    scope: &'scope Scope<'scope, 'env>,
+    modules: Vec<Box<dyn Module>>,
+    once: bool,
+    cache: Option<&'env Path>,
+    store: &'env Store,
+    stop: impl Fn() -> bool + Clone + Send + 'scope,
+) -> Vec<ScopedJoinHandle<'scope, ()>> {
+    let (blocking, quick): (Vec<_>, Vec<_>) =
+        modules.into_iter().partition(|module| module.blocking());
+    let mut lanes = vec![];
+    for module in blocking {
+        let lane = Lane {
+            store,
+            cache,
+            recorded: false,
+        };
+        let stop = stop.clone();
+        lanes.push(scope.spawn(move || {
+            schedule_until(vec![module], once, lane, || signal::stopping() || stop())
+        }));
+    }
+    let lane = Lane {
+        store,
+        cache,
+        recorded: true,
+    };
+    if once {
+        schedule_until(quick, true, lane, signal::stopping);
+    } else {
+        lanes
+            .push(scope.spawn(move || {
+                schedule_until(quick, false, lane, || signal::stopping() || stop())
+            }));
+    }
+    lanes
+}
+
+/// Where a thread of the scheduler publishes its modules
+#[derive(Clone, Copy)]
+struct Lane<'a> {
+    store: &'a Store,
+    cache: Option<&'a Path>,
+    /// whether the module being collected is recorded in the store, left to the thread of the
+    /// quick modules as the others block by design
+    recorded: bool,
+}
+
+/// [`schedule`] until `stop` returns true, checked each time woken and before publishing, or
+/// every module is disabled. The triggers of the modules are stopped on returning.
+fn schedule_until(
+    mut modules: Vec<Box<dyn Module>>,
+    once: bool,
+    lane: Lane,
+    stop: impl Fn() -> bool,
+) {
+    let Lane {
+        store,
+        cache,
+        recorded,
+    } = lane;
+    restore(&modules, cache, store);
+    let mut due = vec![Instant::now(); modules.len()];
+    let mut triggers: Vec<Trigger> = modules.iter().map(|_| Trigger::default()).collect();
+    let _stopping = Stopping(triggers.clone());
+    let mut was_offline = false;
+    if !once {
+        for (module, trigger) in modules.iter_mut().zip(&triggers) {
+            module.watch(trigger.clone());
+        }
+    }
+    loop {
+        if signal::wait_while_paused() {
+            for (module, due) in modules.iter_mut().zip(&mut due) {
+                module.reset();
+                if module.cache_ttl().is_none() {
+                    *due = Instant::now();
+                }
+            }
+        }
+
+        // woken by the change, collapsed into the offline segment meanwhile
+        let offline = signal::offline();
+        if offline != was_offline {
+            let network: Vec<&Box<dyn Module>> = modules
+                .iter()
+                .filter(|module| module.requires_network())
+                .collect();
+            if offline {
+                let hidden: Vec<Segment> = network
+                    .iter()
+                    .map(|module| Segment::hidden(module.name()))
+                    .collect();
+                if !hidden.is_empty() {
+                    store.publish(hidden);
+                }
+            } else {
+                restore(network, cache, store);
+                for (module, due) in modules.iter().zip(&mut due) {
+                    if module.requires_network() {
+                        *due = Instant::now();
+                    }
+                }
+            }
+        }
+        was_offline = offline;
+
+        let now = Instant::now();
+        let mut round = vec![];
+        for (module, due) in modules.iter_mut().zip(&mut due) {
+            if *due > now {
+                continue;
+            }
+            if offline && module.requires_network() {
+                *due = now + aligned(module.interval(), SystemTime::now());
+                continue;
+            }
+            if recorded {
+                store.collecting(Some(module.name()));
+            }
+            replay::collecting(Some(module.name()));
+            let collecting = Instant::now();
+            let segment = module.collect();
+            profile::collected(module.name(), collecting.elapsed(), module.interval());
+            replay::collecting(None);
+            if recorded {
+                store.collecting(None);
+            }
+            // after collecting, as modules such as backup tell their next change
+            *due = Instant::now() + aligned(module.interval(), SystemTime::now());
+            round.extend(segment.map(|segment| match module.cache_ttl() {
+                _ if segment.hidden => segment,
+                Some(ttl) => {
+                    let segment = fetched(segment, ttl);
+                    if let Some(dir) = cache {
+                        cache::keep(dir, &segment);
+                    }
+                    segment
+                }
+                None => {
+                    let stale = Instant::now() + module.interval() * STALE_INTERVALS;
+                    // modules such as net expire theirs sooner
+                    let expires = segment.expires.map_or(stale, |expires| expires.min(stale));
+                    segment.expires(expires)
+                }
+            }));
+            if module.disabled() {
+                round.push(Segment::hidden(module.name()));
+            }
+        }
+        // collected by the modules replacing these meanwhile
+        if stop() {
+            return;
+        }
+        if !round.is_empty() {
+            store.publish(round);
+        }
+        // no longer collected, nor their triggers taken
+        let mut i = 0;
+        while i < modules.len() {
+            if modules[i].disabled() {
+                modules.remove(i);
+                due.remove(i);
+                triggers.remove(i).stop();
+            } else {
+                i += 1;
+            }
+        }
+        if once || modules.is_empty() {
+            return;
+        }
+
+        // fired or stopped while collecting, before the sleep could be woken
+        let wakes = signal::wakes();
+        take_triggers(&triggers, &mut due);
+        if stop() {
+            return;
+        }
+        let next = due
+            .iter()
+            .min()
+            .copied()
+            .unwrap_or(now + Duration::from_secs(1));
+        let refreshed = signal::sleep_since(wakes, next.saturating_duration_since(Instant::now()));
+        if stop() {
+            return;
+        }
+        // reset once resumed, at the top of the loop
+        if signal::paused() {
+            continue;
+        }
+        if refreshed {
+            let now = Instant::now();
+            for (module, due) in modules.iter().zip(&mut due) {
+                if module.cache_ttl().is_none() {
+                    *due = now;
+                }
+            }
+        } else {
+            take_triggers(&triggers, &mut due);
+        }
+    }
+}
+
+/// Publish the segments cached in `cache` of those of `modules` with a [`Module::cache_ttl`],
+/// before they're fetched
+fn restore<'a>(
+    modules: impl IntoIterator<Item = &'a Box<dyn Module>>,
+    cache: Option<&Path>,
+    store: &Store,
+) {
+    let Some(dir) = cache else {
+        return;
+    };
+    let restored: Vec<Segment> = modules
+        .into_iter()
+        .filter(|module| module.cache_ttl().is_some())
+        .filter_map(|module| cache::load(dir, module.name()))
+        .collect();
+    if !restored.is_empty() {
+        store.publish(restored);
+    }
+}
+
+/// `segment` of a module with a [`Module::cache_ttl`] of `ttl`, fetched just now unless it tells
+/// when, fresh until `ttl` after, or sooner as it expires, such as at once after a failed check
+fn fetched(segment: Segment, ttl: Duration) -> Segment {
+    let Some(fetched) = segment.fetched else {
+        return segment.cached(ttl);
+    };
+    let age = fetched.elapsed().unwrap_or_default();
+    let expires = Instant::now() + ttl.saturating_sub(age);
+    let expires = segment
+        .expires
+        .map_or(expires, |sooner| sooner.min(expires));
+    segment.expires(expires)
+}
+
+/// `interval` of whole seconds shortened to end on a second of the wall clock at `now`, so the
+/// time flips on the second and the modules due in the same second are rendered together
+fn aligned(interval: Duration, now: SystemTime) -> Duration {
+    if interval.subsec_nanos() != 0 {
+        return interval;
+    }
+    let since_second = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    interval.saturating_sub(Duration::from_nanos(u64::from(since_second)))
+}
+
+/// Read the file at `path` into `buf`, reusing its allocation between samples, recorded with
+/// `--record-raw`
+fn read_into(path: &Path, buf: &mut String) -> io::Result<()> {
+    buf.clear();
+    File::open(path)?.read_to_string(buf)?;
+    replay::read(path, buf);
+    Ok(())
+}
+
+/// Counter `name` of /proc/vmstat, such as 3 of the `oom_kill 3` line
+fn vmstat_field(vmstat: &str, name: &str) -> Option<u64> {
+    vmstat.lines().find_map(|line| {
+        let (field, value) = line.split_once(' ')?;
+        (field == name).then(|| value.trim().parse().ok())?
+    })
+}
+
+/// Make the modules whose trigger fired due now
+fn take_triggers(triggers: &[Trigger], due: &mut [Instant]) {
+    let now = Instant::now();
+    for (due, trigger) in due.iter_mut().zip(triggers) {
+        if trigger.take() {
+            *due = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    struct Counter(u32);
+
+    impl Module for Counter {
+        fn name(&self) -> &'static str {
+            "counter"
+        }
+
+        fn collect(&mut self) -> Option<Segment> {
+            self.0 += 1;
+            (self.0 % 2 == 1).then(|| Segment::new("counter", self.0.to_string()))
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_millis(10)
+        }
+    }
+
+    #[test]
+    fn whole_seconds_aligned_to_the_wall_clock() {
+        let second = SystemTime::UNIX_EPOCH + Duration::from_secs(1_678_007_047);
+        let later = second + Duration::from_millis(250);
+        assert_eq!(
+            aligned(Duration::from_secs(1), second),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            aligned(Duration::from_secs(1), later),
+            Duration::from_millis(750)
+        );
+        assert_eq!(
+            aligned(Duration::from_secs(60), later),
+            Duration::from_millis(59_750)
+        );
+        let short = Duration::from_millis(10);
+        assert_eq!(aligned(short, later), short);
+    }
+
+    #[test]
+    fn once_publishes_a_single_pass() {
+        let store = Store::default();
+        schedule(vec![Box::new(Counter(0))], true, None, &store);
+        let mut segments = HashMap::new();
+        assert_eq!(store.snapshot(&mut segments), 1);
+        assert_eq!(segments["counter"].text, "1");
+    }
+
+    #[test]
+    fn stale_after_missed_intervals() {
+        let store = Store::default();
+        let before = Instant::now();
+        schedule(vec![Box::new(Counter(0))], true, None, &store);
+        let mut segments = HashMap::new();
+        store.snapshot(&mut segments);
+        let expires = segments["counter"].expires.unwrap();
+        assert!(expires >= before + Duration::from_millis(10) * STALE_INTERVALS);
+        assert!(expires <= Instant::now() + Duration::from_millis(10) * STALE_INTERVALS);
+        assert_eq!(store.collected(), None);
+    }
+
+    #[test]
+    fn none_publishes_nothing() {
+        let store = Store::default();
+        schedule(vec![Box::new(Counter(1))], true, None, &store);
+        assert_eq!(store.generation(), 0);
+    }
+
+    /// Fetched from afar once an hour, counting its fetches, reached only by the first
+    struct Remote(Arc<std::sync::atomic::AtomicU32>);
+
+    impl Module for Remote {
+        fn name(&self) -> &'static str {
+            "remote"
+        }
+
+        fn collect(&mut self) -> Option<Segment> {
+            let fetches = self.0.fetch_add(1, Ordering::AcqRel);
+            (fetches == 0).then(|| Segment::new("remote", "reached"))
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_secs(3600)
+        }
+
+        fn cache_ttl(&self) -> Option<Duration> {
+            Some(Duration::from_secs(60))
+        }
+
+        fn requires_network(&self) -> bool {
+            true
+        }
+    }
+
+    /// Wait for `done`, failing after 5 seconds
+    fn wait_for(done: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !done() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // the only test going offline, as the connectivity is global
+    #[test]
+    fn offline_hides_network_modules() {
+        assert!(!Counter(0).requires_network());
+        let store = Store::default();
+        signal::set_offline(true);
+        let fetches = Arc::default();
+        schedule(
+            vec![Box::new(Remote(Arc::clone(&fetches))), Box::new(Counter(0))],
+            true,
+            None,
+            &store,
+        );
+        signal::set_offline(false);
+        let mut segments = HashMap::new();
+        store.snapshot(&mut segments);
+        assert!(segments.contains_key("counter"));
+        assert!(!segments.contains_key("remote"));
+        assert_eq!(fetches.load(Ordering::Acquire), 0);
+
+        let dir = std::env::temp_dir().join(format!("statusbar-offline-{}", std::process::id()));
+        let store = Arc::new(Store::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::spawn({
+            let (store, stop, fetches, dir) = (
+                Arc::clone(&store),
+                Arc::clone(&stop),
+                Arc::clone(&fetches),
+                dir.clone(),
+            );
+            move || {
+                let lane = Lane {
+                    store: &store,
+                    cache: Some(&dir),
+                    recorded: true,
+                };
+                schedule_until(vec![Box::new(Remote(fetches))], false, lane, || {
+                    stop.load(Ordering::Acquire)
+                });
+            }
+        });
+        let shown = |store: &Store| {
+            let mut segments = HashMap::new();
+            store.snapshot(&mut segments);
+            segments.contains_key("remote")
+        };
+        wait_for(|| shown(&store));
+        assert_eq!(fetches.load(Ordering::Acquire), 1);
+
+        // hidden while offline, without fetching
+        signal::set_offline(true);
+        wait_for(|| !shown(&store));
+        assert_eq!(fetches.load(Ordering::Acquire), 1);
+
+        // shown from the cache and fetched again at once back online, rather than on its
+        // interval
+        signal::set_offline(false);
+        wait_for(|| fetches.load(Ordering::Acquire) == 2);
+        wait_for(|| shown(&store));
+
+        stop.store(true, Ordering::Release);
+        wait_for(|| {
+            signal::wake();
+            thread.is_finished()
+        });
+        thread.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Fetched from afar, `None` failing to
+    struct Slow(Option<&'static str>);
+
+    impl Module for Slow {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn collect(&mut self) -> Option<Segment> {
+            Some(Segment::new("slow", self.0?))
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_secs(3600)
+        }
+
+        fn cache_ttl(&self) -> Option<Duration> {
+            Some(Duration::from_secs(60))
+        }
+    }
+
+    #[test]
+    fn cached_segments_restored() {
+        let dir = std::env::temp_dir().join(format!("statusbar-schedule-{}", std::process::id()));
+        let store = Store::default();
+        schedule(vec![Box::new(Slow(Some("fp 3")))], true, Some(&dir), &store);
+        let mut segments = HashMap::new();
+        store.snapshot(&mut segments);
+        let fetched = segments["slow"].clone();
+        assert!(fetched.fetched.is_some());
+        // fresh for its time to live rather than stale intervals
+        assert!(fetched.expires.unwrap() > Instant::now() + Duration::from_secs(59));
+
+        // restarted, unable to fetch
+        let restarted = Store::default();
+        schedule(vec![Box::new(Slow(None))], true, Some(&dir), &restarted);
+        restarted.snapshot(&mut segments);
+        assert_eq!(segments["slow"].text, "fp 3");
+        assert_eq!(
+            segments["slow"]
+                .fetched
+                .unwrap()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            fetched
+                .fetched
+                .unwrap()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fetched_earlier_expires_sooner() {
+        let ttl = Duration::from_secs(60);
+        let mut earlier = Segment::new("slow", "bak 6h");
+        earlier.fetched = Some(SystemTime::now() - Duration::from_secs(50));
+        let expires = fetched(earlier, ttl).expires.unwrap();
+        assert!(expires <= Instant::now() + Duration::from_secs(10));
+        assert!(fetched(Segment::new("slow", "fp 3"), ttl).fetched.is_some());
+        let mut failed = Segment::new("slow", "bak 6h").expires(Instant::now());
+        failed.fetched = Some(SystemTime::now() - Duration::from_secs(50));
+        assert!(fetched(failed, ttl).expires.unwrap() <= Instant::now());
+    }
+
+    /// Collected once an hour, or on its trigger
+    struct Evented(Arc<std::sync::Mutex<Option<Trigger>>>);
+
+    impl Module for Evented {
+        fn name(&self) -> &'static str {
+            "evented"
+        }
+
+        fn collect(&mut self) -> Option<Segment> {
+            Some(Segment::new("evented", "sampled"))
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_secs(3600)
+        }
+
+        fn watch(&mut self, trigger: Trigger) {
+            *self.0.lock().unwrap() = Some(trigger);
+        }
+    }
+
+    #[test]
+    fn trigger_collects_before_interval() {
+        let slot = Arc::default();
+        let store = Arc::new(Store::default());
+        let module = Evented(Arc::clone(&slot));
+        // stopped on its own, the stop of the process exiting the tests
+        let stop = Arc::new(AtomicBool::new(false));
+        let (scheduler, stopped) = (Arc::clone(&store), Arc::clone(&stop));
+        let thread = std::thread::spawn(move || {
+            let lane = Lane {
+                store: &scheduler,
+                cache: None,
+                recorded: true,
+            };
+            schedule_until(vec![Box::new(module)], false, lane, || {
+                stopped.load(Ordering::Acquire)
+            });
+        });
+        while store.generation() == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        slot.lock().unwrap().as_ref().unwrap().fire();
+        let start = std::time::Instant::now();
+        while store.generation() == 1 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        stop.store(true, Ordering::Release);
+        // until it's asleep to be woken
+        while !thread.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            signal::wake();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn stopped_lanes_stop_their_triggers() {
+        let slot: Arc<std::sync::Mutex<Option<Trigger>>> = Arc::default();
+        let store = Store::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        std::thread::scope(|scope| {
+            let stopped = Arc::clone(&stop);
+            let modules: Vec<Box<dyn Module>> = vec![Box::new(Evented(Arc::clone(&slot)))];
+            let lanes = run(scope, modules, false, None, &store, move || {
+                stopped.load(Ordering::Acquire)
+            });
+            wait_for(|| store.generation() == 1);
+            let trigger = slot.lock().unwrap().clone().unwrap();
+            assert!(!trigger.stopped());
+
+            stop.store(true, Ordering::Release);
+            // until it's asleep to be woken
+            wait_for(|| {
+                signal::wake();
+                lanes.iter().all(|lane| lane.is_finished())
+            });
+            assert!(trigger.stopped());
+            // fired by a watcher still running, no longer collected
+            trigger.fire();
+            std::thread::sleep(Duration::from_millis(20));
+            assert_eq!(store.generation(), 1);
+        });
+    }
+}
@@ -0,0 +1,542 @@
+//! Ipv4 addresses of the interfaces, and their throughput as the view after a click
+//!
+//! The addresses are listed again on the address and link changes sent by rtnetlink, such as an
+//! interface appearing, or on each sample when unable to subscribe to them. Each segment expires
+//! after a few samples, marking the addresses stale if the module gets stuck.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use local_ip_address::list_afinet_netifas;
+use log::warn;
+
+use super::{Module, Trigger};
+use crate::logging::Throttle;
+use crate::marquee::Marquee;
+use crate::netlink::{self, Socket};
+use crate::output::Segment;
+use crate::paths::Paths;
+use crate::pattern::Pattern;
+use crate::throughput::{self, Throughput};
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Age of the addresses marked stale, unless sampled again
+const STALE_AFTER: Duration = Duration::from_secs(10);
+
+pub const SYS_NET_PATH: &str = "/sys/class/net";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// the addresses of every interface
+    #[default]
+    All,
+    /// the addresses of the first interface up, in the order of `--interface`
+    Primary,
+}
+
+/// Interfaces of `--interface` and `--exclude-interface`
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// every interface with an address other than loopback, from `--interface all`
+    all: bool,
+    /// with the alias labeling their addresses, from `--interface wlan0=wifi`
+    include: Vec<(Pattern, Option<String>)>,
+    exclude: Vec<Pattern>,
+}
+
+impl Selection {
+    /// Glob patterns such as `wl*`, or `all`, those included optionally followed by `=alias`.
+    /// Fails on an invalid pattern.
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, String> {
+        let all = include.iter().any(|pattern| pattern == "all");
+        let include = include
+            .iter()
+            .filter(|pattern| *pattern != "all")
+            .map(|pattern| match pattern.split_once('=') {
+                Some((pattern, alias)) => Ok((Pattern::new(pattern)?, Some(alias.to_string()))),
+                None => Ok((Pattern::new(pattern)?, None)),
+            })
+            .collect::<Result<_, String>>()?;
+        let exclude = exclude
+            .iter()
+            .map(|pattern| Pattern::new(pattern))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            all,
+            include,
+            exclude,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.include.is_empty()
+    }
+
+    /// The patterns of `--interface`, without `all`
+    pub fn patterns(&self) -> impl Iterator<Item = &Pattern> {
+        self.include.iter().map(|(pattern, _)| pattern)
+    }
+
+    /// Position of the first pattern matching `name`, those of `all` last
+    fn priority(&self, name: &str) -> usize {
+        self.patterns()
+            .position(|pattern| pattern.matches(name))
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Alias of the interface `name`, else its name
+    fn label<'a>(&'a self, name: &'a str) -> &'a str {
+        self.include
+            .iter()
+            .find(|(pattern, _)| pattern.matches(name))
+            .and_then(|(_, alias)| alias.as_deref())
+            .unwrap_or(name)
+    }
+
+    /// Whether the interface `name` is selected, whatever its addresses
+    pub fn matches(&self, name: &str) -> bool {
+        (self.all && name != "lo" || self.patterns().any(|pattern| pattern.matches(name)))
+            && !self.excludes(name)
+    }
+
+    fn excludes(&self, name: &str) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches(name))
+    }
+
+    /// Names of the interfaces selected among `network_interfaces`, those named literally first
+    /// even without an address
+    fn select(&self, network_interfaces: &[(String, IpAddr)]) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .patterns()
+            .filter(|pattern| pattern.is_literal())
+            .map(ToString::to_string)
+            .filter(|name| !self.excludes(name))
+            .collect();
+        for (name, ip) in network_interfaces {
+            let included = (self.all && !ip.is_loopback())
+                || self.patterns().any(|pattern| pattern.matches(name));
+            if included && !self.excludes(name) && !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+}
+
+pub struct Net {
+    selection: Selection,
+    /// names of the interfaces selected, as of the addresses last listed
+    interfaces: Vec<String>,
+    /// without an address, hide the segment instead of showing "net down"
+    hide_down: bool,
+    /// label the addresses with their interface, when of more than one
+    labels: bool,
+    mode: Mode,
+    throttle: Throttle,
+    /// addresses of all interfaces, as last listed
+    addresses: Vec<(String, IpAddr)>,
+    /// set by the rtnetlink watcher on a change, always set without one
+    changed: Arc<AtomicBool>,
+    /// whether the rtnetlink watcher runs, cleared if it fails
+    events: Arc<AtomicBool>,
+    /// with the throughput view, its sampler and latest rates
+    throughput: Option<(Throughput, Option<(f64, f64)>)>,
+    /// scrolling the addresses, after the fixed label
+    marquee: Marquee,
+    /// such as /sys/class/net, for the link states and the statistics
+    sys_net: PathBuf,
+}
+
+impl Net {
+    pub fn new(
+        selection: Selection,
+        hide_down: bool,
+        labels: bool,
+        mode: Mode,
+        throughput: bool,
+        marquee: Marquee,
+    ) -> Self {
+        Self {
+            interfaces: selection.select(&[]),
+            selection,
+            hide_down,
+            labels,
+            mode,
+            throttle: Throttle::default(),
+            addresses: vec![],
+            changed: Arc::new(AtomicBool::new(true)),
+            events: Arc::default(),
+            throughput: throughput.then(|| (Throughput::default(), None)),
+            marquee,
+            sys_net: SYS_NET_PATH.into(),
+        }
+    }
+
+    pub fn paths(mut self, paths: &Paths) -> Self {
+        self.sys_net = paths.resolve(SYS_NET_PATH);
+        self
+    }
+}
+
+impl Module for Net {
+    fn name(&self) -> &'static str {
+        "net"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let changed = self.changed.swap(false, Ordering::Acquire);
+        if changed || !self.events.load(Ordering::Relaxed) {
+            self.addresses = match list_afinet_netifas() {
+                Ok(network_interfaces) => {
+                    self.throttle.succeeded();
+                    network_interfaces
+                }
+                Err(e) => {
+                    if let Some(suppressed) = self.throttle.failed() {
+                        warn!(target: "net", "unable to list interfaces: {e}{suppressed}");
+                    }
+                    // listed again on the next sample
+                    self.changed.store(true, Ordering::Release);
+                    vec![]
+                }
+            };
+            // such as an interface matching a pattern appearing
+            self.interfaces = self.selection.select(&self.addresses);
+        }
+        // the link state read on each sample, an interface going down keeping its addresses
+        let interfaces = match self.mode {
+            Mode::All => &self.interfaces[..],
+            Mode::Primary => primary(
+                &self.selection,
+                &self.interfaces,
+                &self.addresses,
+                &self.sys_net,
+            ),
+        };
+        let labels = self.labels.then_some(&self.selection);
+        let mut segment = segment(interfaces, &self.addresses, labels, &mut self.marquee)
+            .expires(Instant::now() + STALE_AFTER);
+        if segment.text.is_empty() && !self.selection.is_empty() && !self.hide_down {
+            segment.text = "net down".into();
+        }
+
+        if let Some((throughput, last)) = &mut self.throughput {
+            if let Some(rate) = throughput.sample(&self.sys_net, interfaces) {
+                *last = Some(rate);
+            }
+            let alt = match last {
+                Some((rx, tx)) => {
+                    let rx = throughput::format(*rx);
+                    let tx = throughput::format(*tx);
+                    format!("net rx {rx} tx {tx}")
+                }
+                None => String::new(),
+            };
+            segment = segment.alt(alt);
+        }
+        Some(segment)
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    /// Addresses added or removed, and interfaces created or removed such as by wg-quick, trigger
+    /// listing the addresses again
+    fn watch(&mut self, trigger: Trigger) {
+        let socket = match Socket::open(libc::NETLINK_ROUTE, netlink::ADDRESS_GROUPS) {
+            Ok(socket) => socket,
+            Err(e) => {
+                warn!(target: "net", "unable to watch address changes, polling: {e}");
+                return;
+            }
+        };
+        self.events.store(true, Ordering::Relaxed);
+        let changed = self.changed.clone();
+        let events = self.events.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0_u8; 8192];
+            loop {
+                match socket.recv(&mut buf) {
+                    Ok(_) => (),
+                    Err(e) if netlink::overflowed(&e) => (),
+                    Err(e) => {
+                        warn!(target: "net", "unable to receive address changes, polling: {e}");
+                        events.store(false, Ordering::Relaxed);
+                        return;
+                    }
+                }
+                if trigger.stopped() {
+                    return;
+                }
+                changed.store(true, Ordering::Release);
+                trigger.fire();
+            }
+        });
+    }
+
+    fn reset(&mut self) {
+        if let Some((throughput, _)) = &mut self.throughput {
+            *throughput = Throughput::default();
+        }
+    }
+}
+
+/// The interface of `interfaces` first in the priority of `selection` that is up with an ipv4
+/// address, none if none is
+fn primary<'a>(
+    selection: &Selection,
+    interfaces: &'a [String],
+    network_interfaces: &[(String, IpAddr)],
+    sys_net: &Path,
+) -> &'a [String] {
+    let candidates = interfaces.iter().enumerate().filter(|(_, interface)| {
+        network_interfaces
+            .iter()
+            .any(|(name, ip)| name == *interface && ip.is_ipv4())
+            && is_up(sys_net, interface)
+    });
+    match candidates.min_by_key(|(_, interface)| selection.priority(interface)) {
+        Some((i, _)) => &interfaces[i..=i],
+        None => &[],
+    }
+}
+
+/// Whether the link of `interface` is up, by its carrier where the driver doesn't report its
+/// operational state, such as for tun devices
+fn is_up(sys_net: &Path, interface: &str) -> bool {
+    let read = |name| std::fs::read_to_string(sys_net.join(interface).join(name));
+    match read("operstate") {
+        Ok(state) if state.trim() == "up" => true,
+        Ok(state) if state.trim() == "unknown" => read("carrier").is_ok_and(|c| c.trim() == "1"),
+        _ => false,
+    }
+}
+
+/// `net [192.0.2.2, 198.51.100.7]` of the ipv4 addresses of `interfaces`, empty without one,
+/// or with `labels` and addresses of more than one interface `net [wifi: 192.0.2.2, eth0:
+/// 198.51.100.7]`. The addresses are the value `ip`, and their number per interface
+/// `ip:<interface>`.
+fn segment(
+    interfaces: &[String],
+    network_interfaces: &[(String, IpAddr)],
+    labels: Option<&Selection>,
+    marquee: &mut Marquee,
+) -> Segment {
+    let ipv4 = |(name, ip): &&(String, IpAddr)| interfaces.contains(name) && ip.is_ipv4();
+
+    let mut addresses = vec![];
+    for (_, ip) in network_interfaces.iter().filter(ipv4) {
+        let ip = ip.to_string();
+        if !addresses.contains(&ip) {
+            addresses.push(ip);
+        }
+    }
+
+    // deduplicated per interface instead
+    let mut labeled = vec![];
+    if let Some(selection) = labels {
+        for interface in interfaces {
+            let mut ips: Vec<String> = vec![];
+            for (_, ip) in network_interfaces
+                .iter()
+                .filter(ipv4)
+                .filter(|(name, _)| name == interface)
+            {
+                let ip = ip.to_string();
+                if !ips.contains(&ip) {
+                    ips.push(ip);
+                }
+            }
+            if !ips.is_empty() {
+                let label = selection.label(interface);
+                labeled.push(format!("{label}: {}", ips.join(", ")));
+            }
+        }
+    }
+    let shown = if labeled.len() > 1 {
+        &labeled
+    } else {
+        &addresses
+    };
+
+    let mut segment = if shown.is_empty() {
+        Segment::new("net", "")
+    } else {
+        let addrs = format!("[{}]", shown.join(", "));
+        Segment::new("net", format!("net {}", marquee.apply("net", &addrs)))
+    };
+    segment = segment.value("ip", addresses.join(", "));
+    for interface in interfaces {
+        let count = network_interfaces
+            .iter()
+            .filter(ipv4)
+            .filter(|(name, _)| name == interface)
+            .count();
+        segment = segment.value(format!("ip:{interface}"), count);
+    }
+    segment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// As listed on a laptop on wifi, with a docker bridge
+    fn captured() -> Vec<(String, IpAddr)> {
+        [
+            ("lo", "127.0.0.1"),
+            ("wlan0", "192.0.2.2"),
+            ("docker0", "172.17.0.1"),
+            ("lo", "::1"),
+            ("wlan0", "2001:db8::2"),
+            ("wlan0", "fe80::1"),
+            ("wlan0", "192.0.2.2"),
+        ]
+        .into_iter()
+        .map(|(name, ip)| (name.to_string(), ip.parse().unwrap()))
+        .collect()
+    }
+
+    fn marquee() -> Marquee {
+        Marquee::new(vec![], "   ".into(), true)
+    }
+
+    #[test]
+    fn ipv4_of_interfaces() {
+        let interfaces = ["wlan0".to_string(), "eth0".to_string()];
+        let segment = segment(&interfaces, &captured(), None, &mut marquee());
+        assert_eq!(segment.text, "net [192.0.2.2]");
+        assert_eq!(segment.get::<String>("ip").unwrap(), "192.0.2.2");
+        assert_eq!(segment.get::<usize>("ip:wlan0"), Some(2));
+        assert_eq!(segment.get::<usize>("ip:eth0"), Some(0));
+    }
+
+    #[test]
+    fn without_address() {
+        let segment = segment(&["eth0".to_string()], &captured(), None, &mut marquee());
+        assert_eq!(segment.text, "");
+    }
+
+    fn selection(include: &[&str], exclude: &[&str]) -> Selection {
+        let strings = |patterns: &[&str]| patterns.iter().map(ToString::to_string).collect();
+        let include: Vec<String> = strings(include);
+        let exclude: Vec<String> = strings(exclude);
+        Selection::new(&include, &exclude).unwrap()
+    }
+
+    #[test]
+    fn selected_by_pattern() {
+        let selection = selection(&["eth0", "wl*", "d*"], &["docker*"]);
+        assert_eq!(selection.select(&captured()), ["eth0", "wlan0"]);
+        let selection = self::selection(&["all"], &[]);
+        assert_eq!(selection.select(&captured()), ["wlan0", "docker0"]);
+        assert!(self::selection(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn labeled_by_interface() {
+        let mut network_interfaces = captured();
+        network_interfaces.push(("eth0".into(), "198.51.100.7".parse().unwrap()));
+        network_interfaces.push(("eth0".into(), "192.0.2.2".parse().unwrap()));
+        let selection = selection(&["wlan0=wifi", "eth*"], &[]);
+        let interfaces = selection.select(&network_interfaces);
+        let labeled = segment(
+            &interfaces,
+            &network_interfaces,
+            Some(&selection),
+            &mut marquee(),
+        );
+        assert_eq!(
+            labeled.text,
+            "net [wifi: 192.0.2.2, eth0: 198.51.100.7, 192.0.2.2]"
+        );
+        assert_eq!(
+            labeled.get::<String>("ip").unwrap(),
+            "192.0.2.2, 198.51.100.7"
+        );
+
+        // only one interface with an address
+        let interfaces = selection.select(&captured());
+        let unlabeled = segment(&interfaces, &captured(), Some(&selection), &mut marquee());
+        assert_eq!(unlabeled.text, "net [192.0.2.2]");
+    }
+
+    #[test]
+    fn primary_by_priority() {
+        let dir = std::env::temp_dir().join(format!("statusbar-net-{}", std::process::id()));
+        let link = |interface: &str, operstate: &str, carrier: &str| {
+            std::fs::create_dir_all(dir.join(interface)).unwrap();
+            std::fs::write(dir.join(interface).join("operstate"), operstate).unwrap();
+            std::fs::write(dir.join(interface).join("carrier"), carrier).unwrap();
+        };
+        let mut network_interfaces = captured();
+        network_interfaces.push(("eth0".into(), "198.51.100.7".parse().unwrap()));
+        network_interfaces.push(("tun0".into(), "10.8.0.2".parse().unwrap()));
+        let selection = selection(&["eth*", "wlan0", "tun0"], &[]);
+        let interfaces = selection.select(&network_interfaces);
+        let primary = |selection| primary(selection, &interfaces, &network_interfaces, &dir);
+
+        // undocked, eth0 down with the address of its last lease
+        link("eth0", "down\n", "0\n");
+        link("wlan0", "up\n", "1\n");
+        link("tun0", "unknown\n", "1\n");
+        assert_eq!(primary(&selection), ["wlan0"]);
+
+        link("eth0", "up\n", "1\n");
+        assert_eq!(primary(&selection), ["eth0"]);
+
+        link("eth0", "down\n", "0\n");
+        link("wlan0", "dormant\n", "1\n");
+        assert_eq!(primary(&selection), ["tun0"]);
+
+        link("tun0", "unknown\n", "0\n");
+        assert!(primary(&selection).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let e = Selection::new(&["wl[".to_string()], &[]).unwrap_err();
+        assert_eq!(e, "unclosed [ in pattern \"wl[\"");
+    }
+
+    #[test]
+    fn down_unless_hidden() {
+        let mut net = Net::new(
+            selection(&["nonexistent0"], &[]),
+            false,
+            false,
+            Mode::All,
+            false,
+            marquee(),
+        );
+        let segment = net.collect().unwrap();
+        assert_eq!(segment.text, "net down");
+        assert!(segment.expires.is_some());
+        let mut net = Net::new(
+            selection(&["nonexistent0"], &[]),
+            true,
+            false,
+            Mode::All,
+            false,
+            marquee(),
+        );
+        assert_eq!(net.collect().unwrap().text, "");
+    }
+
+    #[test]
+    fn addresses_scroll_after_label() {
+        let mut marquee = Marquee::new(vec![("net".into(), 6)], " ".into(), true);
+        let interfaces = ["wlan0".to_string(), "docker0".to_string()];
+        let first = segment(&interfaces, &captured(), None, &mut marquee);
+        assert_eq!(first.text, "net [192.0");
+        let second = segment(&interfaces, &captured(), None, &mut marquee);
+        assert_eq!(second.text, "net 192.0.");
+    }
+}
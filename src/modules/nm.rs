@@ -0,0 +1,280 @@
+//! Name of the active NetworkManager connection, such as `HomeWifi`, with the VPNs over it as
+//! `HomeWifi + Office VPN`
+//!
+//! NetworkManager is queried again on any of its signals, such as the PropertiesChanged of its
+//! PrimaryConnection and ActiveConnections, and on its name changing owner. While it doesn't run
+//! the segment is hidden, until it starts.
+
+use std::time::Duration;
+
+use log::{info, warn};
+use zbus::blocking::{proxy::Builder, Connection, MessageIterator, Proxy};
+use zbus::message::Type;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::MatchRule;
+
+use super::{Module, Trigger};
+use crate::glyphs::{Glyph, Glyphs};
+use crate::output::Segment;
+
+const NM: &str = "org.freedesktop.NetworkManager";
+const ACTIVE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+
+/// `State` of an active connection, NMActiveConnectionState
+const STATE_ACTIVATING: u32 = 1;
+const STATE_ACTIVATED: u32 = 2;
+
+/// Interval of querying NetworkManager again without a signal, in case one was missed
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// `Type` of the connections of VPNs, shown after the primary connection
+const VPN_TYPES: &[&str] = &["vpn", "wireguard"];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Active {
+    id: String,
+    kind: String,
+    state: u32,
+}
+
+/// Name of the primary connection, `connecting…` while activating, collected again on the
+/// signals of NetworkManager. Disabled without the system bus.
+pub struct Nm {
+    glyphs: Glyphs,
+    conn: Option<Connection>,
+    trigger: Option<Trigger>,
+    /// whether NetworkManager was found missing, logged once
+    missing: bool,
+    disabled: bool,
+}
+
+impl Nm {
+    pub fn new(glyphs: Glyphs) -> Self {
+        Self {
+            glyphs,
+            conn: None,
+            trigger: None,
+            missing: false,
+            disabled: false,
+        }
+    }
+}
+
+impl Module for Nm {
+    fn name(&self) -> &'static str {
+        "nm"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        if self.conn.is_none() {
+            match connect(self.trigger.as_ref()) {
+                Ok(conn) => self.conn = Some(conn),
+                Err(e) => {
+                    warn!(target: "nm", "system bus unavailable, disabling: {e}");
+                    self.disabled = true;
+                    return None;
+                }
+            }
+        }
+        let conn = self.conn.as_ref()?;
+        let text = match connections(conn) {
+            Ok((primary, others)) => {
+                self.missing = false;
+                segment(primary.as_ref(), &others, &self.glyphs)
+            }
+            Err(e) => {
+                if !self.missing {
+                    info!(target: "nm", "NetworkManager unavailable, nm hidden: {e}");
+                    self.missing = true;
+                }
+                None
+            }
+        };
+        Some(match text {
+            Some(text) => Segment::new("nm", text),
+            None => Segment::hidden("nm"),
+        })
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn watch(&mut self, trigger: Trigger) {
+        self.trigger = Some(trigger);
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn disabled(&self) -> bool {
+        self.disabled
+    }
+}
+
+/// Closing the connection ends the threads forwarding its signals
+impl Drop for Nm {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            let _ = conn.close();
+        }
+    }
+}
+
+/// Connect to the system bus, firing `trigger` on every signal of NetworkManager and its name
+/// gaining or losing an owner
+fn connect(trigger: Option<&Trigger>) -> zbus::Result<Connection> {
+    let conn = Connection::system()?;
+    let Some(trigger) = trigger else {
+        return Ok(conn);
+    };
+
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(NM)?
+        .path_namespace("/org/freedesktop/NetworkManager")?
+        .build();
+    let signals = MessageIterator::for_match_rule(rule, &conn, Some(16))?;
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface("org.freedesktop.DBus")?
+        .member("NameOwnerChanged")?
+        .arg(0, NM)?
+        .build();
+    let owners = MessageIterator::for_match_rule(rule, &conn, Some(4))?;
+    for signals in [signals, owners] {
+        let trigger = trigger.clone();
+        std::thread::spawn(move || {
+            for signal in signals {
+                if signal.is_err() {
+                    return;
+                }
+                trigger.fire();
+            }
+        });
+    }
+    Ok(conn)
+}
+
+fn proxy<'a>(
+    conn: &'a Connection,
+    path: &'a str,
+    interface: &'static str,
+) -> zbus::Result<Proxy<'a>> {
+    Builder::new(conn)
+        .destination(NM)?
+        .path(path)?
+        .interface(interface)?
+        .cache_properties(CacheProperties::No)
+        .build()
+}
+
+/// The primary connection, or the one activating without one, and the other active connections
+fn connections(conn: &Connection) -> zbus::Result<(Option<Active>, Vec<Active>)> {
+    let nm = proxy(conn, "/org/freedesktop/NetworkManager", NM)?;
+    let mut primary: OwnedObjectPath = nm.get_property("PrimaryConnection")?;
+    if primary.as_str() == "/" {
+        primary = nm.get_property("ActivatingConnection")?;
+    }
+    let paths: Vec<OwnedObjectPath> = nm.get_property("ActiveConnections")?;
+
+    let active = |path: &OwnedObjectPath| -> zbus::Result<Active> {
+        let connection = proxy(conn, path.as_str(), ACTIVE)?;
+        Ok(Active {
+            id: connection.get_property("Id")?,
+            kind: connection.get_property("Type")?,
+            state: connection.get_property("State")?,
+        })
+    };
+    let mut others = vec![];
+    for path in paths.iter().filter(|path| **path != primary) {
+        // connections disappear as they're deactivated, skip any gone since listing them
+        if let Ok(connection) = active(path) {
+            others.push(connection);
+        }
+    }
+    let primary = match primary.as_str() {
+        "/" => None,
+        _ => Some(active(&primary)?),
+    };
+    Ok((primary, others))
+}
+
+/// The name of the `primary` connection, followed by the activated VPNs among the `others`.
+/// `connecting…` while the primary connection or a VPN activates, hidden without any.
+fn segment(primary: Option<&Active>, others: &[Active], glyphs: &Glyphs) -> Option<String> {
+    let connecting = format!("connecting{}", glyphs.get(Glyph::Ellipsis));
+    let vpns: Vec<&Active> = others
+        .iter()
+        .filter(|active| VPN_TYPES.contains(&active.kind.as_str()))
+        .collect();
+    let activating = vpns.iter().any(|vpn| vpn.state == STATE_ACTIVATING);
+    let mut names = vec![];
+    match primary {
+        Some(primary) if primary.state == STATE_ACTIVATED => names.push(primary.id.as_str()),
+        Some(primary) if primary.state == STATE_ACTIVATING => return Some(connecting),
+        _ => (),
+    }
+    names.extend(
+        vpns.iter()
+            .filter(|vpn| vpn.state == STATE_ACTIVATED)
+            .map(|vpn| vpn.id.as_str()),
+    );
+    if activating {
+        names.push(&connecting);
+    }
+    (!names.is_empty()).then(|| names.join(" + "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyphs::Set;
+
+    fn active(id: &str, kind: &str, state: u32) -> Active {
+        Active {
+            id: id.to_string(),
+            kind: kind.to_string(),
+            state,
+        }
+    }
+
+    #[test]
+    fn primary_and_vpns() {
+        let wifi = active("HomeWifi", "802-11-wireless", STATE_ACTIVATED);
+        assert_eq!(
+            segment(Some(&wifi), &[], &Glyphs::default()).unwrap(),
+            "HomeWifi"
+        );
+        let others = [
+            active("docker0", "bridge", STATE_ACTIVATED),
+            active("Office VPN", "vpn", STATE_ACTIVATED),
+        ];
+        assert_eq!(
+            segment(Some(&wifi), &others, &Glyphs::default()).unwrap(),
+            "HomeWifi + Office VPN"
+        );
+        assert_eq!(segment(None, &[], &Glyphs::default()), None);
+    }
+
+    #[test]
+    fn activating() {
+        let wifi = active("HomeWifi", "802-11-wireless", STATE_ACTIVATING);
+        assert_eq!(
+            segment(Some(&wifi), &[], &Glyphs::default()).unwrap(),
+            "connecting…"
+        );
+        assert_eq!(
+            segment(Some(&wifi), &[], &Glyphs::new(Set::Ascii, vec![])).unwrap(),
+            "connecting..."
+        );
+        let wifi = active("HomeWifi", "802-11-wireless", STATE_ACTIVATED);
+        let vpn = active("Office VPN", "wireguard", STATE_ACTIVATING);
+        assert_eq!(
+            segment(Some(&wifi), &[vpn], &Glyphs::default()).unwrap(),
+            "HomeWifi + connecting…"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Clock synchronization status from systemd-timedated, with the offset from chrony
+
+use std::process::Command;
+use std::time::Duration;
+
+use log::warn;
+use zbus::blocking::{proxy::Builder, Connection, Proxy};
+use zbus::proxy::CacheProperties;
+
+use super::Module;
+use crate::command::output_timeout;
+use crate::glyphs::{Glyph, Glyphs};
+use crate::output::Segment;
+
+const INTERVAL: Duration = Duration::from_secs(60);
+const CHRONYC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the clock is synchronized, hidden when it is without `offset`.
+///
+/// Machines without timedated (the first query fails) are disabled after a single probe.
+pub struct Ntp {
+    offset: bool,
+    warning: String,
+    conn: Option<Connection>,
+    probed: bool,
+    disabled: bool,
+}
+
+impl Ntp {
+    pub fn new(offset: bool, glyphs: &Glyphs) -> Self {
+        Self {
+            offset,
+            warning: glyphs.get(Glyph::Warning).to_string(),
+            conn: None,
+            probed: false,
+            disabled: false,
+        }
+    }
+}
+
+impl Module for Ntp {
+    fn name(&self) -> &'static str {
+        "ntp"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let conn = match &self.conn {
+            Some(conn) => conn,
+            None => match Connection::system() {
+                Ok(conn) => self.conn.insert(conn),
+                Err(e) => {
+                    warn!("unable to connect to the system bus, disabling: {e}");
+                    self.disabled = true;
+                    return None;
+                }
+            },
+        };
+        let synced = match ntp_synchronized(conn) {
+            Ok(synced) => synced,
+            Err(e) if !self.probed => {
+                warn!("org.freedesktop.timedate1 unavailable, disabling: {e}");
+                self.disabled = true;
+                return None;
+            }
+            // timedated is bus activated, a transient failure keeps the last state
+            Err(_) => return None,
+        };
+        self.probed = true;
+
+        let offset = if self.offset { chrony_offset() } else { None };
+        let offset = offset.map(|ms| format!("{ms:+.1}ms"));
+
+        let warning = &self.warning;
+        Some(match (synced, offset) {
+            (true, None) => Segment::hidden("ntp"),
+            (true, Some(offset)) => Segment::new("ntp", format!("ntp {offset}")),
+            (false, None) => Segment::new("ntp", format!("{warning} clock unsync")),
+            (false, Some(offset)) => {
+                Segment::new("ntp", format!("{warning} clock unsync {offset}"))
+            }
+        })
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn disabled(&self) -> bool {
+        self.disabled
+    }
+}
+
+fn ntp_synchronized(conn: &Connection) -> zbus::Result<bool> {
+    // timedated doesn't emit PropertiesChanged for NTPSynchronized, so don't cache
+    let proxy: Proxy = Builder::new(conn)
+        .destination("org.freedesktop.timedate1")?
+        .path("/org/freedesktop/timedate1")?
+        .interface("org.freedesktop.timedate1")?
+        .cache_properties(CacheProperties::No)
+        .build()?;
+    proxy.get_property("NTPSynchronized")
+}
+
+/// Offset of the system clock from NTP time in milliseconds, positive when fast
+fn chrony_offset() -> Option<f64> {
+    let output =
+        output_timeout(Command::new("chronyc").arg("tracking"), CHRONYC_TIMEOUT).ok()??;
+    parse_chrony_tracking(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the `System time     : 0.000012345 seconds fast of NTP time` line, none while chrony
+/// isn't synchronised, its offset then being zero rather than unknown
+fn parse_chrony_tracking(tracking: &str) -> Option<f64> {
+    let unsynchronised = tracking.lines().any(|line| {
+        line.split_once(':').is_some_and(|(key, value)| {
+            key.trim() == "Leap status" && value.trim() == "Not synchronised"
+        })
+    });
+    if unsynchronised {
+        return None;
+    }
+    let line = tracking
+        .lines()
+        .find(|line| line.starts_with("System time"))?;
+    let (_, value) = line.split_once(':')?;
+    let mut words = value.split_ascii_whitespace();
+    let seconds: f64 = words.next()?.parse().ok()?;
+    let sign = match words.nth(1)? {
+        "fast" => 1.0,
+        "slow" => -1.0,
+        _ => return None,
+    };
+    Some(sign * seconds * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `chronyc tracking` synchronised to a pool server
+    const TRACKING: &str = "\
+Reference ID    : C0A80001 (192.168.0.1)
+Stratum         : 3
+Ref time (UTC)  : Sat Mar 02 09:41:07 2024
+System time     : 0.000012345 seconds fast of NTP time
+Last offset     : +0.000004211 seconds
+RMS offset      : 0.000021483 seconds
+Frequency       : 12.530 ppm slow
+Residual freq   : +0.001 ppm
+Skew            : 0.052 ppm
+Root delay      : 0.012263925 seconds
+Root dispersion : 0.000618455 seconds
+Update interval : 1025.4 seconds
+Leap status     : Normal
+";
+
+    /// `chronyc tracking` right after chronyd started, before reaching any source
+    const UNSYNCHRONISED: &str = "\
+Reference ID    : 00000000 ()
+Stratum         : 0
+Ref time (UTC)  : Thu Jan 01 00:00:00 1970
+System time     : 0.000000000 seconds fast of NTP time
+Last offset     : +0.000000000 seconds
+RMS offset      : 0.000000000 seconds
+Frequency       : 12.530 ppm slow
+Residual freq   : +0.000 ppm
+Skew            : 0.000 ppm
+Root delay      : 1.000000000 seconds
+Root dispersion : 1.000000000 seconds
+Update interval : 0.0 seconds
+Leap status     : Not synchronised
+";
+
+    #[test]
+    fn tracking() {
+        let offset = parse_chrony_tracking(TRACKING).unwrap();
+        assert!((offset - 0.012345).abs() < 1e-9, "{offset}");
+        let slow = TRACKING.replace("seconds fast", "seconds slow");
+        let offset = parse_chrony_tracking(&slow).unwrap();
+        assert!((offset + 0.012345).abs() < 1e-9, "{offset}");
+    }
+
+    #[test]
+    fn tracking_unsynchronised() {
+        assert_eq!(parse_chrony_tracking(UNSYNCHRONISED), None);
+        assert_eq!(parse_chrony_tracking("506 Cannot talk to daemon\n"), None);
+        assert_eq!(parse_chrony_tracking(""), None);
+        let garbled = TRACKING.replace("seconds fast", "seconds ahead");
+        assert_eq!(parse_chrony_tracking(&garbled), None);
+    }
+}
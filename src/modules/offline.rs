@@ -0,0 +1,200 @@
+//! Whether the host is offline, as without a default route, for the modules of
+//! [`crate::NETWORK_MODULES`] to pause instead of each failing to reach its hosts
+//!
+//! The routes are read again on the route changes sent by rtnetlink, and every few seconds in
+//! case unable to subscribe to them. Only whether some default route exists is checked, not whether
+//! it leads anywhere, so a captive portal still counts as online.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{info, warn};
+
+use super::{Module, Trigger};
+use crate::netlink::{self, Socket};
+use crate::output::Segment;
+use crate::paths::Paths;
+use crate::signal;
+
+pub const ROUTE_PATH: &str = "/proc/net/route";
+pub const IPV6_ROUTE_PATH: &str = "/proc/net/ipv6_route";
+
+/// Interval of reading the routes, for when unable to subscribe to their changes
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// `RTF_UP` of the flags of a route
+const RTF_UP: u32 = 0x1;
+/// `RTF_REJECT`, of the unreachable routes such as the ipv6 default route of lo
+const RTF_REJECT: u32 = 0x200;
+
+/// `marker` while without a default route, hidden otherwise, recording the connectivity for the
+/// scheduler to hide the modules that require the network meanwhile
+pub struct Offline {
+    routes: [PathBuf; 2],
+    marker: String,
+}
+
+impl Offline {
+    pub fn new(paths: &Paths, marker: String) -> Self {
+        Self {
+            routes: [paths.resolve(ROUTE_PATH), paths.resolve(IPV6_ROUTE_PATH)],
+            marker,
+        }
+    }
+}
+
+impl Module for Offline {
+    fn name(&self) -> &'static str {
+        "offline"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let online = match default_route(&self.routes) {
+            Ok(online) => online,
+            Err(e) => {
+                warn!(target: "offline", "unable to read the routes: {e}");
+                // rather than pausing the network modules for good
+                true
+            }
+        };
+        // online from the start, as assumed, has nothing to log
+        if signal::set_offline(!online) {
+            if online {
+                info!(target: "offline", "online, resuming the network modules");
+            } else {
+                info!(target: "offline", "offline, without a default route");
+            }
+        }
+        if online {
+            return Some(Segment::hidden("offline"));
+        }
+        Some(Segment::new("offline", self.marker.clone()))
+    }
+
+    fn interval(&self) -> Duration {
+        POLL_INTERVAL
+    }
+
+    /// Fire `trigger` on the route changes sent by rtnetlink
+    fn watch(&mut self, trigger: Trigger) {
+        let groups = (libc::RTMGRP_IPV4_ROUTE | libc::RTMGRP_IPV6_ROUTE) as u32;
+        let socket = match Socket::open(libc::NETLINK_ROUTE, groups) {
+            Ok(socket) => socket,
+            Err(e) => {
+                warn!(target: "offline", "unable to watch route changes, polling: {e}");
+                return;
+            }
+        };
+        std::thread::spawn(move || {
+            let mut buf = [0_u8; 8192];
+            loop {
+                match socket.recv(&mut buf) {
+                    Ok(_) => (),
+                    Err(e) if netlink::overflowed(&e) => (),
+                    Err(e) => {
+                        warn!(target: "offline", "unable to receive route changes, polling: {e}");
+                        return;
+                    }
+                }
+                if trigger.stopped() {
+                    return;
+                }
+                trigger.fire();
+            }
+        });
+    }
+}
+
+/// Whether either table has a default route that is up, ignoring those of lo
+fn default_route([route, ipv6_route]: &[PathBuf; 2]) -> io::Result<bool> {
+    if ipv4_default(&std::fs::read_to_string(route)?) {
+        return Ok(true);
+    }
+    // without ipv6, the table is missing
+    match read_optional(ipv6_route)? {
+        Some(table) => Ok(ipv6_default(&table)),
+        None => Ok(false),
+    }
+}
+
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(table) => Ok(Some(table)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn usable(iface: &str, flags: &str) -> bool {
+    let flags = u32::from_str_radix(flags, 16).unwrap_or_default();
+    iface != "lo" && flags & RTF_UP != 0 && flags & RTF_REJECT == 0
+}
+
+/// /proc/net/route, a header then `Iface Destination Gateway Flags RefCnt Use Metric Mask ...`
+/// in hex
+fn ipv4_default(table: &str) -> bool {
+    table.lines().skip(1).any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        matches!(
+            fields[..],
+            [iface, "00000000", _, flags, _, _, _, "00000000", ..] if usable(iface, flags)
+        )
+    })
+}
+
+/// /proc/net/ipv6_route, `destination prefix_length source source_prefix_length next_hop
+/// metric refcount use flags iface` in hex without a header
+fn ipv6_default(table: &str) -> bool {
+    table.lines().any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        matches!(
+            fields[..],
+            [destination, "00", _, _, _, _, _, _, flags, iface]
+                if destination.bytes().all(|b| b == b'0') && usable(iface, flags)
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTE: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+wlan0\t0001A8C0\t00000000\t0001\t0\t0\t600\t00FFFFFF\t0\t0\t0
+";
+
+    const IPV6_ROUTE: &str = "\
+fe800000000000000000000000000000 40 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000002 00000000 00000001    wlan0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000400 00000001 00000000 00000003    wlan0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 00000000000000000000000000000000 ffffffff 00000001 00000000 00200200       lo
+";
+
+    #[test]
+    fn ipv4_default_route() {
+        assert!(ipv4_default(ROUTE));
+        // only the route of the local network
+        let local: String = ROUTE
+            .lines()
+            .filter(|l| !l.contains("0101A8C0"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(!ipv4_default(&local));
+        // down
+        assert!(!ipv4_default(&ROUTE.replace("0003", "0002")));
+    }
+
+    #[test]
+    fn ipv6_default_route() {
+        assert!(ipv6_default(IPV6_ROUTE));
+        // the unreachable route of lo is always there
+        let unreachable: String = IPV6_ROUTE
+            .lines()
+            .filter(|l| !l.contains("00000003"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(!ipv6_default(&unreachable));
+    }
+}
@@ -0,0 +1,181 @@
+//! Kills of the OOM killer since the bar started, from the `oom_kill` counter of /proc/vmstat,
+//! shown as `oom 1` from the first kill
+//!
+//! The counter is in Linux 4.13 and later. Without it the module is left out.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use log::warn;
+
+use super::{read_into, vmstat_field, Module};
+use crate::logging::Throttle;
+use crate::notify;
+use crate::output::Segment;
+use crate::style::Level;
+
+pub const VMSTAT_PATH: &str = "/proc/vmstat";
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Oom {
+    path: PathBuf,
+    /// counter when the bar started
+    baseline: u64,
+    /// counter as last read
+    last: u64,
+    /// when a kill was last seen
+    killed: Option<Instant>,
+    /// hide the indicator this long after the last kill
+    reset_after: Option<Duration>,
+    notify: bool,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl Oom {
+    /// `None` on kernels without the counter
+    pub fn new(path: impl Into<PathBuf>) -> io::Result<Option<Self>> {
+        let path = path.into();
+        let mut buf = String::new();
+        read_into(&path, &mut buf)?;
+        Ok(oom_kill(&buf).map(|baseline| Self {
+            path,
+            baseline,
+            last: baseline,
+            killed: None,
+            reset_after: None,
+            notify: false,
+            throttle: Throttle::default(),
+            buf,
+        }))
+    }
+
+    pub fn reset_after(mut self, reset_after: Option<Duration>) -> Self {
+        self.reset_after = reset_after;
+        self
+    }
+
+    pub fn notify(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// Count the kills since the last read of `counter`
+    fn update(&mut self, counter: u64, now: Instant) {
+        if counter > self.last {
+            self.killed = Some(now);
+            if self.notify {
+                let kills = counter - self.last;
+                let time = Local::now().format("%H:%M:%S");
+                notify::send(format!("OOM killer fired at {time}, {kills} killed"));
+            }
+        }
+        self.last = counter;
+    }
+
+    fn segment(&self, now: Instant) -> Segment {
+        let shown = self.killed.is_some_and(|killed| {
+            self.reset_after
+                .is_none_or(|reset_after| now.duration_since(killed) < reset_after)
+        });
+        if !shown {
+            return Segment::new("oom", "");
+        }
+        let kills = self.last.saturating_sub(self.baseline);
+        Segment::new("oom", format!("oom {kills}"))
+            .level(Level::Critical)
+            .value("kills", kills)
+    }
+}
+
+impl Module for Oom {
+    fn name(&self) -> &'static str {
+        "oom"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let counter = read_into(&self.path, &mut self.buf).and_then(|()| {
+            oom_kill(&self.buf)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no oom_kill"))
+        });
+        match counter {
+            Ok(counter) => {
+                self.throttle.succeeded();
+                let now = Instant::now();
+                self.update(counter, now);
+                Some(self.segment(now))
+            }
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    let path = self.path.display();
+                    warn!(target: "oom", "unable to read {path}: {e}{suppressed}");
+                }
+                None
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+fn oom_kill(vmstat: &str) -> Option<u64> {
+    vmstat_field(vmstat, "oom_kill")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oom(baseline: u64) -> Oom {
+        Oom {
+            path: PathBuf::new(),
+            baseline,
+            last: baseline,
+            killed: None,
+            reset_after: None,
+            notify: false,
+            throttle: Throttle::default(),
+            buf: String::new(),
+        }
+    }
+
+    #[test]
+    fn parse_vmstat() {
+        let vmstat = "pgfault 1234\noom_kill 3\nnuma_hit 5\n";
+        assert_eq!(oom_kill(vmstat), Some(3));
+        // before Linux 4.13
+        assert_eq!(oom_kill("pgfault 1234\nnuma_hit 5\n"), None);
+    }
+
+    #[test]
+    fn sticky_since_start() {
+        let start = Instant::now();
+        let mut oom = oom(5);
+        oom.update(5, start);
+        assert_eq!(oom.segment(start).text, "");
+        oom.update(6, start);
+        assert_eq!(oom.segment(start).text, "oom 1");
+        let later = start + Duration::from_secs(3600);
+        oom.update(6, later);
+        assert_eq!(oom.segment(later).text, "oom 1");
+        assert_eq!(oom.segment(later).level, Level::Critical);
+    }
+
+    #[test]
+    fn reset_after() {
+        let start = Instant::now();
+        let mut oom = oom(0).reset_after(Some(Duration::from_secs(60)));
+        oom.update(2, start);
+        assert_eq!(oom.segment(start).text, "oom 2");
+        let later = start + Duration::from_secs(60);
+        assert_eq!(oom.segment(later).text, "");
+        // the count since the start on the next kill
+        oom.update(3, later);
+        assert_eq!(oom.segment(later).text, "oom 3");
+    }
+}
@@ -0,0 +1,237 @@
+//! Battery of wireless mice and keyboards, from UPower
+
+use std::time::Duration;
+
+use log::warn;
+use zbus::blocking::{proxy::Builder, Connection, MessageIterator, Proxy};
+use zbus::message::Type;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::MatchRule;
+
+use super::{Module, Trigger};
+use crate::glyphs::{Glyph, Glyphs};
+use crate::output::Segment;
+
+const UPOWER: &str = "org.freedesktop.UPower";
+
+/// Interval of querying UPower again without a signal, in case one was missed
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// `Type` property of org.freedesktop.UPower.Device
+const KIND_MOUSE: u32 = 5;
+const KIND_KEYBOARD: u32 = 6;
+
+/// `State` property of org.freedesktop.UPower.Device
+const STATE_UNKNOWN: u32 = 0;
+
+/// Charge of the mice and keyboards, collected again whenever a device is added, removed, or
+/// changes. Disabled if UPower isn't running.
+pub struct Peripherals {
+    glyphs: Glyphs,
+    conn: Option<Connection>,
+    trigger: Option<Trigger>,
+    disabled: bool,
+}
+
+impl Peripherals {
+    pub fn new(glyphs: Glyphs) -> Self {
+        Self {
+            glyphs,
+            conn: None,
+            trigger: None,
+            disabled: false,
+        }
+    }
+
+    fn devices(&mut self) -> zbus::Result<Vec<Device>> {
+        if self.conn.is_none() {
+            self.conn = Some(connect(self.trigger.as_ref())?);
+        }
+        devices(self.conn.as_ref().unwrap())
+    }
+}
+
+impl Module for Peripherals {
+    fn name(&self) -> &'static str {
+        "peripherals"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let devices = match self.devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                warn!("UPower unavailable, disabling: {e}");
+                self.disabled = true;
+                return None;
+            }
+        };
+        Some(match segment(&devices, &self.glyphs) {
+            Some(text) => Segment::new("peripherals", text),
+            None => Segment::hidden("peripherals"),
+        })
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn watch(&mut self, trigger: Trigger) {
+        self.trigger = Some(trigger);
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn disabled(&self) -> bool {
+        self.disabled
+    }
+}
+
+/// Closing the connection ends the thread forwarding its signals
+impl Drop for Peripherals {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            let _ = conn.close();
+        }
+    }
+}
+
+/// Connect to the system bus, firing `trigger` on every signal from UPower: DeviceAdded,
+/// DeviceRemoved, and PropertiesChanged of devices
+fn connect(trigger: Option<&Trigger>) -> zbus::Result<Connection> {
+    let conn = Connection::system()?;
+    if let Some(trigger) = trigger.cloned() {
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender(UPOWER)?
+            .path_namespace("/org/freedesktop/UPower")?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, &conn, Some(16))?;
+        std::thread::spawn(move || {
+            for signal in signals {
+                if signal.is_err() {
+                    return;
+                }
+                trigger.fire();
+            }
+        });
+    }
+    Ok(conn)
+}
+
+#[derive(Debug)]
+struct Device {
+    kind: u32,
+    percentage: f64,
+}
+
+fn proxy<'a>(
+    conn: &'a Connection,
+    path: &'a str,
+    interface: &'static str,
+) -> zbus::Result<Proxy<'a>> {
+    Builder::new(conn)
+        .destination(UPOWER)?
+        .path(path)?
+        .interface(interface)?
+        .cache_properties(CacheProperties::No)
+        .build()
+}
+
+/// Mice and keyboards with a meaningful battery reading
+fn devices(conn: &Connection) -> zbus::Result<Vec<Device>> {
+    let upower = proxy(conn, "/org/freedesktop/UPower", UPOWER)?;
+    let paths: Vec<OwnedObjectPath> = upower.call("EnumerateDevices", &())?;
+
+    let mut devices = vec![];
+    for path in paths {
+        // devices disappear as receivers sleep, skip any that vanished since enumerating
+        let Ok(device) = proxy(conn, path.as_str(), "org.freedesktop.UPower.Device") else {
+            continue;
+        };
+        let Ok(kind) = device.get_property::<u32>("Type") else {
+            continue;
+        };
+        if kind != KIND_MOUSE && kind != KIND_KEYBOARD {
+            continue;
+        }
+        let (Ok(percentage), Ok(state)) = (
+            device.get_property::<f64>("Percentage"),
+            device.get_property::<u32>("State"),
+        ) else {
+            continue;
+        };
+        devices.extend(reading(kind, percentage, state));
+    }
+
+    // mice before keyboards, for a stable order
+    devices.sort_by_key(|d| d.kind);
+    Ok(devices)
+}
+
+/// The device of the `Type`, `Percentage`, and `State` properties, if a mouse or a keyboard
+/// with a meaningful reading
+fn reading(kind: u32, percentage: f64, state: u32) -> Option<Device> {
+    if kind != KIND_MOUSE && kind != KIND_KEYBOARD {
+        return None;
+    }
+    // reported by UPower right after wake, before the device has been read
+    if percentage == 0.0 && state == STATE_UNKNOWN {
+        return None;
+    }
+    Some(Device { kind, percentage })
+}
+
+/// `mouse 80% kbd 45%`, `None` without any device
+fn segment(devices: &[Device], glyphs: &Glyphs) -> Option<String> {
+    if devices.is_empty() {
+        return None;
+    }
+
+    let segment = devices
+        .iter()
+        .map(|device| {
+            let label = glyphs.get(if device.kind == KIND_MOUSE {
+                Glyph::Mouse
+            } else {
+                Glyph::Keyboard
+            });
+            format!("{label} {}%", device.percentage.round())
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyphs::Set;
+
+    /// `State` of a discharging device
+    const STATE_DISCHARGING: u32 = 2;
+
+    #[test]
+    fn readings() {
+        // a headset, and a mouse just after wake
+        assert!(reading(17, 60.0, STATE_DISCHARGING).is_none());
+        assert!(reading(KIND_MOUSE, 0.0, STATE_UNKNOWN).is_none());
+        let mouse = reading(KIND_MOUSE, 79.6, STATE_DISCHARGING).unwrap();
+        assert_eq!((mouse.kind, mouse.percentage), (KIND_MOUSE, 79.6));
+        // an empty battery, read
+        assert!(reading(KIND_KEYBOARD, 0.0, STATE_DISCHARGING).is_some());
+    }
+
+    #[test]
+    fn labels() {
+        let devices = [
+            reading(KIND_MOUSE, 79.6, STATE_DISCHARGING).unwrap(),
+            reading(KIND_KEYBOARD, 45.0, STATE_UNKNOWN).unwrap(),
+        ];
+        let ascii = Glyphs::new(Set::Ascii, vec![]);
+        assert_eq!(segment(&devices, &ascii).unwrap(), "mouse 80% kbd 45%");
+        assert_eq!(segment(&[], &ascii), None);
+    }
+}
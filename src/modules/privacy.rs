@@ -0,0 +1,198 @@
+//! Camera and microphone in-use indicators
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::Module;
+use crate::command::output_timeout;
+use crate::glyphs::{Glyph, Glyphs};
+use crate::output::Segment;
+use crate::paths::Paths;
+
+const INTERVAL: Duration = Duration::from_secs(2);
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+const PROC_PATH: &str = "/proc";
+
+/// Processes that keep video devices open to offer them to clients, without streaming
+const VIDEO_BROKERS: &[&str] = &["pipewire", "wireplumber"];
+
+/// Clients of PipeWire whose streams only probe or relay a device for another client
+const STREAM_BROKERS: &[&str] = &["pipewire", "wireplumber", "xdg-desktop-portal"];
+
+/// `●cam ●mic` while the camera or the microphone is in use, hidden otherwise
+pub struct Privacy {
+    dot: String,
+    procfs: PathBuf,
+}
+
+impl Privacy {
+    pub fn new(glyphs: &Glyphs, paths: &Paths) -> Self {
+        Self {
+            dot: glyphs.get(Glyph::InUse).to_string(),
+            procfs: paths.resolve(PROC_PATH),
+        }
+    }
+}
+
+impl Module for Privacy {
+    fn name(&self) -> &'static str {
+        "privacy"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let dot = &self.dot;
+        let graph = pipewire_graph();
+        let mut indicators = vec![];
+        if camera_in_use(&self.procfs, graph.as_deref()) {
+            indicators.push(format!("{dot}cam"));
+        }
+        let microphone = match &graph {
+            Some(objects) => capturing(objects, "Stream/Input/Audio"),
+            None => pulse_capturing(),
+        };
+        if microphone {
+            indicators.push(format!("{dot}mic"));
+        }
+        if indicators.is_empty() {
+            return Some(Segment::hidden("privacy"));
+        }
+        Some(Segment::new("privacy", indicators.join(" ")))
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
+
+/// Any process of `procfs` with a `/dev/video*` open, other than a broker with no running
+/// stream in `graph`
+fn camera_in_use(procfs: &Path, graph: Option<&[PwObject]>) -> bool {
+    let Ok(procs) = std::fs::read_dir(procfs) else {
+        return false;
+    };
+
+    let mut broker_holds_device = false;
+    for proc in procs.filter_map(Result::ok) {
+        let path = proc.path();
+        if !proc
+            .file_name()
+            .to_string_lossy()
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+        {
+            continue;
+        }
+        if !holds_video_device(&path) {
+            continue;
+        }
+
+        let comm = std::fs::read_to_string(path.join("comm")).unwrap_or_default();
+        if VIDEO_BROKERS.contains(&comm.trim()) {
+            broker_holds_device = true;
+        } else {
+            return true;
+        }
+    }
+
+    broker_holds_device && graph.is_some_and(|objects| capturing(objects, "Stream/Input/Video"))
+}
+
+fn holds_video_device(proc: &Path) -> bool {
+    // processes of other users can't be inspected, and will be skipped
+    let Ok(fds) = std::fs::read_dir(proc.join("fd")) else {
+        return false;
+    };
+    fds.filter_map(Result::ok).any(|fd| {
+        std::fs::read_link(fd.path())
+            .map(|target| target.to_string_lossy().starts_with("/dev/video"))
+            .unwrap_or(false)
+    })
+}
+
+#[derive(Deserialize)]
+struct PwObject {
+    info: Option<PwInfo>,
+}
+
+#[derive(Deserialize)]
+struct PwInfo {
+    state: Option<String>,
+    props: Option<PwProps>,
+}
+
+#[derive(Deserialize)]
+struct PwProps {
+    #[serde(rename = "media.class")]
+    media_class: Option<String>,
+    #[serde(rename = "application.process.binary")]
+    binary: Option<String>,
+}
+
+/// Objects of `pw-dump`, `None` without PipeWire
+fn pipewire_graph() -> Option<Vec<PwObject>> {
+    let output = output_timeout(&mut Command::new("pw-dump"), TIMEOUT).ok()??;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// Whether a client other than a broker has a stream of `media_class`, such as
+/// `Stream/Input/Video`, running
+fn capturing(objects: &[PwObject], media_class: &str) -> bool {
+    objects.iter().filter_map(|o| o.info.as_ref()).any(|info| {
+        let Some(props) = &info.props else {
+            return false;
+        };
+        info.state.as_deref() == Some("running")
+            && props.media_class.as_deref() == Some(media_class)
+            && !props
+                .binary
+                .as_deref()
+                .is_some_and(|binary| STREAM_BROKERS.contains(&binary))
+    })
+}
+
+/// Any capture stream through PulseAudio, without PipeWire
+fn pulse_capturing() -> bool {
+    let Ok(Some(output)) = output_timeout(
+        Command::new("pactl").args(["list", "short", "source-outputs"]),
+        TIMEOUT,
+    ) else {
+        return false;
+    };
+    output.status.success() && output.stdout.iter().any(|b| !b.is_ascii_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(dump: &str) -> Vec<PwObject> {
+        serde_json::from_str(dump).unwrap()
+    }
+
+    #[test]
+    fn streams_of_a_call() {
+        let objects = graph(include_str!("fixtures/pw-dump-call"));
+        assert!(capturing(&objects, "Stream/Input/Video"));
+        assert!(capturing(&objects, "Stream/Input/Audio"));
+    }
+
+    #[test]
+    fn streams_of_brokers_filtered() {
+        // the portal probing the camera, and wireplumber the microphone, while firefox's capture
+        // is paused
+        let objects = graph(include_str!("fixtures/pw-dump-portal"));
+        assert!(!capturing(&objects, "Stream/Input/Video"));
+        assert!(!capturing(&objects, "Stream/Input/Audio"));
+    }
+}
@@ -0,0 +1,210 @@
+//! Filesystems remounted read-only, such as ext4 on a dying disk, from the `ro` option of the
+//! mount points in /proc/self/mounts, as `FS RO: /home`
+//!
+//! Mount points read-only on purpose, such as /usr on some setups or bind mounts, are left out
+//! by the exclude patterns.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::warn;
+
+use super::{read_into, Module};
+use crate::logging::Throttle;
+use crate::notify;
+use crate::output::Segment;
+use crate::pattern::Pattern;
+use crate::style::Level;
+
+pub const MOUNTS_PATH: &str = "/proc/self/mounts";
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct ReadOnly {
+    path: PathBuf,
+    mounts: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    notify: bool,
+    /// mount points read-only at the last sample, notified once until writable again
+    read_only: HashSet<String>,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl ReadOnly {
+    /// Watching the mount points matching `mounts` but not `exclude`
+    pub fn new(path: impl Into<PathBuf>, mounts: Vec<Pattern>, exclude: Vec<Pattern>) -> Self {
+        Self {
+            path: path.into(),
+            mounts,
+            exclude,
+            notify: false,
+            read_only: HashSet::new(),
+            throttle: Throttle::default(),
+            buf: String::new(),
+        }
+    }
+
+    pub fn notify(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    fn watched(&self, mount_point: &str) -> bool {
+        self.mounts
+            .iter()
+            .any(|pattern| pattern.matches(mount_point))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches(mount_point))
+    }
+
+    /// The watched mount points read-only in `mounts`, in its order
+    fn read_only(&self, mounts: &str) -> Vec<String> {
+        let mut read_only: Vec<String> = vec![];
+        for (mount_point, options) in parse(mounts) {
+            let ro = options.split(',').any(|option| option == "ro");
+            // mounted over, the last mount is the one in use
+            read_only.retain(|seen| *seen != mount_point);
+            if ro && self.watched(&mount_point) {
+                read_only.push(mount_point);
+            }
+        }
+        read_only
+    }
+
+    /// Notify the mount points newly read-only
+    fn update(&mut self, read_only: &[String]) {
+        for mount_point in read_only {
+            if self.notify && !self.read_only.contains(mount_point) {
+                notify::send(format!("{mount_point} was remounted read-only"));
+            }
+        }
+        self.read_only = read_only.iter().cloned().collect();
+    }
+}
+
+impl Module for ReadOnly {
+    fn name(&self) -> &'static str {
+        "readonly"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        match read_into(&self.path, &mut self.buf) {
+            Ok(()) => {
+                self.throttle.succeeded();
+                let read_only = self.read_only(&self.buf);
+                self.update(&read_only);
+                Some(segment(&read_only))
+            }
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    let path = self.path.display();
+                    warn!(target: "readonly", "unable to read {path}: {e}{suppressed}");
+                }
+                None
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+/// Mount points of `mounts`, sorted and each once, such as for completing `--readonly-mount`
+pub fn mount_points(mounts: &str) -> Vec<String> {
+    let mut mount_points: Vec<String> = parse(mounts).map(|(mount_point, _)| mount_point).collect();
+    mount_points.sort_unstable();
+    mount_points.dedup();
+    mount_points
+}
+
+/// (mount point, options) of the lines of /proc/self/mounts such as
+/// `/dev/sda2 /home ext4 rw,relatime 0 0`, with the `\040` escapes of spaces decoded
+fn parse(mounts: &str) -> impl Iterator<Item = (String, &str)> {
+    mounts.lines().filter_map(|line| {
+        let mut fields = line.split_ascii_whitespace();
+        let mount_point = unescape(fields.nth(1)?);
+        let options = fields.nth(1)?;
+        Some((mount_point, options))
+    })
+}
+
+/// `\040` and the other octal escapes of the kernel
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        let octal = rest.get(i + 1..i + 4);
+        match octal.and_then(|octal| u8::from_str_radix(octal, 8).ok()) {
+            Some(byte) => {
+                out.push(char::from(byte));
+                rest = &rest[i + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `FS RO: /, /home`, empty while every one is writable
+fn segment(read_only: &[String]) -> Segment {
+    if read_only.is_empty() {
+        return Segment::new("readonly", "");
+    }
+    Segment::new("readonly", format!("FS RO: {}", read_only.join(", "))).level(Level::Critical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/sda1 / ext4 rw,relatime 0 0
+/dev/sda2 /home ext4 ro,relatime,errors=remount-ro 0 0
+/dev/sda3 /usr ext4 ro,relatime 0 0
+/dev/sdb1 /mnt/my\\040disk vfat ro,relatime 0 0
+";
+
+    fn patterns(patterns: &[&str]) -> Vec<Pattern> {
+        patterns.iter().map(|p| Pattern::new(p).unwrap()).collect()
+    }
+
+    #[test]
+    fn remounted_read_only() {
+        let module = ReadOnly::new("", patterns(&["/", "/home"]), vec![]);
+        let read_only = module.read_only(MOUNTS);
+        assert_eq!(read_only, ["/home"]);
+        assert_eq!(segment(&read_only).text, "FS RO: /home");
+        assert_eq!(segment(&read_only).level, Level::Critical);
+        assert_eq!(segment(&[]).text, "");
+    }
+
+    #[test]
+    fn excluded() {
+        let module = ReadOnly::new("", patterns(&["*"]), patterns(&["/usr", "/mnt/*"]));
+        assert_eq!(module.read_only(MOUNTS), ["/home"]);
+        let module = ReadOnly::new("", patterns(&["/mnt/*"]), vec![]);
+        assert_eq!(module.read_only(MOUNTS), ["/mnt/my disk"]);
+    }
+
+    #[test]
+    fn mounted_over() {
+        let module = ReadOnly::new("", patterns(&["/home"]), vec![]);
+        let remounted = format!("{MOUNTS}/dev/sdc1 /home ext4 rw,relatime 0 0\n");
+        assert!(module.read_only(&remounted).is_empty());
+        assert_eq!(
+            mount_points(&remounted),
+            ["/", "/home", "/mnt/my disk", "/proc", "/usr"]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Description of the default audio output, from PulseAudio or PipeWire's pulse server
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{Module, Trigger};
+use crate::command::output_timeout;
+use crate::output::Segment;
+use crate::signal;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delay before reconnecting after the sound server went away, and interval of collecting
+/// without an event
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Short names for common parts of sink descriptions, checked in order
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("HDMI", "HDMI"),
+    ("DisplayPort", "DP"),
+    ("Headphones", "Headphones"),
+    ("Headset", "Headset"),
+    ("Speaker", "Speakers"),
+    ("Digital Stereo", "Digital"),
+    ("Analog Stereo", "Analog"),
+];
+
+/// clap value parser for `--sink-alias name=alias`
+pub fn parse_alias(s: &str) -> Result<(String, String), String> {
+    let (name, alias) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("invalid sink alias `{s}`, expected name=alias"))?;
+    Ok((name.to_string(), alias.to_string()))
+}
+
+/// Facilities of `pactl subscribe` whose events may change the default sink or its description
+const FACILITIES: &[&str] = &["sink", "server"];
+
+/// Default sink, collected again on the events of `pactl subscribe`. Hidden while no sound
+/// server is available.
+pub struct Sink {
+    aliases: Vec<(String, String)>,
+    /// `pactl subscribe` of the watcher, killed once dropped
+    subscribe: Arc<Mutex<Option<Child>>>,
+}
+
+impl Sink {
+    pub fn new(aliases: Vec<(String, String)>) -> Self {
+        Self {
+            aliases,
+            subscribe: Arc::default(),
+        }
+    }
+}
+
+impl Module for Sink {
+    fn name(&self) -> &'static str {
+        "sink"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        Some(match default_sink(&self.aliases) {
+            Some(name) => Segment::new("sink", format!("out: {name}")),
+            None => Segment::hidden("sink"),
+        })
+    }
+
+    fn interval(&self) -> Duration {
+        RETRY_INTERVAL
+    }
+
+    fn watch(&mut self, trigger: Trigger) {
+        let child = Arc::clone(&self.subscribe);
+        std::thread::spawn(move || subscribe(&trigger, &child));
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
+
+/// Ending the watcher along with the module, as on a reload
+impl Drop for Sink {
+    fn drop(&mut self) {
+        kill(&self.subscribe);
+    }
+}
+
+/// Fire `trigger` on the events of `pactl subscribe` kept in `child`, subscribing again after
+/// the sound server went away. Events while paused are left out, resuming collecting anyway.
+fn subscribe(trigger: &Trigger, child: &Mutex<Option<Child>>) {
+    loop {
+        let subscribe = pactl()
+            .arg("subscribe")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+
+        if let Ok(mut subscribe) = subscribe {
+            let stdout = subscribe.stdout.take().unwrap();
+            *child.lock().unwrap() = Some(subscribe);
+            // the module dropped before it was kept
+            if trigger.stopped() {
+                kill(child);
+                return;
+            }
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if trigger.stopped() {
+                    break;
+                }
+                if relevant(&line) && !signal::paused() {
+                    trigger.fire();
+                }
+            }
+            kill(child);
+        }
+
+        if trigger.stopped() {
+            return;
+        }
+        trigger.fire();
+        if !signal::idle(RETRY_INTERVAL) {
+            return;
+        }
+    }
+}
+
+/// Kill and reap the `pactl subscribe` kept in `child`, if still running
+fn kill(child: &Mutex<Option<Child>>) {
+    if let Some(mut child) = child.lock().unwrap().take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Whether an event of `pactl subscribe` may change the default sink: a sink added, changed, or
+/// removed, or the server changed, such as `Event 'change' on server #-1` when the default sink
+/// changes. Those of sink-input streams are left out.
+fn relevant(event: &str) -> bool {
+    let Some(event) = event.strip_prefix("Event '") else {
+        return false;
+    };
+    let Some((kind, rest)) = event.split_once("' on ") else {
+        return false;
+    };
+    let facility = rest.split_once(" #").map_or(rest, |(facility, _)| facility);
+    matches!(kind, "new" | "change" | "remove") && FACILITIES.contains(&facility)
+}
+
+fn pactl() -> Command {
+    let mut command = Command::new("pactl");
+    // descriptions are parsed from the untranslated output
+    command.env("LC_ALL", "C");
+    command
+}
+
+/// Alias, abbreviation, or description of the default sink
+fn default_sink(aliases: &[(String, String)]) -> Option<String> {
+    let name = output_timeout(pactl().arg("get-default-sink"), TIMEOUT).ok()??;
+    if !name.status.success() {
+        return None;
+    }
+    let name = String::from_utf8_lossy(&name.stdout).trim().to_string();
+
+    if let Some((_, alias)) = aliases.iter().find(|(n, _)| *n == name) {
+        return Some(alias.clone());
+    }
+
+    let sinks = output_timeout(pactl().args(["list", "sinks"]), TIMEOUT).ok()??;
+    Some(label(&String::from_utf8_lossy(&sinks.stdout), &name))
+}
+
+/// Abbreviation or description of the sink called `name` in `pactl list sinks`, its name
+/// without a description
+fn label(sinks: &str, name: &str) -> String {
+    let description = description(sinks, name).unwrap_or(name);
+    ABBREVIATIONS
+        .iter()
+        .find(|(long, _)| description.contains(long))
+        .map_or(description, |(_, short)| short)
+        .to_string()
+}
+
+/// `Description:` of the sink called `name` in `pactl list sinks`
+fn description<'a>(sinks: &'a str, name: &str) -> Option<&'a str> {
+    let mut in_sink = false;
+    for line in sinks.lines().map(str::trim) {
+        if let Some(n) = line.strip_prefix("Name: ") {
+            in_sink = n == name;
+        } else if in_sink {
+            if let Some(description) = line.strip_prefix("Description: ") {
+                return Some(description);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SINKS: &str = include_str!("fixtures/pactl-list-sinks");
+
+    #[test]
+    fn descriptions() {
+        let analog = "alsa_output.pci-0000_00_1f.3.analog-stereo";
+        assert_eq!(
+            description(SINKS, analog),
+            Some("Built-in Audio Analog Stereo")
+        );
+        assert_eq!(
+            description(SINKS, "bluez_output.AC_80_0A_2E_81_6C.1"),
+            Some("WH-1000XM4")
+        );
+        assert_eq!(description(SINKS, "alsa_output.usb"), None);
+    }
+
+    #[test]
+    fn abbreviated() {
+        let label = |name| label(SINKS, name);
+        assert_eq!(
+            label("alsa_output.pci-0000_00_1f.3.analog-stereo"),
+            "Analog"
+        );
+        assert_eq!(label("alsa_output.pci-0000_01_00.1.hdmi-stereo"), "HDMI");
+        assert_eq!(label("bluez_output.AC_80_0A_2E_81_6C.1"), "WH-1000XM4");
+        // gone since listed
+        assert_eq!(label("alsa_output.usb"), "alsa_output.usb");
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!(
+            parse_alias("bluez_output.AC_80_0A_2E_81_6C.1=headphones"),
+            Ok((
+                "bluez_output.AC_80_0A_2E_81_6C.1".to_string(),
+                "headphones".to_string()
+            ))
+        );
+        assert!(parse_alias("headphones").is_err());
+    }
+
+    #[test]
+    fn relevant_events() {
+        assert!(relevant("Event 'change' on server #-1"));
+        assert!(relevant("Event 'new' on sink #71"));
+        assert!(relevant("Event 'remove' on sink #71"));
+        assert!(relevant("Event 'change' on sink #52"));
+        assert!(!relevant("Event 'change' on sink-input #83"));
+        assert!(!relevant("Event 'new' on source-output #90"));
+        assert!(!relevant("Event 'change' on client #65"));
+        assert!(!relevant("Connection failure: Connection refused"));
+    }
+}
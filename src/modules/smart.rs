@@ -0,0 +1,315 @@
+//! Overall SMART health of the disks, from `smartctl -H -j`, as `SMART FAIL: nvme0n1`
+//!
+//! smartctl needs root to open the disks. Without it, such as without a sudoers or polkit rule,
+//! the first check fails with a hint and the module stops instead of retrying.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use log::warn;
+use serde::Deserialize;
+
+use super::Module;
+use crate::command::output_timeout;
+use crate::notify;
+use crate::output::Segment;
+use crate::style::Level;
+
+/// Such as /sys/class/block/sda2, the partitions of the disks
+pub const SYS_BLOCK_PATH: &str = "/sys/class/block";
+
+/// Upper bound on a `smartctl` run, a disk spinning up may take a while
+const SMARTCTL_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+struct Report {
+    smart_status: Option<Status>,
+    #[serde(default)]
+    smartctl: Smartctl,
+}
+
+#[derive(Debug, Deserialize)]
+struct Status {
+    passed: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Smartctl {
+    #[serde(default)]
+    messages: Vec<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    string: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Health {
+    Passed,
+    Failed,
+    /// no SMART status, such as a USB bridge without passthrough
+    Unknown(String),
+    /// smartctl can't open the disk without root
+    PermissionDenied,
+}
+
+/// Disks of `devices`, such as /dev/sda, and those holding the mount points of `mounts`, from
+/// `mounts_path` such as /proc/self/mounts. The partitions of `sys_block` are replaced by their
+/// disk, deduplicated.
+pub fn devices(
+    devices: &[PathBuf],
+    mounts: &[PathBuf],
+    mounts_path: &Path,
+    sys_block: &Path,
+) -> Vec<PathBuf> {
+    let mut disks: Vec<PathBuf> = vec![];
+    let table = std::fs::read_to_string(mounts_path).unwrap_or_else(|e| {
+        if !mounts.is_empty() {
+            warn!(target: "smart", "unable to read {}: {e}", mounts_path.display());
+        }
+        String::new()
+    });
+    let sources = mounts.iter().filter_map(|mount| {
+        // the last mount over the mount point is the one in use
+        let source = table.lines().rev().find_map(|line| {
+            let mut fields = line.split_ascii_whitespace();
+            let source = fields.next()?;
+            (Path::new(fields.next()?) == mount).then_some(source)
+        });
+        if source.is_none() {
+            warn!(target: "smart", "{} isn't mounted", mount.display());
+        }
+        source.filter(|source| source.starts_with("/dev/"))
+    });
+    for device in devices.iter().cloned().chain(sources.map(PathBuf::from)) {
+        let disk = disk(&device, sys_block);
+        if !disks.contains(&disk) {
+            disks.push(disk);
+        }
+    }
+    disks
+}
+
+/// The disk of the partition `device`, such as /dev/nvme0n1 of /dev/nvme0n1p2, from the parent
+/// of its directory in `sys_block`. Anything else, such as a disk, as is.
+fn disk(device: &Path, sys_block: &Path) -> PathBuf {
+    let device = std::fs::canonicalize(device).unwrap_or_else(|_| device.to_path_buf());
+    let Some(name) = device.file_name() else {
+        return device;
+    };
+    let block = sys_block.join(name);
+    if !block.join("partition").exists() {
+        return device;
+    }
+    std::fs::canonicalize(&block)
+        .ok()
+        .and_then(|block| Some(device.with_file_name(block.parent()?.file_name()?)))
+        .unwrap_or(device)
+}
+
+/// The disks of `devices` failing their SMART check every `interval`, hidden while every one
+/// passes
+pub struct Smart {
+    devices: Vec<PathBuf>,
+    interval: Duration,
+    notify: bool,
+    failed: Vec<String>,
+    /// devices without a SMART status, warned about once
+    unknown: Vec<PathBuf>,
+    disabled: bool,
+}
+
+impl Smart {
+    pub fn new(devices: Vec<PathBuf>, interval: Duration) -> Self {
+        Self {
+            devices,
+            interval,
+            notify: false,
+            failed: vec![],
+            unknown: vec![],
+            disabled: false,
+        }
+    }
+
+    /// Send a desktop notification when a disk starts failing
+    pub fn notify(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+}
+
+impl Module for Smart {
+    fn name(&self) -> &'static str {
+        "smart"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let mut failing = vec![];
+        for device in &self.devices {
+            let name = device.file_name().unwrap_or_default().to_string_lossy();
+            match health(device) {
+                Ok(Health::Passed) => (),
+                Ok(Health::Failed) => failing.push(name.into_owned()),
+                Ok(Health::Unknown(reason)) => {
+                    if !self.unknown.contains(device) {
+                        warn!(target: "smart", "no SMART status for {}: {reason}", device.display());
+                        self.unknown.push(device.clone());
+                    }
+                }
+                Ok(Health::PermissionDenied) => {
+                    warn!(
+                        target: "smart",
+                        "smartctl can't open {} without root, disabling. Allow it without a \
+                         password with a sudoers rule or polkit, or grant the user access to the \
+                         disk.",
+                        device.display()
+                    );
+                    self.disabled = true;
+                    return None;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    warn!(target: "smart", "smartctl isn't installed, disabling");
+                    self.disabled = true;
+                    return None;
+                }
+                Err(e) => warn!(target: "smart", "smartctl -H {}: {e}", device.display()),
+            }
+        }
+
+        if self.notify {
+            for name in failing.iter().filter(|name| !self.failed.contains(name)) {
+                notify::send(format!("SMART health check of {name} failed"));
+            }
+        }
+        self.failed = failing;
+        Some(segment(&self.failed))
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        // the devices are checked one after the other, each taking as long as its timeout
+        let devices = u32::try_from(self.devices.len()).unwrap_or(u32::MAX);
+        Some(self.interval + SMARTCTL_TIMEOUT.saturating_mul(devices))
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn disabled(&self) -> bool {
+        self.disabled
+    }
+}
+
+/// Health of `device`, an error if smartctl didn't run
+fn health(device: &Path) -> io::Result<Health> {
+    let output = output_timeout(
+        Command::new("smartctl").arg("-H").arg("-j").arg(device),
+        SMARTCTL_TIMEOUT,
+    )?
+    .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "timed out"))?;
+    // nonzero on a failing disk too, the JSON tells
+    let report: Report = serde_json::from_slice(&output.stdout)?;
+    Ok(parse(report))
+}
+
+fn parse(report: Report) -> Health {
+    match report.smart_status {
+        Some(Status { passed: true }) => Health::Passed,
+        Some(Status { passed: false }) => Health::Failed,
+        None => {
+            let messages = report.smartctl.messages;
+            if messages
+                .iter()
+                .any(|message| message.string.contains("Permission denied"))
+            {
+                Health::PermissionDenied
+            } else {
+                let reason = messages.into_iter().map(|message| message.string);
+                Health::Unknown(reason.collect::<Vec<_>>().join(", "))
+            }
+        }
+    }
+}
+
+/// `SMART FAIL: nvme0n1, sda`, empty while every disk passes
+fn segment(failed: &[String]) -> Segment {
+    if failed.is_empty() {
+        return Segment::new("smart", "");
+    }
+    Segment::new("smart", format!("SMART FAIL: {}", failed.join(", "))).level(Level::Critical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(json: &str) -> Health {
+        parse(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn smartctl_json() {
+        let passed = r#"{"smartctl":{"exit_status":0},"smart_status":{"passed":true}}"#;
+        assert_eq!(health(passed), Health::Passed);
+        let failed = r#"{"smartctl":{"exit_status":8},"smart_status":{"passed":false}}"#;
+        assert_eq!(health(failed), Health::Failed);
+        let denied = r#"{"smartctl":{"exit_status":2,"messages":[
+            {"string":"Smartctl open device: /dev/sda failed: Permission denied","severity":"error"}
+        ]}}"#;
+        assert_eq!(health(denied), Health::PermissionDenied);
+        let usb = r#"{"smartctl":{"exit_status":1,"messages":[
+            {"string":"/dev/sdb: Unknown USB bridge","severity":"error"}
+        ]}}"#;
+        assert_eq!(
+            health(usb),
+            Health::Unknown("/dev/sdb: Unknown USB bridge".into())
+        );
+    }
+
+    #[test]
+    fn failed_segment() {
+        assert_eq!(segment(&[]).text, "");
+        let failed = segment(&["nvme0n1".into()]);
+        assert_eq!(failed.text, "SMART FAIL: nvme0n1");
+        assert_eq!(failed.level, Level::Critical);
+    }
+
+    #[test]
+    fn disks_of_mount_points() {
+        let dir = std::env::temp_dir().join(format!("statusbar-smart-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let devices = dir.join("devices");
+        let sys_block = dir.join("block");
+        std::fs::create_dir_all(devices.join("nvme0n1/nvme0n1p2")).unwrap();
+        std::fs::write(devices.join("nvme0n1/nvme0n1p2/partition"), "2").unwrap();
+        std::fs::create_dir_all(&sys_block).unwrap();
+        for block in ["nvme0n1", "nvme0n1/nvme0n1p2"] {
+            let name = Path::new(block).file_name().unwrap();
+            std::os::unix::fs::symlink(devices.join(block), sys_block.join(name)).unwrap();
+        }
+        let mounts = dir.join("mounts");
+        std::fs::write(
+            &mounts,
+            "/dev/nvme0n1p2 / ext4 rw 0 0\n\
+             tmpfs /tmp tmpfs rw 0 0\n\
+             /dev/nvme0n1p2 /home ext4 rw 0 0\n",
+        )
+        .unwrap();
+
+        let found = super::devices(
+            &["/dev/sda".into()],
+            &["/".into(), "/home".into(), "/tmp".into()],
+            &mounts,
+            &sys_block,
+        );
+        assert_eq!(found, [PathBuf::from("/dev/sda"), "/dev/nvme0n1".into()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
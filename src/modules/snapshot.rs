@@ -0,0 +1,260 @@
+//! Age of the newest filesystem snapshot, from snapper's snapshot list or the newest directory
+//! of the timeshift snapshots
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use clap::ValueEnum;
+use log::{debug, warn};
+use serde::Deserialize;
+
+use super::Module;
+use crate::command::output_timeout;
+use crate::duration;
+use crate::output::Segment;
+use crate::style::Level;
+
+/// How often the timeshift directory is listed, cheap enough to not need configuring
+const TIMESHIFT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bound on a `snapper list` run
+const SNAPPER_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// `snapper list` of `--snapshot-config`
+    Snapper,
+    /// the snapshot directories in `--snapshot-path`
+    Timeshift,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    /// local time such as `2024-01-01 12:00:00`, empty for the current system
+    date: String,
+}
+
+/// Age of the newest snapshot, marked with `!` beyond `warn_age`, collected again as its
+/// display changes. Without snapper installed, disabled.
+pub struct Snapshots {
+    backend: Backend,
+    /// snapper config
+    config: String,
+    /// directory of the timeshift snapshots
+    path: PathBuf,
+    /// interval of checking the backend
+    interval: Duration,
+    warn_age: Duration,
+    newest: Option<DateTime<Local>>,
+    checked: Option<Instant>,
+    /// last successful check, the age of the segment
+    fetched: Option<SystemTime>,
+    failing: bool,
+    missing: bool,
+}
+
+impl Snapshots {
+    /// Listing snapper every `snapper_interval`, or the timeshift directory every minute
+    pub fn new(
+        backend: Backend,
+        config: String,
+        path: PathBuf,
+        snapper_interval: Duration,
+        warn_age: Duration,
+    ) -> Self {
+        let interval = match backend {
+            Backend::Snapper => snapper_interval,
+            Backend::Timeshift => TIMESHIFT_INTERVAL,
+        };
+        Self {
+            backend,
+            config,
+            path,
+            interval,
+            warn_age,
+            newest: None,
+            checked: None,
+            fetched: None,
+            failing: false,
+            missing: false,
+        }
+    }
+
+    fn age(&self) -> Option<Duration> {
+        self.newest
+            .map(|time| (Local::now() - time).to_std().unwrap_or_default())
+    }
+}
+
+impl Module for Snapshots {
+    fn name(&self) -> &'static str {
+        "snapshot"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        if self
+            .checked
+            .is_none_or(|checked| checked.elapsed() >= self.interval)
+        {
+            self.checked = Some(Instant::now());
+            let found = match self.backend {
+                Backend::Snapper => snapper_newest(&self.config),
+                Backend::Timeshift => timeshift_newest(&self.path),
+            };
+            // a failed run keeps the previous time, showing stale data instead of dropping the
+            // segment
+            match found {
+                Ok(found) => {
+                    self.fetched = Some(SystemTime::now());
+                    self.failing = false;
+                    self.newest = found;
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::NotFound && self.backend == Backend::Snapper =>
+                {
+                    debug!(target: "snapshot", "snapper isn't installed, disabling");
+                    self.missing = true;
+                    return None;
+                }
+                Err(e) => {
+                    if !self.failing {
+                        warn!(target: "snapshot", "unable to find the newest snapshot: {e}");
+                        self.failing = true;
+                    }
+                }
+            }
+        }
+        let mut segment = segment(self.age(), self.warn_age);
+        segment.fetched = self.fetched;
+        Some(segment)
+    }
+
+    /// Until the next check, or the displayed age changing
+    fn interval(&self) -> Duration {
+        let checked = self.checked.map_or(self.interval, |c| c.elapsed());
+        let mut interval = self.interval.saturating_sub(checked);
+        if let Some(age) = self.age() {
+            interval = interval.min(duration::until_next_change(age));
+        }
+        interval
+    }
+
+    fn reset(&mut self) {
+        self.checked = None;
+    }
+
+    /// Of snapper, the timeshift directory being cheap to list. Fetched on its last successful
+    /// run, rather than as the age it shows changes.
+    fn cache_ttl(&self) -> Option<Duration> {
+        (self.backend == Backend::Snapper).then_some(self.interval + SNAPPER_TIMEOUT)
+    }
+
+    fn blocking(&self) -> bool {
+        self.backend == Backend::Snapper
+    }
+
+    fn disabled(&self) -> bool {
+        self.missing
+    }
+}
+
+/// `snap 2h`, marked as a warning beyond `warn_age`
+fn segment(age: Option<Duration>, warn_age: Duration) -> Segment {
+    let Some(age) = age else {
+        return Segment::new("snapshot", "");
+    };
+    let (mark, level) = if age > warn_age {
+        ("!", Level::Warning)
+    } else {
+        ("", Level::Normal)
+    };
+    Segment::new(
+        "snapshot",
+        format!("snap {}{mark}", duration::format_age(age)),
+    )
+    .level(level)
+}
+
+/// Time of the newest snapshot of the snapper `config`, `Ok(None)` without any
+fn snapper_newest(config: &str) -> io::Result<Option<DateTime<Local>>> {
+    let output = output_timeout(
+        Command::new("snapper").args(["--jsonout", "-c", config, "list"]),
+        SNAPPER_TIMEOUT,
+    )?
+    .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "timed out"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().next().unwrap_or_default().to_string();
+        return Err(io::Error::other(message));
+    }
+    parse_snapper(&output.stdout, config)
+}
+
+/// The newest date of `{"root": [{"number": 1, "date": "2024-01-01 12:00:00"}]}`
+fn parse_snapper(json: &[u8], config: &str) -> io::Result<Option<DateTime<Local>>> {
+    let mut configs: HashMap<String, Vec<Snapshot>> = serde_json::from_slice(json)?;
+    let snapshots = configs.remove(config).unwrap_or_default();
+    Ok(snapshots
+        .iter()
+        .filter_map(|s| NaiveDateTime::parse_from_str(&s.date, "%Y-%m-%d %H:%M:%S").ok())
+        .filter_map(|date| Local.from_local_datetime(&date).earliest())
+        .max())
+}
+
+/// Modification time of the newest directory of `path`, `Ok(None)` without any
+fn timeshift_newest(path: &Path) -> io::Result<Option<DateTime<Local>>> {
+    let mut newest = None;
+    for entry in std::fs::read_dir(path)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_dir() {
+            let modified = DateTime::<Local>::from(metadata.modified()?);
+            newest = newest.max(Some(modified));
+        }
+    }
+    Ok(newest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapper_json() {
+        let json = br#"{"root": [
+            {"number": 0, "date": "", "description": "current"},
+            {"number": 41, "date": "2024-01-01 10:00:00", "description": "timeline"},
+            {"number": 42, "date": "2024-01-01 11:00:00", "description": "timeline"}
+        ], "home": [{"number": 7, "date": "2024-02-01 00:00:00"}]}"#;
+        let newest = parse_snapper(json, "root").unwrap().unwrap();
+        assert_eq!(newest.naive_local().to_string(), "2024-01-01 11:00:00");
+        assert_eq!(parse_snapper(json, "srv").unwrap(), None);
+    }
+
+    #[test]
+    fn age() {
+        let warn = Duration::from_secs(24 * 3600);
+        assert_eq!(
+            segment(Some(Duration::from_secs(7200)), warn).text,
+            "snap 2h"
+        );
+        let old = segment(Some(Duration::from_secs(2 * 24 * 3600)), warn);
+        assert_eq!((old.text.as_str(), old.level), ("snap 2d!", Level::Warning));
+        assert_eq!(segment(None, warn).text, "");
+    }
+
+    #[test]
+    fn newest_directory() {
+        let dir = std::env::temp_dir().join(format!("statusbar-snapshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(timeshift_newest(&dir).is_err());
+        std::fs::create_dir_all(dir.join("2024-01-01_12-00-01")).unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        assert!(timeshift_newest(&dir).unwrap().is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
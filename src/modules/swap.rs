@@ -0,0 +1,155 @@
+//! Swap activity, the pages swapped in and out per second from the `pswpin` and `pswpout`
+//! counters of /proc/vmstat, as `swp in:12.0M/s out:4.0M/s`
+//!
+//! The percentage of swap used doesn't tell thrashing apart from pages swapped out once and
+//! left there, the rates do.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use super::{read_into, vmstat_field, Module};
+use crate::logging::Throttle;
+use crate::output::Segment;
+use crate::throughput;
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Page size without sysconf
+const PAGE_SIZE: u64 = 4096;
+
+pub struct Swap {
+    path: PathBuf,
+    /// show `swp 0` without activity, instead of hiding
+    idle: bool,
+    /// bytes of a page
+    page_size: u64,
+    /// time and (pswpin, pswpout) of the previous sample
+    last: Option<(Instant, u64, u64)>,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl Swap {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        Self {
+            path: path.into(),
+            idle: false,
+            page_size: u64::try_from(page_size)
+                .ok()
+                .filter(|size| *size > 0)
+                .unwrap_or(PAGE_SIZE),
+            last: None,
+            throttle: Throttle::default(),
+            buf: String::new(),
+        }
+    }
+
+    pub fn idle(mut self, idle: bool) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// (in, out) bytes per second since the previous sample, `None` on the first
+    fn rates(&mut self, now: Instant, pswpin: u64, pswpout: u64) -> Option<(f64, f64)> {
+        let (last_time, last_in, last_out) = self.last.replace((now, pswpin, pswpout))?;
+        let secs = now.duration_since(last_time).as_secs_f64();
+        if secs == 0.0 {
+            return None;
+        }
+        // the counters only go back on a reset, such as a checkpoint restore
+        let rate =
+            |pages: u64, last: u64| (pages.saturating_sub(last) * self.page_size) as f64 / secs;
+        Some((rate(pswpin, last_in), rate(pswpout, last_out)))
+    }
+}
+
+impl Module for Swap {
+    fn name(&self) -> &'static str {
+        "swap"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let counters = read_into(&self.path, &mut self.buf).and_then(|()| {
+            let field = |name| vmstat_field(&self.buf, name);
+            field("pswpin")
+                .zip(field("pswpout"))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no pswpin or pswpout"))
+        });
+        match counters {
+            Ok((pswpin, pswpout)) => {
+                self.throttle.succeeded();
+                let rates = self.rates(Instant::now(), pswpin, pswpout)?;
+                Some(segment(rates, self.idle))
+            }
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    let path = self.path.display();
+                    warn!(target: "swap", "unable to read {path}: {e}{suppressed}");
+                }
+                None
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// `swp in:12.0M/s out:0B/s`, empty or `swp 0` with `idle` when neither is swapping
+fn segment((swap_in, swap_out): (f64, f64), idle: bool) -> Segment {
+    let text = if swap_in == 0.0 && swap_out == 0.0 {
+        if idle { "swp 0" } else { "" }.to_string()
+    } else {
+        format!(
+            "swp in:{} out:{}",
+            throughput::format(swap_in),
+            throughput::format(swap_out)
+        )
+    };
+    Segment::new("swap", text)
+        .value("in_bytes_per_second", swap_in)
+        .value("out_bytes_per_second", swap_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_in_bytes() {
+        let start = Instant::now();
+        let mut swap = Swap::new("");
+        swap.page_size = 4096;
+        assert_eq!(swap.rates(start, 100, 100), None);
+        let rates = swap.rates(start + Duration::from_secs(2), 100 + 3072, 100 + 1024);
+        assert_eq!(rates, Some((6.0 * 1024.0 * 1024.0, 2.0 * 1024.0 * 1024.0)));
+        assert_eq!(
+            segment(rates.unwrap(), false).text,
+            "swp in:6.0M/s out:2.0M/s"
+        );
+    }
+
+    #[test]
+    fn never_negative() {
+        let start = Instant::now();
+        let mut swap = Swap::new("");
+        swap.rates(start, 1000, 1000);
+        let rates = swap.rates(start + Duration::from_secs(1), 10, 1000);
+        assert_eq!(rates, Some((0.0, 0.0)));
+    }
+
+    #[test]
+    fn idle() {
+        assert_eq!(segment((0.0, 0.0), false).text, "");
+        assert_eq!(segment((0.0, 0.0), true).text, "swp 0");
+    }
+}
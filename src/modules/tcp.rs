@@ -0,0 +1,168 @@
+//! Established TCP connections and listening sockets, as `tcp 42/12`, from /proc/net/tcp and
+//! /proc/net/tcp6
+//!
+//! The tables hold a line per socket, so on a busy server they're read on a slower interval than
+//! the other modules, into a buffer kept between samples.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::warn;
+
+use super::{read_into, Module};
+use crate::logging::Throttle;
+use crate::output::Segment;
+
+pub const TCP_PATH: &str = "/proc/net/tcp";
+pub const TCP6_PATH: &str = "/proc/net/tcp6";
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// `st` of a socket, from include/net/tcp_states.h
+const ESTABLISHED: &str = "01";
+const LISTEN: &str = "0A";
+
+pub struct Tcp {
+    tcp: PathBuf,
+    tcp6: PathBuf,
+    /// only the connections to this remote port, and the sockets listening on it
+    port: Option<u16>,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl Tcp {
+    pub fn new(tcp: impl Into<PathBuf>, tcp6: impl Into<PathBuf>) -> Self {
+        Self {
+            tcp: tcp.into(),
+            tcp6: tcp6.into(),
+            port: None,
+            throttle: Throttle::default(),
+            buf: String::new(),
+        }
+    }
+
+    pub fn port(mut self, port: Option<u16>) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Established and listening of both tables, tcp6 missing without ipv6
+    fn count(&mut self) -> io::Result<(u64, u64)> {
+        read_into(&self.tcp, &mut self.buf)?;
+        let (mut established, mut listening) = count(&self.buf, self.port);
+        match read_into(&self.tcp6, &mut self.buf) {
+            Ok(()) => {
+                let (established6, listening6) = count(&self.buf, self.port);
+                established += established6;
+                listening += listening6;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        Ok((established, listening))
+    }
+}
+
+impl Module for Tcp {
+    fn name(&self) -> &'static str {
+        "tcp"
+    }
+
+    /// Keeping the last displayed on failure
+    fn collect(&mut self) -> Option<Segment> {
+        match self.count() {
+            Ok((established, listening)) => {
+                self.throttle.succeeded();
+                Some(
+                    Segment::new("tcp", format!("tcp {established}/{listening}"))
+                        .value("established", established)
+                        .value("listening", listening),
+                )
+            }
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    let path = self.tcp.display();
+                    warn!(target: "tcp", "unable to read {path}: {e}{suppressed}");
+                }
+                None
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+/// Port of an `address:port` of the table, in hex
+fn port(address: &str) -> Option<u16> {
+    let (_, port) = address.rsplit_once(':')?;
+    u16::from_str_radix(port, 16).ok()
+}
+
+/// Established and listening sockets of a table, `sl local_address rem_address st ...` after a
+/// header. With a port to `filter` by, only those connected to it or listening on it.
+fn count(table: &str, filter: Option<u16>) -> (u64, u64) {
+    let (mut established, mut listening) = (0, 0);
+    for line in table.lines().skip(1) {
+        let mut fields = line.split_ascii_whitespace().skip(1);
+        let (Some(local), Some(remote), Some(state)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        match state {
+            ESTABLISHED if filter.is_none() || port(remote) == filter => established += 1,
+            LISTEN if filter.is_none() || port(local) == filter => listening += 1,
+            _ => (),
+        }
+    }
+    (established, listening)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:BC8F 00000000:0000 0A 00000000:00000000 00:00000000 00000000 65534        0 945 1 000000006d0ad3aa 100 0 0 10 0
+   1: 00000000:01BB 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 662 1 00000000c3ffefea 100 0 0 10 0
+   2: 0100007F:B470 0100007F:BC8F 01 00000000:00000000 02:0000084A 00000000     0        0 138236 2 00000000873e0df0 20 4 0 34 8
+   3: 0A00020F:C5D2 5DB8D822:01BB 01 00000000:00000000 02:00000A3B 00000000  1000        0 140012 2 0000000000000000 20 4 30 10 -1
+   4: 0A00020F:C5D4 5DB8D822:01BB 06 00000000:00000000 03:00000A3B 00000000     0        0 0 3 0000000000000000
+";
+
+    const TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000000000000:0016 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1201 1 0000000000000000 100 0 0 10 0
+   1: 0000000000000000FFFF00000A00020F:C5E0 0000000000000000FFFF00005DB8D822:01BB 01 00000000:00000000 02:00000A3B 00000000  1000        0 140311 2 0000000000000000 20 4 30 10 -1
+";
+
+    #[test]
+    fn established_and_listening() {
+        // the socket in TIME_WAIT left out
+        assert_eq!(count(TCP, None), (2, 2));
+        assert_eq!(count(TCP6, None), (1, 1));
+        assert_eq!(count("", None), (0, 0));
+    }
+
+    #[test]
+    fn filtered_by_port() {
+        assert_eq!(count(TCP, Some(443)), (1, 1));
+        assert_eq!(count(TCP6, Some(443)), (1, 0));
+        assert_eq!(count(TCP, Some(22)), (0, 0));
+    }
+
+    #[test]
+    fn without_ipv6() {
+        let dir = std::env::temp_dir().join(format!("statusbar-tcp-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tcp"), TCP).unwrap();
+        let segment = Tcp::new(dir.join("tcp"), dir.join("tcp6"))
+            .collect()
+            .unwrap();
+        assert_eq!(segment.text, "tcp 2/2");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,447 @@
+//! Temperatures of labeled hwmon channels and thermal zones, such as `cpu 62° gpu 71°` of
+//! `--temp k10temp/temp1:cpu --temp amdgpu/temp2:gpu`
+//!
+//! Chips are found by name, their hwmon index changing as the drivers load, and thermal zones by
+//! type for the same reason, a zone given by index being pinned to its type at startup. A
+//! channel failing to read is looked up again, such as after its driver was reloaded.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::warn;
+
+use super::Module;
+use crate::glyphs::{Glyph, Glyphs};
+use crate::logging::Throttle;
+use crate::output::Segment;
+use crate::paths::Paths;
+use crate::sensors::{self, Chip, Zone};
+
+const INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where a temperature is read from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// a channel of `--temp`, such as temp1 of the hwmon chip k10temp
+    Hwmon { chip: String, channel: String },
+    /// a thermal zone of `--thermal-zone`, by type such as acpitz, or by index until pinned
+    Zone(String),
+}
+
+/// A labeled temperature of `--temp` or `--thermal-zone`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensor {
+    pub source: Source,
+    /// shown before the temperature, the chip name or zone type without, empty for a zone by
+    /// index until pinned
+    pub label: String,
+}
+
+/// The hwmon chips and thermal zones sensors are looked up in, and the zone types excluded
+struct Available<'a> {
+    chips: Vec<Chip>,
+    zones: Vec<Zone>,
+    excluded: &'a [String],
+}
+
+impl<'a> Available<'a> {
+    fn new(paths: &Paths, excluded: &'a [String]) -> Self {
+        Self {
+            chips: sensors::chips(paths),
+            zones: sensors::zones(paths),
+            excluded,
+        }
+    }
+
+    /// The zones not excluded
+    fn zones(&self) -> impl Iterator<Item = &Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| !self.excluded.contains(&zone.kind))
+    }
+}
+
+/// `label` of `sensor:label`
+fn split_label(s: &str) -> (&str, Option<&str>) {
+    match s.split_once(':') {
+        Some((sensor, label)) => (sensor, Some(label)),
+        None => (s, None),
+    }
+}
+
+impl Sensor {
+    /// clap value parser of `chip/channel[:label]`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (sensor, label) = split_label(s);
+        let (chip, channel) = sensor
+            .split_once('/')
+            .filter(|(chip, channel)| !chip.is_empty() && channel.starts_with("temp"))
+            .ok_or_else(|| format!("invalid sensor `{s}`, expected e.g. k10temp/temp1:cpu"))?;
+        Ok(Self {
+            source: Source::Hwmon {
+                chip: chip.to_string(),
+                channel: channel.to_string(),
+            },
+            label: label.unwrap_or(chip).to_string(),
+        })
+    }
+
+    /// clap value parser of `type-or-index[:label]`, such as `x86_pkg_temp:cpu` or `3`
+    pub fn parse_zone(s: &str) -> Result<Self, String> {
+        let (zone, label) = split_label(s);
+        let zone = zone.strip_prefix("thermal_zone").unwrap_or(zone);
+        if zone.is_empty() || zone.contains('/') {
+            return Err(format!(
+                "invalid thermal zone `{s}`, expected a type such as x86_pkg_temp:cpu"
+            ));
+        }
+        let index = zone.bytes().all(|b| b.is_ascii_digit());
+        let label = label.map_or(if index { "" } else { zone }, |label| label);
+        Ok(Self {
+            source: Source::Zone(zone.to_string()),
+            label: label.to_string(),
+        })
+    }
+
+    /// A zone by index as the zone of its type from now on, as indices change across boots
+    fn pin(&mut self, available: &Available) -> Result<(), String> {
+        let Source::Zone(zone) = &self.source else {
+            return Ok(());
+        };
+        let Ok(index) = zone.parse::<u32>() else {
+            return Ok(());
+        };
+        let zone = available
+            .zones
+            .iter()
+            .find(|zone| sensors::zone_index(&zone.name) == Some(index))
+            .ok_or_else(|| format!("no thermal zone {index}, found: {}", found_zones(available)))?;
+        if available.excluded.contains(&zone.kind) {
+            return Err(format!(
+                "thermal zone {index} is of type `{}`, which is excluded",
+                zone.kind
+            ));
+        }
+        let first = available.zones().find(|other| other.kind == zone.kind);
+        if first.is_some_and(|first| first.name != zone.name) {
+            warn!(
+                target: "temp",
+                "thermal zone {index} shares its type `{}`, reading the first of the type",
+                zone.kind
+            );
+        }
+        if self.label.is_empty() {
+            self.label.clone_from(&zone.kind);
+        }
+        self.source = Source::Zone(zone.kind.clone());
+        Ok(())
+    }
+
+    /// The `_input` file of the channel of the first chip of the name, or the `temp` file of the
+    /// first zone of the type not excluded
+    fn resolve(&self, available: &Available) -> Result<PathBuf, String> {
+        match &self.source {
+            Source::Hwmon { chip, channel } => resolve_channel(chip, channel, &available.chips),
+            Source::Zone(kind) => {
+                if available.excluded.contains(kind) {
+                    return Err(format!("thermal zone type `{kind}` is excluded"));
+                }
+                available
+                    .zones()
+                    .find(|zone| zone.kind == *kind)
+                    .map(|zone| zone.dir.join("temp"))
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown thermal zone type `{kind}`, found: {}",
+                            found_zones(available)
+                        )
+                    })
+            }
+        }
+    }
+}
+
+/// `acpitz (thermal_zone0), x86_pkg_temp (thermal_zone2)`, of the zones not excluded
+fn found_zones(available: &Available) -> String {
+    let found: Vec<String> = available
+        .zones()
+        .map(|zone| format!("{} ({})", zone.kind, zone.name))
+        .collect();
+    if found.is_empty() {
+        "none".to_string()
+    } else {
+        found.join(", ")
+    }
+}
+
+/// The `_input` file of `channel` of the first chip named `name`
+fn resolve_channel(name: &str, channel: &str, chips: &[Chip]) -> Result<PathBuf, String> {
+    let Some(chip) = chips.iter().find(|chip| chip.name == name) else {
+        let found: Vec<String> = chips
+            .iter()
+            .map(|chip| format!("{} ({})", chip.name, chip.temps.join(", ")))
+            .collect();
+        let found = if found.is_empty() {
+            "none".to_string()
+        } else {
+            found.join(", ")
+        };
+        return Err(format!("unknown hwmon chip `{name}`, found: {found}"));
+    };
+    if !chip.temps.iter().any(|temp| temp == channel) {
+        return Err(format!(
+            "hwmon chip `{name}` has no {channel}, expected one of: {}",
+            chip.temps.join(", ")
+        ));
+    }
+    Ok(chip.dir.join(format!("{channel}_input")))
+}
+
+pub struct Temp {
+    /// the sensors and their input files
+    sensors: Vec<(Sensor, PathBuf)>,
+    /// types of thermal zones never read, such as one of a dead sensor
+    excluded: Vec<String>,
+    paths: Paths,
+    glyphs: Glyphs,
+    throttle: Throttle,
+}
+
+impl Temp {
+    /// Looking up every sensor, failing on the first missing, the zones of `excluded` types
+    /// left out
+    pub fn new(
+        sensors: Vec<Sensor>,
+        excluded: Vec<String>,
+        paths: &Paths,
+        glyphs: Glyphs,
+    ) -> Result<Self, String> {
+        let available = Available::new(paths, &excluded);
+        let sensors = sensors
+            .into_iter()
+            .map(|mut sensor| {
+                sensor.pin(&available)?;
+                let input = sensor.resolve(&available)?;
+                Ok((sensor, input))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            sensors,
+            excluded,
+            paths: paths.clone(),
+            glyphs,
+            throttle: Throttle::default(),
+        })
+    }
+}
+
+impl Module for Temp {
+    fn name(&self) -> &'static str {
+        "temp"
+    }
+
+    /// Leaving out the sensors failing, keeping the last displayed when all fail
+    fn collect(&mut self) -> Option<Segment> {
+        let mut available = None;
+        let mut readings = vec![];
+        let count = self.sensors.len();
+        for (sensor, input) in &mut self.sensors {
+            let mut celsius = read_celsius(input);
+            if celsius.is_err() {
+                let available =
+                    available.get_or_insert_with(|| Available::new(&self.paths, &self.excluded));
+                if let Ok(resolved) = sensor.resolve(available) {
+                    *input = resolved;
+                    celsius = read_celsius(input);
+                }
+            }
+            match celsius {
+                Ok(celsius) => readings.push((sensor.label.as_str(), celsius)),
+                Err(e) => {
+                    if let Some(suppressed) = self.throttle.failed() {
+                        let input = input.display();
+                        warn!(target: "temp", "unable to read {input}: {e}{suppressed}");
+                    }
+                }
+            }
+        }
+        if readings.len() == count {
+            self.throttle.succeeded();
+        }
+        if readings.is_empty() {
+            return None;
+        }
+        Some(segment(&readings, &self.glyphs))
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+/// Degrees Celsius of an `_input` or thermal zone `temp` file, in millidegrees
+fn read_celsius(input: &Path) -> std::io::Result<f64> {
+    let millidegrees: i64 = std::fs::read_to_string(input)?
+        .trim()
+        .parse()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok(millidegrees as f64 / 1000.0)
+}
+
+/// `cpu 62° gpu 71°`, with the hottest as the percentage for `--warning temp=80`
+fn segment(readings: &[(&str, f64)], glyphs: &Glyphs) -> Segment {
+    let degree = glyphs.get(Glyph::Degree);
+    let text: Vec<String> = readings
+        .iter()
+        .map(|(label, celsius)| format!("{label} {celsius:.0}{degree}"))
+        .collect();
+    let hottest = readings
+        .iter()
+        .map(|(_, celsius)| *celsius)
+        .fold(f64::MIN, f64::max);
+    readings
+        .iter()
+        .fold(
+            Segment::new("temp", text.join(" ")),
+            |segment, (label, celsius)| segment.value(*label, celsius),
+        )
+        .percentage(hottest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyphs::Set;
+
+    const EXCLUDED: &[String] = &[];
+
+    fn available(excluded: &[String]) -> Available<'_> {
+        let zone = |index: u32, kind: &str| Zone {
+            name: format!("thermal_zone{index}"),
+            kind: kind.into(),
+            dir: format!("/sys/class/thermal/thermal_zone{index}").into(),
+        };
+        Available {
+            chips: chips(),
+            zones: vec![
+                zone(0, "acpitz"),
+                zone(1, "acpitz"),
+                zone(2, "x86_pkg_temp"),
+            ],
+            excluded,
+        }
+    }
+
+    fn chips() -> Vec<Chip> {
+        vec![
+            Chip {
+                name: "k10temp".into(),
+                dir: "/sys/class/hwmon/hwmon1".into(),
+                temps: vec!["temp1".into(), "temp3".into()],
+            },
+            Chip {
+                name: "amdgpu".into(),
+                dir: "/sys/class/hwmon/hwmon4".into(),
+                temps: vec!["temp1".into(), "temp2".into()],
+            },
+        ]
+    }
+
+    #[test]
+    fn parse_sensor() {
+        assert_eq!(
+            Sensor::parse("amdgpu/temp2:gpu"),
+            Ok(Sensor {
+                source: Source::Hwmon {
+                    chip: "amdgpu".into(),
+                    channel: "temp2".into(),
+                },
+                label: "gpu".into(),
+            })
+        );
+        assert_eq!(Sensor::parse("k10temp/temp1").unwrap().label, "k10temp");
+        assert!(Sensor::parse("k10temp").is_err());
+        assert!(Sensor::parse("k10temp/fan1:cpu").is_err());
+    }
+
+    #[test]
+    fn resolve_by_name() {
+        let gpu = Sensor::parse("amdgpu/temp2:gpu").unwrap();
+        assert_eq!(
+            gpu.resolve(&available(EXCLUDED)),
+            Ok(PathBuf::from("/sys/class/hwmon/hwmon4/temp2_input"))
+        );
+        let unknown = Sensor::parse("nct6775/temp1").unwrap();
+        assert_eq!(
+            unknown.resolve(&available(EXCLUDED)),
+            Err("unknown hwmon chip `nct6775`, found: k10temp (temp1, temp3), amdgpu (temp1, temp2)"
+                .into())
+        );
+        let channel = Sensor::parse("k10temp/temp2").unwrap();
+        assert_eq!(
+            channel.resolve(&available(EXCLUDED)),
+            Err("hwmon chip `k10temp` has no temp2, expected one of: temp1, temp3".into())
+        );
+    }
+
+    #[test]
+    fn zones_by_type() {
+        let zone = |s| Sensor::parse_zone(s).unwrap();
+        assert_eq!(
+            zone("x86_pkg_temp:cpu").source,
+            Source::Zone("x86_pkg_temp".into())
+        );
+        assert_eq!(zone("acpitz").label, "acpitz");
+        assert_eq!(zone("thermal_zone2").source, Source::Zone("2".into()));
+        assert!(Sensor::parse_zone("k10temp/temp1").is_err());
+
+        assert_eq!(
+            zone("acpitz").resolve(&available(EXCLUDED)),
+            Ok(PathBuf::from("/sys/class/thermal/thermal_zone0/temp"))
+        );
+        let mut pinned = zone("2");
+        pinned.pin(&available(EXCLUDED)).unwrap();
+        assert_eq!(pinned, zone("x86_pkg_temp"));
+        assert_eq!(
+            zone("iwlwifi_1").resolve(&available(EXCLUDED)),
+            Err(
+                "unknown thermal zone type `iwlwifi_1`, found: acpitz (thermal_zone0), \
+                 acpitz (thermal_zone1), x86_pkg_temp (thermal_zone2)"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn excluded_zones() {
+        let excluded = ["acpitz".to_string()];
+        assert_eq!(
+            Sensor::parse_zone("acpitz")
+                .unwrap()
+                .resolve(&available(&excluded)),
+            Err("thermal zone type `acpitz` is excluded".into())
+        );
+        let mut dead = Sensor::parse_zone("1").unwrap();
+        assert_eq!(
+            dead.pin(&available(&excluded)),
+            Err("thermal zone 1 is of type `acpitz`, which is excluded".into())
+        );
+        let unknown = Sensor::parse_zone("iwlwifi_1").unwrap();
+        assert_eq!(
+            unknown.resolve(&available(&excluded)),
+            Err(
+                "unknown thermal zone type `iwlwifi_1`, found: x86_pkg_temp (thermal_zone2)".into()
+            )
+        );
+    }
+
+    #[test]
+    fn labeled_temperatures() {
+        let labeled = segment(&[("cpu", 62.4), ("gpu", 71.0)], &Glyphs::default());
+        assert_eq!(labeled.text, "cpu 62° gpu 71°");
+        assert_eq!(labeled.percentage, Some(71.0));
+        assert_eq!(labeled.get::<f64>("cpu"), Some(62.4));
+        let ascii = Glyphs::new(Set::Ascii, vec![]);
+        assert_eq!(segment(&[("cpu", 62.4)], &ascii).text, "cpu 62deg");
+    }
+}
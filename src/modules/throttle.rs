@@ -0,0 +1,252 @@
+//! Thermal throttling of the CPU, shown as `THROTTLE` while throttling and for a quiet period
+//! after
+//!
+//! Intel CPUs count the throttling events in the `core_throttle_count` and
+//! `package_throttle_count` files of /sys/devices/system/cpu/cpu*/thermal_throttle. Elsewhere
+//! the CPU is taken as throttled while its frequency stays well below its maximum under load.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::Module;
+use crate::output::Segment;
+use crate::style::Level;
+
+pub const SYS_CPU_PATH: &str = "/sys/devices/system/cpu";
+pub const PROC_STAT_PATH: &str = "/proc/stat";
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Fraction of the maximum frequency under which a loaded CPU is throttled
+const PINNED_FREQUENCY: f64 = 0.6;
+
+/// Usage from which the CPU is loaded, in percent
+const LOADED_USAGE: f64 = 80.0;
+
+/// Samples in a row pinned under load before flagging, not a dip
+const PINNED_SAMPLES: u32 = 5;
+
+enum Source {
+    /// the thermal_throttle counters of the CPUs
+    Counters(Vec<PathBuf>),
+    /// the cpufreq directories of the CPUs, and /proc/stat
+    Frequency {
+        cpufreq: Vec<PathBuf>,
+        stat: PathBuf,
+    },
+}
+
+pub struct Throttle {
+    source: Source,
+    /// keep showing the marker this long after the last throttling
+    quiet: Duration,
+    /// sum of the counters, or the (busy, total) jiffies of /proc/stat, as last read
+    last: Option<(u64, u64)>,
+    /// samples in a row pinned under load
+    pinned: u32,
+    throttled: Option<Instant>,
+}
+
+impl Throttle {
+    /// From the counters of `sys_cpu`, such as /sys/devices/system/cpu, or its frequencies
+    /// and `stat` without. `None` without either.
+    pub fn new(sys_cpu: &Path, stat: PathBuf, quiet: Duration) -> Option<Self> {
+        let cpus = cpus(sys_cpu);
+        let counters: Vec<PathBuf> = cpus
+            .iter()
+            .map(|cpu| cpu.join("thermal_throttle"))
+            .filter(|dir| dir.is_dir())
+            .flat_map(|dir| {
+                ["core_throttle_count", "package_throttle_count"].map(|name| dir.join(name))
+            })
+            .collect();
+        let source = if !counters.is_empty() {
+            Source::Counters(counters)
+        } else {
+            let cpufreq: Vec<PathBuf> = cpus
+                .iter()
+                .map(|cpu| cpu.join("cpufreq"))
+                .filter(|dir| dir.join("scaling_max_freq").exists())
+                .collect();
+            if cpufreq.is_empty() {
+                return None;
+            }
+            Source::Frequency { cpufreq, stat }
+        };
+        Some(Self {
+            source,
+            quiet,
+            last: None,
+            pinned: 0,
+            throttled: None,
+        })
+    }
+
+    /// Whether throttling since the previous sample
+    fn sample(&mut self) -> bool {
+        match &self.source {
+            Source::Counters(counters) => {
+                let events = counters.iter().filter_map(|path| read_u64(path)).sum();
+                let last = self.last.replace((events, 0));
+                last.is_some_and(|(last, _)| events > last)
+            }
+            Source::Frequency { cpufreq, stat } => {
+                let Some((busy, total)) = std::fs::read_to_string(stat)
+                    .ok()
+                    .and_then(|stat| jiffies(&stat))
+                else {
+                    return false;
+                };
+                let usage = self
+                    .last
+                    .replace((busy, total))
+                    .and_then(|(last_busy, last_total)| {
+                        let total = total.checked_sub(last_total).filter(|total| *total > 0)?;
+                        Some(busy.saturating_sub(last_busy) as f64 * 100.0 / total as f64)
+                    });
+                let pinned = usage.is_some_and(|usage| usage >= LOADED_USAGE)
+                    && frequency_ratio(cpufreq).is_some_and(|ratio| ratio < PINNED_FREQUENCY);
+                self.pinned = if pinned { self.pinned + 1 } else { 0 };
+                self.pinned >= PINNED_SAMPLES
+            }
+        }
+    }
+
+    /// The marker while throttling, and for `quiet` after
+    fn segment(&mut self, throttling: bool, now: Instant) -> Segment {
+        if throttling {
+            self.throttled = Some(now);
+        }
+        let shown = self
+            .throttled
+            .is_some_and(|throttled| now.duration_since(throttled) < self.quiet);
+        if shown {
+            Segment::new("throttle", "THROTTLE").level(Level::Warning)
+        } else {
+            Segment::new("throttle", "")
+        }
+    }
+}
+
+impl Module for Throttle {
+    fn name(&self) -> &'static str {
+        "throttle"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let throttling = self.sample();
+        Some(self.segment(throttling, Instant::now()))
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+        self.pinned = 0;
+    }
+}
+
+/// The cpu0, cpu1… directories of `sys_cpu`
+fn cpus(sys_cpu: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(sys_cpu) else {
+        return vec![];
+    };
+    let mut cpus: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.strip_prefix("cpu")
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    cpus.sort_unstable();
+    cpus
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Average ratio of the current to the maximum frequency of the CPUs
+fn frequency_ratio(cpufreq: &[PathBuf]) -> Option<f64> {
+    let ratios: Vec<f64> = cpufreq
+        .iter()
+        .filter_map(|dir| {
+            let current = read_u64(&dir.join("scaling_cur_freq"))?;
+            let max = read_u64(&dir.join("scaling_max_freq")).filter(|max| *max > 0)?;
+            Some(current as f64 / max as f64)
+        })
+        .collect();
+    (!ratios.is_empty()).then(|| ratios.iter().sum::<f64>() / ratios.len() as f64)
+}
+
+/// (busy, total) jiffies of the `cpu` line of /proc/stat, idle and iowait not busy
+fn jiffies(stat: &str) -> Option<(u64, u64)> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let fields: Vec<u64> = line
+        .split_ascii_whitespace()
+        .skip(1)
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    let total = fields.iter().take(8).sum();
+    let idle = fields.get(3)? + fields.get(4).unwrap_or(&0);
+    Some((total - idle, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(source: Source) -> Throttle {
+        Throttle {
+            source,
+            quiet: Duration::from_secs(30),
+            last: None,
+            pinned: 0,
+            throttled: None,
+        }
+    }
+
+    #[test]
+    fn stat_jiffies() {
+        let stat = "cpu  100 0 50 800 50 0 0 0 0 0\ncpu0 50 0 25 400 25 0 0 0 0 0\n";
+        assert_eq!(jiffies(stat), Some((150, 1000)));
+    }
+
+    #[test]
+    fn counters_incrementing() {
+        let dir = std::env::temp_dir().join(format!("statusbar-throttle-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let counters = dir.join("cpu0/thermal_throttle");
+        std::fs::create_dir_all(&counters).unwrap();
+        std::fs::write(counters.join("core_throttle_count"), "3\n").unwrap();
+        std::fs::write(counters.join("package_throttle_count"), "1\n").unwrap();
+
+        let mut throttle = Throttle::new(&dir, PathBuf::new(), Duration::from_secs(30)).unwrap();
+        assert!(!throttle.sample());
+        assert!(!throttle.sample());
+        std::fs::write(counters.join("core_throttle_count"), "4\n").unwrap();
+        assert!(throttle.sample());
+        assert!(!throttle.sample());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let missing = Path::new("/nonexistent");
+        assert!(Throttle::new(missing, PathBuf::new(), Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn quiet_period() {
+        let start = Instant::now();
+        let mut throttle = throttle(Source::Counters(vec![]));
+        assert_eq!(throttle.segment(false, start).text, "");
+        assert_eq!(throttle.segment(true, start).text, "THROTTLE");
+        let quiet = start + Duration::from_secs(29);
+        assert_eq!(throttle.segment(false, quiet).text, "THROTTLE");
+        let later = start + Duration::from_secs(30);
+        assert_eq!(throttle.segment(false, later).text, "");
+    }
+}
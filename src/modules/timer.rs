@@ -0,0 +1,237 @@
+//! Time until systemd timers next elapse, such as `backup in 3h`, from the system manager or the
+//! user manager, whichever has the unit
+//!
+//! A timer that is no longer active is marked as a warning, as a backup not scheduled anymore is
+//! the point of showing it. Timers on only monotonic schedules, such as OnBootSec, have no next
+//! realtime elapse, so it's computed from the monotonic one.
+
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::warn;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedObjectPath;
+
+use super::Module;
+use crate::duration;
+use crate::output::Segment;
+use crate::style::Level;
+
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Next elapse of a timer, in microseconds since the epoch or since boot
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Elapse {
+    realtime: u64,
+    monotonic: u64,
+}
+
+#[derive(Debug, PartialEq)]
+enum State {
+    /// time until the next elapse
+    Scheduled(Duration),
+    /// not active, or without a next elapse
+    Inactive,
+}
+
+/// Time until each of `units` next elapses, collected again as it changes. Units found on
+/// neither manager are logged once and left out.
+pub struct Timers {
+    units: Vec<String>,
+    /// connections to the system and the session bus, once connected
+    managers: Option<[Option<Connection>; 2]>,
+    missing: HashSet<String>,
+    /// until the soonest displayed time changes
+    next_change: Duration,
+}
+
+impl Timers {
+    /// `units` such as `backup` or `backup.timer`
+    pub fn new(units: Vec<String>) -> Self {
+        let units = units
+            .into_iter()
+            .map(|unit| {
+                if unit.contains('.') {
+                    unit
+                } else {
+                    format!("{unit}.timer")
+                }
+            })
+            .collect();
+        Self {
+            units,
+            managers: None,
+            missing: HashSet::new(),
+            next_change: INTERVAL,
+        }
+    }
+}
+
+impl Module for Timers {
+    fn name(&self) -> &'static str {
+        "timer"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let managers = self
+            .managers
+            .get_or_insert_with(|| [Connection::system().ok(), Connection::session().ok()]);
+        let mut timers = vec![];
+        for unit in &self.units {
+            let state = managers
+                .iter()
+                .flatten()
+                .find_map(|conn| timer(conn, unit).ok());
+            match state {
+                Some(state) => {
+                    self.missing.remove(unit);
+                    timers.push((label(unit), state));
+                }
+                None => {
+                    if self.missing.insert(unit.clone()) {
+                        warn!(target: "timer", "{unit} not found, hiding it");
+                    }
+                }
+            }
+        }
+
+        self.next_change = timers
+            .iter()
+            .filter_map(|(_, state)| match state {
+                State::Scheduled(remaining) => Some(duration::until_countdown_change(*remaining)),
+                State::Inactive => None,
+            })
+            .fold(INTERVAL, Duration::min);
+        Some(segment(&timers))
+    }
+
+    /// Until the next check, or the soonest displayed time changing
+    fn interval(&self) -> Duration {
+        self.next_change
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
+
+/// State of the timer `unit` on the manager at the other end of `conn`, failing without the unit
+fn timer(conn: &Connection, unit: &str) -> zbus::Result<State> {
+    let manager = Proxy::new(
+        conn,
+        "org.freedesktop.systemd1",
+        "/org/freedesktop/systemd1",
+        "org.freedesktop.systemd1.Manager",
+    )?;
+    let path: OwnedObjectPath = manager.call("GetUnit", &(unit,))?;
+    let properties = |interface| Proxy::new(conn, "org.freedesktop.systemd1", &path, interface);
+    let active: String =
+        properties("org.freedesktop.systemd1.Unit")?.get_property("ActiveState")?;
+    if active != "active" {
+        return Ok(State::Inactive);
+    }
+    let timer = properties("org.freedesktop.systemd1.Timer")?;
+    let elapse = Elapse {
+        realtime: timer.get_property("NextElapseUSecRealtime")?,
+        monotonic: timer.get_property("NextElapseUSecMonotonic")?,
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(remaining(elapse, now, monotonic_now()).map_or(State::Inactive, State::Scheduled))
+}
+
+/// CLOCK_MONOTONIC, of the monotonic elapse of timers
+fn monotonic_now() -> Duration {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: plain syscall writing into now
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    Duration::new(now.tv_sec as u64, now.tv_nsec as u32)
+}
+
+/// Time until `elapse`, from the realtime one, or from the monotonic one and the boot time
+/// without. `None` without either, such as a timer that won't elapse again.
+fn remaining(elapse: Elapse, realtime_now: Duration, monotonic_now: Duration) -> Option<Duration> {
+    let unset = |usec: u64| usec == 0 || usec == u64::MAX;
+    if !unset(elapse.realtime) {
+        return Some(Duration::from_micros(elapse.realtime).saturating_sub(realtime_now));
+    }
+    if !unset(elapse.monotonic) {
+        let boot = realtime_now.saturating_sub(monotonic_now);
+        let next = boot + Duration::from_micros(elapse.monotonic);
+        return Some(next.saturating_sub(realtime_now));
+    }
+    None
+}
+
+/// `backup` of backup.timer
+fn label(unit: &str) -> &str {
+    unit.strip_suffix(".timer").unwrap_or(unit)
+}
+
+/// `backup in 3h`, one after another, marked as a warning with an inactive timer
+fn segment(timers: &[(&str, State)]) -> Segment {
+    let mut level = Level::Normal;
+    let text = timers
+        .iter()
+        .map(|(label, state)| match state {
+            State::Scheduled(remaining) if remaining.is_zero() => format!("{label} now"),
+            State::Scheduled(remaining) => {
+                format!("{label} in {}", duration::format_age(*remaining))
+            }
+            State::Inactive => {
+                level = Level::Warning;
+                format!("{label} inactive!")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    Segment::new("timer", text).level(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn realtime_or_monotonic() {
+        let now = Duration::from_secs(1_760_000_000);
+        let uptime = 2 * HOUR;
+        let realtime = Elapse {
+            realtime: (now + 3 * HOUR).as_micros() as u64,
+            monotonic: 0,
+        };
+        assert_eq!(remaining(realtime, now, uptime), Some(3 * HOUR));
+        // OnBootSec=5h, two hours after boot
+        let monotonic = Elapse {
+            realtime: 0,
+            monotonic: (5 * HOUR).as_micros() as u64,
+        };
+        assert_eq!(remaining(monotonic, now, uptime), Some(3 * HOUR));
+        let never = Elapse {
+            realtime: 0,
+            monotonic: u64::MAX,
+        };
+        assert_eq!(remaining(never, now, uptime), None);
+    }
+
+    #[test]
+    fn inactive_marked() {
+        let timers = [
+            (
+                "backup",
+                State::Scheduled(3 * HOUR + Duration::from_secs(59)),
+            ),
+            ("fstrim", State::Inactive),
+        ];
+        let segment = segment(&timers);
+        assert_eq!(segment.text, "backup in 3h fstrim inactive!");
+        assert_eq!(segment.level, Level::Warning);
+        assert_eq!(label("backup.timer"), "backup");
+    }
+}
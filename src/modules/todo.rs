@@ -0,0 +1,259 @@
+//! todo.txt pending task counter
+//!
+//! The parent directory of the todo file is watched with inotify instead of the file itself:
+//! editors commonly save by writing a temp file and renaming it over the original, which
+//! invalidates a watch placed on the old inode.
+
+use std::ffi::{CString, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::warn;
+
+use super::{Module, Trigger};
+use crate::output::Segment;
+
+/// Safety net re-stat of the file, in case inotify is unavailable or missed an event
+const RESTAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Count pending tasks: non-empty lines that aren't completed (`x `), optionally only those
+/// containing `filter`
+pub fn count(contents: &str, filter: Option<&str>) -> usize {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("x "))
+        .filter(|line| filter.is_none_or(|f| line.contains(f)))
+        .count()
+}
+
+/// Pending count of the todo file at `path`, collected again as soon as it changes. No pending
+/// task hides the segment, an unreadable file leaves it without data.
+pub struct Todo {
+    path: PathBuf,
+    filter: Option<String>,
+    /// stamp of the file last read, and its segment
+    last: Option<(Option<(SystemTime, u64)>, Segment)>,
+    warned: bool,
+}
+
+impl Todo {
+    pub fn new(path: PathBuf, filter: Option<String>) -> Self {
+        Self {
+            path,
+            filter,
+            last: None,
+            warned: false,
+        }
+    }
+}
+
+impl Module for Todo {
+    fn name(&self) -> &'static str {
+        "todo"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let stamp = stamp(&self.path);
+        if let Some((last, segment)) = &self.last {
+            if *last == stamp && stamp.is_some() {
+                return Some(segment.clone());
+            }
+        }
+
+        let segment = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => {
+                self.warned = false;
+                match count(&contents, self.filter.as_deref()) {
+                    0 => Segment::hidden("todo"),
+                    pending => Segment::new("todo", format!("todo {pending}")),
+                }
+            }
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound && !self.warned {
+                    warn!("unable to read {}: {e}", self.path.display());
+                    self.warned = true;
+                }
+                Segment::new("todo", "")
+            }
+        };
+        self.last = Some((stamp, segment.clone()));
+        Some(segment)
+    }
+
+    fn interval(&self) -> Duration {
+        RESTAT_INTERVAL
+    }
+
+    /// Fire `trigger` on the changes of the file, through its parent directory
+    fn watch(&mut self, trigger: Trigger) {
+        let dir = match self.path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file_name = self
+            .path
+            .file_name()
+            .map(OsStr::to_os_string)
+            .unwrap_or_default();
+        let Some(inotify) = Inotify::new(&dir) else {
+            warn!("unable to watch {}, falling back to polling", dir.display());
+            return;
+        };
+        std::thread::spawn(move || {
+            while !trigger.stopped() {
+                if inotify.wait(&file_name, RESTAT_INTERVAL) {
+                    trigger.fire();
+                }
+            }
+        });
+    }
+}
+
+/// (mtime, len) of the file, used to detect changes without re-reading
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+struct Inotify {
+    fd: libc::c_int,
+}
+
+impl Inotify {
+    fn new(dir: &Path) -> Option<Self> {
+        let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+        // SAFETY: plain syscalls, fd is owned by the returned struct
+        unsafe {
+            let fd = libc::inotify_init1(libc::IN_CLOEXEC);
+            if fd < 0 {
+                return None;
+            }
+            let mask = libc::IN_CLOSE_WRITE
+                | libc::IN_MODIFY
+                | libc::IN_CREATE
+                | libc::IN_DELETE
+                | libc::IN_MOVED_FROM
+                | libc::IN_MOVED_TO
+                | libc::IN_ATTRIB;
+            if libc::inotify_add_watch(fd, dir.as_ptr(), mask) < 0 {
+                libc::close(fd);
+                return None;
+            }
+            Some(Self { fd })
+        }
+    }
+
+    /// Block until an event for `file_name` arrives, or `timeout` elapses, however many events
+    /// of other files arrive meanwhile. Returns whether the file had an event.
+    fn wait(&self, file_name: &OsStr, timeout: Duration) -> bool {
+        let mut buf = [0_u8; 4096];
+        let mut pollfd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // rounded up, not to return just before the deadline
+            let left = deadline.saturating_duration_since(Instant::now());
+            let left = left.as_nanos().div_ceil(1_000_000);
+            let left = left.try_into().unwrap_or(libc::c_int::MAX);
+            // SAFETY: pollfd is valid for the duration of the call
+            let ready = unsafe { libc::poll(&mut pollfd, 1, left) };
+            if ready <= 0 {
+                return false;
+            }
+
+            // SAFETY: buf is valid for buf.len() bytes
+            let n = unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 {
+                return false;
+            }
+
+            if event_names(&buf[..n as usize]).any(|name| name == file_name.as_bytes()) {
+                return true;
+            }
+        }
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        // SAFETY: fd was opened by inotify_init1 and is only closed here
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Iterate over the names in a buffer of `struct inotify_event`s
+fn event_names(mut buf: &[u8]) -> impl Iterator<Item = &[u8]> {
+    const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+
+    std::iter::from_fn(move || {
+        if buf.len() < HEADER {
+            return None;
+        }
+        // `len` is the last u32 in the header
+        let len = u32::from_ne_bytes(buf[HEADER - 4..HEADER].try_into().unwrap()) as usize;
+        let end = (HEADER + len).min(buf.len());
+        let name = &buf[HEADER..end];
+        buf = &buf[end..];
+
+        // name is nul padded
+        let name_len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        Some(&name[..name_len])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TODO: &str = "\
+(A) call mom @phone
+x 2024-01-02 pay rent @home
+
+  water plants @home  \n\
+x done without a context
+buy milk @shop +errands
+";
+
+    #[test]
+    fn pending_tasks() {
+        assert_eq!(count(TODO, None), 3);
+        assert_eq!(count(TODO, Some("@home")), 1);
+        assert_eq!(count(TODO, Some("+errands")), 1);
+        assert_eq!(count(TODO, Some("@work")), 0);
+        assert_eq!(count("", None), 0);
+        assert_eq!(count("\n  \n\t\n", None), 0);
+        // only a leading `x ` completes a task
+        assert_eq!(count("xylophone lessons\nfix x \n", None), 2);
+    }
+
+    #[test]
+    fn unrelated_events_keep_the_deadline() {
+        let dir = std::env::temp_dir().join(format!("statusbar-todo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let inotify = Inotify::new(&dir).unwrap();
+
+        let writer = {
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                for i in 0..100 {
+                    std::fs::write(dir.join("other.txt"), i.to_string()).unwrap();
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            })
+        };
+        let start = Instant::now();
+        assert!(!inotify.wait(OsStr::new("todo.txt"), Duration::from_millis(100)));
+        let waited = start.elapsed();
+        writer.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(waited >= Duration::from_millis(100), "{waited:?}");
+        assert!(waited < Duration::from_millis(800), "{waited:?}");
+    }
+}
@@ -0,0 +1,440 @@
+//! Bytes transferred today by the interfaces, from the counters of /proc/net/dev, as
+//! `today ↓4.2G ↑310.0M`, and with `--data-cap` the percentage of the cap used in its period
+//!
+//! The totals of each day are saved every minute, so that a restart doesn't reset them or the
+//! budget of the period. A day starts again from zero at local midnight.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Days, Local, NaiveDate};
+use clap::ValueEnum;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::{read_into, Module, Selection};
+use crate::glyphs::{Glyph, Glyphs};
+use crate::logging::Throttle;
+use crate::output::Segment;
+use crate::{notify, throughput};
+
+pub const NET_DEV_PATH: &str = "/proc/net/dev";
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval of saving the totals
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Days of totals kept, enough for any period
+const KEPT_DAYS: u64 = 31;
+
+/// Percentage of the cap notified once, until back under it
+const NOTIFY_PERCENTAGE: f64 = 90.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Period {
+    /// from local midnight
+    Day,
+    /// the calendar month
+    #[default]
+    Month,
+    /// the last 30 days, today included
+    Rolling,
+}
+
+impl Period {
+    /// First day of the period of `today`
+    fn start(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => today,
+            Self::Month => today.with_day(1).unwrap(),
+            Self::Rolling => today - Days::new(29),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Month => "month",
+            Self::Rolling => "30d",
+        }
+    }
+}
+
+/// Bytes that may be received and sent in a period
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataCap {
+    pub bytes: u64,
+    pub period: Period,
+    /// send a desktop notification from 90% of the cap
+    pub notify: bool,
+}
+
+/// Bytes received and sent on `date`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Day {
+    #[serde(with = "date")]
+    date: NaiveDate,
+    rx: u64,
+    tx: u64,
+}
+
+/// Totals of the last days, as saved
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct History {
+    /// oldest first, up to `KEPT_DAYS`
+    days: Vec<Day>,
+    /// whether the cap was notified, until back under `NOTIFY_PERCENTAGE`
+    #[serde(default)]
+    notified: bool,
+}
+
+impl History {
+    /// Totals of `today`, starting from zero on another day
+    fn today(&mut self, today: NaiveDate) -> &mut Day {
+        if self.days.last().is_none_or(|day| day.date != today) {
+            let oldest = today - Days::new(KEPT_DAYS - 1);
+            self.days
+                .retain(|day| day.date >= oldest && day.date < today);
+            self.days.push(Day {
+                date: today,
+                rx: 0,
+                tx: 0,
+            });
+        }
+        self.days.last_mut().unwrap()
+    }
+
+    /// Bytes received and sent from `start`
+    fn since(&self, start: NaiveDate) -> u64 {
+        self.days
+            .iter()
+            .filter(|day| day.date >= start)
+            .map(|day| day.rx + day.tx)
+            .sum()
+    }
+}
+
+/// Dates as `2024-01-01`, chrono being built without serde
+mod date {
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(date)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+pub struct Traffic {
+    selection: Selection,
+    /// such as /proc/net/dev
+    path: PathBuf,
+    /// such as $XDG_STATE_HOME/statusbar/traffic.json, not saved without
+    state: Option<PathBuf>,
+    glyphs: Glyphs,
+    data_cap: Option<DataCap>,
+    history: History,
+    /// raw (rx, tx) counters of each interface, as last read
+    counters: HashMap<String, (u64, u64)>,
+    saved: Instant,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl Traffic {
+    /// Counting the interfaces of `selection`, every one but loopback without any
+    pub fn new(
+        selection: Selection,
+        path: PathBuf,
+        state: Option<PathBuf>,
+        glyphs: Glyphs,
+    ) -> Self {
+        let history = state.as_deref().and_then(load).unwrap_or_default();
+        Self {
+            selection,
+            path,
+            state,
+            glyphs,
+            data_cap: None,
+            history,
+            counters: HashMap::new(),
+            saved: Instant::now(),
+            throttle: Throttle::default(),
+            buf: String::new(),
+        }
+    }
+
+    pub fn data_cap(mut self, data_cap: DataCap) -> Self {
+        self.data_cap = Some(data_cap);
+        self
+    }
+
+    fn counted(&self, interface: &str) -> bool {
+        if self.selection.is_empty() {
+            interface != "lo"
+        } else {
+            self.selection.matches(interface)
+        }
+    }
+
+    fn save(&mut self) {
+        self.saved = Instant::now();
+        let Some(state) = &self.state else {
+            return;
+        };
+        if let Err(e) = save(state, &self.history) {
+            warn!(target: "traffic", "unable to save {}: {e}", state.display());
+        }
+    }
+
+    /// Whether to notify `percentage` of the cap: once when reaching `NOTIFY_PERCENTAGE`, again
+    /// only after going back under it, as in the next period
+    fn crossed(&mut self, percentage: f64) -> bool {
+        let over = percentage >= NOTIFY_PERCENTAGE;
+        let crossed = over && !self.history.notified;
+        if over != self.history.notified {
+            self.history.notified = over;
+            self.save();
+        }
+        crossed
+    }
+}
+
+/// Saved once no longer collected, as when replaced on a reload, the new module reading the
+/// totals of the last minute
+impl Drop for Traffic {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
+
+/// `$XDG_STATE_HOME/statusbar/traffic.json`, or in `~/.local/state` without
+pub fn default_state_path() -> Option<PathBuf> {
+    let state_home = match std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(state_home.join("statusbar").join("traffic.json"))
+}
+
+fn load(path: &Path) -> Option<History> {
+    let json = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Write to a temporary file renamed over `path`, never leaving a partial file
+fn save(path: &Path, history: &History) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string(history)?)?;
+    std::fs::rename(tmp, path)
+}
+
+impl Module for Traffic {
+    fn name(&self) -> &'static str {
+        "traffic"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let today = Local::now().date_naive();
+        if self.history.days.last().is_none_or(|day| day.date != today) {
+            self.history.today(today);
+            self.save();
+        }
+
+        match read_into(&self.path, &mut self.buf) {
+            Ok(()) => {
+                self.throttle.succeeded();
+                let buf = std::mem::take(&mut self.buf);
+                for (interface, rx, tx) in parse(&buf) {
+                    if !self.counted(interface) {
+                        continue;
+                    }
+                    let last = self.counters.insert(interface.to_string(), (rx, tx));
+                    if let Some((last_rx, last_tx)) = last {
+                        let day = self.history.today(today);
+                        day.rx += delta(last_rx, rx);
+                        day.tx += delta(last_tx, tx);
+                    }
+                }
+                self.buf = buf;
+            }
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    let path = self.path.display();
+                    warn!(target: "traffic", "unable to read {path}: {e}{suppressed}");
+                }
+                return None;
+            }
+        }
+
+        let mut segment = segment(self.history.today(today), &self.glyphs);
+        if let Some(data_cap) = self.data_cap {
+            let used = self.history.since(data_cap.period.start(today));
+            let percentage = used as f64 * 100.0 / data_cap.bytes.max(1) as f64;
+            segment.text = format!(
+                "{} {} {percentage:.0}%",
+                segment.text,
+                data_cap.period.label()
+            );
+            segment = segment.percentage(percentage);
+            if self.crossed(percentage) && data_cap.notify {
+                notify::send(format!(
+                    "{percentage:.0}% of the {} {} data cap used",
+                    throughput::format_bytes(data_cap.bytes as f64),
+                    data_cap.period.label()
+                ));
+            }
+        }
+
+        if self.saved.elapsed() >= SAVE_INTERVAL {
+            self.save();
+        }
+        Some(segment)
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+/// Bytes since the counter read `last`. A counter going backwards was reset, such as by the
+/// interface being created again, and counts from zero.
+fn delta(last: u64, counter: u64) -> u64 {
+    counter.checked_sub(last).unwrap_or(counter)
+}
+
+/// (interface, received bytes, sent bytes) of the lines of /proc/net/dev such as
+/// `  eth0: 1234 5 0 0 0 0 0 0 5678 6 0 0 0 0 0 0`, after its two header lines
+fn parse(dev: &str) -> impl Iterator<Item = (&str, u64, u64)> {
+    dev.lines().skip(2).filter_map(|line| {
+        let (interface, counters) = line.split_once(':')?;
+        let mut fields = counters.split_ascii_whitespace();
+        let rx = fields.next()?.parse().ok()?;
+        let tx = fields.nth(7)?.parse().ok()?;
+        Some((interface.trim(), rx, tx))
+    })
+}
+
+fn segment(day: &Day, glyphs: &Glyphs) -> Segment {
+    let rx = throughput::format_bytes(day.rx as f64);
+    let tx = throughput::format_bytes(day.tx as f64);
+    let (down, up) = (glyphs.get(Glyph::Down), glyphs.get(Glyph::Up));
+    Segment::new("traffic", format!("today {down}{rx} {up}{tx}"))
+        .value("rx_bytes", day.rx)
+        .value("tx_bytes", day.tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  104876     512    0    0    0     0          0         0   104876     512    0    0    0     0       0          0
+  eth0: 4509715456 3020417 0 0 0 0 0 1024 325058560 1298112 0 0 0 0 0 0
+";
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_counters() {
+        let counters: Vec<_> = parse(DEV).collect();
+        assert_eq!(
+            counters,
+            [("lo", 104876, 104876), ("eth0", 4509715456, 325058560)]
+        );
+    }
+
+    #[test]
+    fn reset_counter_not_subtracted() {
+        assert_eq!(delta(1000, 1500), 500);
+        // interface created again
+        assert_eq!(delta(1000, 200), 200);
+    }
+
+    #[test]
+    fn rolls_at_midnight() {
+        let mut history = History::default();
+        history.today(date("2024-01-01")).rx = 10;
+        assert_eq!(history.today(date("2024-01-01")).rx, 10);
+        assert_eq!(history.today(date("2024-01-02")).rx, 0);
+        assert_eq!(history.days.len(), 2);
+        // past any period
+        history.today(date("2024-03-01"));
+        assert_eq!(history.days.len(), 1);
+    }
+
+    #[test]
+    fn period_boundaries() {
+        let mut history = History::default();
+        for (day, bytes) in [
+            ("2024-01-02", 1000),
+            ("2024-01-03", 1),
+            ("2024-01-31", 10),
+            ("2024-02-01", 100),
+        ] {
+            history.today(date(day)).tx = bytes;
+        }
+        let today = date("2024-02-01");
+        let used = |period: Period| history.since(period.start(today));
+        assert_eq!(used(Period::Day), 100);
+        assert_eq!(used(Period::Month), 100);
+        assert_eq!(used(Period::Rolling), 111);
+        assert_eq!(Period::Rolling.start(today), date("2024-01-03"));
+    }
+
+    #[test]
+    fn notified_once_per_crossing() {
+        let mut traffic = Traffic::new(
+            Selection::default(),
+            PathBuf::new(),
+            None,
+            Glyphs::default(),
+        );
+        assert!(!traffic.crossed(50.0));
+        assert!(traffic.crossed(90.0));
+        assert!(!traffic.crossed(95.0));
+        // a new period
+        assert!(!traffic.crossed(1.0));
+        assert!(traffic.crossed(91.0));
+    }
+
+    #[test]
+    fn restored_across_restarts() {
+        let dir = std::env::temp_dir().join(format!("statusbar-traffic-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let state = dir.join("traffic.json");
+        let dev = dir.join("dev");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&dev, DEV).unwrap();
+
+        let mut history = History::default();
+        history.today(Local::now().date_naive()).rx = 1 << 30;
+        history.notified = true;
+        save(&state, &history).unwrap();
+        let data_cap = DataCap {
+            bytes: 4 << 30,
+            period: Period::Day,
+            notify: false,
+        };
+        let traffic = Traffic::new(Selection::default(), dev, Some(state), Glyphs::default());
+        assert!(traffic.history.notified);
+        let segment = traffic.data_cap(data_cap).collect().unwrap();
+        assert_eq!(segment.text, "today ↓1.0G ↑0B day 25%");
+        assert_eq!(segment.percentage, Some(25.0));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
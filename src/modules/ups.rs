@@ -0,0 +1,309 @@
+//! UPS charge and runtime from apcupsd's network information server or NUT's `upsc`
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::process::Command;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use log::warn;
+
+use super::Module;
+use crate::command::output_timeout;
+use crate::glyphs::{Glyph, Glyphs};
+use crate::output::Segment;
+
+const INTERVAL: Duration = Duration::from_secs(15);
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Backend {
+    /// apcupsd NIS protocol, `--ups-name` is the host:port of the server
+    Apcupsd,
+    /// NUT through `upsc`, `--ups-name` is the ups name such as `ups@localhost`
+    Nut,
+}
+
+impl Backend {
+    pub fn default_name(self) -> &'static str {
+        match self {
+            Self::Apcupsd => "localhost:3551",
+            Self::Nut => "ups@localhost",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Status {
+    charge: f64,
+    on_battery: bool,
+    runtime: Option<Duration>,
+}
+
+impl Status {
+    fn segment(&self, glyphs: &Glyphs) -> String {
+        let charge = self.charge.round();
+        if self.on_battery {
+            match self.runtime {
+                Some(runtime) => format!("ups {charge}% {}min!", runtime.as_secs() / 60),
+                None => format!("ups {charge}%!"),
+            }
+        } else {
+            format!("ups {charge}% {}", glyphs.get(Glyph::Ac))
+        }
+    }
+}
+
+/// Charge of the UPS `name` of `backend`, and its runtime while on battery
+pub struct Ups {
+    backend: Backend,
+    name: String,
+    glyphs: Glyphs,
+    down: bool,
+}
+
+impl Ups {
+    pub fn new(backend: Backend, name: String, glyphs: Glyphs) -> Self {
+        Self {
+            backend,
+            name,
+            glyphs,
+            down: false,
+        }
+    }
+}
+
+impl Module for Ups {
+    fn name(&self) -> &'static str {
+        "ups"
+    }
+
+    fn collect(&mut self) -> Option<Segment> {
+        let status = match self.backend {
+            Backend::Apcupsd => apcupsd_status(&self.name),
+            Backend::Nut => nut_status(&self.name),
+        };
+
+        // monitoring being down is shown, instead of silently dropping the segment
+        let text = match status {
+            Ok(status) => {
+                self.down = false;
+                status.segment(&self.glyphs)
+            }
+            Err(e) => {
+                if !self.down {
+                    warn!("monitoring down: {e}");
+                    self.down = true;
+                }
+                "ups monitoring down".to_string()
+            }
+        };
+        Some(Segment::new("ups", text))
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
+
+/// Query apcupsd's NIS: each message is prefixed by its length as a big endian u16, and
+/// the reply to `status` is a sequence of `KEY : value` records ended by an empty one
+fn apcupsd_status(addr: &str) -> io::Result<Status> {
+    let addr = std::net::ToSocketAddrs::to_socket_addrs(addr)?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unresolved address"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let command = b"status";
+    stream.write_all(&(command.len() as u16).to_be_bytes())?;
+    stream.write_all(command)?;
+
+    let mut records = String::new();
+    loop {
+        let mut len = [0; 2];
+        stream.read_exact(&mut len)?;
+        let len = u16::from_be_bytes(len) as usize;
+        if len == 0 {
+            break;
+        }
+        let mut record = vec![0; len];
+        stream.read_exact(&mut record)?;
+        records.push_str(&String::from_utf8_lossy(&record));
+    }
+
+    parse_apcupsd(&records)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing STATUS or BCHARGE"))
+}
+
+fn parse_apcupsd(records: &str) -> Option<Status> {
+    let mut status = None;
+    let mut charge = None;
+    let mut runtime = None;
+
+    for line in records.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // values carry units, such as `100.0 Percent` and `12.0 Minutes`
+        let value = value.trim();
+        let number = value
+            .split_ascii_whitespace()
+            .next()
+            .and_then(|v| v.parse::<f64>().ok());
+        match key.trim() {
+            "STATUS" => status = Some(value.to_string()),
+            "BCHARGE" => charge = number,
+            "TIMELEFT" => runtime = number.map(|m| Duration::from_secs_f64(m * 60.0)),
+            _ => (),
+        }
+    }
+
+    Some(Status {
+        charge: charge?,
+        on_battery: status?.contains("ONBATT"),
+        runtime,
+    })
+}
+
+fn nut_status(name: &str) -> io::Result<Status> {
+    let output = output_timeout(Command::new("upsc").arg(name), TIMEOUT)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "upsc timed out"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+
+    parse_upsc(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing ups.status or battery.charge",
+        )
+    })
+}
+
+fn parse_upsc(output: &str) -> Option<Status> {
+    let mut status = None;
+    let mut charge = None;
+    let mut runtime = None;
+
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(": ") else {
+            continue;
+        };
+        match key {
+            "ups.status" => status = Some(value.to_string()),
+            "battery.charge" => charge = value.parse::<f64>().ok(),
+            "battery.runtime" => runtime = value.parse::<f64>().ok().map(Duration::from_secs_f64),
+            _ => (),
+        }
+    }
+
+    // status flags such as `OL CHRG` or `OB DISCHRG LB`
+    Some(Status {
+        charge: charge?,
+        on_battery: status?.split_ascii_whitespace().any(|flag| flag == "OB"),
+        runtime,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `apcaccess status` of a Back-UPS, its records as sent by the NIS
+    const APCACCESS: &str = "\
+APC      : 001,036,0868
+DATE     : 2024-03-02 10:41:07 +0100  
+HOSTNAME : nas
+VERSION  : 3.14.14 (31 May 2016) debian
+UPSNAME  : nas
+CABLE    : USB Cable
+DRIVER   : USB UPS Driver
+UPSMODE  : Stand Alone
+STARTTIME: 2024-02-28 08:12:55 +0100  
+MODEL    : Back-UPS XS 700U 
+STATUS   : ONLINE 
+LINEV    : 232.0 Volts
+LOADPCT  : 11.0 Percent
+BCHARGE  : 100.0 Percent
+TIMELEFT : 54.3 Minutes
+MBATTCHG : 5 Percent
+MINTIMEL : 3 Minutes
+END APC  : 2024-03-02 10:41:36 +0100  
+";
+
+    /// `upsc ups@localhost` of the same ups through NUT's usbhid-ups, on battery
+    const UPSC: &str = "\
+battery.charge: 87
+battery.charge.low: 10
+battery.runtime: 2460
+battery.type: PbAc
+device.model: Back-UPS XS 700U
+driver.name: usbhid-ups
+input.voltage: 0.0
+ups.load: 12
+ups.status: OB DISCHRG
+";
+
+    #[test]
+    fn apcaccess() {
+        assert_eq!(
+            parse_apcupsd(APCACCESS),
+            Some(Status {
+                charge: 100.0,
+                on_battery: false,
+                runtime: Some(Duration::from_secs(3258)),
+            })
+        );
+        let on_battery = APCACCESS.replace("ONLINE", "ONBATT LOWBATT");
+        assert!(parse_apcupsd(&on_battery).unwrap().on_battery);
+    }
+
+    #[test]
+    fn apcaccess_missing_fields() {
+        let no_runtime = APCACCESS.replace("TIMELEFT", "XTIMELEFT");
+        assert_eq!(parse_apcupsd(&no_runtime).unwrap().runtime, None);
+        assert_eq!(
+            parse_apcupsd(&APCACCESS.replace("BCHARGE", "XBCHARGE")),
+            None
+        );
+        assert_eq!(parse_apcupsd(&APCACCESS.replace("STATUS", "XSTATUS")), None);
+        assert_eq!(parse_apcupsd(""), None);
+    }
+
+    #[test]
+    fn upsc() {
+        assert_eq!(
+            parse_upsc(UPSC),
+            Some(Status {
+                charge: 87.0,
+                on_battery: true,
+                runtime: Some(Duration::from_secs(2460)),
+            })
+        );
+        let online = UPSC.replace("OB DISCHRG", "OL CHRG");
+        assert!(!parse_upsc(&online).unwrap().on_battery);
+    }
+
+    #[test]
+    fn upsc_missing_fields() {
+        let no_runtime = UPSC.replace("battery.runtime: 2460\n", "");
+        assert_eq!(parse_upsc(&no_runtime).unwrap().runtime, None);
+        assert_eq!(parse_upsc(&UPSC.replace("battery.charge: 87\n", "")), None);
+        assert_eq!(parse_upsc(&UPSC.replace("ups.status", "ups.mfr")), None);
+        assert_eq!(parse_upsc("Error: Data stale\n"), None);
+    }
+
+    #[test]
+    fn segments() {
+        let glyphs = Glyphs::new(crate::glyphs::Set::Ascii, vec![]);
+        let apcaccess = parse_apcupsd(APCACCESS).unwrap();
+        assert_eq!(apcaccess.segment(&glyphs), "ups 100% ac");
+        assert_eq!(parse_upsc(UPSC).unwrap().segment(&glyphs), "ups 87% 41min!");
+    }
+}
@@ -0,0 +1,286 @@
+//! Compression of the pages swapped to zram, as `zram 1.9G→640.0M (3.0x)`, or to zswap on systems
+//! without a zram device
+//!
+//! The zram devices are listed again on each sample, as zramctl can add one at any time. Their
+//! mm_stat gained columns over kernel versions, so only the first three are read and the rest
+//! ignored. Kernels before Linux 4.1 only have a file per statistic, read instead.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::warn;
+
+use super::{read_into, Module};
+use crate::glyphs::{Glyph, Glyphs};
+use crate::logging::Throttle;
+use crate::output::Segment;
+use crate::paths::Paths;
+use crate::throughput;
+
+pub const SYS_BLOCK_PATH: &str = "/sys/block";
+pub const ZSWAP_ENABLED_PATH: &str = "/sys/module/zswap/parameters/enabled";
+/// only readable by root
+pub const ZSWAP_DEBUG_PATH: &str = "/sys/kernel/debug/zswap";
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// Page size without sysconf
+const PAGE_SIZE: u64 = 4096;
+
+/// Bytes before and after compression
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Stored {
+    original: u64,
+    compressed: u64,
+}
+
+impl std::ops::AddAssign for Stored {
+    fn add_assign(&mut self, other: Self) {
+        self.original += other.original;
+        self.compressed += other.compressed;
+    }
+}
+
+pub struct Zram {
+    sys_block: PathBuf,
+    zswap_enabled: PathBuf,
+    zswap_debug: PathBuf,
+    meminfo: PathBuf,
+    glyphs: Glyphs,
+    throttle: Throttle,
+    buf: String,
+}
+
+impl Zram {
+    pub fn new(paths: &Paths, glyphs: Glyphs) -> Self {
+        Self {
+            sys_block: paths.resolve(SYS_BLOCK_PATH),
+            zswap_enabled: paths.resolve(ZSWAP_ENABLED_PATH),
+            zswap_debug: paths.resolve(ZSWAP_DEBUG_PATH),
+            meminfo: paths.resolve(super::MEMINFO_PATH),
+            glyphs,
+            throttle: Throttle::default(),
+            buf: String::new(),
+        }
+    }
+
+    /// Totals of the zram devices, `None` without any. Devices without a disksize, such as the
+    /// one created by loading the module, aren't set up.
+    fn zram(&mut self) -> io::Result<Option<Stored>> {
+        let mut devices: Vec<PathBuf> = match std::fs::read_dir(&self.sys_block) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with("zram"))
+                .map(|entry| entry.path())
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        devices.sort();
+        let mut total = None;
+        for device in devices {
+            let disksize = std::fs::read_to_string(device.join("disksize"));
+            if disksize.is_ok_and(|disksize| disksize.trim() == "0") {
+                continue;
+            }
+            let stored = match read_into(&device.join("mm_stat"), &mut self.buf) {
+                Ok(()) => mm_stat(&self.buf)?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => legacy_stat(&device)?,
+                Err(e) => return Err(e),
+            };
+            *total.get_or_insert_with(Stored::default) += stored;
+        }
+        Ok(total)
+    }
+
+    /// Zswap while enabled, from /proc/meminfo since Linux 5.19, or from debugfs before
+    fn zswap(&mut self) -> io::Result<Option<Stored>> {
+        let enabled =
+            std::fs::read_to_string(&self.zswap_enabled).is_ok_and(|enabled| enabled.trim() == "Y");
+        if !enabled {
+            return Ok(None);
+        }
+        read_into(&self.meminfo, &mut self.buf)?;
+        if let Some(stored) = meminfo_zswap(&self.buf) {
+            return Ok(Some(stored));
+        }
+        let counter = |name: &str| -> io::Result<u64> {
+            let read = std::fs::read_to_string(self.zswap_debug.join(name))?;
+            parse(read.trim())
+        };
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        let page_size = u64::try_from(page_size)
+            .ok()
+            .filter(|size| *size > 0)
+            .unwrap_or(PAGE_SIZE);
+        Ok(Some(Stored {
+            original: counter("stored_pages")? * page_size,
+            compressed: counter("pool_total_size")?,
+        }))
+    }
+}
+
+impl Module for Zram {
+    fn name(&self) -> &'static str {
+        "zram"
+    }
+
+    /// Hidden without a zram device or zswap enabled, keeping the last displayed on failure
+    fn collect(&mut self) -> Option<Segment> {
+        let sampled = match self.zram() {
+            Ok(Some(stored)) => Ok(Some(("zram", stored))),
+            Ok(None) => self
+                .zswap()
+                .map(|stored| stored.map(|stored| ("zswap", stored))),
+            Err(e) => Err(e),
+        };
+        match sampled {
+            Ok(sampled) => {
+                self.throttle.succeeded();
+                Some(segment(sampled, &self.glyphs))
+            }
+            Err(e) => {
+                if let Some(suppressed) = self.throttle.failed() {
+                    warn!(target: "zram", "unable to read the statistics: {e}{suppressed}");
+                }
+                None
+            }
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+}
+
+fn parse(field: &str) -> io::Result<u64> {
+    field
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{field:?}: {e}")))
+}
+
+/// `orig_data_size compr_data_size mem_used_total ...`, the columns after the third ignored
+fn mm_stat(mm_stat: &str) -> io::Result<Stored> {
+    let mut fields = mm_stat.split_ascii_whitespace();
+    let mut next = || {
+        let field = fields
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated mm_stat"))?;
+        parse(field)
+    };
+    let original = next()?;
+    let compressed = next()?;
+    // mem_used_total, checked to tell a truncated line apart
+    next()?;
+    Ok(Stored {
+        original,
+        compressed,
+    })
+}
+
+/// A file per statistic, before mm_stat
+fn legacy_stat(device: &Path) -> io::Result<Stored> {
+    let read = |name: &str| -> io::Result<u64> {
+        let read = std::fs::read_to_string(device.join(name))?;
+        parse(read.trim())
+    };
+    Ok(Stored {
+        original: read("orig_data_size")?,
+        compressed: read("compr_data_size")?,
+    })
+}
+
+/// `Zswapped`, the pages stored, and `Zswap`, their compressed size, in KiB
+fn meminfo_zswap(meminfo: &str) -> Option<Stored> {
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(':')?;
+            value.split_ascii_whitespace().next()?.parse::<u64>().ok()
+        })
+    };
+    Some(Stored {
+        original: field("Zswapped")? * 1024,
+        compressed: field("Zswap")? * 1024,
+    })
+}
+
+/// `zram 1.9G→640.0M (3.0x)` of the stored bytes of `zram` or `zswap`, `zram 0B` while none is
+/// stored, and empty without either
+fn segment(sampled: Option<(&str, Stored)>, glyphs: &Glyphs) -> Segment {
+    let Some((kind, stored)) = sampled else {
+        return Segment::new("zram", "");
+    };
+    let text = if stored.original == 0 {
+        format!("{kind} 0B")
+    } else {
+        let arrow = glyphs.get(Glyph::Arrow);
+        format!(
+            "{kind} {}{arrow}{} ({:.1}x)",
+            throughput::format_bytes(stored.original as f64),
+            throughput::format_bytes(stored.compressed as f64),
+            stored.original as f64 / stored.compressed.max(1) as f64
+        )
+    };
+    Segment::new("zram", text)
+        .value("original_bytes", stored.original)
+        .value("compressed_bytes", stored.compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyphs::Set;
+
+    fn fixtures(sys: &str) -> Paths {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/modules/fixtures/zram");
+        Paths {
+            sysfs: fixtures.join(sys),
+            procfs: fixtures.join("proc"),
+        }
+    }
+
+    #[test]
+    fn mm_stat_columns() {
+        // Linux 4.1, without the huge pages of 4.19 and 5.15
+        let stored = mm_stat("   8192   4096   12288        0    12288        0        0\n");
+        assert_eq!(
+            stored.unwrap(),
+            Stored {
+                original: 8192,
+                compressed: 4096
+            }
+        );
+        assert!(mm_stat("8192 4096").is_err());
+        assert!(mm_stat("8192 x 12288").is_err());
+    }
+
+    #[test]
+    fn devices_summed() {
+        let segment = Zram::new(&fixtures("sys"), Glyphs::default())
+            .collect()
+            .unwrap();
+        assert_eq!(segment.text, "zram 1.9G→640.0M (3.0x)");
+        assert_eq!(segment.get::<u64>("original_bytes"), Some(2040109465));
+        assert_eq!(
+            Zram::new(&fixtures("sys"), Glyphs::new(Set::Ascii, vec![]))
+                .collect()
+                .unwrap()
+                .text,
+            "zram 1.9G->640.0M (3.0x)"
+        );
+    }
+
+    #[test]
+    fn zswap_without_zram() {
+        let segment = Zram::new(&fixtures("sys-zswap"), Glyphs::default())
+            .collect()
+            .unwrap();
+        assert_eq!(segment.text, "zswap 512.0M→128.0M (4.0x)");
+        // neither configured
+        let segment = Zram::new(&fixtures("sys-none"), Glyphs::default())
+            .collect()
+            .unwrap();
+        assert_eq!(segment.text, "");
+    }
+}
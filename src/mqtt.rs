@@ -0,0 +1,360 @@
+//! MQTT output of the displayed values, each on a retained topic of its own such as
+//! `statusbar/box/cpu` and `statusbar/box/bat/BAT0`
+//!
+//! The connection is left to rumqttc, publishing at QoS 0 with a Last Will setting
+//! `<prefix>/status` to `offline`. The render thread only queues the values that changed. The
+//! queue is bounded and drops the oldest, so a slow or missing broker never holds up the bar;
+//! the latest value of every topic is published again on reconnecting.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+
+use crate::output::Segment;
+use crate::signal;
+
+/// Values queued for the broker before the oldest are dropped
+const QUEUE_CAPACITY: usize = 256;
+
+/// Keep alive of the connection, pinged at half of it while idle
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// Upper bound on publishing the offline status once stopping
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// First delay of reconnecting, doubled up to [`MAX_BACKOFF`]
+const BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A broker of `--mqtt-broker`, such as `mqtt://host:1883`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Broker {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Broker {
+    /// clap value parser of `mqtt://host[:port]`, the port defaulting to 1883
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid broker `{s}`, expected e.g. mqtt://host:1883");
+        let address = s.strip_prefix("mqtt://").ok_or_else(invalid)?;
+        let address = address.strip_suffix('/').unwrap_or(address);
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (address, 1883),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// (topic, payload) waiting for the broker
+#[derive(Default)]
+pub struct Queue {
+    messages: Mutex<VecDeque<(String, String)>>,
+    condvar: Condvar,
+}
+
+impl Queue {
+    /// Queue a message, dropping the oldest when full
+    fn push(&self, topic: String, payload: String) {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() >= QUEUE_CAPACITY {
+            messages.pop_front();
+        }
+        messages.push_back((topic, payload));
+        self.condvar.notify_one();
+    }
+
+    /// The oldest message, waiting up to `timeout` for one
+    fn pop(&self, timeout: Duration) -> Option<(String, String)> {
+        let messages = self.messages.lock().unwrap();
+        let (mut messages, _) = self
+            .condvar
+            .wait_timeout_while(messages, timeout, |messages| messages.is_empty())
+            .unwrap();
+        messages.pop_front()
+    }
+}
+
+/// Queues the values of the render thread that changed since last published
+pub struct Publisher {
+    prefix: String,
+    queue: Arc<Queue>,
+    last: HashMap<String, String>,
+}
+
+impl Publisher {
+    pub fn new(prefix: String) -> Self {
+        Self {
+            prefix,
+            queue: Arc::default(),
+            last: HashMap::new(),
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Shared with [`run`]
+    pub fn queue(&self) -> Arc<Queue> {
+        self.queue.clone()
+    }
+
+    /// Queue the topics of `segments` whose payload changed, but the time, which changes every
+    /// second and which the subscribers have a clock of their own for
+    pub fn publish<'a>(&mut self, segments: impl IntoIterator<Item = &'a Segment>) {
+        for segment in segments {
+            if segment.module == "time" {
+                continue;
+            }
+            for (topic, payload) in topics(segment) {
+                let topic = format!("{}/{topic}", self.prefix);
+                if self.last.get(&topic) != Some(&payload) {
+                    self.last.insert(topic.clone(), payload.clone());
+                    self.queue.push(topic, payload);
+                }
+            }
+        }
+    }
+}
+
+/// `module` with the percentage, or else the text, and `module/name` with each value. Topic
+/// wildcards and levels in the names are replaced.
+fn topics(segment: &Segment) -> Vec<(String, String)> {
+    let payload = match segment.percentage {
+        Some(percentage) => percentage.to_string(),
+        None => segment.text.clone(),
+    };
+    let mut topics = vec![(segment.module.to_string(), payload)];
+    for (name, value) in &segment.values {
+        let name = name.replace(['/', '+', '#'], "_");
+        topics.push((format!("{}/{name}", segment.module), value.clone()));
+    }
+    topics
+}
+
+/// Published to the broker while connected, and the latest payload of every topic to publish
+/// again on reconnecting
+#[derive(Default)]
+struct State {
+    connected: bool,
+    latest: HashMap<String, String>,
+}
+
+/// Publish the messages of `queue` to `broker`, reconnecting with backoff, until stopping
+pub fn run(broker: Broker, prefix: String, queue: Arc<Queue>) {
+    run_until(broker, prefix, &queue, signal::stopping);
+}
+
+/// [`run`] until `stop` returns true, checked at least every second
+fn run_until(broker: Broker, prefix: String, queue: &Queue, stop: impl Fn() -> bool) {
+    let status = format!("{prefix}/status");
+    let client_id = format!("statusbar-{}", std::process::id());
+    let mut options = MqttOptions::new(client_id, broker.host.clone(), broker.port);
+    options.set_keep_alive(KEEP_ALIVE);
+    // a clean disconnect doesn't send the will, published by hand once stopping
+    options.set_last_will(LastWill::new(&status, "offline", QoS::AtMostOnce, true));
+    let (client, mut connection) = Client::new(options, QUEUE_CAPACITY);
+    let state = Arc::new(Mutex::new(State::default()));
+
+    // driving the connection, ending once disconnected or the client dropped
+    let (done, disconnected) = mpsc::channel();
+    std::thread::spawn({
+        let (client, state, status) = (client.clone(), state.clone(), status.clone());
+        move || {
+            let mut backoff = BACKOFF;
+            let mut failing = false;
+            for event in connection.iter() {
+                match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!(target: "mqtt", "connected to {}:{}", broker.host, broker.port);
+                        (backoff, failing) = (BACKOFF, false);
+                        let mut state = state.lock().unwrap();
+                        state.connected = true;
+                        send(&client, &status, "online");
+                        for (topic, payload) in &state.latest {
+                            send(&client, topic, payload);
+                        }
+                    }
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(_) => (),
+                    Err(e) => {
+                        state.lock().unwrap().connected = false;
+                        if !failing {
+                            warn!(target: "mqtt", "{}:{}: {e}, reconnecting", broker.host, broker.port);
+                            failing = true;
+                        }
+                        if !signal::idle(backoff) {
+                            break;
+                        }
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            }
+            let _ = done.send(());
+        }
+    });
+
+    while !stop() {
+        // woken every second to notice stopping
+        if let Some((topic, payload)) = queue.pop(Duration::from_secs(1)) {
+            let mut state = state.lock().unwrap();
+            if state.connected {
+                send(&client, &topic, &payload);
+            }
+            state.latest.insert(topic, payload);
+        }
+    }
+    if state.lock().unwrap().connected {
+        send(&client, &status, "offline");
+        let _ = client.try_disconnect();
+        let _ = disconnected.recv_timeout(TIMEOUT);
+    }
+}
+
+/// Queue a retained message for the connection, dropped when its queue is full
+fn send(client: &Client, topic: &str, payload: &str) {
+    if let Err(e) = client.try_publish(topic, QoS::AtMostOnce, true, payload) {
+        debug!(target: "mqtt", "unable to publish to {topic}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn parse_broker() {
+        let broker = |host: &str, port| Broker {
+            host: host.into(),
+            port,
+        };
+        assert_eq!(Broker::parse("mqtt://nas:1884"), Ok(broker("nas", 1884)));
+        assert_eq!(Broker::parse("mqtt://nas/"), Ok(broker("nas", 1883)));
+        assert!(Broker::parse("nas:1883").is_err());
+        assert!(Broker::parse("mqtt://:1883").is_err());
+    }
+
+    #[test]
+    fn changed_values_only() {
+        let mut publisher = Publisher::new("statusbar/box".into());
+        let bat = Segment::new("bat", "BAT0 80%")
+            .percentage(80.0)
+            .value("BAT0", 80);
+        publisher.publish([&bat, &Segment::new("time", "12:00")]);
+        publisher.publish([&bat, &Segment::new("time", "12:01")]);
+        let queued: Vec<_> = std::iter::from_fn(|| publisher.queue.pop(Duration::ZERO)).collect();
+        let queued: Vec<(&str, &str)> = queued
+            .iter()
+            .map(|(topic, payload)| (topic.as_str(), payload.as_str()))
+            .collect();
+        assert_eq!(
+            queued,
+            [
+                ("statusbar/box/bat", "80"),
+                ("statusbar/box/bat/BAT0", "80")
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_oldest() {
+        let queue = Queue::default();
+        for i in 0..QUEUE_CAPACITY + 1 {
+            queue.push(i.to_string(), String::new());
+        }
+        assert_eq!(queue.pop(Duration::ZERO).unwrap().0, "1");
+    }
+
+    /// Type and flags, and the body of the next packet of `stream`
+    fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        let kind = byte[0];
+        let (mut length, mut shift) = (0, 0);
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            length |= usize::from(byte[0] & 0x7f) << shift;
+            shift += 7;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+        (kind, body)
+    }
+
+    /// Topic and payload of the next retained publish of `stream`
+    fn read_publish(stream: &mut TcpStream) -> (String, String) {
+        let (kind, body) = read_packet(stream);
+        assert_eq!(kind, 0x31);
+        let length = usize::from(u16::from_be_bytes([body[0], body[1]]));
+        let topic = String::from_utf8(body[2..2 + length].to_vec()).unwrap();
+        (
+            topic,
+            String::from_utf8(body[2 + length..].to_vec()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn publishes_to_the_broker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let broker = Broker::parse(&format!("mqtt://{}", listener.local_addr().unwrap())).unwrap();
+        let queue = Arc::new(Queue::default());
+        // queued before connecting, kept to be published once connected
+        queue.push("statusbar/box/cpu".into(), "5".into());
+        let stop = Arc::new(AtomicBool::new(false));
+        let client = std::thread::spawn({
+            let (queue, stop) = (queue.clone(), stop.clone());
+            move || {
+                run_until(broker, "statusbar/box".into(), &queue, || {
+                    stop.load(Ordering::Acquire)
+                })
+            }
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let (kind, connect) = read_packet(&mut stream);
+        assert_eq!(kind, 0x10);
+        let will = b"statusbar/box/status";
+        assert!(connect.windows(will.len()).any(|window| window == will));
+        stream.write_all(&[0x20, 2, 0, 0]).unwrap();
+
+        let mut published = vec![read_publish(&mut stream), read_publish(&mut stream)];
+        published.sort();
+        let topic = |topic: &str, payload: &str| (topic.to_string(), payload.to_string());
+        assert_eq!(
+            published,
+            [
+                topic("statusbar/box/cpu", "5"),
+                topic("statusbar/box/status", "online")
+            ]
+        );
+        queue.push("statusbar/box/cpu".into(), "7".into());
+        assert_eq!(read_publish(&mut stream), topic("statusbar/box/cpu", "7"));
+
+        stop.store(true, Ordering::Release);
+        assert_eq!(
+            read_publish(&mut stream),
+            topic("statusbar/box/status", "offline")
+        );
+        assert_eq!(read_packet(&mut stream), (0xe0, vec![]));
+        client.join().unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+//! Netlink sockets subscribed to multicast groups of the kernel, for the uevents of devices and
+//! the changes of addresses and links
+
+use std::io;
+use std::mem;
+
+/// `RTMGRP_*` groups of the changes of the links and their addresses
+pub const ADDRESS_GROUPS: u32 =
+    (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+
+pub struct Socket {
+    fd: libc::c_int,
+}
+
+impl Socket {
+    /// Socket of `protocol`, such as `NETLINK_ROUTE`, bound to the multicast `groups`
+    pub fn open(protocol: libc::c_int, groups: u32) -> io::Result<Self> {
+        // SAFETY: plain syscalls, fd is owned by the returned struct
+        unsafe {
+            let fd = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                protocol,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let socket = Self { fd };
+
+            let mut addr: libc::sockaddr_nl = mem::zeroed();
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            addr.nl_groups = groups;
+            let bound = libc::bind(
+                fd,
+                (&addr as *const libc::sockaddr_nl).cast(),
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            );
+            if bound < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(socket)
+        }
+    }
+
+    /// Block until a message arrives, returning its length. Fails with [`overflowed`] when
+    /// messages were dropped for lack of room.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            // SAFETY: buf is valid for buf.len() bytes
+            let n = unsafe { libc::recv(self.fd, buf.as_mut_ptr().cast(), buf.len(), 0) };
+            if n >= 0 {
+                return Ok(n as usize);
+            }
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        // SAFETY: fd was opened by socket and is only closed here
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Whether [`Socket::recv`] failed as messages were dropped, some of which may have mattered
+pub fn overflowed(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ENOBUFS)
+}
@@ -0,0 +1,26 @@
+//! Desktop notifications with notify-send, for the events worth more than a segment, such as
+//! a data cap reached or an OOM kill
+
+use std::process::Command;
+use std::time::Duration;
+
+use log::warn;
+
+use crate::command::output_timeout;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Send a critical notification of `body` on its own thread, as the notification daemon may be
+/// slow to answer. Failures are only logged.
+pub fn send(body: String) {
+    std::thread::spawn(move || {
+        let mut command = Command::new("notify-send");
+        command.args(["--urgency=critical", "statusbar", &body]);
+        match output_timeout(&mut command, TIMEOUT) {
+            Ok(Some(output)) if output.status.success() => (),
+            Ok(Some(output)) => warn!(target: "notify", "notify-send failed: {}", output.status),
+            Ok(None) => warn!(target: "notify", "notify-send timed out"),
+            Err(e) => warn!(target: "notify", "unable to run notify-send: {e}"),
+        }
+    });
+}
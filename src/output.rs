@@ -0,0 +1,1133 @@
+//! Composing the segments into the status, in the format of the output
+
+use std::fmt::Write as _;
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::ValueEnum;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::command::output_timeout;
+use crate::logging::Throttle;
+use crate::sanitize;
+use crate::style::{Colors, Level};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Format {
+    /// set the X root window name, as read by dwm
+    #[default]
+    Xsetroot,
+    /// lines for lemonbar on stdout, with alignment and colors
+    Lemonbar,
+    /// lines for dzen2 on stdout, with colors
+    Dzen2,
+    /// JSON lines for a waybar custom module on stdout, refreshed on SIGUSR1
+    Waybar,
+    /// i3bar and swaybar JSON protocol on stdout, with click events read from stdin
+    I3bar,
+    /// lines written to the named pipe at `--fifo-path`
+    Fifo,
+    /// atomically replaced contents of the file at `--file-path`
+    File,
+    /// tmux's `status-right` option
+    Tmux,
+    /// plain lines on stdout, such as on a TTY
+    Stdout,
+}
+
+impl Format {
+    /// Whether the output writes lines to stdout, which only one output can use
+    pub fn is_stdout(self) -> bool {
+        matches!(
+            self,
+            Self::Lemonbar | Self::Dzen2 | Self::Waybar | Self::I3bar | Self::Stdout
+        )
+    }
+
+    /// The output of the environment without `--output`, and why: i3bar under sway, as its
+    /// swaybar, xsetroot under X, and stdout otherwise. Every output checks its environment
+    /// itself, so a wrong guess is never worse than giving it.
+    pub fn detect() -> (Self, String) {
+        let wayland =
+            std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty());
+        let sway = std::env::var_os("SWAYSOCK").is_some_and(|sock| Path::new(&sock).exists());
+        let x = std::env::var_os("DISPLAY").is_some_and(|display| !display.is_empty());
+        Self::detect_from(wayland, sway, x.then(Xsetroot::check))
+    }
+
+    /// [`Self::detect`] of a Wayland session, sway running, and the check of xsetroot under X
+    fn detect_from(
+        wayland: bool,
+        sway: bool,
+        xsetroot: Option<Result<(), String>>,
+    ) -> (Self, String) {
+        match (wayland, sway, xsetroot) {
+            (true, true, _) => (
+                Self::I3bar,
+                "sway is running, for the status_command of its swaybar".into(),
+            ),
+            (true, false, _) => (
+                Self::Stdout,
+                "Wayland without sway, pass --output waybar for a waybar custom module".into(),
+            ),
+            (false, _, Some(Ok(()))) => (Self::Xsetroot, "DISPLAY is set".into()),
+            (false, _, Some(Err(e))) => (Self::Stdout, e),
+            (false, _, None) => (Self::Stdout, "neither X nor Wayland is running".into()),
+        }
+    }
+}
+
+/// Destination of the status
+pub trait Output: Send {
+    /// Name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Compose `segments` into `status`, in the format of the output
+    fn compose(&self, segments: &[Segment], status: &mut String) {
+        plain(segments, status);
+    }
+
+    /// Write the composed status
+    fn write(&mut self, status: &str) -> io::Result<()>;
+}
+
+/// First delay before writing again to a failing output, doubled on each failure
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+
+/// Longest delay before writing again to a failing output, such as while X restarts
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Every output, each receiving the status in its own format
+pub struct Outputs {
+    outputs: Vec<Destination>,
+    scratch: String,
+}
+
+struct Destination {
+    output: Box<dyn Output>,
+    throttle: Throttle,
+    /// delay since the last failure, zero while succeeding
+    backoff: Duration,
+    /// when to write again after a failure
+    retry: Option<Instant>,
+}
+
+impl Outputs {
+    pub fn new(outputs: Vec<Box<dyn Output>>) -> Self {
+        Self {
+            outputs: outputs
+                .into_iter()
+                .map(|output| Destination {
+                    output,
+                    throttle: Throttle::default(),
+                    backoff: Duration::ZERO,
+                    retry: None,
+                })
+                .collect(),
+            scratch: String::new(),
+        }
+    }
+
+    /// Compose and write to every output, leaving the status of the first in `status`.
+    ///
+    /// A failing output doesn't affect the others, its errors are throttled, and it's only
+    /// written again after a delay increasing with each failure.
+    pub fn write(&mut self, segments: &[Segment], status: &mut String) {
+        let now = Instant::now();
+        for (i, destination) in self.outputs.iter_mut().enumerate() {
+            let composed = if i == 0 {
+                &mut *status
+            } else {
+                &mut self.scratch
+            };
+            composed.clear();
+            destination.output.compose(segments, composed);
+
+            if destination.retry.is_some_and(|retry| now < retry) {
+                continue;
+            }
+            let name = destination.output.name();
+            match destination.output.write(composed) {
+                Ok(()) => {
+                    if destination.throttle.succeeded() {
+                        info!(target: name, "writing again");
+                    }
+                    destination.backoff = Duration::ZERO;
+                    destination.retry = None;
+                }
+                Err(e) => {
+                    destination.backoff = (destination.backoff * 2).clamp(BACKOFF_MIN, BACKOFF_MAX);
+                    destination.retry = Some(now + destination.backoff);
+                    if let Some(suppressed) = destination.throttle.failed() {
+                        let delay = destination.backoff.as_secs();
+                        warn!(target: name, "{e}, retrying in {delay}s{suppressed}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Click ids of the dwm statuscmd patch, written as a byte before the text of the module
+pub const STATUSCMD_IDS: &[(&str, u8)] = &[
+    ("cpu", 1),
+    ("mem", 2),
+    ("net", 3),
+    ("bat", 4),
+    ("todo", 5),
+    ("backup", 6),
+    ("ntp", 7),
+    ("ups", 8),
+    ("lid", 9),
+    ("displays", 10),
+    ("peripherals", 11),
+    ("privacy", 12),
+    ("sink", 13),
+    ("time", 14),
+    ("prefix", 15),
+    ("host", 15),
+    ("user", 15),
+    ("page", 16),
+    ("traffic", 17),
+    ("oom", 18),
+    ("swap", 19),
+    ("readonly", 20),
+    ("smart", 21),
+    ("throttle", 22),
+    ("temp", 23),
+    ("im", 24),
+    ("flatpak", 25),
+    ("snapshot", 26),
+    ("cert", 27),
+    ("http", 28),
+    ("offline", 29),
+    ("zram", 30),
+    ("cgroup", 31),
+    ("tcp", 32),
+    ("fd", 33),
+    ("nm", 34),
+    ("timer", 35),
+    ("idle", 36),
+    ("calendar", 37),
+];
+
+/// Upper bound on an xsetroot run, such as while the X server is unresponsive
+const XSETROOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The X root window name
+#[derive(Default)]
+pub struct Xsetroot {
+    /// mark the segments with their click id for the dwm statuscmd patch
+    pub statuscmd: bool,
+    /// modules of the bottom bar of the dwm extrabar patch, in order, after `separator`
+    pub bottom: Vec<String>,
+    pub separator: char,
+}
+
+impl Xsetroot {
+    /// Check for an X server to write to, and xsetroot to write with
+    pub fn check() -> Result<(), String> {
+        if std::env::var_os("DISPLAY").is_none_or(|display| display.is_empty()) {
+            return Err("DISPLAY isn't set, --output xsetroot needs an X server".into());
+        }
+        if !in_path("xsetroot") {
+            return Err("xsetroot isn't installed, or isn't in PATH".into());
+        }
+        Ok(())
+    }
+
+    /// One bar of the status, with the click ids of `statuscmd`
+    fn compose_bar(&self, segments: &[Segment], status: &mut String) {
+        if !self.statuscmd {
+            plain(segments, status);
+            return;
+        }
+
+        // statuscmd takes each control byte as the start of the next clickable block,
+        // so control bytes can only appear as markers
+        plain_with(segments, status, |segment, status| {
+            let id = STATUSCMD_IDS
+                .iter()
+                .find(|(module, _)| *module == segment.module)
+                .map(|(_, id)| char::from(*id));
+            status.extend(id);
+            status.extend(segment.text.chars().filter(|c| !c.is_control()));
+        });
+    }
+}
+
+/// Whether `program` is in a directory of `PATH`
+fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
+
+impl Output for Xsetroot {
+    fn name(&self) -> &'static str {
+        "xsetroot"
+    }
+
+    /// With `bottom`, the top bar, `separator`, then the bottom bar, the separator left out of
+    /// the segments
+    fn compose(&self, segments: &[Segment], status: &mut String) {
+        if self.bottom.is_empty() {
+            self.compose_bar(segments, status);
+            return;
+        }
+
+        let (mut bottom, top): (Vec<Segment>, Vec<Segment>) = segments
+            .iter()
+            .map(|segment| {
+                let mut segment = segment.clone();
+                segment.text.retain(|c| c != self.separator);
+                segment
+            })
+            .partition(|segment| self.bottom.iter().any(|m| m == segment.module));
+        sort(&mut bottom, &self.bottom);
+        self.compose_bar(&top, status);
+        status.push(self.separator);
+        self.compose_bar(&bottom, status);
+    }
+
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        let output = output_timeout(
+            Command::new("xsetroot").args(["-name", status]),
+            XSETROOT_TIMEOUT,
+        )?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "xsetroot timed out"))?;
+        let exit = output.status;
+        if exit.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("xsetroot failed with {exit}")))
+        }
+    }
+}
+
+/// Composed as the wrapped output, but printed to stdout
+pub struct Stdout(pub Box<dyn Output>);
+
+impl Output for Stdout {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn compose(&self, segments: &[Segment], status: &mut String) {
+        self.0.compose(segments, status);
+    }
+
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        write_stdout(status)
+    }
+}
+
+/// Plain lines on stdout
+pub struct Plain;
+
+impl Output for Plain {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        write_stdout(status)
+    }
+}
+
+/// Write `status` as a line to stdout
+fn write_stdout(status: &str) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{status}")?;
+    stdout.flush()
+}
+
+/// Displayed text of one module
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub module: &'static str,
+    pub text: String,
+    pub level: Level,
+    /// value of percentage type modules
+    pub percentage: Option<f64>,
+    /// percentage as displayed once smoothed, from which the level is computed instead
+    pub shown: Option<f64>,
+    /// displayed instead of the text after clicking the module, such as the date for time
+    pub alt: Option<String>,
+    /// named values behind the text, for the metrics and the session bus
+    pub values: Vec<(String, String)>,
+    /// when the text is marked stale unless published again, such as by a stuck module
+    pub expires: Option<Instant>,
+    /// when a slow module fetched the text, whose age is shown once expired instead of marking
+    /// it stale
+    pub fetched: Option<SystemTime>,
+    /// removed from the store instead, hiding the module even with `--keep-empty`
+    pub hidden: bool,
+}
+
+impl Segment {
+    pub fn new(module: &'static str, text: impl Into<String>) -> Self {
+        Self {
+            module,
+            text: text.into(),
+            level: Level::Normal,
+            percentage: None,
+            shown: None,
+            alt: None,
+            values: vec![],
+            expires: None,
+            fetched: None,
+            hidden: false,
+        }
+    }
+
+    /// Nothing to show for `module`, such as the lid while open, rather than no data
+    pub fn hidden(module: &'static str) -> Self {
+        Self {
+            hidden: true,
+            ..Self::new(module, "")
+        }
+    }
+
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn percentage(mut self, percentage: f64) -> Self {
+        self.percentage = Some(percentage);
+        self
+    }
+
+    pub fn shown(mut self, shown: f64) -> Self {
+        self.shown = Some(shown);
+        self
+    }
+
+    pub fn alt(mut self, alt: impl Into<String>) -> Self {
+        self.alt = Some(alt.into());
+        self
+    }
+
+    pub fn value(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.values.push((name.into(), value.to_string()));
+        self
+    }
+
+    pub fn expires(mut self, expires: Instant) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// Fetched just now by a slow module, its age shown unless fetched again within `ttl`
+    pub fn cached(mut self, ttl: Duration) -> Self {
+        self.fetched = Some(SystemTime::now());
+        self.expires = Some(Instant::now() + ttl);
+        self
+    }
+
+    /// Value named `name`, parsed
+    pub fn get<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        let (_, value) = self.values.iter().find(|(n, _)| n == name)?;
+        value.parse().ok()
+    }
+}
+
+/// Sort `segments` by the position of their module in `order`, keeping unlisted modules after
+/// the listed ones in their current order
+pub fn sort(segments: &mut [Segment], order: &[String]) {
+    if order.is_empty() {
+        return;
+    }
+    segments.sort_by_key(|s| {
+        order
+            .iter()
+            .position(|module| module == s.module)
+            .unwrap_or(order.len())
+    });
+}
+
+/// The `prefix` segment of `--prefix`, its `{host}` and `{user}` replaced by their text
+pub fn prefix(template: &str, host: Option<&Segment>, user: Option<&Segment>) -> Segment {
+    let host = host.map_or("", |s| &*s.text);
+    let user = user.map_or("", |s| &*s.text);
+    let text = template.replace("{host}", host).replace("{user}", user);
+    Segment::new("prefix", text)
+}
+
+/// `[host][user] => cpu 02%, mem 03%, ..., time`, without the brackets of missing host or user,
+/// or with a `prefix` segment that prefix instead
+/// and without the arrow when nothing follows
+pub fn plain(segments: &[Segment], status: &mut String) {
+    plain_with(segments, status, |segment, status| {
+        status.push_str(&segment.text)
+    });
+}
+
+/// As [`plain`], each segment written by `text`
+fn plain_with(segments: &[Segment], status: &mut String, text: impl Fn(&Segment, &mut String)) {
+    // from --prefix, in place of the host and user, its separator included
+    if segments.iter().any(|s| s.module == "prefix") {
+        let mut separated = true;
+        for segment in segments {
+            if !separated {
+                status.push_str(", ");
+            }
+            text(segment, status);
+            separated = segment.module == "prefix";
+        }
+        return;
+    }
+
+    let mut prefixed = false;
+    for module in ["host", "user"] {
+        if let Some(segment) = segments.iter().find(|s| s.module == module) {
+            status.push('[');
+            text(segment, status);
+            status.push(']');
+            prefixed = true;
+        }
+    }
+
+    for (i, segment) in segments
+        .iter()
+        .filter(|s| !matches!(s.module, "host" | "user"))
+        .enumerate()
+    {
+        let separator = match (i, prefixed) {
+            (0, true) => " => ",
+            (0, false) => "",
+            _ => ", ",
+        };
+        status.push_str(separator);
+        text(segment, status);
+    }
+}
+
+/// Modules of each lemonbar alignment section
+pub struct Lemonbar {
+    pub left: Vec<String>,
+    pub center: Vec<String>,
+    pub right: Vec<String>,
+    pub colors: Colors,
+}
+
+impl Lemonbar {
+    fn segment(&self, segment: &Segment, status: &mut String) {
+        let text = sanitize::escape(&segment.text, '%');
+        match self.colors.get(segment.level) {
+            Some((fg, Some(bg))) => write!(status, "%{{F{fg}}}%{{B{bg}}}{text}%{{B-}}%{{F-}}"),
+            Some((fg, None)) => write!(status, "%{{F{fg}}}{text}%{{F-}}"),
+            None => write!(status, "{text}"),
+        }
+        .unwrap();
+    }
+}
+
+impl Output for Lemonbar {
+    fn name(&self) -> &'static str {
+        "lemonbar"
+    }
+
+    /// Segments in `%{l}`, `%{c}` and `%{r}` sections, modules not assigned to a section go to
+    /// the right
+    fn compose(&self, segments: &[Segment], status: &mut String) {
+        let listed = |module: &str| {
+            [&self.left, &self.center, &self.right]
+                .iter()
+                .any(|section| section.iter().any(|m| m == module))
+        };
+
+        for (alignment, section) in [("l", &self.left), ("c", &self.center), ("r", &self.right)] {
+            let mut section_segments: Vec<&Segment> = section
+                .iter()
+                .filter_map(|module| segments.iter().find(|s| s.module == module))
+                .collect();
+            if alignment == "r" {
+                section_segments.extend(segments.iter().filter(|s| !listed(s.module)));
+            }
+            if section_segments.is_empty() {
+                continue;
+            }
+
+            write!(status, "%{{{alignment}}}").unwrap();
+            for (i, segment) in section_segments.iter().enumerate() {
+                if i != 0 {
+                    status.push_str(" | ");
+                }
+                self.segment(segment, status);
+            }
+        }
+    }
+
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        write_stdout(status)
+    }
+}
+
+pub struct Dzen2 {
+    pub colors: Colors,
+    /// pixels between segments
+    pub padding: u32,
+}
+
+impl Output for Dzen2 {
+    fn name(&self) -> &'static str {
+        "dzen2"
+    }
+
+    fn compose(&self, segments: &[Segment], status: &mut String) {
+        dzen2(segments, &self.colors, self.padding, status);
+    }
+
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        write_stdout(status)
+    }
+}
+
+/// Segments separated by `padding` pixels, with dzen2 `^fg()` and `^bg()` colors
+fn dzen2(segments: &[Segment], colors: &Colors, padding: u32, status: &mut String) {
+    for (i, segment) in segments.iter().enumerate() {
+        if i != 0 {
+            write!(status, "^p({padding})").unwrap();
+        }
+        let text = sanitize::escape(&segment.text, '^');
+        match colors.get(segment.level) {
+            Some((fg, Some(bg))) => write!(status, "^fg({fg})^bg({bg}){text}^bg()^fg()"),
+            Some((fg, None)) => write!(status, "^fg({fg}){text}^fg()"),
+            None => write!(status, "{text}"),
+        }
+        .unwrap();
+    }
+}
+
+pub struct Waybar {
+    /// only output this module, for running an instance per module
+    pub module: Option<String>,
+}
+
+impl Output for Waybar {
+    fn name(&self) -> &'static str {
+        "waybar"
+    }
+
+    fn compose(&self, segments: &[Segment], status: &mut String) {
+        waybar(segments, self.module.as_deref(), status);
+    }
+
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        write_stdout(status)
+    }
+}
+
+#[derive(Serialize)]
+struct WaybarJson<'a> {
+    text: &'a str,
+    tooltip: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    class: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percentage: Option<u8>,
+}
+
+/// JSON object for a waybar custom module, of all segments or only `module`
+fn waybar(segments: &[Segment], module: Option<&str>, status: &mut String) {
+    let mut tooltip = String::new();
+    plain(segments, &mut tooltip);
+
+    let (text, level, percentage) = match module {
+        Some(module) => match segments.iter().find(|s| s.module == module) {
+            Some(segment) => (&*segment.text, segment.level, segment.percentage),
+            None => ("", Level::Normal, None),
+        },
+        None => {
+            let level = segments.iter().map(|s| s.level).max().unwrap_or_default();
+            (&*tooltip, level, None)
+        }
+    };
+
+    let waybar = WaybarJson {
+        text,
+        tooltip: &tooltip,
+        class: match level {
+            Level::Normal => "",
+            Level::Warning => "warning",
+            Level::Critical => "critical",
+        },
+        percentage: percentage.map(|p| p.clamp(0.0, 100.0).round() as u8),
+    };
+    status.push_str(&serde_json::to_string(&waybar).unwrap());
+}
+
+/// Blocks of the i3bar protocol, one per segment
+pub struct I3bar {
+    pub colors: Colors,
+    /// whether the header and the start of the infinite array have been written
+    started: bool,
+}
+
+impl I3bar {
+    pub fn new(colors: Colors) -> Self {
+        Self {
+            colors,
+            started: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct I3barBlock<'a> {
+    full_text: &'a str,
+    name: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    urgent: bool,
+}
+
+impl Output for I3bar {
+    fn name(&self) -> &'static str {
+        "i3bar"
+    }
+
+    fn compose(&self, segments: &[Segment], status: &mut String) {
+        let blocks: Vec<I3barBlock> = segments
+            .iter()
+            .map(|segment| {
+                let (color, background) = match self.colors.get(segment.level) {
+                    Some((fg, bg)) => (Some(fg), bg),
+                    None => (None, None),
+                };
+                I3barBlock {
+                    full_text: &segment.text,
+                    name: segment.module,
+                    color,
+                    background,
+                    urgent: segment.level == Level::Critical,
+                }
+            })
+            .collect();
+        status.push_str(&serde_json::to_string(&blocks).unwrap());
+    }
+
+    /// Write the blocks as the next element of the infinite array, after the header on the
+    /// first call
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        if !self.started {
+            writeln!(stdout, r#"{{"version":1,"click_events":true}}"#)?;
+            writeln!(stdout, "[")?;
+            self.started = true;
+        }
+        writeln!(stdout, "{status},")?;
+        stdout.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Segments as built by the render thread, with the optional modules present or not
+    fn segments(net: bool, bat: bool, todo: bool) -> Vec<Segment> {
+        let mut segments = vec![
+            Segment::new("host", "vm"),
+            Segment::new("user", "root"),
+            Segment::new("cpu", "cpu 02%"),
+        ];
+        if net {
+            segments.push(Segment::new("net", "net [10.0.0.2]"));
+        }
+        if bat {
+            segments.push(Segment::new("bat", "bat [80%]"));
+        }
+        if todo {
+            segments.push(Segment::new("todo", "todo 3"));
+        }
+        segments.push(Segment::new("time", "2024-01-01 00:00:00"));
+        segments
+    }
+
+    fn compose(output: &dyn Output, segments: &[Segment]) -> String {
+        let mut status = String::new();
+        output.compose(segments, &mut status);
+        status
+    }
+
+    /// Output failing every write, as xsetroot without an X server
+    struct Failing(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Output for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn write(&mut self, _status: &str) -> io::Result<()> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Err(io::Error::other("no display"))
+        }
+    }
+
+    #[test]
+    fn failing_output_backs_off() {
+        let writes = std::sync::Arc::default();
+        let mut outputs = Outputs::new(vec![Box::new(Failing(std::sync::Arc::clone(&writes)))]);
+        let mut status = String::new();
+        for _ in 0..3 {
+            outputs.write(&[Segment::new("cpu", "cpu 02%")], &mut status);
+        }
+        assert_eq!(writes.load(std::sync::atomic::Ordering::Relaxed), 1);
+        // still composed for the service and the logs
+        assert_eq!(status, "cpu 02%");
+        assert_eq!(outputs.outputs[0].backoff, BACKOFF_MIN);
+    }
+
+    #[test]
+    fn plain_optional_segments() {
+        for net in [false, true] {
+            for bat in [false, true] {
+                for todo in [false, true] {
+                    let mut expected = "[vm][root] => cpu 02%".to_string();
+                    if net {
+                        expected.push_str(", net [10.0.0.2]");
+                    }
+                    if bat {
+                        expected.push_str(", bat [80%]");
+                    }
+                    if todo {
+                        expected.push_str(", todo 3");
+                    }
+                    expected.push_str(", 2024-01-01 00:00:00");
+
+                    let status = compose(&Xsetroot::default(), &segments(net, bat, todo));
+                    assert_eq!(status, expected, "net {net}, bat {bat}, todo {todo}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn custom_prefix() {
+        let segments = segments(false, false, false);
+        let prefix = prefix("{user}@{host} | ", Some(&segments[0]), Some(&segments[1]));
+        assert_eq!(prefix.text, "root@vm | ");
+
+        let mut with_prefix = vec![prefix];
+        with_prefix.extend(segments[2..].iter().cloned());
+        assert_eq!(
+            compose(&Xsetroot::default(), &with_prefix),
+            "root@vm | cpu 02%, 2024-01-01 00:00:00"
+        );
+        // moved by --order
+        with_prefix.rotate_left(1);
+        assert_eq!(
+            compose(&Xsetroot::default(), &with_prefix),
+            "cpu 02%, 2024-01-01 00:00:00, root@vm | "
+        );
+    }
+
+    #[test]
+    fn plain_without_host_user() {
+        let segments = segments(false, false, false);
+        let without_user: Vec<_> = segments
+            .iter()
+            .filter(|s| s.module != "user")
+            .cloned()
+            .collect();
+        assert_eq!(
+            compose(&Xsetroot::default(), &without_user),
+            "[vm] => cpu 02%, 2024-01-01 00:00:00"
+        );
+
+        let without_prefix: Vec<_> = without_user
+            .into_iter()
+            .filter(|s| s.module != "host")
+            .collect();
+        assert_eq!(
+            compose(&Xsetroot::default(), &without_prefix),
+            "cpu 02%, 2024-01-01 00:00:00"
+        );
+
+        assert_eq!(compose(&Xsetroot::default(), &segments[..2]), "[vm][root]");
+        assert_eq!(compose(&Xsetroot::default(), &[]), "");
+    }
+
+    #[test]
+    fn separators_optional_segments() {
+        let colors = Colors {
+            warning: ("#ffb52a".to_string(), None),
+            critical: ("#fb4934".to_string(), None),
+        };
+        let lemonbar = Lemonbar {
+            left: vec!["net".to_string()],
+            center: vec![],
+            right: vec![],
+            colors: colors.clone(),
+        };
+        let dzen2 = Dzen2 {
+            colors,
+            padding: 10,
+        };
+
+        for net in [false, true] {
+            for bat in [false, true] {
+                for todo in [false, true] {
+                    let segments = segments(net, bat, todo);
+                    let texts: Vec<_> = segments.iter().map(|s| s.text.as_str()).collect();
+
+                    let status = compose(&dzen2, &segments);
+                    assert_eq!(status, texts.join("^p(10)"));
+
+                    let status = compose(&lemonbar, &segments);
+                    let right: Vec<_> = texts
+                        .iter()
+                        .filter(|text| !text.starts_with("net"))
+                        .map(|text| text.replace('%', "%%"))
+                        .collect();
+                    let expected = if net {
+                        format!("%{{l}}net [10.0.0.2]%{{r}}{}", right.join(" | "))
+                    } else {
+                        format!("%{{r}}{}", right.join(" | "))
+                    };
+                    assert_eq!(status, expected, "net {net}, bat {bat}, todo {todo}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn statuscmd_markers() {
+        let xsetroot = Xsetroot {
+            statuscmd: true,
+            ..Default::default()
+        };
+        let mut segments = segments(false, true, false);
+        segments[2].text = "cpu\x0702%\n".to_string();
+        assert_eq!(
+            compose(&xsetroot, &segments),
+            "[\x0fvm][\x0froot] => \x01cpu02%, \x04bat [80%], \x0e2024-01-01 00:00:00"
+        );
+
+        // only the statuscmd output is marked
+        let status = compose(&Xsetroot::default(), &segments);
+        assert!(!status.contains(['\x01', '\x04', '\x0e', '\x0f']));
+    }
+
+    #[test]
+    fn extrabar_halves() {
+        let xsetroot = Xsetroot {
+            bottom: vec!["time".into(), "bat".into()],
+            separator: ';',
+            ..Default::default()
+        };
+        let mut segments = segments(false, true, false);
+        segments[2].text = "cpu;02%".to_string();
+        assert_eq!(
+            compose(&xsetroot, &segments),
+            "[vm][root] => cpu02%;2024-01-01 00:00:00, bat [80%]"
+        );
+    }
+
+    #[test]
+    fn i3bar_blocks() {
+        let colors = Colors {
+            warning: ("#ffb52a".to_string(), None),
+            critical: ("#fb4934".to_string(), Some("#000000".to_string())),
+        };
+        let segments = [
+            Segment::new("cpu", "cpu 95%").level(Level::Critical),
+            Segment::new("mem", "mem \"5%\"").level(Level::Warning),
+            Segment::new("time", "2024-01-01 00:00:00"),
+        ];
+        assert_eq!(
+            compose(&I3bar::new(colors), &segments),
+            concat!(
+                r##"[{"full_text":"cpu 95%","name":"cpu","color":"#fb4934","background":"#000000","urgent":true},"##,
+                r##"{"full_text":"mem \"5%\"","name":"mem","color":"#ffb52a"},"##,
+                r##"{"full_text":"2024-01-01 00:00:00","name":"time"}]"##
+            )
+        );
+    }
+
+    #[test]
+    fn lemonbar_sections() {
+        let colors = Colors {
+            warning: ("#ffb52a".to_string(), None),
+            critical: ("#fb4934".to_string(), Some("#000000".to_string())),
+        };
+        let lemonbar = Lemonbar {
+            left: vec!["cpu".to_string(), "mem".to_string()],
+            center: vec!["time".to_string()],
+            right: vec!["bat".to_string()],
+            colors,
+        };
+        let segments = [
+            Segment::new("time", "2024-01-01 00:00:00"),
+            Segment::new("cpu", "cpu 95%").level(Level::Critical),
+            Segment::new("todo", "todo 3"),
+            Segment::new("mem", "mem 80%").level(Level::Warning),
+            Segment::new("bat", "bat [80%]"),
+        ];
+        assert_eq!(
+            compose(&lemonbar, &segments),
+            concat!(
+                "%{l}%{F#fb4934}%{B#000000}cpu 95%%%{B-}%{F-} | %{F#ffb52a}mem 80%%%{F-}",
+                "%{c}2024-01-01 00:00:00",
+                "%{r}bat [80%%] | todo 3"
+            )
+        );
+        // sections without a segment left out
+        assert_eq!(
+            compose(&lemonbar, &segments[..1]),
+            "%{c}2024-01-01 00:00:00"
+        );
+    }
+
+    #[test]
+    fn dzen2_colors() {
+        let colors = Colors {
+            warning: ("#ffb52a".to_string(), None),
+            critical: ("#fb4934".to_string(), Some("#000000".to_string())),
+        };
+        let dzen2 = Dzen2 { colors, padding: 6 };
+        let segments = [
+            Segment::new("cpu", "cpu 95%").level(Level::Critical),
+            Segment::new("mem", "mem 80%").level(Level::Warning),
+            Segment::new("time", "2024-01-01 00:00:00"),
+        ];
+        assert_eq!(
+            compose(&dzen2, &segments),
+            concat!(
+                "^fg(#fb4934)^bg(#000000)cpu 95%^bg()^fg()^p(6)",
+                "^fg(#ffb52a)mem 80%^fg()^p(6)",
+                "2024-01-01 00:00:00"
+            )
+        );
+        assert_eq!(compose(&dzen2, &segments[2..]), "2024-01-01 00:00:00");
+    }
+
+    #[test]
+    fn waybar_fields() {
+        let segments = [
+            Segment::new("cpu", "cpu 95%")
+                .level(Level::Critical)
+                .percentage(95.4),
+            Segment::new("mem", "mem 80%").level(Level::Warning),
+            Segment::new("time", "2024-01-01 00:00:00"),
+        ];
+        let waybar = |module: Option<&str>| {
+            compose(
+                &Waybar {
+                    module: module.map(str::to_string),
+                },
+                &segments,
+            )
+        };
+        let tooltip = "cpu 95%, mem 80%, 2024-01-01 00:00:00";
+        // all segments, of the highest level, without a single percentage
+        assert_eq!(
+            waybar(None),
+            format!(r#"{{"text":"{tooltip}","tooltip":"{tooltip}","class":"critical"}}"#)
+        );
+        assert_eq!(
+            waybar(Some("cpu")),
+            format!(
+                r#"{{"text":"cpu 95%","tooltip":"{tooltip}","class":"critical","percentage":95}}"#
+            )
+        );
+        assert_eq!(
+            waybar(Some("mem")),
+            format!(r#"{{"text":"mem 80%","tooltip":"{tooltip}","class":"warning"}}"#)
+        );
+        assert_eq!(
+            waybar(Some("time")),
+            format!(r#"{{"text":"2024-01-01 00:00:00","tooltip":"{tooltip}"}}"#)
+        );
+        // not collected yet
+        assert_eq!(
+            waybar(Some("bat")),
+            format!(r#"{{"text":"","tooltip":"{tooltip}"}}"#)
+        );
+    }
+
+    #[test]
+    fn hostile_text_every_output() {
+        let colors = Colors {
+            warning: ("#ffb52a".to_string(), None),
+            critical: ("#fb4934".to_string(), None),
+        };
+        let hostile = "50% ^fg(red)\"}]\n\x1b[2J\x07 東京 👩‍💻";
+        let text = sanitize::strip_control(hostile);
+        assert_eq!(text, "50% ^fg(red)\"}] [2J 東京 👩‍💻");
+        let segments = [Segment::new("sink", text.as_ref())];
+
+        for status in [
+            compose(&Xsetroot::default(), &segments),
+            compose(
+                &Xsetroot {
+                    statuscmd: true,
+                    ..Default::default()
+                },
+                &segments,
+            ),
+        ] {
+            assert!(status.ends_with(&*text), "{status:?}");
+        }
+        let lemonbar = Lemonbar {
+            left: vec![],
+            center: vec![],
+            right: vec![],
+            colors: colors.clone(),
+        };
+        assert_eq!(
+            compose(&lemonbar, &segments),
+            "%{r}50%% ^fg(red)\"}] [2J 東京 👩‍💻"
+        );
+        let dzen2 = Dzen2 {
+            colors: colors.clone(),
+            padding: 10,
+        };
+        assert_eq!(compose(&dzen2, &segments), "50% ^^fg(red)\"}] [2J 東京 👩‍💻");
+
+        // a single JSON value, the text round-tripping unchanged
+        let waybar: serde_json::Value =
+            serde_json::from_str(&compose(&Waybar { module: None }, &segments)).unwrap();
+        assert_eq!(waybar["text"], *text);
+        let i3bar: serde_json::Value =
+            serde_json::from_str(&compose(&I3bar::new(colors), &segments)).unwrap();
+        assert_eq!(i3bar[0]["full_text"], *text);
+    }
+
+    #[test]
+    fn sort_unlisted_after() {
+        let mut segments = segments(true, true, false);
+        sort(&mut segments, &["time".to_string(), "bat".to_string()]);
+        let modules: Vec<_> = segments.iter().map(|s| s.module).collect();
+        assert_eq!(modules, ["time", "bat", "host", "user", "cpu", "net"]);
+    }
+
+    #[test]
+    fn detected_output() {
+        assert_eq!(Format::detect_from(true, true, None).0, Format::I3bar);
+        assert_eq!(
+            Format::detect_from(true, false, Some(Ok(()))).0,
+            Format::Stdout
+        );
+        assert_eq!(
+            Format::detect_from(false, false, Some(Ok(()))).0,
+            Format::Xsetroot
+        );
+        // never fatal, xsetroot missing falling back to stdout
+        let missing = Some(Err("xsetroot isn't installed".to_string()));
+        assert_eq!(
+            Format::detect_from(false, false, missing),
+            (Format::Stdout, "xsetroot isn't installed".to_string())
+        );
+        assert_eq!(Format::detect_from(false, false, None).0, Format::Stdout);
+    }
+}
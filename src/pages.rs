@@ -0,0 +1,171 @@
+//! Pages of modules the bar rotates between, for more modules than fit in it
+//!
+//! Pages flip on a timer only, so that a module updating on another page doesn't bring it up.
+//! Modules in no page, such as time, are shown on every page.
+
+use std::time::{Duration, Instant};
+
+use crate::output::Segment;
+
+/// Modules of one `--page`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page(pub Vec<String>);
+
+impl Page {
+    /// clap value parser for `--page cpu,mem,bat`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let modules: Vec<String> = s.split(',').map(|m| m.trim().to_string()).collect();
+        if let Some(module) = modules
+            .iter()
+            .find(|m| !crate::MODULES.contains(&m.as_str()))
+        {
+            return Err(format!(
+                "unknown module `{module}`, expected one of: {}",
+                crate::MODULES.join(", ")
+            ));
+        }
+        Ok(Self(modules))
+    }
+}
+
+pub struct Pages {
+    pages: Vec<Page>,
+    interval: Duration,
+    current: usize,
+    /// when the current page was shown
+    shown: Instant,
+}
+
+impl Pages {
+    pub fn new(pages: Vec<Page>, interval: Duration) -> Self {
+        Self {
+            pages,
+            interval,
+            current: 0,
+            shown: Instant::now(),
+        }
+    }
+
+    /// Show the next page, such as after a click, for a whole interval
+    pub fn next(&mut self, now: Instant) {
+        self.current = (self.current + 1) % self.pages.len().max(1);
+        self.shown = now;
+    }
+
+    /// When the next page is due, never without a timer, such as with a zero interval only
+    /// flipping on clicks
+    pub fn due(&self) -> Option<Instant> {
+        (self.pages.len() > 1 && !self.interval.is_zero()).then(|| self.shown + self.interval)
+    }
+
+    /// Leave out the segments of the other pages, then append the `page` segment such as `1/2`
+    pub fn apply(&mut self, segments: &mut Vec<Segment>, now: Instant) {
+        if self.pages.len() < 2 {
+            return;
+        }
+        while self.due().is_some_and(|due| now >= due) {
+            self.current = (self.current + 1) % self.pages.len();
+            self.shown += self.interval;
+        }
+        // behind by more than a page, such as after a suspend
+        if self.due().is_some() && now.duration_since(self.shown) >= self.interval {
+            self.shown = now;
+        }
+
+        let current = &self.pages[self.current].0;
+        let on_page = |module: &str| {
+            current.iter().any(|m| m == module)
+                || !self
+                    .pages
+                    .iter()
+                    .any(|page| page.0.iter().any(|m| m == module))
+        };
+        segments.retain(|segment| on_page(segment.module));
+        let indicator = format!("{}/{}", self.current + 1, self.pages.len());
+        segments.push(Segment::new("page", indicator));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments() -> Vec<Segment> {
+        ["cpu", "mem", "net", "bat", "time"]
+            .into_iter()
+            .map(|module| Segment::new(module, module))
+            .collect()
+    }
+
+    fn modules(segments: &[Segment]) -> Vec<&str> {
+        segments.iter().map(|segment| &*segment.text).collect()
+    }
+
+    fn pages() -> Pages {
+        let pages = vec![
+            Page::parse("cpu,mem").unwrap(),
+            Page::parse("net,bat").unwrap(),
+        ];
+        Pages::new(pages, Duration::from_secs(5))
+    }
+
+    #[test]
+    fn rotates_on_interval() {
+        let mut pages = pages();
+        let start = pages.shown;
+
+        let mut shown = segments();
+        pages.apply(&mut shown, start + Duration::from_secs(4));
+        assert_eq!(modules(&shown), ["cpu", "mem", "time", "1/2"]);
+
+        let mut shown = segments();
+        pages.apply(&mut shown, start + Duration::from_secs(5));
+        assert_eq!(modules(&shown), ["net", "bat", "time", "2/2"]);
+
+        let mut shown = segments();
+        pages.apply(&mut shown, start + Duration::from_secs(11));
+        assert_eq!(modules(&shown), ["cpu", "mem", "time", "1/2"]);
+        assert_eq!(pages.due(), Some(start + Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn next_restarts_interval() {
+        let mut pages = pages();
+        let clicked = pages.shown + Duration::from_secs(3);
+        pages.next(clicked);
+        let mut shown = segments();
+        pages.apply(&mut shown, clicked + Duration::from_secs(4));
+        assert_eq!(modules(&shown), ["net", "bat", "time", "2/2"]);
+    }
+
+    #[test]
+    fn single_page_unchanged() {
+        let mut pages = Pages::new(vec![Page::parse("cpu").unwrap()], Duration::from_secs(5));
+        let mut shown = segments();
+        pages.apply(&mut shown, Instant::now());
+        assert_eq!(shown, segments());
+    }
+
+    #[test]
+    fn unknown_module() {
+        assert!(Page::parse("cpu,tmep")
+            .unwrap_err()
+            .starts_with("unknown module `tmep`"));
+    }
+
+    #[test]
+    fn zero_interval_flips_on_clicks_only() {
+        let mut pages = Pages::new(pages().pages, Duration::ZERO);
+        let start = pages.shown;
+        assert_eq!(pages.due(), None);
+
+        let mut shown = segments();
+        pages.apply(&mut shown, start + Duration::from_secs(60));
+        assert_eq!(modules(&shown), ["cpu", "mem", "time", "1/2"]);
+
+        pages.next(start + Duration::from_secs(61));
+        let mut shown = segments();
+        pages.apply(&mut shown, start + Duration::from_secs(62));
+        assert_eq!(modules(&shown), ["net", "bat", "time", "2/2"]);
+    }
+}
@@ -0,0 +1,64 @@
+//! Roots of sysfs and procfs, overridden by `--sysfs-root` and `--procfs-root` to run against
+//! fixture trees in tests
+//!
+//! Only the files read directly are redirected: sysinfo reads the real procfs for cpu, host,
+//! and user.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paths {
+    /// standing in for /sys
+    pub sysfs: PathBuf,
+    /// standing in for /proc
+    pub procfs: PathBuf,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Self {
+            sysfs: "/sys".into(),
+            procfs: "/proc".into(),
+        }
+    }
+}
+
+impl Paths {
+    /// `path`, such as /sys/class/power_supply, below its root. Paths of neither are unchanged.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        if let Ok(relative) = path.strip_prefix("/sys") {
+            self.sysfs.join(relative)
+        } else if let Ok(relative) = path.strip_prefix("/proc") {
+            self.procfs.join(relative)
+        } else {
+            path.to_path_buf()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_below_roots() {
+        let paths = Paths {
+            sysfs: "fixtures/sys".into(),
+            procfs: "fixtures/proc".into(),
+        };
+        assert_eq!(
+            paths.resolve("/sys/class/net"),
+            Path::new("fixtures/sys/class/net")
+        );
+        assert_eq!(
+            paths.resolve("/proc/meminfo"),
+            Path::new("fixtures/proc/meminfo")
+        );
+        assert_eq!(paths.resolve("/system"), Path::new("/system"));
+        assert_eq!(
+            Paths::default().resolve("/proc/meminfo"),
+            Path::new("/proc/meminfo")
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Glob patterns of names, such as `wl*` for the interfaces of `--interface`
+//!
+//! `*` matches any run of characters, `?` any one character, and `[a-z0-9]` or `[!0-9]` one
+//! character of, or not of, a set. Anything else matches itself.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    source: String,
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    /// ranges of characters, and whether they're negated
+    Set(Vec<(char, char)>, bool),
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Self, String> {
+        if source.is_empty() {
+            return Err("empty pattern".into());
+        }
+        let chars: Vec<char> = source.chars().collect();
+        let mut tokens = vec![];
+        let mut i = 0;
+        while i < chars.len() {
+            let token = match chars[i] {
+                '*' => Token::Star,
+                '?' => Token::Any,
+                '[' => {
+                    let (set, end) = set(&chars, i + 1)
+                        .ok_or_else(|| format!("unclosed [ in pattern \"{source}\""))?;
+                    i = end;
+                    set
+                }
+                c => Token::Char(c),
+            };
+            tokens.push(token);
+            i += 1;
+        }
+        Ok(Self {
+            source: source.to_string(),
+            tokens,
+        })
+    }
+
+    /// Whether the pattern has no wildcard, only matching itself
+    pub fn is_literal(&self) -> bool {
+        self.tokens
+            .iter()
+            .all(|token| matches!(token, Token::Char(_)))
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        matches(&self.tokens, name)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Set starting at `start` after its `[`, and the index of its `]`, `None` if unclosed. A `]`
+/// first in the set is part of it.
+fn set(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let negated = chars.get(start) == Some(&'!');
+    let mut i = start + usize::from(negated);
+    let mut ranges = vec![];
+    let first = i;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && i != first {
+            return Some((Token::Set(ranges, negated), i));
+        }
+        match chars.get(i + 1..i + 3) {
+            Some(['-', end]) if *end != ']' => {
+                ranges.push((c, *end));
+                i += 3;
+            }
+            _ => {
+                ranges.push((c, c));
+                i += 1;
+            }
+        }
+    }
+}
+
+fn matches(tokens: &[Token], name: &str) -> bool {
+    match tokens.split_first() {
+        None => name.is_empty(),
+        Some((Token::Star, rest)) => (0..=name.len())
+            .filter(|i| name.is_char_boundary(*i))
+            .any(|i| matches(rest, &name[i..])),
+        Some((token, rest)) => {
+            let mut chars = name.chars();
+            let Some(c) = chars.next() else {
+                return false;
+            };
+            let matched = match token {
+                Token::Char(expected) => c == *expected,
+                Token::Any => true,
+                Token::Set(ranges, negated) => {
+                    ranges
+                        .iter()
+                        .any(|(start, end)| (*start..=*end).contains(&c))
+                        != *negated
+                }
+                Token::Star => unreachable!(),
+            };
+            matched && matches(rest, chars.as_str())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        Pattern::new(pattern).unwrap().matches(name)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("wl*", "wlan0"));
+        assert!(matches("wl*", "wlp3s0"));
+        assert!(!matches("wl*", "enp0s31f6"));
+        assert!(matches("en*s*", "enp0s31f6"));
+        assert!(matches("eth?", "eth0"));
+        assert!(!matches("eth?", "eth10"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn sets() {
+        assert!(matches("eth[0-3]", "eth2"));
+        assert!(!matches("eth[0-3]", "eth4"));
+        assert!(matches("veth[!0-9]*", "vethab12"));
+        assert!(!matches("veth[!0-9]*", "veth0"));
+        assert!(matches("a[]]", "a]"));
+        assert!(matches("a[-x]", "a-"));
+    }
+
+    #[test]
+    fn literal() {
+        assert!(Pattern::new("wlan0").unwrap().is_literal());
+        assert!(!Pattern::new("wlan?").unwrap().is_literal());
+        assert!(matches("wlan0", "wlan0"));
+        assert!(!matches("wlan0", "wlan01"));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            Pattern::new("eth[0-").unwrap_err(),
+            "unclosed [ in pattern \"eth[0-\""
+        );
+        assert_eq!(Pattern::new("").unwrap_err(), "empty pattern");
+    }
+}
@@ -0,0 +1,74 @@
+//! Formatting of the percentages of cpu, mem, and bat, padded to a fixed width so that the bar
+//! doesn't shift as they change magnitude
+
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Percent {
+    /// zero padded width, the decimals and their point included
+    pub width: usize,
+    pub decimals: usize,
+}
+
+impl Default for Percent {
+    /// As `05`
+    fn default() -> Self {
+        Self {
+            width: 2,
+            decimals: 0,
+        }
+    }
+}
+
+impl Percent {
+    /// Append `value` rounded half away from zero, without the `%`
+    pub fn write(&self, value: f64, out: &mut String) {
+        let scale = 10_f64.powi(self.decimals as i32);
+        let rounded = (value * scale).round() / scale;
+        let (width, decimals) = (self.width, self.decimals);
+        write!(out, "{rounded:0width$.decimals$}").unwrap();
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        let mut out = String::new();
+        self.write(value, &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARIES: [f64; 4] = [0.0, 9.4, 99.5, 100.0];
+
+    fn formatted(percent: Percent) -> Vec<String> {
+        BOUNDARIES
+            .iter()
+            .map(|value| percent.format(*value))
+            .collect()
+    }
+
+    #[test]
+    fn default_width() {
+        assert_eq!(formatted(Percent::default()), ["00", "09", "100", "100"]);
+    }
+
+    #[test]
+    fn fixed_width() {
+        let percent = Percent {
+            width: 3,
+            decimals: 0,
+        };
+        assert_eq!(formatted(percent), ["000", "009", "100", "100"]);
+    }
+
+    #[test]
+    fn one_decimal() {
+        let percent = Percent {
+            width: 5,
+            decimals: 1,
+        };
+        assert_eq!(formatted(percent), ["000.0", "009.4", "099.5", "100.0"]);
+    }
+}
@@ -0,0 +1,245 @@
+//! Time each scheduled module takes to collect, and the render thread to compose and write the
+//! status, for `--profile`
+//!
+//! Kept over the last [`SAMPLES`] passes of each, as a table logged every minute and replied to
+//! the `stats` command of `--control`. A module averaging more than `--profile-warn` of its
+//! interval is warned about once, until back under.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::signal;
+
+/// Passes the timings are kept over
+const SAMPLES: usize = 60;
+
+/// Passes before a module can be warned about, not on a slow first one
+const WARN_SAMPLES: usize = 5;
+
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name of the render thread in the table
+const RENDER: &str = "render";
+
+static PROFILE: Mutex<Option<Profile>> = Mutex::new(None);
+
+/// clap value parser of `--profile-warn`, a fraction of the interval within (0, 1]
+pub fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        _ => Err(format!(
+            "invalid fraction `{s}`, expected e.g. 0.5, within (0, 1]"
+        )),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Timings {
+    samples: VecDeque<Duration>,
+    /// of the module, none for the render thread
+    interval: Option<Duration>,
+    /// warned about, until its average is back under
+    slow: bool,
+}
+
+impl Timings {
+    fn push(&mut self, elapsed: Duration) {
+        if self.samples.len() == SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(elapsed);
+    }
+
+    fn min(&self) -> Duration {
+        self.samples.iter().min().copied().unwrap_or_default()
+    }
+
+    fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+
+    fn average(&self) -> Duration {
+        let count = self.samples.len().max(1) as u32;
+        self.samples.iter().sum::<Duration>() / count
+    }
+}
+
+#[derive(Debug)]
+struct Profile {
+    /// of the interval, from which a module is slow
+    fraction: f64,
+    /// by module, in the order first collected
+    timings: Vec<(&'static str, Timings)>,
+}
+
+impl Profile {
+    fn new(fraction: f64) -> Self {
+        Self {
+            fraction,
+            timings: vec![],
+        }
+    }
+
+    fn timings(&mut self, module: &'static str) -> &mut Timings {
+        let i = match self.timings.iter().position(|(name, _)| *name == module) {
+            Some(i) => i,
+            None => {
+                self.timings.push((module, Timings::default()));
+                self.timings.len() - 1
+            }
+        };
+        &mut self.timings[i].1
+    }
+
+    /// Record a pass of `module`, whether it just became slow or back under, if either
+    fn collected(
+        &mut self,
+        module: &'static str,
+        elapsed: Duration,
+        interval: Duration,
+    ) -> Option<bool> {
+        let fraction = self.fraction;
+        let timings = self.timings(module);
+        timings.push(elapsed);
+        timings.interval = Some(interval);
+        if timings.samples.len() < WARN_SAMPLES {
+            return None;
+        }
+        let slow = timings.average().as_secs_f64() > interval.as_secs_f64() * fraction;
+        (slow != timings.slow).then(|| {
+            timings.slow = slow;
+            slow
+        })
+    }
+
+    /// One line per module, the slowest first, then the render thread
+    fn table(&self) -> String {
+        let mut rows: Vec<&(&str, Timings)> = self
+            .timings
+            .iter()
+            .filter(|(module, _)| *module != RENDER)
+            .collect();
+        rows.sort_by_key(|(_, timings)| std::cmp::Reverse(timings.average()));
+        rows.extend(self.timings.iter().filter(|(module, _)| *module == RENDER));
+
+        let millis = |duration: Duration| format!("{:.1}ms", duration.as_secs_f64() * 1000.0);
+        let mut table = format!(
+            "{:<12} {:>6} {:>9} {:>9} {:>9} {:>9}\n",
+            "module", "passes", "min", "avg", "max", "interval"
+        );
+        for (module, timings) in rows {
+            let interval = timings
+                .interval
+                .map_or("-".to_string(), |interval| format!("{interval:?}"));
+            let _ = writeln!(
+                table,
+                "{module:<12} {:>6} {:>9} {:>9} {:>9} {interval:>9}",
+                timings.samples.len(),
+                millis(timings.min()),
+                millis(timings.average()),
+                millis(timings.max()),
+            );
+        }
+        table
+    }
+}
+
+/// Start measuring, a module being slow from `fraction` of its interval
+pub fn enable(fraction: f64) {
+    *PROFILE.lock().unwrap() = Some(Profile::new(fraction));
+}
+
+/// Record that `module` took `elapsed` to collect, warning once it's slow
+pub fn collected(module: &'static str, elapsed: Duration, interval: Duration) {
+    let mut profile = PROFILE.lock().unwrap();
+    let Some(profile) = profile.as_mut() else {
+        return;
+    };
+    let fraction = profile.fraction;
+    match profile.collected(module, elapsed, interval) {
+        Some(true) => warn!(
+            target: module,
+            "collecting takes {:?} on average, over {:.0}% of its {interval:?} interval",
+            profile.timings(module).average(),
+            fraction * 100.0
+        ),
+        Some(false) => info!(target: module, "collecting is back under its interval"),
+        None => (),
+    }
+}
+
+/// Record that composing and writing the status took `elapsed`
+pub fn rendered(elapsed: Duration) {
+    if let Some(profile) = PROFILE.lock().unwrap().as_mut() {
+        profile.timings(RENDER).push(elapsed);
+    }
+}
+
+/// The table of the timings, `None` unless measuring
+pub fn table() -> Option<String> {
+    PROFILE.lock().unwrap().as_ref().map(Profile::table)
+}
+
+/// Log the table every [`REPORT_INTERVAL`] until stopping
+pub fn report() {
+    while signal::idle(REPORT_INTERVAL) {
+        if let Some(table) = table() {
+            for line in table.lines() {
+                info!(target: "profile", "{line}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn rolling_timings() {
+        let mut timings = Timings::default();
+        for ms in 0..=SAMPLES as u32 {
+            timings.push(ms * MS);
+        }
+        assert_eq!(timings.samples.len(), SAMPLES);
+        assert_eq!(timings.min(), MS);
+        assert_eq!(timings.max(), SAMPLES as u32 * MS);
+        assert_eq!(timings.average(), Duration::from_micros(30_500));
+    }
+
+    #[test]
+    fn slow_module_warned_once() {
+        let mut profile = Profile::new(0.5);
+        let second = Duration::from_secs(1);
+        for _ in 0..WARN_SAMPLES - 1 {
+            assert_eq!(profile.collected("sink", 800 * MS, second), None);
+        }
+        assert_eq!(profile.collected("sink", 800 * MS, second), Some(true));
+        assert_eq!(profile.collected("sink", 800 * MS, second), None);
+        for _ in 0..SAMPLES {
+            profile.collected("sink", MS, second);
+        }
+        assert!(!profile.timings("sink").slow);
+    }
+
+    #[test]
+    fn slowest_first() {
+        let mut profile = Profile::new(0.5);
+        profile.collected("cpu", MS, Duration::from_secs(1));
+        profile.timings(RENDER).push(2 * MS);
+        profile.collected("smart", 40 * MS, Duration::from_secs(600));
+        assert_eq!(
+            profile.table(),
+            "module       passes       min       avg       max  interval\n\
+             smart             1    40.0ms    40.0ms    40.0ms      600s\n\
+             cpu               1     1.0ms     1.0ms     1.0ms        1s\n\
+             render            1     2.0ms     2.0ms     2.0ms         -\n"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! History of the displayed values for `--record`, appended to a CSV or JSON Lines file by a
+//! thread of its own so that a slow disk never holds up the bar
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, SecondsFormat};
+use clap::ValueEnum;
+use log::warn;
+
+use crate::output::Segment;
+
+/// Interval of flushing the buffered samples
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Percentages of the modules, the columns of the CSV format, fixed for its header to hold
+const COLUMNS: &[&str] = &["cpu", "mem", "bat"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// a `timestamp,cpu,mem,bat` header, then a row of their percentages per sample
+    #[default]
+    Csv,
+    /// an object per line of every numeric value, such as
+    /// {"timestamp":"2024-01-01T09:00:00+01:00","cpu":12.5,"temp.cpu":62.0}
+    Jsonl,
+}
+
+/// Values of one render pass: the percentage of each module under its name, and its other
+/// numeric values as `module.value` such as `traffic.rx_bytes`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub time: DateTime<Local>,
+    pub values: Vec<(String, f64)>,
+}
+
+impl Sample {
+    /// The numeric values of `segments` at `time`, in the order of their modules
+    pub fn new<'a>(time: DateTime<Local>, segments: impl IntoIterator<Item = &'a Segment>) -> Self {
+        let mut segments: Vec<&Segment> = segments.into_iter().collect();
+        segments.sort_by_key(|segment| segment.module);
+        let mut values = vec![];
+        for segment in segments {
+            if let Some(percentage) = segment.percentage {
+                values.push((segment.module.to_string(), percentage));
+            }
+            for (name, value) in &segment.values {
+                if let Some(value) = value.parse::<f64>().ok().filter(|value| value.is_finite()) {
+                    values.push((format!("{}.{name}", segment.module), value));
+                }
+            }
+        }
+        Self { time, values }
+    }
+}
+
+/// clap value parser for sizes such as `512K`, `10M`, or `1G`, in bytes
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid size `{s}`, expected e.g. 512K, 10M, 1G"))?;
+    let multiplier = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => {
+            return Err(format!(
+                "invalid size unit `{unit}`, expected one of K, M, G"
+            ))
+        }
+    };
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("invalid size `{s}`, too large"))
+}
+
+struct Recorder {
+    path: PathBuf,
+    format: Format,
+    max_size: u64,
+    writer: BufWriter<File>,
+    /// bytes in the file, written or buffered
+    size: u64,
+}
+
+impl Recorder {
+    fn open(path: PathBuf, format: Format, max_size: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        let mut recorder = Self {
+            path,
+            format,
+            max_size,
+            writer: BufWriter::new(file),
+            size,
+        };
+        if size == 0 {
+            recorder.header()?;
+        }
+        Ok(recorder)
+    }
+
+    fn header(&mut self) -> io::Result<()> {
+        if self.format == Format::Csv {
+            let header = format!("timestamp,{}\n", COLUMNS.join(","));
+            self.write(&header)?;
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, sample: &Sample) -> io::Result<()> {
+        let line = line(sample, self.format);
+        if self.size + line.len() as u64 > self.max_size && self.size > 0 {
+            self.rotate()?;
+        }
+        self.write(&line)
+    }
+
+    fn write(&mut self, s: &str) -> io::Result<()> {
+        self.writer.write_all(s.as_bytes())?;
+        self.size += s.len() as u64;
+        Ok(())
+    }
+
+    /// Keep the full file as `<path>.1`, replacing the previous one, and start anew
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        std::fs::rename(&self.path, rotated(&self.path))?;
+        let file = File::create(&self.path)?;
+        self.writer = BufWriter::new(file);
+        self.size = 0;
+        self.header()
+    }
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    rotated.into()
+}
+
+/// A line of `sample`, newline included, its values rounded to hundredths
+fn line(sample: &Sample, format: Format) -> String {
+    let round = |value: f64| (value * 100.0).round() / 100.0;
+    let timestamp = sample.time.to_rfc3339_opts(SecondsFormat::Secs, false);
+    match format {
+        Format::Csv => {
+            let mut line = timestamp;
+            for column in COLUMNS {
+                line.push(',');
+                if let Some((_, value)) = sample.values.iter().find(|(m, _)| m == column) {
+                    line.push_str(&round(*value).to_string());
+                }
+            }
+            line.push('\n');
+            line
+        }
+        Format::Jsonl => {
+            let mut object = serde_json::Map::new();
+            object.insert("timestamp".into(), timestamp.into());
+            for (name, value) in &sample.values {
+                object.insert(name.clone(), round(*value).into());
+            }
+            format!("{}\n", serde_json::Value::Object(object))
+        }
+    }
+}
+
+/// Append the samples of `rx` to `path` until the render thread hangs up, flushed every few
+/// seconds. An IO error disables recording, leaving the bar unaffected.
+pub fn serve(path: PathBuf, format: Format, max_size: u64, rx: Receiver<Sample>) {
+    let mut recorder = match Recorder::open(path.clone(), format, max_size) {
+        Ok(recorder) => recorder,
+        Err(e) => {
+            warn!("unable to record to {}, disabling: {e}", path.display());
+            return;
+        }
+    };
+    let mut flushed = Instant::now();
+    loop {
+        let result = match rx.recv_timeout(FLUSH_INTERVAL.saturating_sub(flushed.elapsed())) {
+            Ok(sample) => recorder.record(&sample),
+            Err(RecvTimeoutError::Timeout) => Ok(()),
+            Err(RecvTimeoutError::Disconnected) => {
+                if let Err(e) = recorder.writer.flush() {
+                    warn!("unable to record to {}: {e}", path.display());
+                }
+                return;
+            }
+        };
+        let result = result.and_then(|()| {
+            if flushed.elapsed() >= FLUSH_INTERVAL {
+                flushed = Instant::now();
+                recorder.writer.flush()?;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            warn!("unable to record to {}, disabling: {e}", path.display());
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn sample() -> Sample {
+        Sample {
+            time: Local.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap(),
+            values: vec![("bat".into(), 80.0), ("cpu".into(), 12.504)],
+        }
+    }
+
+    fn timestamp() -> String {
+        sample().time.to_rfc3339_opts(SecondsFormat::Secs, false)
+    }
+
+    #[test]
+    fn lines() {
+        let timestamp = timestamp();
+        assert_eq!(
+            line(&sample(), Format::Csv),
+            format!("{timestamp},12.5,,80\n")
+        );
+        assert_eq!(
+            line(&sample(), Format::Jsonl),
+            format!("{{\"bat\":80.0,\"cpu\":12.5,\"timestamp\":\"{timestamp}\"}}\n")
+        );
+    }
+
+    #[test]
+    fn every_numeric_value() {
+        let segments = [
+            Segment::new("temp", "cpu 62°")
+                .percentage(62.0)
+                .value("cpu", 62.4),
+            Segment::new("traffic", "today ↓1.0G ↑0B")
+                .value("rx_bytes", 1u64 << 30)
+                .value("tx_bytes", 0),
+            Segment::new("nm", "HomeWifi").value("id", "HomeWifi"),
+            Segment::new("cpu", "cpu 12%").percentage(12.504),
+        ];
+        let sample = Sample::new(sample().time, &segments);
+        let names: Vec<&str> = sample.values.iter().map(|(name, _)| &**name).collect();
+        assert_eq!(
+            names,
+            [
+                "cpu",
+                "temp",
+                "temp.cpu",
+                "traffic.rx_bytes",
+                "traffic.tx_bytes"
+            ]
+        );
+        assert_eq!(
+            line(&sample, Format::Jsonl),
+            format!(
+                "{{\"cpu\":12.5,\"temp\":62.0,\"temp.cpu\":62.4,\"timestamp\":\"{}\",\
+                 \"traffic.rx_bytes\":1073741824.0,\"traffic.tx_bytes\":0.0}}\n",
+                timestamp()
+            )
+        );
+        assert_eq!(
+            line(&sample, Format::Csv),
+            format!("{},12.5,,\n", timestamp())
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("10M"), Ok(10 << 20));
+        assert!(parse_size("10MB").is_err());
+        assert!(parse_size("M").is_err());
+        assert_eq!(parse_size("17179869183G"), Ok(17179869183 << 30));
+        assert!(parse_size("17179869184G")
+            .unwrap_err()
+            .contains("too large"));
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = std::env::temp_dir().join(format!("statusbar-record-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.csv");
+        let header = "timestamp,cpu,mem,bat\n";
+        let row = format!("{},12.5,,80\n", timestamp());
+        // room for the header and two rows
+        let max_size = (header.len() + 2 * row.len()) as u64;
+
+        let (tx, rx) = channel();
+        for _ in 0..3 {
+            tx.send(sample()).unwrap();
+        }
+        drop(tx);
+        serve(path.clone(), Format::Csv, max_size, rx);
+
+        let read = |path| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(rotated(&path)), format!("{header}{row}{row}"));
+        assert_eq!(read(path), format!("{header}{row}"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+//! The one place a module is registered, built from the arguments
+//!
+//! Every segment but the user is a [`Module`], run by the scheduler of [`statusbar::modules::run`].
+
+use log::warn;
+use statusbar::glyphs::{Glyph, Glyphs};
+use statusbar::locale::Locale;
+use statusbar::marquee::Marquee;
+#[cfg(feature = "x11")]
+use statusbar::modules::Displays;
+use statusbar::modules::{
+    smart_devices, traffic_state_path, Backup, BackupSource, Battery, Calendar, Cgroup, Clock, Cpu,
+    CpuDetail, DataCap, Fd, Flatpak, Host, Idle, Lid, Memory, Module, Net, Offline, Oom, Privacy,
+    ReadOnly, Selection, Sink, Smart, Snapshots, Swap, Tcp, Temp, Throttle, Todo, Traffic, Ups,
+    Zram, CGROUP_PATH, FILE_NR_PATH, MEMINFO_PATH, MOUNTS_PATH, NET_DEV_PATH, POWER_SUPPLY_PATH,
+    PROC_STAT_PATH, SELF_CGROUP_PATH, SELF_FD_PATH, SYS_BLOCK_PATH, SYS_CPU_PATH, TCP6_PATH,
+    TCP_PATH, VMSTAT_PATH,
+};
+#[cfg(feature = "network")]
+use statusbar::modules::{Cert, Http};
+#[cfg(feature = "dbus")]
+use statusbar::modules::{Im, Nm, Ntp, Peripherals, Timers};
+use statusbar::paths::Paths;
+use statusbar::percent::Percent;
+
+use crate::Cli;
+
+/// Modules of the segments enabled by `args`, `clicks` letting net sample the throughput for
+/// its view after a click. An error is of an invalid argument.
+pub fn modules(
+    args: &Cli,
+    paths: &Paths,
+    glyphs: &Glyphs,
+    selection: Selection,
+    time_locale: Option<Locale>,
+    clicks: bool,
+) -> Result<Vec<Box<dyn Module>>, String> {
+    let net_marquee = Marquee::new(
+        args.max_width.clone(),
+        args.scroll_gap.clone(),
+        !args.no_scroll,
+    )
+    .ellipsis(glyphs.get(Glyph::Ellipsis));
+    let percent = Percent {
+        width: args.percent_width,
+        decimals: args.percent_decimals.into(),
+    };
+    let traffic = args.traffic.then(|| {
+        let dev = paths.resolve(NET_DEV_PATH);
+        let traffic = Traffic::new(selection.clone(), dev, traffic_state_path(), glyphs.clone());
+        match args.data_cap {
+            Some(bytes) => traffic.data_cap(DataCap {
+                bytes,
+                period: args.data_cap_period,
+                notify: args.data_cap_notify,
+            }),
+            None => traffic,
+        }
+    });
+    let mut battery = Battery::new(&paths.resolve(POWER_SUPPLY_PATH))
+        .percent(percent)
+        .show_limit(args.battery_show_limit)
+        .glyphs(glyphs.clone());
+    if let Some(points) = args.bat_hysteresis {
+        battery = battery.hysteresis(points);
+    }
+    let cpu: Box<dyn Module> = if args.cpu_detail {
+        Box::new(CpuDetail::new(paths.resolve(PROC_STAT_PATH)).fields(args.cpu_fields.clone()))
+    } else {
+        let mut cpu = Cpu::new().percent(percent);
+        if let Some(alpha) = args.cpu_smoothing {
+            cpu = cpu.smoothing(alpha);
+        }
+        Box::new(cpu)
+    };
+    let mut modules: Vec<Box<dyn Module>> = vec![
+        Box::new(battery),
+        Box::new(
+            Memory::new(paths.resolve(MEMINFO_PATH))
+                .percent(percent)
+                .mode(args.mem_mode)
+                .cgroup(paths.resolve(SELF_CGROUP_PATH), paths.resolve(CGROUP_PATH)),
+        ),
+        cpu,
+        Box::new(
+            Net::new(
+                selection,
+                args.hide_net_down,
+                args.net_labels,
+                args.net_mode,
+                clicks,
+                net_marquee,
+            )
+            .paths(paths),
+        ),
+        Box::new(
+            Clock::new(&args.time_format, &args.date_format)
+                .locale(time_locale)
+                .seconds(!args.clock_no_seconds)
+                .map_err(|e| format!("invalid --time-format: {e}"))?
+                .blink(args.clock_blink),
+        ),
+    ];
+    if let Some(traffic) = traffic {
+        modules.push(Box::new(traffic));
+    }
+    if args.readonly {
+        let readonly = ReadOnly::new(
+            paths.resolve(MOUNTS_PATH),
+            args.readonly_mount.clone(),
+            args.readonly_exclude.clone(),
+        );
+        modules.push(Box::new(readonly.notify(args.readonly_notify)));
+    }
+    if !args.temp.is_empty() || !args.thermal_zone.is_empty() {
+        let sensors = args
+            .temp
+            .iter()
+            .chain(&args.thermal_zone)
+            .cloned()
+            .collect();
+        let excluded = args.thermal_zone_exclude.clone();
+        match Temp::new(sensors, excluded, paths, glyphs.clone()) {
+            Ok(temp) => modules.push(Box::new(temp)),
+            Err(e) if args.thermal_zone.is_empty() => return Err(format!("invalid --temp: {e}")),
+            Err(e) => return Err(format!("invalid --temp or --thermal-zone: {e}")),
+        }
+    }
+    if args.throttle {
+        let sys_cpu = paths.resolve(SYS_CPU_PATH);
+        let stat = paths.resolve(PROC_STAT_PATH);
+        match Throttle::new(&sys_cpu, stat, args.throttle_quiet) {
+            Some(throttle) => modules.push(Box::new(throttle)),
+            None => warn!(
+                target: "throttle",
+                "no throttling counters or cpufreq in {}, throttle hidden",
+                sys_cpu.display()
+            ),
+        }
+    }
+    if args.swap {
+        let swap = Swap::new(paths.resolve(VMSTAT_PATH)).idle(args.swap_show_idle);
+        modules.push(Box::new(swap));
+    }
+    if args.zram {
+        modules.push(Box::new(Zram::new(paths, glyphs.clone())));
+    }
+    if args.tcp {
+        let tcp = Tcp::new(paths.resolve(TCP_PATH), paths.resolve(TCP6_PATH));
+        modules.push(Box::new(tcp.port(args.tcp_filter_port)));
+    }
+    if args.fd {
+        let fd = Fd::new(paths.resolve(FILE_NR_PATH));
+        modules.push(Box::new(
+            fd.self_fd(args.fd_self.then(|| paths.resolve(SELF_FD_PATH))),
+        ));
+    }
+    if let Some(target) = &args.cgroup {
+        match Cgroup::new(target, paths.resolve(CGROUP_PATH)) {
+            Ok(cgroup) => modules.push(Box::new(cgroup)),
+            Err(e) => warn!(target: "cgroup", "{e}, cgroup hidden"),
+        }
+    }
+    if args.oom {
+        let vmstat = paths.resolve(VMSTAT_PATH);
+        match Oom::new(&vmstat) {
+            Ok(Some(oom)) => modules.push(Box::new(
+                oom.reset_after(args.oom_reset_after)
+                    .notify(args.oom_notify),
+            )),
+            Ok(None) => warn!(target: "oom", "no oom_kill in {}, oom hidden", vmstat.display()),
+            Err(e) => warn!(target: "oom", "unable to read {}: {e}, oom hidden", vmstat.display()),
+        }
+    }
+    modules.push(Box::new(Host::new(
+        args.hostname.clone(),
+        args.hostname_short,
+    )));
+
+    if let Some(todo) = args.todo.clone() {
+        modules.push(Box::new(Todo::new(todo, args.todo_filter.clone())));
+    }
+    let backup_source = match (&args.backup_stamp, args.backup_restic) {
+        (Some(stamp), _) => Some(BackupSource::Stamp(stamp.clone())),
+        (None, true) => Some(BackupSource::Restic),
+        (None, false) => None,
+    };
+    if let Some(source) = backup_source {
+        let backup = Backup::new(source, args.backup_interval, args.backup_warn);
+        modules.push(Box::new(backup));
+    }
+    if !args.ics.is_empty() {
+        modules.push(Box::new(Calendar::new(
+            args.ics.clone(),
+            args.ics_lookahead,
+        )));
+    }
+    #[cfg(feature = "dbus")]
+    if !args.timer.is_empty() {
+        modules.push(Box::new(Timers::new(args.timer.clone())));
+    }
+    let smart_devices = smart_devices(
+        &args.smart_device,
+        &args.smart_mount,
+        &paths.resolve(MOUNTS_PATH),
+        &paths.resolve(SYS_BLOCK_PATH),
+    );
+    if !smart_devices.is_empty() {
+        let smart = Smart::new(smart_devices, args.smart_interval);
+        modules.push(Box::new(smart.notify(args.smart_notify)));
+    }
+    if let Some(backend) = args.snapshot_backend {
+        modules.push(Box::new(Snapshots::new(
+            backend,
+            args.snapshot_config.clone(),
+            args.snapshot_path.clone(),
+            args.snapshot_interval,
+            args.snapshot_warn,
+        )));
+    }
+    if args.flatpak_updates {
+        modules.push(Box::new(Flatpak::new(args.updates_interval)));
+    }
+    if args.offline {
+        let offline = Offline::new(paths, args.offline_marker.clone());
+        modules.push(Box::new(offline));
+    }
+    #[cfg(feature = "network")]
+    if !args.cert_check.is_empty() {
+        modules.push(Box::new(Cert::new(
+            args.cert_check.clone(),
+            args.cert_interval,
+            args.cert_warn_days,
+        )));
+    }
+    #[cfg(feature = "network")]
+    if !args.http_check.is_empty() {
+        modules.push(Box::new(Http::new(
+            args.http_check.clone(),
+            args.http_method,
+        )));
+    }
+    #[cfg(feature = "dbus")]
+    if args.ntp {
+        modules.push(Box::new(Ntp::new(args.ntp_offset, glyphs)));
+    }
+    if let Some(backend) = args.ups_backend {
+        let name = args
+            .ups_name
+            .clone()
+            .unwrap_or_else(|| backend.default_name().to_string());
+        modules.push(Box::new(Ups::new(backend, name, glyphs.clone())));
+    }
+    if args.lid {
+        match Lid::new(paths) {
+            Some(lid) => modules.push(Box::new(lid)),
+            None => warn!(target: "lid", "no lid device found, lid hidden"),
+        }
+    }
+    #[cfg(feature = "x11")]
+    if args.displays {
+        modules.push(Box::new(Displays::new(args.displays_names)));
+    }
+    #[cfg(feature = "dbus")]
+    if args.nm {
+        modules.push(Box::new(Nm::new(glyphs.clone())));
+    }
+    if args.idle {
+        modules.push(Box::new(Idle::new(args.idle_floor)));
+    }
+    #[cfg(feature = "dbus")]
+    if args.peripherals {
+        modules.push(Box::new(Peripherals::new(glyphs.clone())));
+    }
+    #[cfg(feature = "dbus")]
+    if let Some(backend) = args.im_backend {
+        modules.push(Box::new(Im::new(backend, glyphs)));
+    }
+    if args.privacy_indicators {
+        modules.push(Box::new(Privacy::new(glyphs, paths)));
+    }
+    if args.sink {
+        modules.push(Box::new(Sink::new(args.sink_alias.clone())));
+    }
+    Ok(modules)
+}
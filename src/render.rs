@@ -0,0 +1,510 @@
+//! The render thread, composing the status from the latest segments of the store and writing it
+//! to the outputs, the control socket, the metrics and the other consumers of the values
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::ScopedJoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::Local;
+use log::{info, warn};
+use statusbar::control;
+use statusbar::glyphs::{Glyph, Glyphs};
+use statusbar::marquee::Marquee;
+use statusbar::metrics::Metrics;
+#[cfg(feature = "mqtt")]
+use statusbar::mqtt;
+use statusbar::output::{self, Outputs, Segment};
+use statusbar::pages::Pages;
+#[cfg(feature = "dbus")]
+use statusbar::service;
+use statusbar::store::Store;
+use statusbar::style::{self, Level, Thresholds, Visibility};
+use statusbar::truncate::Limits;
+use statusbar::{duration, profile, record, sanitize, signal, systemd, MODULES};
+
+use crate::Cli;
+
+/// Interval of rendering the status, as the clock publishes the time every second
+const RENDER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest wait for the first pass of the scheduler before the first render
+const RENDER_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Delay of rendering after a pause, for the modules on their own threads to send their new
+/// values
+const RESUME_SETTLE: Duration = Duration::from_millis(100);
+
+/// Delay of rendering with `--once`, for the modules on their own threads to send their first
+/// values. Modules still probing by then are left out.
+const ONCE_SETTLE: Duration = Duration::from_millis(500);
+
+/// Time a module may take to collect before the scheduler is shown as stalled
+const STALL_AFTER: Duration = Duration::from_secs(30);
+
+/// Percentages of `--data-cap` styling traffic, without `--warning traffic=` or
+/// `--critical traffic=`
+const DATA_CAP_WARNING: f64 = 75.0;
+const DATA_CAP_CRITICAL: f64 = 90.0;
+
+/// Percentages of `--fd` from which it's shown and styled, without `--show-above fd=`,
+/// `--warning fd=`, or `--critical fd=`
+const FD_SHOW_ABOVE: f64 = 50.0;
+const FD_WARNING: f64 = 70.0;
+const FD_CRITICAL: f64 = 90.0;
+
+/// The outputs, and the other consumers of the values rendered
+pub struct Render<'scope> {
+    pub outputs: Outputs,
+    /// modules clicked in i3bar, or `page`
+    pub clicks: Receiver<String>,
+    pub control: Arc<Mutex<control::State>>,
+    pub metrics: Arc<Mutex<Metrics>>,
+    /// samples of `--record`, and the thread writing them
+    pub recording: Option<(Sender<record::Sample>, ScopedJoinHandle<'scope, ()>)>,
+    #[cfg(feature = "dbus")]
+    pub service: Option<Sender<service::Values>>,
+    #[cfg(feature = "mqtt")]
+    pub mqtt: Option<mqtt::Publisher>,
+    /// generation of the store before the modules started
+    pub started: u64,
+    /// options reloaded on SIGHUP, along with their glyphs
+    pub reloads: Receiver<(Arc<Cli>, Glyphs)>,
+}
+
+/// What the render thread derives from the options, rebuilt on a reload
+struct Settings {
+    marquee: Marquee,
+    thresholds: Thresholds,
+    visibility: Visibility,
+    limits: Limits,
+    pages: Pages,
+}
+
+impl Settings {
+    fn new(args: &Cli, glyphs: &Glyphs) -> Self {
+        let marquee = Marquee::new(
+            args.max_width.clone(),
+            args.scroll_gap.clone(),
+            !args.no_scroll,
+        )
+        .ellipsis(glyphs.get(Glyph::Ellipsis));
+        // defaults before those of --warning, --critical and --show-above, which override
+        // them
+        let (mut warning, mut critical, mut show_above) = (vec![], vec![], vec![]);
+        if args.data_cap.is_some() {
+            warning.push(("traffic".to_string(), DATA_CAP_WARNING));
+            critical.push(("traffic".to_string(), DATA_CAP_CRITICAL));
+        }
+        if args.fd {
+            warning.push(("fd".to_string(), FD_WARNING));
+            critical.push(("fd".to_string(), FD_CRITICAL));
+            show_above.push(("fd".to_string(), FD_SHOW_ABOVE));
+        }
+        let thresholds = Thresholds::new(
+            warning
+                .into_iter()
+                .chain(args.warning.iter().cloned())
+                .collect(),
+            critical
+                .into_iter()
+                .chain(args.critical.iter().cloned())
+                .collect(),
+        );
+        let visibility = Visibility::new(
+            show_above
+                .into_iter()
+                .chain(args.show_above.iter().cloned())
+                .collect(),
+            args.show_below.clone(),
+        );
+        let limits = Limits::new(args.max.clone(), args.max_length, args.priority.clone())
+            .ellipsis(glyphs.get(Glyph::Ellipsis));
+        let pages = Pages::new(args.page.clone(), args.page_interval);
+        Self {
+            marquee,
+            thresholds,
+            visibility,
+            limits,
+            pages,
+        }
+    }
+}
+
+impl Render<'_> {
+    /// Render the status whenever a module publishes, until exiting. The outputs and consumers
+    /// are kept on a reload, their options only read on starting.
+    pub fn run(self, mut args: Arc<Cli>, store: &Store, glyphs: &Glyphs) -> ! {
+        let Render {
+            mut outputs,
+            clicks: clicks_rx,
+            control,
+            metrics,
+            mut recording,
+            #[cfg(feature = "dbus")]
+                service: service_tx,
+            #[cfg(feature = "mqtt")]
+                mqtt: mut mqtt_publisher,
+            started,
+            reloads,
+        } = self;
+
+        // Status string
+        let mut readings: HashMap<&'static str, Segment> = HashMap::new();
+        let mut toggled = HashSet::new();
+        #[cfg(feature = "dbus")]
+        let mut last_values = None;
+
+        let mut status = String::new();
+        let mut segments = vec![];
+        let mut settings = Settings::new(&args, glyphs);
+        let mut watchdog = systemd::Watchdog::from_env(RENDER_INTERVAL);
+        let mut ready = false;
+        let mut recorded = None;
+        // start of the collection the scheduler is stuck in
+        let mut stalled = None;
+
+        // the first pass of the scheduler, or with --once the first values of the modules
+        // on their own threads
+        if args.once {
+            std::thread::sleep(ONCE_SETTLE);
+        } else {
+            wait_for_publish(store, started, Some(Instant::now() + RENDER_TIMEOUT));
+        }
+
+        loop {
+            if signal::paused() {
+                let paused = Instant::now();
+                if args.pause_marker {
+                    outputs.write(&[Segment::new("paused", "[paused]")], &mut status);
+                }
+                match &mut watchdog {
+                    // a pause isn't a hang
+                    Some(watchdog) => {
+                        while signal::wait_while_paused_for(RENDER_INTERVAL) {
+                            watchdog.ping();
+                        }
+                    }
+                    None => {
+                        signal::wait_while_paused();
+                    }
+                }
+                store.postpone(paused.elapsed());
+                // for the modules woken along with this thread
+                std::thread::sleep(RESUME_SETTLE);
+            }
+
+            // clear the bar instead of leaving the last status, then close the outputs
+            if signal::stopping() {
+                let exit = [Segment::new("exit", args.exit_text.clone())];
+                let segments = if args.exit_text.is_empty() {
+                    &[][..]
+                } else {
+                    &exit[..]
+                };
+                outputs.write(segments, &mut status);
+                drop(outputs);
+                // flushing the recorded samples
+                if let Some((tx, recorder)) = recording.take() {
+                    drop(tx);
+                    let _ = recorder.join();
+                }
+                signal::exit(0);
+            }
+
+            // published along with the hidden segments of the modules left out
+            while let Ok((reloaded, glyphs)) = reloads.try_recv() {
+                settings = Settings::new(&reloaded, &glyphs);
+                args = reloaded;
+            }
+            let Settings {
+                marquee,
+                thresholds,
+                visibility,
+                limits,
+                pages,
+            } = &mut settings;
+
+            let rendering = Instant::now();
+            segments.clear();
+            let generation = store.snapshot(&mut readings);
+
+            // Clicks toggling the alternative views, such as the date for time, or showing
+            // the next page
+            while let Ok(module) = clicks_rx.try_recv() {
+                if module == "page" {
+                    pages.next(Instant::now());
+                } else if !toggled.remove(&module) {
+                    toggled.insert(module);
+                }
+            }
+            if args.control && std::mem::take(&mut control.lock().unwrap().next_page) {
+                pages.next(Instant::now());
+            }
+            // in their default order, scrolled when wider than their max width, net scrolling
+            // its addresses itself
+            let now = Instant::now();
+            // flipped on each second of the wall clock, at most once a second however often
+            // rendered
+            let flash_marked = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .is_ok_and(|since| since.as_secs() % 2 == 0);
+            for module in MODULES {
+                // with --prefix, host and user only shown in it
+                if let Some(template) = &args.prefix {
+                    if *module == "prefix" {
+                        let mut prefix =
+                            output::prefix(template, readings.get("host"), readings.get("user"));
+                        if let Cow::Owned(text) = sanitize::strip_control(&prefix.text) {
+                            prefix.text = text;
+                        }
+                        segments.push(prefix);
+                    }
+                    if matches!(*module, "host" | "user") {
+                        continue;
+                    }
+                }
+                let Some(segment) = readings.get(module) else {
+                    continue;
+                };
+                let toggled = toggled.contains(*module);
+                let mut segment = display(segment, toggled, args.keep_empty, thresholds, now);
+                // left out of every output, each escaping its own markup
+                if let Cow::Owned(text) = sanitize::strip_control(&segment.text) {
+                    segment.text = text;
+                }
+                // left out as a module without data, separator included
+                if let Some(percentage) = segment.shown.or(segment.percentage) {
+                    if !visibility.shown(segment.module, percentage) {
+                        continue;
+                    }
+                }
+                if segment.level == Level::Critical
+                    && args
+                        .critical_flash
+                        .iter()
+                        .any(|flashing| flashing == module)
+                {
+                    style::flash(&mut segment.text, flash_marked);
+                }
+                if *module != "net" {
+                    if let Cow::Owned(text) = marquee.apply(module, &segment.text) {
+                        segment.text = text;
+                    }
+                }
+                segments.push(segment);
+            }
+
+            // modules without data contribute nothing, not even a separator
+            segments.retain(|s| !s.text.is_empty());
+            output::sort(&mut segments, &args.order);
+
+            // only the modules of the current page, flipped on its own timer rather than
+            // by modules publishing
+            pages.apply(&mut segments, now);
+
+            // a module stuck in a read, holding up every scheduled module
+            let stuck = store
+                .collected()
+                .filter(|(_, since)| now.duration_since(*since) >= STALL_AFTER);
+            match stuck {
+                Some((module, since)) => {
+                    if stalled != Some(since) {
+                        stalled = Some(since);
+                        warn!(
+                            target: module,
+                            "stuck collecting for {}s, the scheduled modules are stale",
+                            STALL_AFTER.as_secs()
+                        );
+                    }
+                    segments
+                        .push(Segment::new("stalled", "collector stalled").level(Level::Critical));
+                }
+                None => {
+                    if stalled.take().is_some() {
+                        info!("the scheduler is collecting again");
+                    }
+                }
+            }
+
+            // Hidden through the control socket
+            if args.control {
+                let control = control.lock().unwrap();
+                segments.retain(|s| !control.hidden.contains(s.module));
+            }
+
+            limits.apply(&mut segments);
+
+            // Metrics
+            if args.metrics_listen.is_some() {
+                let values = |name| readings.get(name).map(|s| &s.values[..]);
+                let batteries = values("bat")
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|(name, capacity)| Some((name.clone(), capacity.parse().ok()?)))
+                    .collect();
+                let ip_counts = values("net")
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|(name, count)| {
+                        Some((name.strip_prefix("ip:")?.to_string(), count.parse().ok()?))
+                    })
+                    .collect();
+                *metrics.lock().unwrap() = Metrics {
+                    cpu_usage_percent: percentage(&readings, "cpu").unwrap_or_default(),
+                    memory_used_bytes: readings
+                        .get("mem")
+                        .and_then(|mem| mem.get("used_bytes"))
+                        .unwrap_or_default(),
+                    batteries,
+                    ip_counts,
+                };
+            }
+
+            // MQTT, the topics that changed
+            #[cfg(feature = "mqtt")]
+            if let Some(publisher) = &mut mqtt_publisher {
+                publisher.publish(readings.values());
+            }
+
+            // Write and flush the status
+            outputs.write(&segments, &mut status);
+            store.rendered(generation);
+            profile::rendered(rendering.elapsed());
+            if args.once {
+                signal::exit(0);
+            }
+            if !ready {
+                systemd::notify("READY=1");
+                ready = true;
+            }
+            if let Some(watchdog) = &mut watchdog {
+                watchdog.ping();
+            }
+
+            // once a second, however often rendered
+            if let Some((tx, _)) = &recording {
+                let time = Local::now();
+                if recorded != Some(time.timestamp()) {
+                    recorded = Some(time.timestamp());
+                    let sample = record::Sample::new(time, readings.values());
+                    // disabled by an error of the recorder
+                    if tx.send(sample).is_err() {
+                        recording = None;
+                    }
+                }
+            }
+
+            if args.control {
+                let mut control = control.lock().unwrap();
+                control.status.clone_from(&status);
+                control.values = segments
+                    .iter()
+                    .map(|s| (s.module, s.text.clone()))
+                    .collect();
+            }
+
+            #[cfg(feature = "dbus")]
+            if let Some(service_tx) = &service_tx {
+                let values = service::Values {
+                    status: status.clone(),
+                    cpu_percent: percentage(&readings, "cpu").unwrap_or_default(),
+                    mem_percent: percentage(&readings, "mem").unwrap_or_default(),
+                    battery_percent: percentage(&readings, "bat").unwrap_or(-1.0),
+                    ip: readings
+                        .get("net")
+                        .and_then(|net| net.get("ip"))
+                        .unwrap_or_default(),
+                };
+                if last_values.as_ref() != Some(&values) {
+                    last_values = Some(values.clone());
+                    let _ = service_tx.send(values);
+                }
+            }
+
+            // rendered again as soon as a module publishes, the clock every second, and
+            // otherwise only on a refresh, to mark a segment stale or the scheduler stalled,
+            // or to flip the page: no timer of its own to fall out of phase with
+            let stalls = store
+                .collected()
+                .map(|(_, since)| since + STALL_AFTER)
+                .filter(|stalls| *stalls > now);
+            // the age of expired cached segments changing
+            let ages = readings
+                .values()
+                .filter(|segment| segment.expires.is_some_and(|expires| expires <= now))
+                .filter_map(cached_age)
+                .map(|age| now + duration::until_next_change(age));
+            let deadline = readings
+                .values()
+                .filter_map(|segment| segment.expires)
+                .filter(|expires| *expires > now)
+                .chain(pages.due())
+                .chain(stalls)
+                .chain(ages)
+                .min();
+            wait_for_publish(store, generation, deadline);
+        }
+    }
+}
+
+/// Age of a segment fetched by a slow module
+fn cached_age(segment: &Segment) -> Option<Duration> {
+    Some(segment.fetched?.elapsed().unwrap_or_default())
+}
+
+/// Latest segment of a module as displayed: its alternative view once toggled by a
+/// click, "n/a" without data with `--keep-empty`, and the level of its percentage
+fn display(
+    segment: &Segment,
+    toggled: bool,
+    keep_empty: bool,
+    thresholds: &Thresholds,
+    now: Instant,
+) -> Segment {
+    let mut segment = segment.clone();
+    if let Some(alt) = segment.alt.take().filter(|_| toggled) {
+        return Segment::new(segment.module, alt);
+    }
+    if segment.text.is_empty() && keep_empty {
+        segment.text = format!("{} n/a", segment.module);
+    }
+    if segment.expires.is_some_and(|expires| now >= expires) && !segment.text.is_empty() {
+        // cached by a slow module, still worth showing along with its age
+        if let Some(age) = cached_age(&segment) {
+            segment.text = format!("{} ({})", segment.text, duration::format_age(age));
+            return segment;
+        }
+        segment.text.push_str(" (stale)");
+        segment.level = Level::Warning;
+        return segment;
+    }
+    // smoothed, the thresholds apply to the value displayed
+    if let Some(percentage) = segment.shown.or(segment.percentage) {
+        segment.level = thresholds.level(segment.module, percentage);
+    }
+    segment
+}
+
+/// Sleep until `store` is past `generation`, or `until` if any, returning `true` on a refresh, a
+/// pause, or a stop
+fn wait_for_publish(store: &Store, generation: u64, until: Option<Instant>) -> bool {
+    loop {
+        let wakes = signal::wakes();
+        let now = Instant::now();
+        if store.generation() != generation || until.is_some_and(|until| now >= until) {
+            return false;
+        }
+        let timeout = until.map_or(Duration::MAX, |until| until - now);
+        if signal::sleep_since(wakes, timeout) {
+            return true;
+        }
+    }
+}
+
+/// Percentage of the latest segment of a module
+fn percentage(readings: &HashMap<&str, Segment>, module: &str) -> Option<f64> {
+    readings.get(module)?.percentage
+}
@@ -0,0 +1,352 @@
+//! Raw samples of the modules for `--record-raw`, and their replay with `--replay`
+//!
+//! Recording keeps each segment as its module published it, before the thresholds, order, and
+//! output shape it, along with the files the scheduled modules read to collect it, as JSON Lines
+//! such as {"timestamp":"2024-01-01T09:00:00.000+01:00","module":"cpu","segment":{...}}.
+//! Unchanged segments and files are only written once.
+//!
+//! A replay publishes the recorded segments again, at their pace or faster, for the render
+//! thread to compose them with the flags of the replay. A bar seen on other hardware then renders
+//! the same here, thresholds and formatting included, without reading anything of this machine.
+//! The recorded files are what to turn into fixtures of `--sysfs-root` and `--procfs-root` when
+//! the module itself is at fault.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::output::Segment;
+use crate::store::Store;
+use crate::style::Level;
+use crate::{signal, MODULES};
+
+/// Sender of the recording thread, once recording
+static RECORDER: OnceLock<Mutex<Sender<Entry>>> = OnceLock::new();
+
+thread_local! {
+    /// module being collected by the scheduler, whose reads are recorded under its name
+    static COLLECTING: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// A line of the recording: a segment published, a module hidden, or a file read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// RFC 3339 in milliseconds, the same for the segments published together
+    timestamp: String,
+    module: String,
+    /// the segment published, neither it nor a file for a module hidden
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    segment: Option<Recorded>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    contents: Option<String>,
+}
+
+/// A segment without its expiry, which is relative to the recording process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Recorded {
+    text: String,
+    #[serde(default)]
+    level: Level,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    percentage: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shown: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alt: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    values: Vec<(String, String)>,
+}
+
+impl Recorded {
+    fn new(segment: &Segment) -> Self {
+        Self {
+            text: segment.text.clone(),
+            level: segment.level,
+            percentage: segment.percentage,
+            shown: segment.shown,
+            alt: segment.alt.clone(),
+            values: segment.values.clone(),
+        }
+    }
+
+    fn segment(self, module: &'static str) -> Segment {
+        Segment {
+            level: self.level,
+            percentage: self.percentage,
+            shown: self.shown,
+            alt: self.alt,
+            values: self.values,
+            ..Segment::new(module, self.text)
+        }
+    }
+}
+
+/// The time of an entry recorded now, `None` when not recording
+pub fn timestamp() -> Option<String> {
+    RECORDER
+        .get()
+        .map(|_| Local::now().to_rfc3339_opts(SecondsFormat::Millis, false))
+}
+
+/// Send `entry` to the recording thread, unless not recording or the recording stopped
+fn send(entry: Entry) {
+    if let Some(recorder) = RECORDER.get() {
+        let _ = recorder.lock().unwrap().send(entry);
+    }
+}
+
+/// Record from now on into the thread of [`serve`]
+pub fn record_to(tx: Sender<Entry>) {
+    let _ = RECORDER.set(Mutex::new(tx));
+}
+
+/// Record `segment`, published at `timestamp` of [`timestamp`]
+pub fn published(timestamp: &str, segment: &Segment) {
+    send(Entry {
+        timestamp: timestamp.to_string(),
+        module: segment.module.to_string(),
+        segment: Some(Recorded::new(segment)),
+        file: None,
+        contents: None,
+    });
+}
+
+/// Record the segment of `module` removed from the store
+pub fn hidden(module: &str) {
+    if let Some(timestamp) = timestamp() {
+        send(Entry {
+            timestamp,
+            module: module.to_string(),
+            segment: None,
+            file: None,
+            contents: None,
+        });
+    }
+}
+
+/// Name the module the reads of this thread are recorded under, `None` once collected
+pub fn collecting(module: Option<&'static str>) {
+    COLLECTING.set(module);
+}
+
+/// Record the contents of `path`, read by the module being collected on this thread
+pub fn read(path: &Path, contents: &str) {
+    let (Some(module), Some(timestamp)) = (COLLECTING.get(), timestamp()) else {
+        return;
+    };
+    send(Entry {
+        timestamp,
+        module: module.to_string(),
+        segment: None,
+        file: Some(path.to_path_buf()),
+        contents: Some(contents.to_string()),
+    });
+}
+
+/// Append the entries of `rx` to `path` a line at a time, leaving out the segments and files
+/// unchanged since last written. An IO error disables recording, leaving the bar unaffected.
+pub fn serve(path: PathBuf, rx: Receiver<Entry>) {
+    let mut writer = match OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => LineWriter::new(file),
+        Err(e) => {
+            warn!("unable to record to {}, disabling: {e}", path.display());
+            return;
+        }
+    };
+    // by module, or by file
+    let mut last: HashMap<(String, Option<PathBuf>), Entry> = HashMap::new();
+    for entry in rx {
+        let key = (entry.module.clone(), entry.file.clone());
+        let unchanged = last
+            .get(&key)
+            .is_some_and(|last| last.segment == entry.segment && last.contents == entry.contents);
+        if unchanged {
+            continue;
+        }
+        let written = serde_json::to_string(&entry)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(writer, "{line}"));
+        if let Err(e) = written {
+            warn!("unable to record to {}, disabling: {e}", path.display());
+            return;
+        }
+        last.insert(key, entry);
+    }
+}
+
+/// clap value parser of `--replay-speed`, a factor of the recorded pace from 0
+pub fn parse_speed(s: &str) -> Result<f64, String> {
+    s.parse()
+        .ok()
+        .filter(|speed: &f64| speed.is_finite() && *speed >= 0.0)
+        .ok_or_else(|| format!("invalid speed `{s}`, expected e.g. 1 or 10, or 0 for unpaced"))
+}
+
+/// A publish of the recording: the segments, or `None` for a module hidden
+#[derive(Debug, PartialEq)]
+pub struct Step {
+    time: DateTime<FixedOffset>,
+    segments: Vec<(&'static str, Option<Segment>)>,
+}
+
+/// The publishes of the recording at `path`, its segments published together grouped, failing
+/// on an unreadable file or line. Files and unknown modules are left out.
+pub fn load(path: &Path) -> Result<Vec<Step>, String> {
+    let file = File::open(path).map_err(|e| format!("unable to open {}: {e}", path.display()))?;
+    let mut steps: Vec<Step> = vec![];
+    let mut unknown = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |e: String| format!("{}:{}: {e}", path.display(), i + 1);
+        let entry: Entry = serde_json::from_str(&line).map_err(|e| invalid(e.to_string()))?;
+        let time = DateTime::parse_from_rfc3339(&entry.timestamp)
+            .map_err(|e| invalid(format!("invalid timestamp: {e}")))?;
+        if entry.file.is_some() {
+            continue;
+        }
+        let Some(module) = MODULES.iter().find(|module| **module == entry.module) else {
+            if !unknown.contains(&entry.module) {
+                warn!(
+                    "{}: unknown module {}, left out",
+                    path.display(),
+                    entry.module
+                );
+                unknown.push(entry.module);
+            }
+            continue;
+        };
+        let segment = entry.segment.map(|recorded| recorded.segment(module));
+        match steps.last_mut() {
+            Some(step) if step.time == time => step.segments.push((module, segment)),
+            _ => steps.push(Step {
+                time,
+                segments: vec![(module, segment)],
+            }),
+        }
+    }
+    Ok(steps)
+}
+
+/// Publish `steps` into `store`, `speed` times as fast as recorded or each as soon as the
+/// previous is rendered with 0, then exit once the last is rendered
+pub fn replay(steps: Vec<Step>, speed: f64, store: &Store) {
+    let mut previous: Option<DateTime<FixedOffset>> = None;
+    for step in steps {
+        if let Some(previous) = previous.filter(|_| speed > 0.0) {
+            let elapsed = (step.time - previous).to_std().unwrap_or_default();
+            if !signal::idle(Duration::from_secs_f64(elapsed.as_secs_f64() / speed)) {
+                return;
+            }
+        }
+        previous = Some(step.time);
+        let mut shown = vec![];
+        for (module, segment) in step.segments {
+            match segment {
+                Some(segment) => shown.push(segment),
+                None => store.set(module, None),
+            }
+        }
+        if !shown.is_empty() {
+            store.publish(shown);
+        }
+        if !store.wait_rendered() {
+            return;
+        }
+    }
+    signal::exit(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    fn entry(timestamp: &str, module: &str, segment: Option<&Segment>) -> Entry {
+        Entry {
+            timestamp: timestamp.into(),
+            module: module.into(),
+            segment: segment.map(Recorded::new),
+            file: None,
+            contents: None,
+        }
+    }
+
+    #[test]
+    fn recorded_and_loaded() {
+        let dir = std::env::temp_dir().join(format!("statusbar-replay-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("raw.jsonl");
+
+        let cpu = Segment::new("cpu", "cpu 95%")
+            .percentage(95.0)
+            .level(Level::Critical)
+            .value("user", 80.0);
+        let mem = Segment::new("mem", "mem 04%").percentage(4.0);
+        let first = "2024-01-01T09:00:00.000+01:00";
+        let second = "2024-01-01T09:00:01.000+01:00";
+        let (tx, rx) = channel();
+        tx.send(entry(first, "cpu", Some(&cpu))).unwrap();
+        tx.send(entry(first, "mem", Some(&mem))).unwrap();
+        tx.send(Entry {
+            file: Some("/proc/meminfo".into()),
+            contents: Some("MemTotal: 1 kB\n".into()),
+            ..entry(first, "mem", None)
+        })
+        .unwrap();
+        // unchanged, left out
+        tx.send(entry(second, "mem", Some(&mem))).unwrap();
+        tx.send(entry(second, "bat", None)).unwrap();
+        tx.send(entry(second, "gpu", Some(&mem))).unwrap();
+        drop(tx);
+        serve(path.clone(), rx);
+
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(recorded.lines().count(), 5);
+        assert!(recorded.contains(r#""contents":"MemTotal: 1 kB\n""#));
+        let steps = load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let time = |timestamp| DateTime::parse_from_rfc3339(timestamp).unwrap();
+        assert_eq!(
+            steps,
+            [
+                Step {
+                    time: time(first),
+                    segments: vec![("cpu", Some(cpu)), ("mem", Some(mem))],
+                },
+                Step {
+                    time: time(second),
+                    segments: vec![("bat", None)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let dir = std::env::temp_dir().join(format!("statusbar-replay-bad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("raw.jsonl");
+        std::fs::write(&path, "{\"timestamp\":\"yesterday\",\"module\":\"cpu\"}\n").unwrap();
+        let error = load(&path).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(error.contains("raw.jsonl:1: invalid timestamp"), "{error}");
+        assert!(load(Path::new("/nonexistent.jsonl")).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Cleaning of the segment text before the outputs compose it, so that a value such as the
+//! output of a misbehaving script can't break their format
+//!
+//! Control characters are left out of every segment by the render thread, then each output
+//! escapes its own markup: `%` for lemonbar, `^` for dzen2, `#` for tmux, and JSON for i3bar and
+//! waybar through serde.
+
+use std::borrow::Cow;
+
+/// `text` without control characters, newlines and tabs turned into spaces. Other characters,
+/// such as CJK or emoji sequences, are kept as they are.
+pub fn strip_control(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+    let stripped = text
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    Cow::Owned(stripped)
+}
+
+/// `text` with `markup` doubled, as lemonbar's `%%` and dzen2's `^^`
+pub fn escape(text: &str, markup: char) -> Cow<'_, str> {
+    if text.contains(markup) {
+        let doubled: String = [markup, markup].iter().collect();
+        Cow::Owned(text.replace(markup, &doubled))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_characters() {
+        assert_eq!(strip_control("song\ntitle\t1"), "song title 1");
+        assert_eq!(strip_control("\x1b[31mred\x1b[0m\0"), "[31mred[0m");
+        assert_eq!(strip_control("a\u{85}b\u{7f}"), "ab");
+    }
+
+    #[test]
+    fn unicode_kept() {
+        for text in ["東京 23°C", "👩‍💻 busy", "🇫🇷 café", "ℵ₀ ≠ ∞"] {
+            assert!(matches!(strip_control(text), Cow::Borrowed(_)), "{text}");
+            assert!(matches!(escape(text, '%'), Cow::Borrowed(_)), "{text}");
+        }
+    }
+
+    #[test]
+    fn markup_doubled() {
+        assert_eq!(escape("vol 50%", '%'), "vol 50%%");
+        assert_eq!(escape("^fg(red)", '^'), "^^fg(red)");
+    }
+}
@@ -0,0 +1,269 @@
+//! Listing the power supplies, hwmon chips, thermal zones, and backlights in sysfs, for
+//! `--list-sensors`
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::paths::Paths;
+
+const SYSFS_CLASS: &str = "/sys/class";
+
+/// A hwmon chip, such as hwmon2 named k10temp
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chip {
+    pub name: String,
+    /// such as /sys/class/hwmon/hwmon2
+    pub dir: PathBuf,
+    /// channels such as "temp1", sorted
+    pub temps: Vec<String>,
+}
+
+/// A thermal zone, such as thermal_zone1 of type x86_pkg_temp
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    /// such as thermal_zone1, which can change across boots
+    pub name: String,
+    /// such as acpitz
+    pub kind: String,
+    /// such as /sys/class/thermal/thermal_zone1
+    pub dir: PathBuf,
+}
+
+/// Print every sensor, one per line such as "power_supply BAT0 type=Battery capacity=80%",
+/// the thermal zones of `excluded` types marked
+pub fn list(paths: &Paths, excluded: &[String]) {
+    print!("{}", listing(paths, excluded));
+}
+
+/// The lines of [`list`]
+pub fn listing(paths: &Paths, excluded: &[String]) -> String {
+    let mut out = String::new();
+    list_in(&paths.resolve(SYSFS_CLASS), excluded, &mut out);
+    out
+}
+
+/// The hwmon chips, by hwmon index. Their indices change as their drivers load, their names
+/// don't.
+pub fn chips(paths: &Paths) -> Vec<Chip> {
+    chips_in(&paths.resolve(SYSFS_CLASS).join("hwmon"))
+}
+
+fn chips_in(hwmon: &Path) -> Vec<Chip> {
+    entries(hwmon, "hwmon", &mut String::new())
+        .into_iter()
+        .filter_map(|entry| {
+            let dir = hwmon.join(entry);
+            let name = std::fs::read_to_string(dir.join("name")).ok()?;
+            let (temps, _) = channels(&dir).ok()?;
+            Some(Chip {
+                name: name.trim().to_string(),
+                dir,
+                temps,
+            })
+        })
+        .collect()
+}
+
+/// The thermal zones, by zone index. Like hwmon indices, these aren't stable, their types are.
+pub fn zones(paths: &Paths) -> Vec<Zone> {
+    zones_in(&paths.resolve(SYSFS_CLASS).join("thermal"))
+}
+
+fn zones_in(thermal: &Path) -> Vec<Zone> {
+    let mut names: Vec<String> = entries(thermal, "thermal", &mut String::new())
+        .into_iter()
+        .filter(|name| name.starts_with("thermal_zone"))
+        .collect();
+    // thermal_zone10 after thermal_zone9
+    names.sort_by_key(|name| zone_index(name));
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let dir = thermal.join(&name);
+            let kind = std::fs::read_to_string(dir.join("type")).ok()?;
+            Some(Zone {
+                name,
+                kind: kind.trim().to_string(),
+                dir,
+            })
+        })
+        .collect()
+}
+
+/// 3 of thermal_zone3
+pub fn zone_index(name: &str) -> Option<u32> {
+    name.strip_prefix("thermal_zone")?.parse().ok()
+}
+
+fn list_in(class: &Path, excluded: &[String], out: &mut String) {
+    for name in entries(&class.join("power_supply"), "power_supply", out) {
+        let dir = class.join("power_supply").join(&name);
+        let mut line = format!("power_supply {name} type={}", attribute(&dir, "type"));
+        if dir.join("capacity").exists() {
+            line.push_str(&format!(" capacity={}%", attribute(&dir, "capacity")));
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+
+    for name in entries(&class.join("hwmon"), "hwmon", out) {
+        let dir = class.join("hwmon").join(&name);
+        let mut line = format!("hwmon {name} name={}", attribute(&dir, "name"));
+        match channels(&dir) {
+            Ok((temps, fans)) => {
+                line.push_str(&format!(" temp={} fan={}", temps.join(","), fans.join(",")));
+            }
+            Err(e) => line.push_str(&format!(" channels=error({e})")),
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+
+    let mut zones = entries(&class.join("thermal"), "thermal", out);
+    zones.retain(|name| name.starts_with("thermal_zone"));
+    zones.sort_by_key(|name| zone_index(name));
+    for name in zones {
+        let dir = class.join("thermal").join(&name);
+        let kind = attribute(&dir, "type");
+        let mut line = format!(
+            "thermal {name} type={kind} temp={}",
+            attribute(&dir, "temp")
+        );
+        if excluded.contains(&kind) {
+            line.push_str(" excluded");
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+
+    for name in entries(&class.join("backlight"), "backlight", out) {
+        let dir = class.join("backlight").join(&name);
+        out.push_str(&format!(
+            "backlight {name} brightness={}/{}\n",
+            attribute(&dir, "brightness"),
+            attribute(&dir, "max_brightness")
+        ));
+    }
+}
+
+/// Sorted names in `dir`, or none when the class doesn't exist. Other failures are listed as
+/// an entry of their own.
+fn entries(dir: &Path, class: &str, out: &mut String) -> Vec<String> {
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            out.push_str(&format!("{class} error({e})\n"));
+            return vec![];
+        }
+    };
+    let mut names: Vec<String> = read_dir
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+/// Trimmed contents of the attribute, or the error reading it
+fn attribute(dir: &Path, name: &str) -> String {
+    match std::fs::read_to_string(dir.join(name)) {
+        Ok(value) => value.trim().to_string(),
+        Err(e) => format!("error({e})"),
+    }
+}
+
+/// Sorted temp and fan channels of a hwmon chip, such as "temp1"
+fn channels(dir: &Path) -> io::Result<(Vec<String>, Vec<String>)> {
+    let mut temps = vec![];
+    let mut fans = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let file_name = entry?.file_name();
+        let Some(channel) = file_name.to_str().and_then(|n| n.strip_suffix("_input")) else {
+            continue;
+        };
+        if channel.starts_with("temp") {
+            temps.push(channel.to_string());
+        } else if channel.starts_with("fan") {
+            fans.push(channel.to_string());
+        }
+    }
+    temps.sort_unstable();
+    fans.sort_unstable();
+    Ok((temps, fans))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_fake_sysfs() {
+        let class = std::env::temp_dir().join(format!("statusbar-sensors-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&class);
+        let write = |path: &str, contents: &str| {
+            let path = class.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write("power_supply/BAT0/type", "Battery\n");
+        write("power_supply/BAT0/capacity", "80\n");
+        write("power_supply/AC/type", "Mains\n");
+        write("hwmon/hwmon0/name", "coretemp\n");
+        write("hwmon/hwmon0/temp2_input", "41000\n");
+        write("hwmon/hwmon0/temp1_input", "40000\n");
+        write("hwmon/hwmon0/temp1_label", "Package id 0\n");
+        write("hwmon/hwmon1/fan1_input", "1200\n");
+        write("thermal/thermal_zone10/type", "iwlwifi_1\n");
+        write("thermal/thermal_zone10/temp", "38000\n");
+        write("thermal/thermal_zone0/type", "acpitz\n");
+        write("thermal/thermal_zone0/temp", "26800\n");
+        write("thermal/thermal_zone2/type", "x86_pkg_temp\n");
+        write("thermal/thermal_zone2/temp", "52000\n");
+        write("thermal/cooling_device0/type", "Processor\n");
+        write("backlight/intel_backlight/brightness", "120\n");
+        write("backlight/intel_backlight/max_brightness", "1000\n");
+
+        let mut out = String::new();
+        list_in(&class, &["acpitz".to_string()], &mut out);
+        let chips = chips_in(&class.join("hwmon"));
+        let zones = zones_in(&class.join("thermal"));
+        std::fs::remove_dir_all(&class).unwrap();
+
+        assert_eq!(
+            chips,
+            [Chip {
+                name: "coretemp".into(),
+                dir: class.join("hwmon/hwmon0"),
+                temps: vec!["temp1".into(), "temp2".into()],
+            }]
+        );
+        let zones: Vec<(&str, &str)> = zones
+            .iter()
+            .map(|zone| (zone.name.as_str(), zone.kind.as_str()))
+            .collect();
+        assert_eq!(
+            zones,
+            [
+                ("thermal_zone0", "acpitz"),
+                ("thermal_zone2", "x86_pkg_temp"),
+                ("thermal_zone10", "iwlwifi_1"),
+            ]
+        );
+
+        let error = io::Error::from_raw_os_error(libc::ENOENT);
+        assert_eq!(
+            out,
+            format!(
+                "power_supply AC type=Mains\n\
+                 power_supply BAT0 type=Battery capacity=80%\n\
+                 hwmon hwmon0 name=coretemp temp=temp1,temp2 fan=\n\
+                 hwmon hwmon1 name=error({error}) temp= fan=fan1\n\
+                 thermal thermal_zone0 type=acpitz temp=26800 excluded\n\
+                 thermal thermal_zone2 type=x86_pkg_temp temp=52000\n\
+                 thermal thermal_zone10 type=iwlwifi_1 temp=38000\n\
+                 backlight intel_backlight brightness=120/1000\n"
+            )
+        );
+    }
+}
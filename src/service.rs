@@ -0,0 +1,192 @@
+//! Session bus service exposing the status and the values of the render thread
+
+use std::sync::mpsc::Receiver;
+
+use log::warn;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::fdo::{RequestNameFlags, RequestNameReply};
+use zbus::interface;
+
+use crate::signal;
+
+pub const NAME: &str = "dev.wcampbell.statusbar";
+const PATH: &str = "/dev/wcampbell/statusbar";
+
+/// Latest values, as displayed by the render thread
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Values {
+    pub status: String,
+    pub cpu_percent: f64,
+    pub mem_percent: f64,
+    /// emptiest battery, -1 without a battery
+    pub battery_percent: f64,
+    pub ip: String,
+}
+
+struct Statusbar {
+    values: Values,
+}
+
+#[interface(name = "dev.wcampbell.statusbar")]
+impl Statusbar {
+    /// Render and write the status now
+    fn refresh(&self) {
+        signal::request_refresh();
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        self.values.status.clone()
+    }
+
+    #[zbus(property)]
+    fn cpu_percent(&self) -> f64 {
+        self.values.cpu_percent
+    }
+
+    #[zbus(property)]
+    fn mem_percent(&self) -> f64 {
+        self.values.mem_percent
+    }
+
+    #[zbus(property)]
+    fn battery_percent(&self) -> f64 {
+        self.values.battery_percent
+    }
+
+    #[zbus(property)]
+    fn ip(&self) -> String {
+        self.values.ip.clone()
+    }
+}
+
+/// Connect to the session bus and own [`NAME`], failing with `NameTaken` if another instance
+/// already owns it
+pub fn connect() -> zbus::Result<Connection> {
+    let conn = Builder::session()?
+        .serve_at(
+            PATH,
+            Statusbar {
+                values: Values::default(),
+            },
+        )?
+        .build()?;
+    // queueing would leave this instance silently waiting for the other to exit
+    match conn.request_name_with_flags(NAME, RequestNameFlags::DoNotQueue.into())? {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => Ok(conn),
+        RequestNameReply::Exists | RequestNameReply::InQueue => Err(zbus::Error::NameTaken),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Property {
+    Status,
+    CpuPercent,
+    MemPercent,
+    BatteryPercent,
+    Ip,
+}
+
+/// Properties of `new` that changed from `last`
+fn changed(last: &Values, new: &Values) -> Vec<Property> {
+    [
+        (Property::Status, new.status != last.status),
+        (Property::CpuPercent, new.cpu_percent != last.cpu_percent),
+        (Property::MemPercent, new.mem_percent != last.mem_percent),
+        (
+            Property::BatteryPercent,
+            new.battery_percent != last.battery_percent,
+        ),
+        (Property::Ip, new.ip != last.ip),
+    ]
+    .into_iter()
+    .filter_map(|(property, changed)| changed.then_some(property))
+    .collect()
+}
+
+/// Update the properties from `rx`, emitting `PropertiesChanged` for those that changed
+pub fn serve(conn: Connection, rx: Receiver<Values>) {
+    let iface = match conn.object_server().interface::<_, Statusbar>(PATH) {
+        Ok(iface) => iface,
+        Err(e) => {
+            warn!("interface missing, disabling: {e}");
+            return;
+        }
+    };
+    let emitter = iface.signal_emitter();
+
+    for values in rx {
+        let mut statusbar = iface.get_mut();
+        let last = std::mem::replace(&mut statusbar.values, values);
+        let new = &statusbar.values;
+
+        let result = zbus::block_on(async {
+            for property in changed(&last, new) {
+                match property {
+                    Property::Status => statusbar.status_changed(emitter).await?,
+                    Property::CpuPercent => statusbar.cpu_percent_changed(emitter).await?,
+                    Property::MemPercent => statusbar.mem_percent_changed(emitter).await?,
+                    Property::BatteryPercent => statusbar.battery_percent_changed(emitter).await?,
+                    Property::Ip => statusbar.ip_changed(emitter).await?,
+                }
+            }
+            zbus::Result::Ok(())
+        });
+        if let Err(e) = result {
+            warn!("unable to emit PropertiesChanged: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Values {
+        Values {
+            status: "cpu 02%, bat [80%]".to_string(),
+            cpu_percent: 2.0,
+            mem_percent: 41.5,
+            battery_percent: 80.0,
+            ip: "10.0.0.2".to_string(),
+        }
+    }
+
+    #[test]
+    fn properties() {
+        let statusbar = Statusbar { values: values() };
+        assert_eq!(statusbar.status(), "cpu 02%, bat [80%]");
+        assert_eq!(statusbar.cpu_percent(), 2.0);
+        assert_eq!(statusbar.mem_percent(), 41.5);
+        assert_eq!(statusbar.battery_percent(), 80.0);
+        assert_eq!(statusbar.ip(), "10.0.0.2");
+    }
+
+    #[test]
+    fn changed_properties() {
+        let last = values();
+        assert!(changed(&last, &values()).is_empty());
+
+        let new = Values {
+            status: "cpu 03%, bat [80%]".to_string(),
+            cpu_percent: 3.0,
+            ..values()
+        };
+        assert_eq!(
+            changed(&last, &new),
+            [Property::Status, Property::CpuPercent]
+        );
+        // from the defaults, before the first render
+        assert_eq!(changed(&Values::default(), &last).len(), 5);
+        let unplugged = Values {
+            battery_percent: -1.0,
+            ip: String::new(),
+            ..values()
+        };
+        assert_eq!(
+            changed(&last, &unplugged),
+            [Property::BatteryPercent, Property::Ip]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Requested refreshes through SIGUSR1, reloads of the config file through SIGHUP, pausing
+//! through SIGUSR2, and stopping through SIGTERM and SIGINT
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::Duration;
+
+use log::warn;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1, SIGUSR2};
+use signal_hook::iterator::Signals;
+
+use crate::systemd;
+
+/// Time for the render thread to clear the bar once stopping, before exiting without
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+struct Refresh {
+    /// incremented for each requested refresh
+    generation: u64,
+    /// incremented for each event of a module, waking the sleepers without a refresh
+    wakes: u64,
+    /// incremented for each SIGHUP
+    reloads: u64,
+    paused: bool,
+    stopping: bool,
+    /// without a default route, as detected by [`crate::modules::Offline`]
+    offline: bool,
+}
+
+/// Sleepers wait on the condvar for the generation, the pause, the connectivity, or stopping to
+/// change
+static REFRESH: (Mutex<Refresh>, Condvar) = (
+    Mutex::new(Refresh {
+        generation: 0,
+        wakes: 0,
+        reloads: 0,
+        paused: false,
+        stopping: false,
+        offline: false,
+    }),
+    Condvar::new(),
+);
+
+/// Handle SIGUSR1 as refresh requests, SIGHUP as reload requests, SIGUSR2 as pause toggle, and
+/// SIGTERM and SIGINT as stop requests, instead of terminating. A second SIGTERM or SIGINT exits
+/// at once.
+pub fn install() {
+    let mut signals = match Signals::new([SIGUSR1, SIGUSR2, SIGHUP, SIGTERM, SIGINT]) {
+        Ok(signals) => signals,
+        Err(e) => {
+            warn!("unable to handle signals: {e}");
+            return;
+        }
+    };
+    // signals delivered while a refresh is pending are coalesced by `Signals`, and by sleepers
+    // only checking whether the generation changed
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            match signal {
+                SIGUSR2 => {
+                    toggle_pause();
+                }
+                SIGHUP => request_reload(),
+                SIGTERM | SIGINT if stopping() => exit(0),
+                SIGTERM | SIGINT => request_stop(),
+                _ => request_refresh(),
+            }
+        }
+    });
+}
+
+/// Wake every thread currently sleeping in [`sleep`]
+pub fn request_refresh() {
+    let (refresh, condvar) = &REFRESH;
+    refresh.lock().unwrap().generation += 1;
+    condvar.notify_all();
+}
+
+/// Wake every thread currently sleeping in [`sleep`] without requesting a refresh, such as the
+/// scheduler for an event of one of its modules
+pub fn wake() {
+    let (refresh, condvar) = &REFRESH;
+    refresh.lock().unwrap().wakes += 1;
+    condvar.notify_all();
+}
+
+/// Have the thread waiting in [`wait_for_reload`] reload the config file
+pub fn request_reload() {
+    let (refresh, condvar) = &REFRESH;
+    refresh.lock().unwrap().reloads += 1;
+    condvar.notify_all();
+}
+
+/// Block until a reload is requested since `seen`, the number of reloads requested when last
+/// woken, updating it. Returns `false` once stopping instead.
+pub fn wait_for_reload(seen: &mut u64) -> bool {
+    let (refresh, condvar) = &REFRESH;
+    let refresh = refresh.lock().unwrap();
+    let refresh = condvar
+        .wait_while(refresh, |refresh| {
+            refresh.reloads == *seen && !refresh.stopping
+        })
+        .unwrap();
+    *seen = refresh.reloads;
+    !refresh.stopping
+}
+
+/// Wake every sleeping thread to stop, the render thread then clears the bar and exits.
+/// Exits after [`STOP_TIMEOUT`] if it doesn't, such as when wedged.
+pub fn request_stop() {
+    let (refresh, condvar) = &REFRESH;
+    refresh.lock().unwrap().stopping = true;
+    condvar.notify_all();
+
+    std::thread::spawn(|| {
+        std::thread::sleep(STOP_TIMEOUT);
+        warn!(
+            "the bar wasn't cleared within {}s, exiting",
+            STOP_TIMEOUT.as_secs()
+        );
+        exit(1);
+    });
+}
+
+pub fn stopping() -> bool {
+    REFRESH.0.lock().unwrap().stopping
+}
+
+/// Pause or resume, returning whether now paused. Resuming also requests a refresh.
+pub fn toggle_pause() -> bool {
+    let (refresh, condvar) = &REFRESH;
+    let mut refresh = refresh.lock().unwrap();
+    refresh.paused = !refresh.paused;
+    if !refresh.paused {
+        refresh.generation += 1;
+    }
+    condvar.notify_all();
+    refresh.paused
+}
+
+/// Paused, and not stopping
+pub fn paused() -> bool {
+    let refresh = REFRESH.0.lock().unwrap();
+    refresh.paused && !refresh.stopping
+}
+
+/// Block while paused, returning whether it was paused so rate baselines can be reset
+pub fn wait_while_paused() -> bool {
+    let (refresh, condvar) = &REFRESH;
+    let refresh = refresh.lock().unwrap();
+    if !refresh.paused {
+        return false;
+    }
+    let _refresh = condvar
+        .wait_while(refresh, |refresh| refresh.paused && !refresh.stopping)
+        .unwrap();
+    true
+}
+
+/// Block while paused for at most `timeout`, returning whether still paused
+pub fn wait_while_paused_for(timeout: Duration) -> bool {
+    let (refresh, condvar) = &REFRESH;
+    let refresh = refresh.lock().unwrap();
+    let (refresh, _) = condvar
+        .wait_timeout_while(refresh, timeout, |refresh| {
+            refresh.paused && !refresh.stopping
+        })
+        .unwrap();
+    refresh.paused && !refresh.stopping
+}
+
+/// Sleep for `duration`, returning early with `true` if a refresh was requested, or the bar was
+/// paused or is stopping meanwhile. Returns early with `false` when woken by [`wake`].
+pub fn sleep(duration: Duration) -> bool {
+    sleep_since(wakes(), duration)
+}
+
+/// Number of calls to [`wake`] so far
+pub fn wakes() -> u64 {
+    REFRESH.0.lock().unwrap().wakes
+}
+
+/// As [`sleep`], also returning at once if woken since [`wakes`] returned `wakes`, so a wake
+/// between checking for its cause and sleeping isn't missed
+pub fn sleep_since(wakes: u64, duration: Duration) -> bool {
+    let (refresh, condvar) = &REFRESH;
+    let guard = refresh.lock().unwrap();
+    let generation = guard.generation;
+    let (refresh, _) = condvar
+        .wait_timeout_while(guard, duration, |refresh| {
+            refresh.generation == generation
+                && refresh.wakes == wakes
+                && !refresh.paused
+                && !refresh.stopping
+        })
+        .unwrap();
+    refresh.generation != generation || refresh.paused || refresh.stopping
+}
+
+/// Sleep between the updates of a module on its own interval, returning early with `false` once
+/// stopping. Refreshes don't wake it, as its probe may not be cheap.
+pub fn idle(duration: Duration) -> bool {
+    let (refresh, condvar) = &REFRESH;
+    let guard = refresh.lock().unwrap();
+    let (refresh, _) = condvar
+        .wait_timeout_while(guard, duration, |refresh| !refresh.stopping)
+        .unwrap();
+    !refresh.stopping
+}
+
+/// Record whether offline, returning whether it changed. Going offline or back online wakes the
+/// sleepers, such as the scheduler for the modules that need the network.
+pub fn set_offline(offline: bool) -> bool {
+    let (refresh, condvar) = &REFRESH;
+    let mut refresh = refresh.lock().unwrap();
+    if refresh.offline == offline {
+        return false;
+    }
+    refresh.offline = offline;
+    refresh.wakes += 1;
+    condvar.notify_all();
+    true
+}
+
+pub fn offline() -> bool {
+    REFRESH.0.lock().unwrap().offline
+}
+
+/// Files removed on exit
+static CLEANUP: [OnceLock<CString>; 4] = [
+    OnceLock::new(),
+    OnceLock::new(),
+    OnceLock::new(),
+    OnceLock::new(),
+];
+
+fn remove_files() {
+    for path in CLEANUP.iter().filter_map(OnceLock::get) {
+        // SAFETY: path is nul terminated
+        unsafe { libc::unlink(path.as_ptr()) };
+    }
+}
+
+/// Remove `path` on [`exit`], as when stopped by SIGTERM or SIGINT
+pub fn remove_on_exit(path: &Path) {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return;
+    };
+    let mut path = Some(path);
+    for slot in &CLEANUP {
+        let _ = slot.get_or_init(|| path.take().unwrap());
+        if path.is_none() {
+            break;
+        }
+    }
+}
+
+/// Remove the files registered with [`remove_on_exit`], tell systemd, and exit
+pub fn exit(code: i32) -> ! {
+    systemd::notify("STOPPING=1");
+    remove_files();
+    std::process::exit(code)
+}
@@ -0,0 +1,121 @@
+//! Smoothing of the displayed values of flappy modules, such as cpu bouncing around a threshold
+//!
+//! Only the text and the level follow the smoothed value: the percentage of the segment stays
+//! raw for the metrics, the session bus, and the recordings.
+
+use std::time::{Duration, Instant};
+
+/// Intervals without a sample after which the average restarts, such as across a suspend
+const GAP_INTERVALS: u32 = 3;
+
+/// clap value parser of the weight of the latest sample, in (0, 1]
+pub fn parse_alpha(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(alpha) if alpha > 0.0 && alpha <= 1.0 => Ok(alpha),
+        _ => Err(format!(
+            "invalid alpha `{s}`, expected e.g. 0.3, within (0, 1]"
+        )),
+    }
+}
+
+/// Exponential moving average of samples taken every `interval`
+#[derive(Debug)]
+pub struct Ema {
+    alpha: f64,
+    interval: Duration,
+    /// average and when last sampled
+    average: Option<(f64, Instant)>,
+}
+
+impl Ema {
+    pub fn new(alpha: f64, interval: Duration) -> Self {
+        Self {
+            alpha,
+            interval,
+            average: None,
+        }
+    }
+
+    /// Average including `value`, which starts it again after a gap between samples
+    pub fn update(&mut self, value: f64) -> f64 {
+        self.update_at(value, Instant::now())
+    }
+
+    fn update_at(&mut self, value: f64, now: Instant) -> f64 {
+        let average = match self.average {
+            Some((average, sampled))
+                if now.duration_since(sampled) <= self.interval * GAP_INTERVALS =>
+            {
+                self.alpha * value + (1.0 - self.alpha) * average
+            }
+            _ => value,
+        };
+        self.average = Some((average, now));
+        average
+    }
+
+    /// Start again from the next sample, after a pause
+    pub fn reset(&mut self) {
+        self.average = None;
+    }
+}
+
+/// Displayed value of integer values such as a battery capacity, only following the raw value
+/// once it moved by more than `band` from the one shown
+#[derive(Debug, Default)]
+pub struct Hysteresis {
+    band: f64,
+    shown: Option<f64>,
+}
+
+impl Hysteresis {
+    pub fn new(band: f64) -> Self {
+        Self { band, shown: None }
+    }
+
+    pub fn update(&mut self, value: f64) -> f64 {
+        let shown = match self.shown {
+            Some(shown) if (value - shown).abs() <= self.band => shown,
+            _ => value,
+        };
+        self.shown = Some(shown);
+        shown
+    }
+
+    pub fn reset(&mut self) {
+        self.shown = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_restarts_after_gap() {
+        let second = Duration::from_secs(1);
+        let mut ema = Ema::new(0.5, second);
+        let start = Instant::now();
+        assert_eq!(ema.update_at(80.0, start), 80.0);
+        assert_eq!(ema.update_at(0.0, start + second), 40.0);
+        assert_eq!(ema.update_at(20.0, start + second * 2), 30.0);
+        // resumed from a suspend, not blending what came before
+        assert_eq!(ema.update_at(10.0, start + second * 60), 10.0);
+        ema.reset();
+        assert_eq!(ema.update_at(90.0, start + second * 61), 90.0);
+
+        assert_eq!(parse_alpha("0.3"), Ok(0.3));
+        assert!(parse_alpha("0").is_err() && parse_alpha("1.5").is_err());
+    }
+
+    #[test]
+    fn flapping_held() {
+        let mut shown = Hysteresis::new(1.0);
+        let shown: Vec<f64> = [80.0, 79.0, 80.0, 79.0, 78.0, 79.0]
+            .into_iter()
+            .map(|value| shown.update(value))
+            .collect();
+        assert_eq!(shown, [80.0, 80.0, 80.0, 80.0, 78.0, 78.0]);
+        assert_eq!(Hysteresis::default().update(79.0), 79.0);
+    }
+}
@@ -0,0 +1,84 @@
+//! Writing the status to a regular file, atomically replaced on each update
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::output::Output;
+
+pub struct StatusFile {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    newline: bool,
+}
+
+impl StatusFile {
+    pub fn new(path: PathBuf, newline: bool) -> Self {
+        // same directory as the target, so the rename can't cross filesystems
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(path.file_name().unwrap_or_default());
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        Self {
+            path,
+            tmp_path,
+            newline,
+        }
+    }
+
+    fn try_write(&self, status: &str) -> io::Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut tmp = std::fs::File::create(&self.tmp_path)?;
+        tmp.write_all(status.as_bytes())?;
+        if self.newline {
+            tmp.write_all(b"\n")?;
+        }
+        drop(tmp);
+
+        // readers see either the previous or the new status, never a partial write
+        std::fs::rename(&self.tmp_path, &self.path)
+    }
+}
+
+impl Output for StatusFile {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    /// Replace the file with `status`, failures are retried on the next update
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        self.try_write(status).map_err(|e| {
+            let _ = std::fs::remove_file(&self.tmp_path);
+            io::Error::new(
+                e.kind(),
+                format!("unable to write {}: {e}", self.path.display()),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaced() {
+        let dir = std::env::temp_dir().join(format!("statusbar-file-{}", std::process::id()));
+        let path = dir.join("bar").join("status");
+
+        let mut file = StatusFile::new(path.clone(), true);
+        assert_eq!(file.tmp_path, dir.join("bar").join(".status.tmp"));
+        file.write("cpu 01%").unwrap();
+        file.write("cpu 02%").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "cpu 02%\n");
+        assert!(!file.tmp_path.exists());
+
+        let mut file = StatusFile::new(path.clone(), false);
+        file.write("cpu 03%").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "cpu 03%");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
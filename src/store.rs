@@ -0,0 +1,198 @@
+//! Latest segment of each module, overwritten by the modules and read by the render thread
+//!
+//! Unlike a channel nothing queues up: a module publishing faster than the bar renders only
+//! replaces its segment.
+
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::output::Segment;
+use crate::{replay, signal};
+
+/// Interval of checking for a stop while waiting for a render
+const STOP_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+pub struct Store {
+    inner: Mutex<Inner>,
+    /// notified on each render
+    rendered: Condvar,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// incremented on each publish
+    generation: u64,
+    segments: HashMap<&'static str, Segment>,
+    /// module being collected by the scheduler, and since when
+    collecting: Option<(&'static str, Instant)>,
+    /// generation last rendered
+    rendered: u64,
+}
+
+impl Store {
+    /// Replace the segments of their modules, or remove those of [`Segment::hidden`], waking the
+    /// render thread once
+    pub fn publish(&self, segments: impl IntoIterator<Item = Segment>) {
+        // the same for the segments published together
+        let recorded = replay::timestamp();
+        let mut inner = self.inner.lock().unwrap();
+        inner.generation += 1;
+        for segment in segments {
+            if segment.hidden {
+                if inner.segments.remove(segment.module).is_some() {
+                    replay::hidden(segment.module);
+                }
+                continue;
+            }
+            if let Some(timestamp) = &recorded {
+                replay::published(timestamp, &segment);
+            }
+            inner.segments.insert(segment.module, segment);
+        }
+        drop(inner);
+        signal::wake();
+    }
+
+    /// Publish `text` as the segment of `module`, or remove it with `None` to hide the module,
+    /// even with `--keep-empty`
+    pub fn set(&self, module: &'static str, text: Option<String>) {
+        let Some(text) = text else {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.segments.remove(module).is_some() {
+                inner.generation += 1;
+                drop(inner);
+                replay::hidden(module);
+                signal::wake();
+            }
+            return;
+        };
+        self.publish([Segment::new(module, text)]);
+    }
+
+    /// Push the expiry of every segment back by `paused`, a pause not making them stale
+    pub fn postpone(&self, paused: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        for segment in inner.segments.values_mut() {
+            segment.expires = segment.expires.map(|expires| expires + paused);
+        }
+    }
+
+    /// Record the module the scheduler is collecting, `None` once done
+    pub fn collecting(&self, module: Option<&'static str>) {
+        self.inner.lock().unwrap().collecting = module.map(|module| (module, Instant::now()));
+    }
+
+    /// Module the scheduler is collecting, and since when, to tell one stuck in a read
+    pub fn collected(&self) -> Option<(&'static str, Instant)> {
+        self.inner.lock().unwrap().collecting
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.inner.lock().unwrap().generation
+    }
+
+    /// Record that the segments of `generation` were rendered
+    pub fn rendered(&self, generation: u64) {
+        self.inner.lock().unwrap().rendered = generation;
+        self.rendered.notify_all();
+    }
+
+    /// Wait until the latest segments are rendered, false when stopping instead
+    pub fn wait_rendered(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        while inner.rendered < inner.generation {
+            if signal::stopping() {
+                return false;
+            }
+            inner = self.rendered.wait_timeout(inner, STOP_POLL).unwrap().0;
+        }
+        true
+    }
+
+    /// Copy the segments into `segments`, returning the generation they are of
+    pub fn snapshot(&self, segments: &mut HashMap<&'static str, Segment>) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        segments.clone_from(&inner.segments);
+        inner.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_replaces_the_segment_of_its_module() {
+        let store = Store::default();
+        store.publish([
+            Segment::new("cpu", "cpu 10%"),
+            Segment::new("mem", "mem 04%"),
+        ]);
+        store.publish([Segment::new("cpu", "cpu 12%")]);
+
+        let mut segments = HashMap::new();
+        assert_eq!(store.snapshot(&mut segments), 2);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments["cpu"].text, "cpu 12%");
+        assert_eq!(segments["mem"].text, "mem 04%");
+        assert_eq!(store.generation(), 2);
+    }
+
+    #[test]
+    fn pause_postpones_expiry() {
+        let store = Store::default();
+        let expires = Instant::now();
+        store.publish([
+            Segment::new("cpu", "cpu 10%").expires(expires),
+            Segment::new("time", "12:00"),
+        ]);
+        store.postpone(Duration::from_secs(5));
+
+        let mut segments = HashMap::new();
+        store.snapshot(&mut segments);
+        assert_eq!(
+            segments["cpu"].expires,
+            Some(expires + Duration::from_secs(5))
+        );
+        assert_eq!(segments["time"].expires, None);
+    }
+
+    #[test]
+    fn waits_for_the_render() {
+        let store = std::sync::Arc::new(Store::default());
+        assert!(store.wait_rendered());
+        store.publish([Segment::new("cpu", "cpu 10%")]);
+        let renderer = std::sync::Arc::clone(&store);
+        let render = std::thread::spawn(move || {
+            let generation = renderer.snapshot(&mut HashMap::new());
+            renderer.rendered(generation);
+        });
+        assert!(store.wait_rendered());
+        render.join().unwrap();
+    }
+
+    #[test]
+    fn set_none_removes_the_segment() {
+        let store = Store::default();
+        store.set("lid", Some("lid closed".into()));
+        store.set("lid", None);
+        store.set("lid", None);
+
+        let mut segments = HashMap::new();
+        assert_eq!(store.snapshot(&mut segments), 2);
+        assert!(segments.is_empty());
+    }
+
+    #[test]
+    fn hidden_removes_the_segment() {
+        let store = Store::default();
+        store.publish([Segment::new("nm", "HomeWifi")]);
+        store.publish([Segment::hidden("nm"), Segment::hidden("lid")]);
+
+        let mut segments = HashMap::new();
+        store.snapshot(&mut segments);
+        assert!(segments.is_empty());
+    }
+}
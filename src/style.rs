@@ -0,0 +1,191 @@
+//! Warning and critical thresholds of segments, the thresholds they are shown from, and the
+//! colors they are displayed with
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+/// Modules that thresholds can be configured for
+const THRESHOLD_MODULES: &[&str] = &["cpu", "mem", "bat", "fd"];
+
+/// Deadband of `--show-above` and `--show-below`, so that a value hovering at the threshold
+/// doesn't show and hide the segment on every sample
+const HYSTERESIS: f64 = 2.0;
+
+/// Modules where the value getting lower is worse
+const LOW_IS_BAD: &[&str] = &["bat"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// clap value parser for `--warning module=value` and `--critical module=value`
+pub fn parse_threshold(s: &str) -> Result<(String, f64), String> {
+    let (module, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid threshold `{s}`, expected module=value"))?;
+    if !THRESHOLD_MODULES.contains(&module) {
+        return Err(format!(
+            "thresholds aren't supported for `{module}`, expected one of: {}",
+            THRESHOLD_MODULES.join(", ")
+        ));
+    }
+    let value = value
+        .parse()
+        .map_err(|_| format!("invalid threshold value `{value}`"))?;
+    Ok((module.to_string(), value))
+}
+
+#[derive(Debug, Default)]
+pub struct Thresholds {
+    warning: HashMap<String, f64>,
+    critical: HashMap<String, f64>,
+}
+
+impl Thresholds {
+    pub fn new(warning: Vec<(String, f64)>, critical: Vec<(String, f64)>) -> Self {
+        Self {
+            warning: warning.into_iter().collect(),
+            critical: critical.into_iter().collect(),
+        }
+    }
+
+    /// Level of `value` for `module`, thresholds are inclusive
+    pub fn level(&self, module: &str, value: f64) -> Level {
+        let crossed = |thresholds: &HashMap<String, f64>| {
+            thresholds.get(module).is_some_and(|threshold| {
+                if LOW_IS_BAD.contains(&module) {
+                    value <= *threshold
+                } else {
+                    value >= *threshold
+                }
+            })
+        };
+
+        if crossed(&self.critical) {
+            Level::Critical
+        } else if crossed(&self.warning) {
+            Level::Warning
+        } else {
+            Level::Normal
+        }
+    }
+}
+
+/// Segments only shown above or below a value, such as cpu when busy
+#[derive(Debug, Default)]
+pub struct Visibility {
+    above: HashMap<String, f64>,
+    below: HashMap<String, f64>,
+    /// modules shown as of their last value
+    shown: HashSet<&'static str>,
+}
+
+impl Visibility {
+    pub fn new(above: Vec<(String, f64)>, below: Vec<(String, f64)>) -> Self {
+        Self {
+            above: above.into_iter().collect(),
+            below: below.into_iter().collect(),
+            shown: HashSet::new(),
+        }
+    }
+
+    /// Whether to show `module` at `value`: from its thresholds, inclusive, until back past them
+    /// by [`HYSTERESIS`]
+    pub fn shown(&mut self, module: &'static str, value: f64) -> bool {
+        let was_shown = self.shown.contains(module);
+        let margin = if was_shown { HYSTERESIS } else { 0.0 };
+        let above = self
+            .above
+            .get(module)
+            .is_none_or(|threshold| value >= threshold - margin);
+        let below = self
+            .below
+            .get(module)
+            .is_none_or(|threshold| value <= threshold + margin);
+        let shown = above && below;
+        if shown != was_shown {
+            if shown {
+                self.shown.insert(module);
+            } else {
+                self.shown.remove(module);
+            }
+        }
+        shown
+    }
+}
+
+/// Alternate `text` between `!! text !!` and as wide without the marks, for `--critical-flash`
+pub fn flash(text: &mut String, marked: bool) {
+    let mark = if marked { "!! " } else { "   " };
+    text.insert_str(0, mark);
+    text.extend(mark.chars().rev());
+}
+
+/// clap value parser for `#rrggbb` colors
+pub fn parse_color(s: &str) -> Result<String, String> {
+    let valid = s.len() == 7 && s.starts_with('#') && s[1..].bytes().all(|b| b.is_ascii_hexdigit());
+    if valid {
+        Ok(s.to_string())
+    } else {
+        Err(format!("invalid color `{s}`, expected #rrggbb"))
+    }
+}
+
+/// Foreground and optional background colors per level
+#[derive(Debug, Clone)]
+pub struct Colors {
+    pub warning: (String, Option<String>),
+    pub critical: (String, Option<String>),
+}
+
+impl Colors {
+    pub fn get(&self, level: Level) -> Option<(&str, Option<&str>)> {
+        let (fg, bg) = match level {
+            Level::Normal => return None,
+            Level::Warning => &self.warning,
+            Level::Critical => &self.critical,
+        };
+        Some((fg, bg.as_deref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shown_with_hysteresis() {
+        let mut visibility = Visibility::new(vec![("cpu".into(), 20.0)], vec![]);
+        let shown: Vec<bool> = [5.0, 19.0, 20.0, 19.0, 18.5, 17.0, 19.0, 21.0]
+            .into_iter()
+            .map(|value| visibility.shown("cpu", value))
+            .collect();
+        assert_eq!(shown, [false, false, true, true, true, false, false, true]);
+        assert!(visibility.shown("mem", 0.0));
+    }
+
+    #[test]
+    fn flash_keeps_width() {
+        let mut marked = "bat [04%]".to_string();
+        flash(&mut marked, true);
+        assert_eq!(marked, "!! bat [04%] !!");
+        let mut unmarked = "bat [04%]".to_string();
+        flash(&mut unmarked, false);
+        assert_eq!(unmarked, "   bat [04%]   ");
+    }
+
+    #[test]
+    fn shown_below() {
+        let mut visibility = Visibility::new(vec![], vec![("bat".into(), 30.0)]);
+        assert!(!visibility.shown("bat", 80.0));
+        assert!(visibility.shown("bat", 30.0));
+        assert!(visibility.shown("bat", 31.0));
+        assert!(!visibility.shown("bat", 33.0));
+    }
+}
@@ -0,0 +1,122 @@
+//! Notifications of a systemd `Type=notify` service, when started with `NOTIFY_SOCKET` set
+//!
+//! The watchdog is pinged from the render loop instead of a thread of its own, so that a wedged
+//! render thread (such as one waiting on a lock held by a stuck collector) misses its pings and
+//! gets the service restarted.
+
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+static SOCKET: OnceLock<(UnixDatagram, SocketAddr)> = OnceLock::new();
+
+/// Connect to `NOTIFY_SOCKET`, if set. [`notify`] does nothing otherwise.
+pub fn connect() {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let path = path.to_string_lossy();
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(&*path),
+    };
+    match addr.and_then(|addr| Ok((UnixDatagram::unbound()?, addr))) {
+        Ok(socket) => {
+            info!("notifying systemd on {path}");
+            let _ = SOCKET.set(socket);
+        }
+        Err(e) => warn!("unable to notify systemd on {path}: {e}"),
+    }
+}
+
+/// Send `state`, such as "READY=1"
+pub fn notify(state: &str) {
+    if let Some((socket, addr)) = SOCKET.get() {
+        let _ = socket.send_to_addr(state.as_bytes(), addr);
+    }
+}
+
+/// Pings of `WatchdogSec`, at half its interval
+pub struct Watchdog {
+    interval: Duration,
+    /// interval between calls to [`Watchdog::ping`]
+    tick: Duration,
+    pinged: Option<Instant>,
+}
+
+impl Watchdog {
+    /// The watchdog of `WATCHDOG_USEC`, if set for this process and notifying systemd, pinged
+    /// every `tick`
+    pub fn from_env(tick: Duration) -> Option<Self> {
+        SOCKET.get()?;
+        let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+        let pid = std::env::var("WATCHDOG_PID").ok();
+        if pid.is_some_and(|pid| pid.parse() != Ok(std::process::id())) {
+            return None;
+        }
+        let interval = Duration::from_micros(usec) / 2;
+        if interval <= tick {
+            warn!("WatchdogSec is too close to the render interval, expect restarts");
+        }
+        Some(Self {
+            interval,
+            tick,
+            pinged: None,
+        })
+    }
+
+    /// Ping if the next call would be late
+    pub fn ping(&mut self) {
+        let due = self.interval.saturating_sub(self.tick);
+        if self.pinged.is_none_or(|pinged| pinged.elapsed() >= due) {
+            notify("WATCHDOG=1");
+            self.pinged = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Datagrams sent so far to `socket`
+    fn received(socket: &UnixDatagram) -> Vec<String> {
+        let mut buf = [0; 64];
+        let mut received = vec![];
+        while let Ok(len) = socket.recv(&mut buf) {
+            received.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        received
+    }
+
+    #[test]
+    fn pinged_at_half_the_interval() {
+        let path = std::env::temp_dir().join(format!("statusbar-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd.set_nonblocking(true).unwrap();
+        let addr = SocketAddr::from_pathname(&path).unwrap();
+        SOCKET
+            .set((UnixDatagram::unbound().unwrap(), addr))
+            .unwrap();
+
+        notify("READY=1");
+        let mut watchdog = Watchdog {
+            interval: Duration::from_secs(10),
+            tick: Duration::from_secs(1),
+            pinged: None,
+        };
+        watchdog.ping();
+        watchdog.ping();
+        assert_eq!(received(&systemd), ["READY=1", "WATCHDOG=1"]);
+
+        // the next call would be past the interval
+        watchdog.pinged = Some(Instant::now() - Duration::from_secs(9));
+        watchdog.ping();
+        assert_eq!(received(&systemd), ["WATCHDOG=1"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+//! Network throughput, from the statistics of the interfaces in sysfs
+
+use std::path::Path;
+use std::time::Instant;
+
+#[derive(Default)]
+pub struct Throughput {
+    /// time and total (rx, tx) bytes of the previous sample
+    last: Option<(Instant, u64, u64)>,
+}
+
+impl Throughput {
+    /// (rx, tx) bytes per second of `interfaces` of `sys_net`, such as /sys/class/net, since the
+    /// previous call, `None` on the first
+    pub fn sample(&mut self, sys_net: &Path, interfaces: &[String]) -> Option<(f64, f64)> {
+        let now = Instant::now();
+        let (rx, tx) = interfaces.iter().fold((0, 0), |(rx, tx), interface| {
+            (
+                rx + statistic(sys_net, interface, "rx_bytes"),
+                tx + statistic(sys_net, interface, "tx_bytes"),
+            )
+        });
+
+        let (last_time, last_rx, last_tx) = self.last.replace((now, rx, tx))?;
+        let secs = now.duration_since(last_time).as_secs_f64();
+        if secs == 0.0 {
+            return None;
+        }
+        // counters restart when an interface goes away
+        let rate = |bytes: u64, last: u64| bytes.saturating_sub(last) as f64 / secs;
+        Some((rate(rx, last_rx), rate(tx, last_tx)))
+    }
+}
+
+fn statistic(sys_net: &Path, interface: &str, name: &str) -> u64 {
+    std::fs::read_to_string(sys_net.join(interface).join("statistics").join(name))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// `1.2M/s`, in 1024 multiples of bytes
+pub fn format(bytes_per_sec: f64) -> String {
+    format!("{}/s", format_bytes(bytes_per_sec))
+}
+
+/// `1.2M`, in 1024 multiples of bytes
+pub fn format_bytes(bytes: f64) -> String {
+    let mut value = bytes;
+    for unit in ["B", "K", "M", "G"] {
+        if value < 1024.0 {
+            return if unit == "B" {
+                format!("{value:.0}{unit}")
+            } else {
+                format!("{value:.1}{unit}")
+            };
+        }
+        value /= 1024.0;
+    }
+    format!("{value:.1}T")
+}
@@ -0,0 +1,103 @@
+//! Setting tmux's `status-right` to the status
+
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use crate::command::output_timeout;
+use crate::output::Output;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delay between attempts while no tmux server is running
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct Tmux {
+    socket: Option<PathBuf>,
+    retry_at: Option<Instant>,
+}
+
+impl Tmux {
+    pub fn new(socket: Option<PathBuf>) -> Self {
+        Self {
+            socket,
+            retry_at: None,
+        }
+    }
+}
+
+impl Output for Tmux {
+    fn name(&self) -> &'static str {
+        "tmux"
+    }
+
+    /// Set `status-right`, backing off while there is no server instead of spawning a
+    /// failing client on every update
+    fn write(&mut self, status: &str) -> io::Result<()> {
+        if self
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            return Ok(());
+        }
+
+        let mut command = Command::new("tmux");
+        if let Some(socket) = &self.socket {
+            command.arg("-S").arg(socket);
+        }
+        command.args(["set-option", "-g", "status-right", &escape(status)]);
+
+        match output_timeout(&mut command, TIMEOUT) {
+            Ok(Some(output)) if output.status.success() => {
+                if self.retry_at.take().is_some() {
+                    info!("server found");
+                }
+                Ok(())
+            }
+            _ => {
+                self.retry_at = Some(Instant::now() + RETRY_INTERVAL);
+                Err(io::Error::other(format!(
+                    "no server running, retrying every {}s",
+                    RETRY_INTERVAL.as_secs()
+                )))
+            }
+        }
+    }
+}
+
+/// Escape `status` for use as an option value. The value is passed as its own argument, so
+/// quotes need no shell escaping, but `#` starts tmux formats and an argument ending in `;`
+/// is taken as a command separator.
+fn escape(status: &str) -> String {
+    let mut escaped = status.replace('#', "##");
+    if escaped.ends_with(';') {
+        escaped.insert(escaped.len() - 1, '\\');
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped() {
+        assert_eq!(escape("cpu 02%, #1"), "cpu 02%, ##1");
+        assert_eq!(escape("todo 3;"), r"todo 3\;");
+        assert_eq!(escape("a;b"), "a;b");
+        assert_eq!(escape("\"quoted\" 'text'"), "\"quoted\" 'text'");
+    }
+
+    #[test]
+    fn without_server_backs_off() {
+        let socket = std::env::temp_dir().join(format!("statusbar-tmux-{}", std::process::id()));
+        let mut tmux = Tmux::new(Some(socket));
+        assert!(tmux.write("cpu 02%").is_err());
+        assert!(tmux.retry_at.is_some());
+        // not tried again until the retry
+        assert!(tmux.write("cpu 03%").is_ok());
+    }
+}
@@ -0,0 +1,183 @@
+//! Fitting the segments in the maximum length of the status
+
+use std::collections::HashMap;
+
+use crate::marquee::{truncate, width};
+use crate::output::{self, Segment};
+
+pub struct Limits {
+    /// per module maximum width of the segment
+    max: HashMap<String, usize>,
+    /// maximum width of the plain status
+    max_length: Option<usize>,
+    /// modules kept the longest, highest priority first
+    priority: Vec<String>,
+    /// end of the truncated segments
+    ellipsis: String,
+}
+
+impl Limits {
+    pub fn new(
+        max: Vec<(String, usize)>,
+        max_length: Option<usize>,
+        priority: Vec<String>,
+    ) -> Self {
+        Self {
+            max: max.into_iter().collect(),
+            max_length,
+            priority,
+            ellipsis: "…".to_string(),
+        }
+    }
+
+    /// End of the truncated segments, `…` by default
+    pub fn ellipsis(mut self, ellipsis: impl Into<String>) -> Self {
+        self.ellipsis = ellipsis.into();
+        self
+    }
+
+    /// Truncate segments wider than the maximum of their module, then drop the lowest priority
+    /// segments until the plain status fits in the maximum length, truncating the last one if
+    /// even it doesn't fit.
+    ///
+    /// Modules missing from the priority order are dropped first, from the right.
+    pub fn apply(&self, segments: &mut Vec<Segment>) {
+        for segment in segments.iter_mut() {
+            let max = self.max.get(segment.module);
+            if let Some(&max) = max.filter(|max| width(&segment.text) > **max) {
+                segment.text = truncate(&segment.text, max, &self.ellipsis);
+            }
+        }
+
+        let Some(max_length) = self.max_length else {
+            return;
+        };
+        while segments.len() > 1 && plain_width(segments) > max_length {
+            let lowest = (0..segments.len())
+                .max_by_key(|i| self.rank(segments[*i].module, *i))
+                .unwrap();
+            segments.remove(lowest);
+        }
+
+        if let [segment] = &mut segments[..] {
+            let text_width = width(&segment.text);
+            let overhead = plain_width(std::slice::from_ref(segment)) - text_width;
+            if text_width + overhead > max_length {
+                match max_length.checked_sub(overhead).filter(|w| *w > 0) {
+                    Some(w) => segment.text = truncate(&segment.text, w, &self.ellipsis),
+                    None => segments.clear(),
+                }
+            }
+        }
+    }
+
+    /// Rank of the segment at `index`, the highest rank is dropped first
+    fn rank(&self, module: &str, index: usize) -> usize {
+        self.priority
+            .iter()
+            .position(|m| m == module)
+            .unwrap_or(self.priority.len() + index)
+    }
+}
+
+fn plain_width(segments: &[Segment]) -> usize {
+    let mut status = String::new();
+    output::plain(segments, &mut status);
+    width(&status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments() -> Vec<Segment> {
+        vec![
+            Segment::new("host", "vm"),
+            Segment::new("cpu", "cpu 02%"),
+            Segment::new("net", "net [10.0.0.2, 192.168.1.20]"),
+            Segment::new("time", "2024-01-01 00:00:00"),
+        ]
+    }
+
+    fn plain(segments: &[Segment]) -> String {
+        let mut status = String::new();
+        output::plain(segments, &mut status);
+        status
+    }
+
+    #[test]
+    fn per_module_max() {
+        let limits = Limits::new(vec![("net".to_string(), 10)], None, vec![]);
+        let mut segments = segments();
+        limits.apply(&mut segments);
+        assert_eq!(segments[2].text, "net [10.0…");
+        assert_eq!(width(&segments[2].text), 10);
+        assert_eq!(segments[3].text, "2024-01-01 00:00:00");
+    }
+
+    #[test]
+    fn truncate_char_boundary() {
+        let limits = Limits::new(vec![("net".to_string(), 4)], None, vec![]);
+        let mut segments = vec![Segment::new("net", "αβγδε"), Segment::new("cpu", "日本語")];
+        limits.apply(&mut segments);
+        assert_eq!(segments[0].text, "αβγ…");
+
+        let limits = Limits::new(vec![("cpu".to_string(), 4)], None, vec![]);
+        limits.apply(&mut segments);
+        assert_eq!(segments[1].text, "日…");
+    }
+
+    #[test]
+    fn fits_unchanged() {
+        let limits = Limits::new(vec![], Some(100), vec![]);
+        let mut segments = segments();
+        limits.apply(&mut segments);
+        assert_eq!(segments.len(), 4);
+    }
+
+    #[test]
+    fn drop_from_right_without_priority() {
+        let limits = Limits::new(vec![], Some(46), vec![]);
+        let mut segments = segments();
+        limits.apply(&mut segments);
+        assert_eq!(
+            plain(&segments),
+            "[vm] => cpu 02%, net [10.0.0.2, 192.168.1.20]"
+        );
+    }
+
+    #[test]
+    fn drop_lowest_priority() {
+        let limits = Limits::new(
+            vec![],
+            Some(35),
+            vec!["time".to_string(), "cpu".to_string()],
+        );
+        let mut segments = segments();
+        limits.apply(&mut segments);
+        assert_eq!(plain(&segments), "cpu 02%, 2024-01-01 00:00:00");
+    }
+
+    #[test]
+    fn truncate_last_segment() {
+        let limits = Limits::new(vec![], Some(10), vec!["time".to_string()]);
+        let mut fitted = segments();
+        limits.apply(&mut fitted);
+        assert_eq!(plain(&fitted), "2024-01-0…");
+
+        let limits = Limits::new(vec![], Some(3), vec!["host".to_string()]);
+        let mut fitted = segments();
+        limits.apply(&mut fitted);
+        assert_eq!(plain(&fitted), "[…]");
+
+        let limits = Limits::new(vec![], Some(6), vec!["time".to_string()]).ellipsis("...");
+        let mut fitted = segments();
+        limits.apply(&mut fitted);
+        assert_eq!(plain(&fitted), "202...");
+
+        let limits = Limits::new(vec![], Some(2), vec!["host".to_string()]);
+        let mut fitted = segments();
+        limits.apply(&mut fitted);
+        assert!(fitted.is_empty());
+    }
+}
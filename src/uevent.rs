@@ -0,0 +1,73 @@
+//! Kernel uevents, received on a netlink `NETLINK_KOBJECT_UEVENT` socket
+
+use std::io;
+
+use crate::netlink::{self, Socket};
+
+/// Multicast group of the uevents sent by the kernel, as opposed to those relayed by udev
+const KERNEL_GROUP: u32 = 1;
+
+pub struct Monitor(Socket);
+
+impl Monitor {
+    /// Subscribe to the uevents of the kernel
+    pub fn open() -> io::Result<Self> {
+        Socket::open(libc::NETLINK_KOBJECT_UEVENT, KERNEL_GROUP).map(Self)
+    }
+
+    /// Block until a uevent of a device of `subsystem` arrives
+    pub fn wait(&self, subsystem: &str) -> io::Result<()> {
+        let mut buf = [0_u8; 8192];
+        loop {
+            let n = match self.0.recv(&mut buf) {
+                Ok(n) => n,
+                // a burst of events overflowed the socket, some may have been ours
+                Err(e) if netlink::overflowed(&e) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            if field(&buf[..n], "SUBSYSTEM") == Some(subsystem) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Value of `name` in a uevent, `action@devpath` followed by nul separated `KEY=value` pairs
+fn field<'a>(uevent: &'a [u8], name: &str) -> Option<&'a str> {
+    uevent
+        .split(|b| *b == 0)
+        .skip(1)
+        .filter_map(|pair| std::str::from_utf8(pair).ok())
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unplugging AC on a ThinkPad
+    const AC_CHANGE: &[u8] =
+        b"change@/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC\0\
+        ACTION=change\0\
+        DEVPATH=/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC\0\
+        SUBSYSTEM=power_supply\0\
+        POWER_SUPPLY_NAME=AC\0\
+        POWER_SUPPLY_TYPE=Mains\0\
+        POWER_SUPPLY_ONLINE=0\0\
+        SEQNUM=4242\0";
+
+    #[test]
+    fn subsystem_of_uevent() {
+        assert_eq!(field(AC_CHANGE, "SUBSYSTEM"), Some("power_supply"));
+        assert_eq!(field(AC_CHANGE, "POWER_SUPPLY_ONLINE"), Some("0"));
+        assert_eq!(field(AC_CHANGE, "POWER_SUPPLY"), None);
+    }
+
+    #[test]
+    fn header_isnt_a_field() {
+        assert_eq!(
+            field(b"add@/SUBSYSTEM=x\0ACTION=add\0", "add@/SUBSYSTEM"),
+            None
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Running the binary against a runtime dir of its own, shared by the integration tests
+
+// each test uses only some of them
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An empty `XDG_RUNTIME_DIR` for the test `name`
+pub fn runtime_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("statusbar-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The binary writing its status to the file `status` of `runtime_dir`
+pub fn statusbar(runtime_dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_statusbar"));
+    command
+        .env("XDG_RUNTIME_DIR", runtime_dir)
+        // rather than the config file of the user
+        .env("XDG_CONFIG_HOME", runtime_dir)
+        .args(["--output", "file", "--file-path"])
+        .arg(runtime_dir.join("status"))
+        .args(args)
+        .stdout(Stdio::null());
+    command
+}
+
+pub fn spawn(runtime_dir: &Path, args: &[&str]) -> Child {
+    statusbar(runtime_dir, args).spawn().unwrap()
+}
+
+/// Wait for a status to be written
+pub fn wait_status(runtime_dir: &Path) {
+    let start = Instant::now();
+    while std::fs::read_to_string(runtime_dir.join("status")).is_err() {
+        assert!(start.elapsed() < TIMEOUT, "no status written");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Wait for the status to contain `text`, returning how long it took
+pub fn wait_for(runtime_dir: &Path, text: &str) -> Duration {
+    let start = Instant::now();
+    loop {
+        let status = std::fs::read_to_string(runtime_dir.join("status")).unwrap_or_default();
+        if status.contains(text) {
+            return start.elapsed();
+        }
+        assert!(start.elapsed() < TIMEOUT, "{text:?} not in {status:?}");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// Wait for the pid of `child` in the lock file
+pub fn wait_locked(runtime_dir: &Path, child: &Child) {
+    let path = runtime_dir.join("statusbar.lock");
+    let start = Instant::now();
+    while std::fs::read_to_string(&path).ok().as_deref() != Some(&format!("{}\n", child.id())) {
+        assert!(start.elapsed() < TIMEOUT, "instance didn't lock");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+pub fn wait_exit(child: &mut Child) -> ExitStatus {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        assert!(start.elapsed() < TIMEOUT, "instance didn't exit");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,150 @@
+//! The composed status against the sysfs and procfs fixture trees of tests/fixtures
+
+use std::process::Command;
+
+fn status(sysfs: &str, procfs: &str, args: &[&str]) -> String {
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/");
+    let output = Command::new(env!("CARGO_BIN_EXE_statusbar"))
+        .arg("--once")
+        .arg("--sysfs-root")
+        .arg(format!("{fixtures}{sysfs}"))
+        .arg("--procfs-root")
+        .arg(format!("{fixtures}{procfs}"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn batteries() {
+    let status = status("sys", "proc", &[]);
+    assert!(status.contains(", bat [83%, 07%], "), "{status:?}");
+}
+
+#[test]
+fn meminfo_variants() {
+    let status_6_18 = status("sys", "proc", &[]);
+    assert!(status_6_18.contains(", mem 10%, "), "{status_6_18:?}");
+    // before MemAvailable
+    let status_2_6 = status("sys", "proc-2.6", &[]);
+    assert!(status_2_6.contains(", mem 93%, "), "{status_2_6:?}");
+}
+
+#[test]
+fn mem_modes() {
+    let free = status("sys", "proc", &["--mem-mode", "free"]);
+    assert!(free.contains(", mem 55%, "), "{free:?}");
+    // the slice of the service without a limit, out of MemTotal
+    let cgroup = status("sys", "proc", &["--mem-mode", "cgroup"]);
+    assert!(cgroup.contains(", mem 25%, "), "{cgroup:?}");
+}
+
+#[test]
+fn link_state() {
+    let args = ["--interface", "lo", "--net-mode", "primary"];
+    let up = status("sys", "proc", &args);
+    assert!(up.contains(", net [127.0.0.1], "), "{up:?}");
+    let down = status("sys-link-down", "proc", &args);
+    assert!(down.contains(", net down, "), "{down:?}");
+}
+
+#[test]
+fn missing_files() {
+    let status = status("nonexistent", "nonexistent", &[]);
+    assert!(!status.contains("bat"), "{status:?}");
+    assert!(!status.contains("mem"), "{status:?}");
+}
+
+#[test]
+fn doctor_gates_on_missing() {
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/");
+    let doctor = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_statusbar"))
+            .arg("--sysfs-root")
+            .arg(format!("{fixtures}sys"))
+            .arg("--procfs-root")
+            .arg(format!("{fixtures}proc"))
+            .args(["--output", "i3bar"])
+            .args(args)
+            .arg("doctor")
+            .output()
+            .unwrap()
+    };
+    let found = doctor(&["--interface", "lo"]);
+    assert!(found.status.success(), "{found:?}");
+    let report = String::from_utf8(found.stdout).unwrap();
+    assert!(report.contains("ok    bat: found BAT0, BAT1\n"), "{report}");
+
+    let typo = doctor(&["--interface", "wlna0"]);
+    assert_eq!(typo.status.code(), Some(1));
+}
+
+#[test]
+fn temperatures() {
+    let zones = status(
+        "sys",
+        "proc",
+        &[
+            "--thermal-zone",
+            "x86_pkg_temp:pkg",
+            "--temp",
+            "k10temp/temp1:cpu",
+        ],
+    );
+    assert!(zones.contains(", cpu 62° pkg 52°, "), "{zones:?}");
+
+    let status = status("sys", "proc", &["--temp", "k10temp/temp1:cpu"]);
+    assert!(status.contains(", cpu 62°, "), "{status:?}");
+
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/");
+    let unknown = Command::new(env!("CARGO_BIN_EXE_statusbar"))
+        .args(["--once", "--temp", "amdgpu/temp2:gpu", "--sysfs-root"])
+        .arg(format!("{fixtures}sys"))
+        .output()
+        .unwrap();
+    assert_eq!(unknown.status.code(), Some(2));
+    let stderr = String::from_utf8(unknown.stderr).unwrap();
+    assert!(
+        stderr.contains("unknown hwmon chip `amdgpu`, found: k10temp (temp1)"),
+        "{stderr}"
+    );
+}
+
+/// Every module with a glyph that the fixtures can show, composed in plain ASCII
+#[test]
+fn ascii_glyphs() {
+    let args = [
+        "--thermal-zone",
+        "x86_pkg_temp:pkg",
+        "--temp",
+        "k10temp/temp1:cpu",
+        "--max",
+        "mem=6",
+    ];
+    let unicode = status("sys", "proc", &args);
+    assert!(unicode.contains(", cpu 62° pkg 52°, "), "{unicode:?}");
+    assert!(unicode.contains(", mem 1…, "), "{unicode:?}");
+
+    let ascii = status("sys", "proc", &[&args[..], &["--glyphs", "ascii"]].concat());
+    assert!(ascii.is_ascii(), "{ascii:?}");
+    assert!(ascii.contains(", cpu 62deg pkg 52deg, "), "{ascii:?}");
+    assert!(ascii.contains(", mem..., "), "{ascii:?}");
+    // the temperatures, mem and bat, without the live cpu and time
+    let fixed = |status: &str| -> Vec<String> {
+        status
+            .trim_end()
+            .split(", ")
+            .filter(|segment| {
+                ["cpu 62", "mem", "bat"]
+                    .iter()
+                    .any(|m| segment.starts_with(m))
+            })
+            .map(String::from)
+            .collect()
+    };
+    let alias = status("sys", "proc", &[&args[..], &["--ascii"]].concat());
+    assert_eq!(fixed(&alias).len(), 3, "{alias:?}");
+    assert_eq!(fixed(&alias), fixed(&ascii));
+}
@@ -0,0 +1,81 @@
+//! Smoke benchmark of a tick of the file based modules and the composition of the status,
+//! counting allocations so they don't creep back in. Run with `--nocapture` for the timings.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use statusbar::modules::{Battery, Memory, Module};
+use statusbar::output::{self, Output, Segment, Xsetroot};
+
+const ITERATIONS: usize = 10_000;
+
+/// Allocations of a tick, mostly the segments and their values
+const MAX_ALLOCATIONS: usize = 16;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+// SAFETY: forwards to the system allocator
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+#[test]
+fn tick() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/modules/fixtures");
+    let mut battery = Battery::new(&fixtures.join("power_supply"));
+    let mut memory = Memory::new(fixtures.join("meminfo"));
+    let xsetroot = Xsetroot {
+        statuscmd: true,
+        ..Default::default()
+    };
+    let mut segments: Vec<Segment> = Vec::with_capacity(8);
+    let mut status = String::new();
+
+    let mut tick = || {
+        segments.clear();
+        segments.push(Segment::new("host", "host"));
+        segments.extend(battery.collect());
+        segments.extend(memory.collect());
+        status.clear();
+        xsetroot.compose(&segments, &mut status);
+        output::plain(&segments, &mut status);
+    };
+    // buffers grown to their size
+    tick();
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        tick();
+    }
+    let elapsed = start.elapsed();
+    let per_tick = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS;
+
+    println!(
+        "{:?} and {per_tick} allocations per tick",
+        elapsed / ITERATIONS as u32
+    );
+    assert!(
+        per_tick <= MAX_ALLOCATIONS,
+        "{per_tick} allocations per tick"
+    );
+}
@@ -0,0 +1,29 @@
+//! Delay between a module publishing a new value and the bar showing it
+
+mod common;
+
+use std::time::Duration;
+
+use common::{runtime_dir, spawn, wait_for};
+
+/// Well within the 30s the todo file is checked again without a change, so the change was
+/// published by the watch of the module, yet far from the few milliseconds it takes to leave
+/// room for a loaded machine
+const MAX_LATENCY: Duration = Duration::from_secs(5);
+
+#[test]
+fn publish_is_rendered_immediately() {
+    let dir = runtime_dir("latency");
+    let todo = dir.join("todo.txt");
+    std::fs::write(&todo, "call mom\n").unwrap();
+    let mut child = spawn(&dir, &["--todo", todo.to_str().unwrap()]);
+    wait_for(&dir, "todo 1");
+
+    std::fs::write(&todo, "call mom\nwater plants\n").unwrap();
+    let latency = wait_for(&dir, "todo 2");
+    assert!(latency < MAX_LATENCY, "shown after {latency:?}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,47 @@
+//! Reloading the config file through SIGHUP
+
+mod common;
+
+use common::{runtime_dir, spawn, wait_exit, wait_for};
+
+#[test]
+fn sighup_reloads_config() {
+    let dir = runtime_dir("reload");
+    let todo = dir.join("todo.txt");
+    std::fs::write(&todo, "call the plumber\n").unwrap();
+    let config = dir.join("config");
+    let text = format!("time_format = \"[before]\"\ntodo = {}\n", todo.display());
+    std::fs::write(&config, text).unwrap();
+    let mut child = spawn(&dir, &["--config", config.to_str().unwrap()]);
+    wait_for(&dir, "todo 1");
+    wait_for(&dir, "[before]");
+
+    std::fs::write(&config, "time_format = '[after]'\n").unwrap();
+    // SAFETY: plain syscall
+    assert_eq!(
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGHUP) },
+        0
+    );
+    wait_for(&dir, "[after]");
+    let status = std::fs::read_to_string(dir.join("status")).unwrap();
+    assert!(!status.contains("todo"), "{status}");
+
+    // an invalid config keeps the options
+    std::fs::write(&config, "time_format = '[after]'\ncpu_smoothing = 2\n").unwrap();
+    // SAFETY: plain syscall
+    assert_eq!(
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGHUP) },
+        0
+    );
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    wait_for(&dir, "[after]");
+
+    // SAFETY: plain syscall
+    assert_eq!(
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) },
+        0
+    );
+    wait_exit(&mut child);
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
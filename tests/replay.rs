@@ -0,0 +1,42 @@
+use std::process::Command;
+
+/// Replaying a recording through the thresholds, order, and output of the flags
+fn replay(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_statusbar"))
+        .arg("--replay")
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/raw.jsonl"
+        ))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn renders_each_publish() {
+    let statuses = replay(&["--order", "time,mem"]);
+    assert_eq!(
+        statuses,
+        "2024-01-01 09:00:00, mem 40%, cpu 12%\n\
+         2024-01-01 09:00:01, mem 40%, cpu 97%\n\
+         2024-01-01 09:00:01, mem 40%, cpu 97%, lid closed\n\
+         2024-01-01 09:00:01, mem 40%, cpu 97%\n"
+    );
+}
+
+#[test]
+fn thresholds_of_the_replay() {
+    let statuses = replay(&["--output", "i3bar", "--critical", "cpu=90"]);
+    let last = statuses
+        .lines()
+        .rev()
+        .find(|line| line.contains("cpu 97%"))
+        .unwrap();
+    assert!(
+        last.contains(r##""full_text":"cpu 97%","name":"cpu","color":"#fb4934","urgent":true"##),
+        "{last}"
+    );
+}
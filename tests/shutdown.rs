@@ -0,0 +1,42 @@
+//! Stopping through SIGTERM and SIGINT
+
+mod common;
+
+use std::process::Child;
+
+use common::{runtime_dir, spawn, wait_exit, wait_status};
+
+fn stop(child: &mut Child, signal: libc::c_int) {
+    // SAFETY: plain syscall
+    assert_eq!(unsafe { libc::kill(child.id() as libc::pid_t, signal) }, 0);
+    wait_exit(child);
+}
+
+#[test]
+fn sigterm_clears_bar() {
+    let dir = runtime_dir("sigterm");
+    let mut child = spawn(&dir, &["--control"]);
+    wait_status(&dir);
+
+    stop(&mut child, libc::SIGTERM);
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+    assert_eq!(std::fs::read_to_string(dir.join("status")).unwrap(), "");
+    assert!(!dir.join("statusbar.sock").exists());
+    assert!(!dir.join("statusbar.lock").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sigint_writes_exit_text() {
+    let dir = runtime_dir("sigint");
+    let mut child = spawn(&dir, &["--control", "--exit-text", "statusbar stopped"]);
+    wait_status(&dir);
+
+    stop(&mut child, libc::SIGINT);
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("status")).unwrap(),
+        "statusbar stopped"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
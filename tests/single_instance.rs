@@ -0,0 +1,63 @@
+//! Two instances sharing an `XDG_RUNTIME_DIR`
+
+mod common;
+
+use std::process::Stdio;
+
+use common::{runtime_dir, spawn, statusbar, wait_exit, wait_locked};
+
+#[test]
+fn second_instance_exits() {
+    let dir = runtime_dir("second");
+    let mut first = spawn(&dir, &[]);
+    wait_locked(&dir, &first);
+
+    let second = statusbar(&dir, &[])
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&second.stderr);
+    assert_eq!(second.status.code(), Some(1));
+    assert!(
+        stderr.contains(&format!("another instance is running (pid {})", first.id())),
+        "{stderr}"
+    );
+
+    first.kill().unwrap();
+    first.wait().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replace_takes_over() {
+    let dir = runtime_dir("replace");
+    let mut first = spawn(&dir, &[]);
+    wait_locked(&dir, &first);
+
+    let mut second = spawn(&dir, &["--replace"]);
+    assert!(wait_exit(&mut first).code().is_none_or(|code| code == 0));
+    wait_locked(&dir, &second);
+    assert!(second.try_wait().unwrap().is_none());
+
+    second.kill().unwrap();
+    second.wait().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stale_lock_after_crash() {
+    let dir = runtime_dir("stale");
+    let mut crashed = spawn(&dir, &[]);
+    wait_locked(&dir, &crashed);
+    crashed.kill().unwrap();
+    crashed.wait().unwrap();
+
+    // the file is left with the pid of the crashed instance
+    let mut next = spawn(&dir, &[]);
+    wait_locked(&dir, &next);
+    assert!(next.try_wait().unwrap().is_none());
+
+    next.kill().unwrap();
+    next.wait().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}